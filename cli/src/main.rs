@@ -1,7 +1,132 @@
-use d_compiler::compile;
+use d_compiler::codegen::codegen::CodeGenerator;
+use d_compiler::diagnostics::{apply_fixes, DiagnosticSink, Fix};
+use d_compiler::mir::passes::OptLevel;
+use d_compiler::semantic::lints::{LintLevel, LINTS, LINT_GROUPS};
+use d_compiler::{compile, CompileOptions, CompileOutput, Emit};
+use std::io::Write;
+use std::process::exit;
+
+const USAGE: &str = "usage: d_cli [-A <lint>] [-W <lint>] [-D <lint>] [--deny-warnings] [--fix] [--list-lints] \
+                     [-O0|-O1|-O2] [--remarks] [--emit tokens|ast|hir|mir|lir|wat|wasm] [-o <output>] \
+                     <file>";
 
 fn main() {
-    let input = "1 + 2 * 3";
-    let result = compile(input);
-    println!("{:?}", result);
+    let mut options = CompileOptions::new();
+    let mut path = None;
+    let mut output_path = None;
+    let mut fix = false;
+    let mut remarks = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let level = match arg.as_str() {
+            "-A" => LintLevel::Allow,
+            "-W" => LintLevel::Warn,
+            "-D" => LintLevel::Deny,
+            "--deny-warnings" => {
+                options.lint_levels.deny_warnings = true;
+                continue;
+            }
+            "--emit" => {
+                let name = args.next().unwrap_or_default();
+                options.emit = Emit::from_name(&name)
+                    .unwrap_or_else(|| fail(&format!("unknown output '{}'\n{}", name, USAGE)));
+                continue;
+            }
+            "-o" => {
+                output_path = Some(args.next().unwrap_or_else(|| fail(USAGE)));
+                continue;
+            }
+            _ if arg.starts_with("-O") => {
+                options.opt_level = OptLevel::from_name(&arg[2..])
+                    .unwrap_or_else(|| fail(&format!("unknown optimization level '{}'\n{}", arg, USAGE)));
+                continue;
+            }
+            "--fix" => {
+                fix = true;
+                continue;
+            }
+            "--remarks" => {
+                remarks = true;
+                continue;
+            }
+            "--list-lints" => {
+                list_lints();
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option '{}'\n{}", arg, USAGE)),
+            _ => {
+                if path.replace(arg).is_some() {
+                    fail(USAGE);
+                }
+                continue;
+            }
+        };
+        let Some(name) = args.next() else {
+            fail(&format!("'{}' needs a lint name\n{}", arg, USAGE));
+        };
+        if let Err(error) = options.lint_levels.set(&name, level) {
+            fail(&error);
+        }
+    }
+
+    let Some(path) = path else { fail(USAGE) };
+    let source = std::fs::read_to_string(&path).unwrap_or_else(|error| fail(&format!("cannot read '{}': {}", path, error)));
+    let mut sink = DiagnosticSink::new();
+    let result = compile(&source, &options, &mut sink);
+    for diagnostic in sink.diagnostics() {
+        eprintln!("{}", diagnostic.render(&source));
+    }
+    if remarks {
+        for remark in sink.remarks() {
+            eprintln!("{}", remark);
+        }
+    }
+    let fixes: Vec<Fix> = sink.diagnostics().iter().flat_map(|diagnostic| diagnostic.fixes.clone()).collect();
+    if fix && !fixes.is_empty() {
+        let fixed = apply_fixes(&source, &fixes);
+        if let Err(io_error) = std::fs::write(&path, fixed) {
+            fail(&format!("cannot write '{}': {}", path, io_error));
+        }
+        for applied in &fixes {
+            eprintln!("fixed: {} at line {}, column {}", applied.message, applied.span.line, applied.span.column);
+        }
+    }
+    let Ok(output) = result else { exit(1) };
+    let bytes = match output {
+        CompileOutput::Tokens(tokens) => tokens
+            .iter()
+            .map(|token| format!("{}:{} {}\n", token.line, token.column, token.token))
+            .collect::<String>()
+            .into_bytes(),
+        CompileOutput::Ast(statements) => {
+            let text = CodeGenerator::new().codegen(statements, &mut sink).unwrap_or_else(|error| fail(&error.message));
+            format!("{}\n", text).into_bytes()
+        }
+        CompileOutput::Hir(hir) => format!("{:#?}\n", hir).into_bytes(),
+        CompileOutput::Mir(mir) => mir.to_string().into_bytes(),
+        CompileOutput::Lir(lir) => format!("{:#?}\n", lir).into_bytes(),
+        CompileOutput::Wat(text) => text.into_bytes(),
+        CompileOutput::Wasm(binary) => binary,
+    };
+    let written = match &output_path {
+        Some(output_path) => std::fs::write(output_path, bytes),
+        None => std::io::stdout().write_all(&bytes),
+    };
+    if let Err(error) = written {
+        fail(&format!("cannot write output: {}", error));
+    }
+}
+
+fn list_lints() {
+    for lint in LINTS {
+        println!("{:<20} {:<6} {}", lint.name, lint.default_level, lint.description);
+    }
+    for (group, lints) in LINT_GROUPS {
+        println!("{:<20} group  {}", group, lints.join(", "));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    exit(1)
 }
@@ -1,4 +1,6 @@
-use crate::parser::ast::Expr;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::lexer::token::{Operation, Token};
+use crate::parser::ast::{Expr, Param, Stmt};
 
 pub struct CodeGenerator {}
 
@@ -7,27 +9,242 @@ impl CodeGenerator {
         Self {}
     }
 
-    pub fn codegen(&mut self, ast: Expr) -> Result<String, String> {
-        self.codegen_expr(ast)
+    pub fn codegen(&mut self, ast: Vec<Stmt>, sink: &mut DiagnosticSink) -> Result<String, Diagnostic> {
+        self.codegen_program(ast)
+            .map_err(|error| sink.fail(Diagnostic::error(error).with_code(codes::LOWERING)))
+    }
+
+    fn codegen_program(&mut self, ast: Vec<Stmt>) -> Result<String, String> {
+        let statements = ast
+            .into_iter()
+            .map(|statement| self.codegen_stmt(statement))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(statements.join("\n"))
+    }
+
+    fn codegen_stmt(&mut self, statement: Stmt) -> Result<String, String> {
+        match statement {
+            Stmt::Block { statements, .. } => Ok(format!("(block {})", self.codegen_body(statements)?)),
+            Stmt::Expression { expression, .. } => self.codegen_expr(expression),
+            Stmt::Function {
+                name,
+                params,
+                body,
+                ..
+            } => Ok(format!(
+                "(fn {} ({}) {})",
+                name,
+                codegen_params(&params),
+                self.codegen_body(body)?
+            )),
+            Stmt::Let {
+                name, initializer, ..
+            } => match initializer {
+                Some(initializer) => Ok(format!("(let {} {})", name, self.codegen_expr(initializer)?)),
+                None => Ok(format!("(let {})", name)),
+            },
+            Stmt::Return { value, .. } => match value {
+                Some(value) => Ok(format!("(return {})", self.codegen_expr(value)?)),
+                None => Ok("(return)".to_string()),
+            },
+            Stmt::If {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.codegen_expr(condition)?;
+                let then_branch = self.codegen_body(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => Ok(format!(
+                        "(if {} ({}) ({}))",
+                        condition,
+                        then_branch,
+                        self.codegen_body(else_branch)?
+                    )),
+                    None => Ok(format!("(if {} ({}))", condition, then_branch)),
+                }
+            }
+            Stmt::While { condition, body, .. } => Ok(format!(
+                "(while {} {})",
+                self.codegen_expr(condition)?,
+                self.codegen_body(body)?
+            )),
+            Stmt::For {
+                initializer,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                let initializer = match initializer {
+                    Some(initializer) => self.codegen_stmt(*initializer)?,
+                    None => "()".to_string(),
+                };
+                let condition = match condition {
+                    Some(condition) => self.codegen_expr(condition)?,
+                    None => "()".to_string(),
+                };
+                let increment = match increment {
+                    Some(increment) => self.codegen_expr(increment)?,
+                    None => "()".to_string(),
+                };
+                Ok(format!("(for {} {} {} {})", initializer, condition, increment, self.codegen_body(body)?))
+            }
+            Stmt::Break { .. } => Ok("(break)".to_string()),
+            Stmt::Continue { .. } => Ok("(continue)".to_string()),
+            Stmt::Const { name, initializer, .. } => {
+                Ok(format!("(const {} {})", name, self.codegen_expr(initializer)?))
+            }
+            Stmt::Static { name, initializer, .. } => {
+                Ok(format!("(static {} {})", name, self.codegen_expr(initializer)?))
+            }
+            Stmt::TypeAlias { name, .. } => Ok(format!("(alias {})", name)),
+            Stmt::TypeDef { name, .. } => Ok(format!("(def {})", name)),
+            Stmt::Struct { name, .. } => Ok(format!("(struct {})", name)),
+            Stmt::Enum { name, .. } => Ok(format!("(enum {})", name)),
+            Stmt::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => Ok(format!(
+                "(#if {} ({}) ({}))",
+                self.codegen_expr(condition)?,
+                self.codegen_body(then_branch)?,
+                self.codegen_body(else_branch)?
+            )),
+        }
+    }
+
+    fn codegen_body(&mut self, statements: Vec<Stmt>) -> Result<String, String> {
+        let statements = statements
+            .into_iter()
+            .map(|statement| self.codegen_stmt(statement))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(statements.join(" "))
     }
 
     fn codegen_expr(&mut self, expr: Expr) -> Result<String, String> {
         match expr {
+            Expr::Assign { name, value, .. } => {
+                let value = self.codegen_expr(*value)?;
+                Ok(format!("(= {} {})", name, value))
+            }
+            Expr::CompoundAssign { name, operator, value, .. } => {
+                let value = self.codegen_expr(*value)?;
+                Ok(format!("({}Assign {} {})", operator, name, value))
+            }
             Expr::Binary {
                 left,
                 operator,
                 right,
+                ..
             } => {
                 let left = self.codegen_expr(*left)?;
                 let right = self.codegen_expr(*right)?;
                 Ok(format!("({} {} {})", operator, left, right))
             }
-            Expr::Grouping { expression } => self.codegen_expr(*expression),
-            Expr::Literal { value } => Ok(value.to_string()),
-            Expr::Unary { operator, right } => {
+            Expr::Call { callee, arguments, .. } => {
+                let mut parts = vec![self.codegen_expr(*callee)?];
+                for argument in arguments {
+                    parts.push(self.codegen_expr(argument)?);
+                }
+                Ok(format!("(call {})", parts.join(" ")))
+            }
+            Expr::Cast { expr, .. } => Ok(format!("(as {})", self.codegen_expr(*expr)?)),
+            Expr::ArrayLiteral { elements, .. } => {
+                let elements = elements
+                    .into_iter()
+                    .map(|element| self.codegen_expr(element))
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(format!("(array {})", elements.join(" ")))
+            }
+            Expr::StructLiteral { name, fields, .. } => {
+                let mut parts = vec![name];
+                for field in fields {
+                    parts.push(format!("({} {})", field.name, self.codegen_expr(field.value)?));
+                }
+                Ok(format!("(struct {})", parts.join(" ")))
+            }
+            Expr::EnumVariant { enum_name, variant, .. } => Ok(format!("{}::{}", enum_name, variant)),
+            Expr::Field { object, name, .. } => Ok(format!("(. {} {})", self.codegen_expr(*object)?, name)),
+            Expr::Index { object, index, .. } => {
+                let object = self.codegen_expr(*object)?;
+                let index = self.codegen_expr(*index)?;
+                Ok(format!("([] {} {})", object, index))
+            }
+            Expr::SetField {
+                object,
+                name,
+                operator,
+                value,
+                ..
+            } => {
+                let object = self.codegen_expr(*object)?;
+                let value = self.codegen_expr(*value)?;
+                let operator = operator.map_or("=".to_string(), |operator| format!("{}Assign", operator));
+                Ok(format!("({} (. {} {}) {})", operator, object, name, value))
+            }
+            Expr::SetIndex {
+                object,
+                index,
+                operator,
+                value,
+                ..
+            } => {
+                let object = self.codegen_expr(*object)?;
+                let index = self.codegen_expr(*index)?;
+                let value = self.codegen_expr(*value)?;
+                let operator = operator.map_or("=".to_string(), |operator| format!("{}Assign", operator));
+                Ok(format!("({} ([] {} {}) {})", operator, object, index, value))
+            }
+            Expr::Grouping { expression, .. } => self.codegen_expr(*expression),
+            Expr::Lambda { params, body, .. } => {
+                Ok(format!("(lambda ({}) {})", codegen_params(&params), self.codegen_body(body)?))
+            }
+            Expr::Literal { value, .. } => Ok(value.to_string()),
+            Expr::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.codegen_expr(*left)?;
+                let right = self.codegen_expr(*right)?;
+                Ok(format!("({} {} {})", operator, left, right))
+            }
+            Expr::Ternary {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                let condition = self.codegen_expr(*condition)?;
+                let then_branch = self.codegen_expr(*then_branch)?;
+                let else_branch = self.codegen_expr(*else_branch)?;
+                Ok(format!("(Ternary {} {} {})", condition, then_branch, else_branch))
+            }
+            Expr::Unary { operator, right, .. } => {
                 let right = self.codegen_expr(*right)?;
                 Ok(format!("({} {})", operator, right))
             }
+            Expr::Update { name, operator, prefix, .. } => {
+                let update = match operator {
+                    Token::Operation(Operation::Add) => "Increment",
+                    _ => "Decrement",
+                };
+                Ok(format!("({}{} {})", if prefix { "" } else { "Post" }, update, name))
+            }
+            Expr::Variable { name, .. } => Ok(name),
         }
     }
 }
+
+fn codegen_params(params: &[Param]) -> String {
+    params
+        .iter()
+        .map(|param| param.name.clone())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1 +1,2 @@
 pub mod codegen;
+pub mod wat;
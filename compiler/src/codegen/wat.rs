@@ -0,0 +1,530 @@
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::lir::lir::{
+    LirBlock, LirConstant, LirFunction, LirInstruction, LirInstructionKind, LirModule, LirOperand, LirTerminator, LirType,
+};
+
+/// Emits a LIR module as a WebAssembly text module.
+///
+/// Registers become locals. Functions with more than one block run a
+/// dispatch loop: `$block` holds the label to run next and a `br_table`
+/// jumps to it, which handles any control-flow graph without restructuring.
+/// Closures are called through the module's function table.
+#[derive(Default)]
+pub struct WatGenerator {
+    output: String,
+}
+
+impl WatGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn generate(&mut self, module: &LirModule, sink: &mut DiagnosticSink) -> Result<String, Diagnostic> {
+        self.generate_module(module)
+            .map_err(|error| sink.fail(Diagnostic::error(error).with_code(codes::LOWERING)))
+    }
+
+    fn generate_module(&mut self, module: &LirModule) -> Result<String, String> {
+        self.output.clear();
+        self.line(0, "(module");
+        self.line(1, "(memory (export \"memory\") 1)");
+        for segment in &module.data {
+            let bytes: String = segment.bytes.iter().map(|byte| format!("\\{:02x}", byte)).collect();
+            self.line(1, &format!("(data (i32.const {}) \"{}\")", segment.offset, bytes));
+        }
+        // The heap starts past the statics; address 0 is never handed out so
+        // that it can act as a null pointer.
+        self.line(1, &format!("(global $heap (mut i32) (i32.const {}))", module.heap_start));
+        if !module.table.is_empty() {
+            self.line(1, &format!("(table {} funcref)", module.table.len()));
+            let elements: Vec<String> = module.table.iter().map(|&function| format!("$f{}", function)).collect();
+            self.line(1, &format!("(elem (i32.const 0) func {})", elements.join(" ")));
+        }
+
+        for (index, function) in module.functions.iter().enumerate() {
+            self.function(index, function, module)?;
+        }
+        if uses(module, |kind| matches!(kind, LirInstructionKind::Alloc(_) | LirInstructionKind::Concat)) {
+            self.alloc_function();
+        }
+        if uses(module, |kind| matches!(kind, LirInstructionKind::Concat)) {
+            self.concat_function();
+        }
+        if uses(module, |kind| matches!(kind, LirInstructionKind::StringEq)) {
+            self.string_eq_function();
+        }
+        if uses(module, |kind| matches!(kind, LirInstructionKind::Element)) {
+            self.element_function();
+        }
+
+        self.line(1, &format!("(export \"main\" (func $f{}))", module.entry));
+        for (index, function) in module.functions.iter().enumerate() {
+            if let Some(name) = &function.attributes.export {
+                self.line(1, &format!("(export \"{}\" (func $f{}))", name.escape_default(), index));
+            }
+        }
+        self.line(0, ")");
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    fn function(&mut self, index: usize, function: &LirFunction, module: &LirModule) -> Result<(), String> {
+        let mut signature = format!("(func $f{}", index);
+        for register in 0..function.params {
+            signature.push_str(&format!(" (param $r{} {})", register, wasm_type(function.registers[register])));
+        }
+        if let Some(result) = function.result {
+            signature.push_str(&format!(" (result {})", wasm_type(result)));
+        }
+        self.line(1, &format!("{} ;; {}", signature, function.name));
+        for (register, ty) in function.registers.iter().enumerate().skip(function.params) {
+            self.line(2, &format!("(local $r{} {})", register, wasm_type(*ty)));
+        }
+
+        if function.blocks.len() == 1 {
+            self.block(2, &function.blocks[0], function, module)?;
+        } else {
+            self.line(2, "(local $block i32)");
+            self.line(2, "(loop $dispatch");
+            for block in function.blocks.iter().rev() {
+                self.line(3, &format!("(block $b{}", block.label));
+            }
+            let labels: Vec<String> = function.blocks.iter().map(|block| format!("$b{}", block.label)).collect();
+            self.line(4, &format!("(br_table {} (local.get $block))", labels.join(" ")));
+            for block in &function.blocks {
+                self.line(3, ")");
+                self.block(3, block, function, module)?;
+            }
+            self.line(2, ")");
+            self.line(2, "unreachable");
+        }
+        self.line(1, ")");
+        Ok(())
+    }
+
+    /// `$alloc` bump-allocates the number of bytes in its parameter and
+    /// grows memory by as many pages as the heap then reaches past its end.
+    /// Memory is never freed.
+    fn alloc_function(&mut self) {
+        self.line(1, "(func $alloc (param $size i32) (result i32)");
+        self.line(2, "(local $address i32)");
+        self.line(2, "global.get $heap");
+        self.line(2, "local.tee $address");
+        self.line(2, "local.get $size");
+        self.line(2, "i32.add");
+        self.line(2, "global.set $heap");
+        self.line(2, "(block $fits");
+        self.line(3, "global.get $heap");
+        self.line(3, "memory.size");
+        self.line(3, "i32.const 16");
+        self.line(3, "i32.shl");
+        self.line(3, "i32.le_u");
+        self.line(3, "br_if $fits");
+        self.line(3, ";; The pages needed, rounded up.");
+        self.line(3, "global.get $heap");
+        self.line(3, "memory.size");
+        self.line(3, "i32.const 16");
+        self.line(3, "i32.shl");
+        self.line(3, "i32.sub");
+        self.line(3, "i32.const 65535");
+        self.line(3, "i32.add");
+        self.line(3, "i32.const 16");
+        self.line(3, "i32.shr_u");
+        self.line(3, "memory.grow");
+        self.line(3, "i32.const -1");
+        self.line(3, "i32.ne");
+        self.line(3, "br_if $fits");
+        self.line(3, "unreachable");
+        self.line(2, ")");
+        self.line(2, "local.get $address");
+        self.line(1, ")");
+    }
+
+    /// `$concat` allocates a string holding the bytes of its first parameter
+    /// followed by those of its second. Allocations are kept 8-byte aligned.
+    fn concat_function(&mut self) {
+        self.line(1, "(func $concat (param $left i32) (param $right i32) (result i32)");
+        self.line(2, "(local $left_length i32)");
+        self.line(2, "(local $right_length i32)");
+        self.line(2, "(local $string i32)");
+        self.line(2, "local.get $left");
+        self.line(2, "i32.load");
+        self.line(2, "local.set $left_length");
+        self.line(2, "local.get $right");
+        self.line(2, "i32.load");
+        self.line(2, "local.set $right_length");
+        self.line(2, "local.get $left_length");
+        self.line(2, "local.get $right_length");
+        self.line(2, "i32.add");
+        self.line(2, "i32.const 11");
+        self.line(2, "i32.add");
+        self.line(2, "i32.const -8");
+        self.line(2, "i32.and");
+        self.line(2, "call $alloc");
+        self.line(2, "local.tee $string");
+        self.line(2, "local.get $left_length");
+        self.line(2, "local.get $right_length");
+        self.line(2, "i32.add");
+        self.line(2, "i32.store");
+        self.line(2, "local.get $string");
+        self.line(2, "i32.const 4");
+        self.line(2, "i32.add");
+        self.line(2, "local.get $left");
+        self.line(2, "i32.const 4");
+        self.line(2, "i32.add");
+        self.line(2, "local.get $left_length");
+        self.line(2, "memory.copy");
+        self.line(2, "local.get $string");
+        self.line(2, "i32.const 4");
+        self.line(2, "i32.add");
+        self.line(2, "local.get $left_length");
+        self.line(2, "i32.add");
+        self.line(2, "local.get $right");
+        self.line(2, "i32.const 4");
+        self.line(2, "i32.add");
+        self.line(2, "local.get $right_length");
+        self.line(2, "memory.copy");
+        self.line(2, "local.get $string");
+        self.line(1, ")");
+    }
+
+    /// `$element` returns the address of the element of an array at an `i64`
+    /// index, trapping unless the index is below the length stored in the
+    /// array's first slot. Negative indices compare as large unsigned ones.
+    fn element_function(&mut self) {
+        self.line(1, "(func $element (param $array i32) (param $index i64) (result i32)");
+        self.line(2, "local.get $index");
+        self.line(2, "local.get $array");
+        self.line(2, "i32.load");
+        self.line(2, "i64.extend_i32_u");
+        self.line(2, "i64.ge_u");
+        self.line(2, "if");
+        self.line(3, "unreachable");
+        self.line(2, "end");
+        self.line(2, "local.get $array");
+        self.line(2, "local.get $index");
+        self.line(2, "i32.wrap_i64");
+        self.line(2, "i32.const 3");
+        self.line(2, "i32.shl");
+        self.line(2, "i32.add");
+        self.line(2, "i32.const 8");
+        self.line(2, "i32.add");
+        self.line(1, ")");
+    }
+
+    /// `$string_eq` compares two strings byte by byte, returning 1 when they
+    /// have the same length and contents.
+    fn string_eq_function(&mut self) {
+        self.line(1, "(func $string_eq (param $left i32) (param $right i32) (result i32)");
+        self.line(2, "(local $length i32)");
+        self.line(2, "(local $index i32)");
+        self.line(2, "local.get $left");
+        self.line(2, "i32.load");
+        self.line(2, "local.tee $length");
+        self.line(2, "local.get $right");
+        self.line(2, "i32.load");
+        self.line(2, "i32.ne");
+        self.line(2, "if");
+        self.line(3, "i32.const 0");
+        self.line(3, "return");
+        self.line(2, "end");
+        self.line(2, "(block $done");
+        self.line(3, "(loop $bytes");
+        self.line(4, "local.get $index");
+        self.line(4, "local.get $length");
+        self.line(4, "i32.ge_u");
+        self.line(4, "br_if $done");
+        self.line(4, "local.get $left");
+        self.line(4, "local.get $index");
+        self.line(4, "i32.add");
+        self.line(4, "i32.load8_u offset=4");
+        self.line(4, "local.get $right");
+        self.line(4, "local.get $index");
+        self.line(4, "i32.add");
+        self.line(4, "i32.load8_u offset=4");
+        self.line(4, "i32.ne");
+        self.line(4, "if");
+        self.line(5, "i32.const 0");
+        self.line(5, "return");
+        self.line(4, "end");
+        self.line(4, "local.get $index");
+        self.line(4, "i32.const 1");
+        self.line(4, "i32.add");
+        self.line(4, "local.set $index");
+        self.line(4, "br $bytes");
+        self.line(3, ")");
+        self.line(2, ")");
+        self.line(2, "i32.const 1");
+        self.line(1, ")");
+    }
+
+    fn block(&mut self, depth: usize, block: &LirBlock, function: &LirFunction, module: &LirModule) -> Result<(), String> {
+        self.line(depth, &format!(";; block {}", block.label));
+        for instruction in &block.instructions {
+            self.instruction(depth, instruction, function, module)?;
+        }
+
+        match &block.terminator {
+            LirTerminator::Return(value) => {
+                if let Some(value) = value {
+                    self.operand(depth, value);
+                }
+                self.line(depth, "return");
+            }
+            LirTerminator::Jump(target) => {
+                self.line(depth, &format!("i32.const {}", target));
+                self.line(depth, "local.set $block");
+                self.line(depth, "br $dispatch");
+            }
+            LirTerminator::Branch(condition, then_block, else_block) => {
+                self.line(depth, &format!("i32.const {}", then_block));
+                self.line(depth, &format!("i32.const {}", else_block));
+                self.operand(depth, condition);
+                self.line(depth, "select");
+                self.line(depth, "local.set $block");
+                self.line(depth, "br $dispatch");
+            }
+        }
+        Ok(())
+    }
+
+    fn instruction(
+        &mut self,
+        depth: usize,
+        instruction: &LirInstruction,
+        function: &LirFunction,
+        module: &LirModule,
+    ) -> Result<(), String> {
+        let ty = wasm_type(instruction.ty);
+        let is_float = instruction.ty.is_float();
+        match &instruction.kind {
+            LirInstructionKind::Add
+            | LirInstructionKind::Sub
+            | LirInstructionKind::Mul
+            | LirInstructionKind::Div
+            | LirInstructionKind::DivU
+            | LirInstructionKind::RemU
+            | LirInstructionKind::Eq
+            | LirInstructionKind::Ne
+            | LirInstructionKind::Gt
+            | LirInstructionKind::Lt
+            | LirInstructionKind::Ge
+            | LirInstructionKind::Le
+            | LirInstructionKind::GtU
+            | LirInstructionKind::LtU
+            | LirInstructionKind::GeU
+            | LirInstructionKind::LeU
+            | LirInstructionKind::Min
+            | LirInstructionKind::Max => {
+                let op = match (&instruction.kind, is_float) {
+                    (LirInstructionKind::Add, _) => "add",
+                    (LirInstructionKind::Sub, _) => "sub",
+                    (LirInstructionKind::Mul, _) => "mul",
+                    (LirInstructionKind::Div, true) => "div",
+                    (LirInstructionKind::Div, false) => "div_s",
+                    (LirInstructionKind::DivU, _) => "div_u",
+                    (LirInstructionKind::RemU, _) => "rem_u",
+                    (LirInstructionKind::Eq, _) => "eq",
+                    (LirInstructionKind::Ne, _) => "ne",
+                    (LirInstructionKind::Gt, true) => "gt",
+                    (LirInstructionKind::Gt, false) => "gt_s",
+                    (LirInstructionKind::Lt, true) => "lt",
+                    (LirInstructionKind::Lt, false) => "lt_s",
+                    (LirInstructionKind::Ge, true) => "ge",
+                    (LirInstructionKind::Ge, false) => "ge_s",
+                    (LirInstructionKind::Le, true) => "le",
+                    (LirInstructionKind::Le, false) => "le_s",
+                    (LirInstructionKind::GtU, _) => "gt_u",
+                    (LirInstructionKind::LtU, _) => "lt_u",
+                    (LirInstructionKind::GeU, _) => "ge_u",
+                    (LirInstructionKind::LeU, _) => "le_u",
+                    (LirInstructionKind::Min, _) => "min",
+                    (LirInstructionKind::Max, _) => "max",
+                    _ => unreachable!("only binary operators reach this arm"),
+                };
+                self.operands(depth, &instruction.operands);
+                self.line(depth, &format!("{}.{}", ty, op));
+            }
+            LirInstructionKind::Neg => {
+                if is_float {
+                    self.operands(depth, &instruction.operands);
+                    self.line(depth, &format!("{}.neg", ty));
+                } else {
+                    self.line(depth, &format!("{}.const 0", ty));
+                    self.operands(depth, &instruction.operands);
+                    self.line(depth, &format!("{}.sub", ty));
+                }
+            }
+            LirInstructionKind::Rem if is_float => {
+                // WebAssembly has no float remainder: a - trunc(a / b) * b.
+                let [dividend, divisor] = instruction.operands.as_slice() else {
+                    return Err("Remainder needs two operands".to_string());
+                };
+                self.operand(depth, dividend);
+                self.operand(depth, dividend);
+                self.operand(depth, divisor);
+                self.line(depth, &format!("{}.div", ty));
+                self.line(depth, &format!("{}.trunc", ty));
+                self.operand(depth, divisor);
+                self.line(depth, &format!("{}.mul", ty));
+                self.line(depth, &format!("{}.sub", ty));
+            }
+            LirInstructionKind::Rem => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, &format!("{}.rem_s", ty));
+            }
+            LirInstructionKind::Not => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, "i32.eqz");
+            }
+            LirInstructionKind::Mov => self.operands(depth, &instruction.operands),
+            LirInstructionKind::Convert { from, signed } => {
+                self.operands(depth, &instruction.operands);
+                let sign = if *signed { "s" } else { "u" };
+                let from_type = wasm_type(*from);
+                let op = match (from.is_float(), is_float) {
+                    (false, false) if instruction.ty == LirType::I64 => format!("extend_{}_{}", from_type, sign),
+                    (false, false) => format!("wrap_{}", from_type),
+                    (false, true) => format!("convert_{}_{}", from_type, sign),
+                    (true, false) => format!("trunc_sat_{}_{}", from_type, sign),
+                    (true, true) if instruction.ty == LirType::F64 => format!("promote_{}", from_type),
+                    (true, true) => format!("demote_{}", from_type),
+                };
+                self.line(depth, &format!("{}.{}", ty, op));
+            }
+            LirInstructionKind::Wrap { bits, signed } => {
+                self.operands(depth, &instruction.operands);
+                if *signed {
+                    self.line(depth, &format!("{}.extend{}_s", ty, bits));
+                } else {
+                    self.line(depth, &format!("{}.const {}", ty, (1u64 << bits) - 1));
+                    self.line(depth, &format!("{}.and", ty));
+                }
+            }
+            LirInstructionKind::Call(callee) => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, &format!("call $f{}", callee));
+            }
+            LirInstructionKind::CallIndirect => {
+                let (closure, args) = instruction
+                    .operands
+                    .split_first()
+                    .ok_or_else(|| "Indirect call without a callee".to_string())?;
+                // The closure is both the first argument and where the table slot is stored.
+                self.operand(depth, closure);
+                self.operands(depth, args);
+                self.operand(depth, closure);
+                self.line(depth, "i32.load");
+
+                let mut signature = String::from("call_indirect (param i32");
+                for arg in args {
+                    signature.push(' ');
+                    signature.push_str(wasm_type(operand_type(arg, function)));
+                }
+                signature.push(')');
+                if let Some(dest) = instruction.dest {
+                    signature.push_str(&format!(" (result {})", wasm_type(function.registers[dest])));
+                }
+                self.line(depth, &signature);
+            }
+            LirInstructionKind::Alloc(size) => {
+                let dest = instruction.dest.ok_or_else(|| "Allocation without a destination".to_string())?;
+                self.line(depth, &format!("i32.const {}", size));
+                self.line(depth, "call $alloc");
+                self.line(depth, &format!("local.set $r{}", dest));
+                return Ok(());
+            }
+            LirInstructionKind::Load(offset) => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, &format!("{}.load offset={}", ty, offset));
+            }
+            LirInstructionKind::Store(offset) => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, &format!("{}.store offset={}", ty, offset));
+            }
+            LirInstructionKind::Concat => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, "call $concat");
+            }
+            LirInstructionKind::StringEq => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, "call $string_eq");
+            }
+            LirInstructionKind::Element => {
+                self.operands(depth, &instruction.operands);
+                self.line(depth, "call $element");
+            }
+        }
+
+        if let Some(dest) = instruction.dest {
+            self.line(depth, &format!("local.set $r{}", dest));
+        } else if let LirInstructionKind::Call(callee) = instruction.kind {
+            // Results of calls whose value is unused still have to be dropped.
+            if module.functions[callee].result.is_some() {
+                self.line(depth, "drop");
+            }
+        }
+        Ok(())
+    }
+
+    fn operands(&mut self, depth: usize, operands: &[LirOperand]) {
+        for operand in operands {
+            self.operand(depth, operand);
+        }
+    }
+
+    fn operand(&mut self, depth: usize, operand: &LirOperand) {
+        let text = match operand {
+            LirOperand::Register(register) => format!("local.get $r{}", register),
+            LirOperand::Constant(LirConstant::I32(value)) => format!("i32.const {}", value),
+            LirOperand::Constant(LirConstant::I64(value)) => format!("i64.const {}", value),
+            LirOperand::Constant(LirConstant::F32(value)) => format!("f32.const {}", float_literal(f64::from(*value))),
+            LirOperand::Constant(LirConstant::F64(value)) => format!("f64.const {}", float_literal(*value)),
+        };
+        self.line(depth, &text);
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        for _ in 0..depth {
+            self.output.push_str("  ");
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+}
+
+fn wasm_type(ty: LirType) -> &'static str {
+    match ty {
+        LirType::I32 => "i32",
+        LirType::I64 => "i64",
+        LirType::F32 => "f32",
+        LirType::F64 => "f64",
+    }
+}
+
+fn operand_type(operand: &LirOperand, function: &LirFunction) -> LirType {
+    match operand {
+        LirOperand::Register(register) => function.registers[*register],
+        LirOperand::Constant(constant) => constant.ty(),
+    }
+}
+
+/// Whether any function has an instruction of a kind `needs` accepts, and
+/// so needs the helper function that implements it.
+fn uses(module: &LirModule, needs: impl Fn(&LirInstructionKind) -> bool) -> bool {
+    module.functions.iter().flat_map(|function| &function.blocks).any(|block| {
+        block
+            .instructions
+            .iter()
+            .any(|instruction| needs(&instruction.kind))
+    })
+}
+
+fn float_literal(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        format!("{:?}", value)
+    }
+}
@@ -0,0 +1,416 @@
+use std::fmt::{Display, Formatter};
+
+/// A stretch of source text on one line. Lines and columns count from 1,
+/// columns in characters, as in `TokenInfo`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    /// Length in characters.
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Self { line, column, length }
+    }
+}
+
+/// An edit that replaces the text at `span` and can be applied without
+/// asking, such as the correction of a misspelled name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fix {
+    /// What the fix does, e.g. "replace `retrun` with `return`".
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Fix {
+    /// A fix that replaces a misspelled `name` at `span` with `suggestion`.
+    pub fn rename(span: Span, name: &str, suggestion: &str) -> Self {
+        Self {
+            message: format!("replace `{}` with `{}`", name, suggestion),
+            span,
+            replacement: suggestion.to_string(),
+        }
+    }
+}
+
+/// Applies fixes to `source`. A fix that overlaps one earlier in the
+/// source, or that points outside it, is skipped.
+pub fn apply_fixes(source: &str, fixes: &[Fix]) -> String {
+    let mut edits: Vec<(usize, usize, &str)> = fixes
+        .iter()
+        .filter_map(|fix| {
+            let start = offset(source, fix.span.line, fix.span.column)?;
+            let end = offset(source, fix.span.line, fix.span.column + fix.span.length)?;
+            Some((start, end, fix.replacement.as_str()))
+        })
+        .collect();
+    edits.sort_by_key(|&(start, end, _)| (start, end));
+
+    let mut fixed = String::new();
+    let mut position = 0;
+    for (start, end, replacement) in edits {
+        if start < position {
+            continue;
+        }
+        fixed.push_str(&source[position..start]);
+        fixed.push_str(replacement);
+        position = end;
+    }
+    fixed.push_str(&source[position..]);
+    fixed
+}
+
+/// The byte offset of a line and column, which may be just past the end
+/// of the line.
+fn offset(source: &str, line: usize, column: usize) -> Option<usize> {
+    let start = if line == 1 {
+        0
+    } else {
+        source.match_indices('\n').nth(line.checked_sub(2)?)?.0 + 1
+    };
+    let text = source[start..].split('\n').next().unwrap_or_default();
+    let characters = column.checked_sub(1)?;
+    match text.char_indices().nth(characters) {
+        Some((index, _)) => Some(start + index),
+        None if characters == text.chars().count() => Some(start + text.len()),
+        None => None,
+    }
+}
+
+/// The number of single-character insertions, deletions, substitutions and
+/// swaps of adjacent characters that turn `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // `rows[i][j]` is the distance between the first `i` characters of `a`
+    // and the first `j` characters of `b`.
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution.min(rows[i - 1][j] + 1).min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// The candidate closest to a misspelled `name`, if any is close enough to
+/// be what was meant: at most one edit for every three characters. Ties go
+/// to the candidate listed first.
+pub fn closest_match<'c>(name: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<&'c str> {
+    let limit = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// The help text of a diagnostic that suggests a name.
+pub fn did_you_mean(suggestion: &str) -> String {
+    format!("did you mean `{}`?", suggestion)
+}
+
+/// Error codes, which tell kinds of errors apart without matching on
+/// their messages. Lints use their names as codes instead.
+pub mod codes {
+    pub const INVALID_TOKEN: &str = "E0001";
+    pub const SYNTAX: &str = "E0002";
+    pub const UNDEFINED_NAME: &str = "E0003";
+    pub const TYPE_MISMATCH: &str = "E0004";
+    pub const IMMUTABLE_ASSIGNMENT: &str = "E0005";
+    /// Any other error found by semantic analysis.
+    pub const SEMANTIC: &str = "E0006";
+    pub const CONTROL_FLOW: &str = "E0007";
+    pub const CONST_EVALUATION: &str = "E0008";
+    /// An error raised while lowering to an intermediate representation or
+    /// generating code.
+    pub const LOWERING: &str = "E0009";
+    /// A broken invariant between compiler stages, which is a compiler bug
+    /// rather than a problem with the program.
+    pub const INTERNAL: &str = "E0010";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A span of source a diagnostic points at. The primary label marks where
+/// the problem is; secondary labels mark code that explains it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// An error or warning reported by a stage of the compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub fixes: Vec<Fix>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message.into())
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message.into())
+    }
+
+    fn new(severity: Severity, message: String) -> Self {
+        Self {
+            severity,
+            code: None,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Sets the code unless an earlier stage already has.
+    pub fn or_code(mut self, code: &str) -> Self {
+        self.code.get_or_insert_with(|| code.to_string());
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    /// Points the diagnostic at `span` unless a more precise label is
+    /// already attached.
+    pub fn or_label(self, span: Span) -> Self {
+        if self.labels.is_empty() {
+            self.with_label(span, "")
+        } else {
+            self
+        }
+    }
+
+    pub fn with_secondary_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fixes.push(fix);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The span of the primary label, if the diagnostic has one.
+    pub fn span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.primary).map(|label| label.span)
+    }
+
+    /// Renders the diagnostic with the lines of `source` its labels point
+    /// at, for printing to a terminal:
+    ///
+    /// ```text
+    /// error[E0003]: Undefined variable 'countr'
+    ///  --> 2:7
+    ///   |
+    /// 2 | while countr < 3 {
+    ///   |       ^^^^^^
+    ///   = help: did you mean `counter`?
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut text = self.severity.to_string();
+        if let Some(code) = &self.code {
+            text.push_str(&format!("[{}]", code));
+        }
+        text.push_str(&format!(": {}\n", self.message));
+
+        let lines: Vec<&str> = source.lines().collect();
+        let mut labels: Vec<&Label> = self.labels.iter().filter(|label| label.span.line >= 1).collect();
+        labels.sort_by_key(|label| (label.span.line, label.span.column));
+        let width = labels.iter().map(|label| label.span.line.to_string().len()).max().unwrap_or(0);
+        if let Some(span) = self.span().or(labels.first().map(|label| label.span)) {
+            text.push_str(&format!("{:width$}--> {}:{}\n", "", span.line, span.column));
+            text.push_str(&format!("{:width$} |\n", ""));
+        }
+        for label in &labels {
+            let line = lines.get(label.span.line - 1).copied().unwrap_or_default();
+            let marker = if label.primary { "^" } else { "-" };
+            let underline = format!(
+                "{}{} {}",
+                " ".repeat(label.span.column.saturating_sub(1)),
+                marker.repeat(label.span.length.max(1)),
+                label.message
+            );
+            text.push_str(&format!("{:>width$} | {}\n", label.span.line, line));
+            text.push_str(&format!("{:width$} | {}\n", "", underline.trim_end()));
+        }
+        for note in &self.notes {
+            text.push_str(&format!("{:width$} = note: {}\n", "", note));
+        }
+        if let Some(help) = &self.help {
+            text.push_str(&format!("{:width$} = help: {}\n", "", help));
+        }
+        text
+    }
+}
+
+/// The message with its notes and help, one per line, as errors read
+/// before they were structured.
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        if let Some(help) = &self.help {
+            write!(f, "\n  help: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Diagnostic::error(message)
+    }
+}
+
+impl From<Diagnostic> for String {
+    fn from(diagnostic: Diagnostic) -> Self {
+        diagnostic.to_string()
+    }
+}
+
+/// A note from an optimization pass about a change it made to a function,
+/// or one it considered and did not make. Remarks are not diagnostics: they
+/// say nothing about the program being wrong, and are only shown on request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remark {
+    pub pass: &'static str,
+    pub function: String,
+    pub message: String,
+    /// Whether the pass made the change; a missed remark says why not.
+    pub applied: bool,
+}
+
+/// `remark[inline]: main: inlined 'square' (cost 3)`, or `missed[...]` for
+/// a change that was not made.
+impl Display for Remark {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.applied { "remark" } else { "missed" };
+        write!(f, "{}[{}]: {}: {}", kind, self.pass, self.function, self.message)
+    }
+}
+
+/// Collects the diagnostics of a compilation from every stage, so that all
+/// errors and warnings can be reported together, along with the remarks of
+/// the optimizer.
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticSink {
+    diagnostics: Vec<Diagnostic>,
+    remarks: Vec<Remark>,
+}
+
+impl DiagnosticSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Emits an error that stops a stage and returns it, for the stage to
+    /// return in turn.
+    pub fn fail(&mut self, error: Diagnostic) -> Diagnostic {
+        self.emit(error.clone());
+        error
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.is_error())
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| !diagnostic.is_error())
+    }
+
+    /// Every diagnostic in the order the stages emitted them.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    pub fn remark(&mut self, remark: Remark) {
+        self.remarks.push(remark);
+    }
+
+    pub fn remarks(&self) -> &[Remark] {
+        &self.remarks
+    }
+}
@@ -0,0 +1,126 @@
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink, Span};
+use crate::hir::const_eval::ConstValue;
+use crate::hir::hir::HirLiteral;
+use crate::hir::lowerer::HirLowerer;
+use crate::parser::ast::{Expr, NodeId, Stmt, TypeExpr};
+use crate::semantic::analyzer::{SemanticAnalyzer, CONDITION};
+
+/// Replaces each top-level `#if` with the statements of the branch its
+/// condition selects, until none are left, so that the branches may hold
+/// `#if`s of their own. A condition may use the constants, const functions
+/// and types declared outside of the `#if`s still to be resolved: it is
+/// analyzed and evaluated as the initializer of a `bool` constant declared
+/// among them. The functions it calls are recorded in `analyzer`, for the
+/// analysis of the resolved program to count them as used.
+pub fn resolve_conditionals(
+    mut statements: Vec<Stmt>,
+    analyzer: &mut SemanticAnalyzer,
+    sink: &mut DiagnosticSink,
+) -> Result<Vec<Stmt>, Diagnostic> {
+    while let Some(index) = statements.iter().position(|statement| matches!(statement, Stmt::Conditional { .. })) {
+        let Stmt::Conditional { condition, then_branch, else_branch, span, id } = statements.remove(index) else {
+            unreachable!("the statement was matched above")
+        };
+        calls(&condition, &mut analyzer.called_by_conditions);
+        let branch = if holds(&mut statements, condition, span, id, sink)? { then_branch } else { else_branch };
+        statements.splice(index..index, branch);
+    }
+    Ok(statements)
+}
+
+/// Evaluates `condition` in the context of the items of `statements` it may
+/// use, which are moved out for the analysis and back after it.
+fn holds(
+    statements: &mut Vec<Stmt>,
+    condition: Expr,
+    span: Span,
+    id: NodeId,
+    sink: &mut DiagnosticSink,
+) -> Result<bool, Diagnostic> {
+    let (usable, others): (Vec<_>, Vec<_>) =
+        std::mem::take(statements).into_iter().enumerate().partition(|(_, statement)| usable(statement));
+    let (indices, mut context): (Vec<usize>, Vec<Stmt>) = usable.into_iter().unzip();
+    context.push(Stmt::Const {
+        attributes: Vec::new(),
+        name: CONDITION.to_string(),
+        span,
+        ty: TypeExpr::Named("bool".to_string()),
+        initializer: condition,
+        id,
+    });
+    let value = evaluate(&context, sink);
+    context.pop();
+    let mut restored: Vec<_> = indices.into_iter().zip(context).chain(others).collect();
+    restored.sort_by_key(|(index, _)| *index);
+    statements.extend(restored.into_iter().map(|(_, statement)| statement));
+    value
+}
+
+/// Analyzes and lowers `context`, and returns the value of its condition.
+/// Only errors are passed on: warnings about the items are reported when
+/// the whole program is analyzed.
+fn evaluate(context: &[Stmt], sink: &mut DiagnosticSink) -> Result<bool, Diagnostic> {
+    let mut scratch = DiagnosticSink::new();
+    let mut analyzer = SemanticAnalyzer::new();
+    let program = analyzer
+        .analyze_program(context, &mut scratch)
+        .and_then(|_| HirLowerer::new().lower_program(context, &analyzer, &mut scratch));
+    let program = match program {
+        Ok(program) => program,
+        Err(error) => {
+            scratch.errors().for_each(|error| sink.emit(error.clone()));
+            return Err(error);
+        }
+    };
+    match program.consts.iter().find(|constant| constant.name == CONDITION).map(|constant| &constant.value) {
+        Some(ConstValue::Scalar(HirLiteral::Boolean(value))) => Ok(*value),
+        _ => Err(sink.fail(
+            Diagnostic::error("Internal error: '#if' condition is not a bool".to_string()).with_code(codes::INTERNAL),
+        )),
+    }
+}
+
+/// Whether a condition may use the item `statement` declares.
+fn usable(statement: &Stmt) -> bool {
+    matches!(
+        statement,
+        Stmt::Const { .. }
+            | Stmt::Function { is_const: true, .. }
+            | Stmt::TypeAlias { .. }
+            | Stmt::TypeDef { .. }
+            | Stmt::Struct { .. }
+            | Stmt::Enum { .. }
+    )
+}
+
+/// Collects the functions a constant expression calls.
+fn calls(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Grouping { expression, .. } => calls(expression, names),
+        Expr::Unary { right, .. } => calls(right, names),
+        Expr::Cast { expr, .. } => calls(expr, names),
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            calls(left, names);
+            calls(right, names);
+        }
+        Expr::Ternary { condition, then_branch, else_branch, .. } => {
+            calls(condition, names);
+            calls(then_branch, names);
+            calls(else_branch, names);
+        }
+        Expr::Call { callee, arguments, .. } => {
+            if let Expr::Variable { name, .. } = callee.as_ref() {
+                names.push(name.clone());
+            }
+            arguments.iter().for_each(|argument| calls(argument, names));
+        }
+        Expr::StructLiteral { fields, .. } => fields.iter().for_each(|field| calls(&field.value, names)),
+        Expr::ArrayLiteral { elements, .. } => elements.iter().for_each(|element| calls(element, names)),
+        Expr::Field { object, .. } => calls(object, names),
+        Expr::Index { object, index, .. } => {
+            calls(object, names);
+            calls(index, names);
+        }
+        _ => {}
+    }
+}
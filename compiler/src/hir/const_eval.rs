@@ -0,0 +1,517 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::hir::hir::{
+    HirBinaryOp, HirExpr, HirFunction, HirLiteral, HirLogicalOp, HirPlace, HirStmt, HirStmtKind, HirUnaryOp,
+};
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+
+/// Expressions a single constant may evaluate, counting those in the const
+/// functions it calls.
+pub const STEP_LIMIT: usize = 1_000_000;
+
+/// How deeply const function calls may nest.
+pub const CALL_DEPTH_LIMIT: usize = 128;
+
+/// A value computed at compile time.
+#[derive(Debug, Clone)]
+pub enum ConstValue {
+    Scalar(HirLiteral),
+    /// The fields of a struct, in declaration order, or the elements of an
+    /// array. Like the heap objects they stand for, aggregates are shared
+    /// between copies, so a write through one copy is seen through all.
+    Aggregate(Elements),
+}
+
+/// The values an aggregate holds.
+pub type Elements = Rc<RefCell<Vec<ConstValue>>>;
+
+impl ConstValue {
+    pub fn aggregate(values: Vec<ConstValue>) -> Self {
+        ConstValue::Aggregate(Rc::new(RefCell::new(values)))
+    }
+
+    /// A copy that shares no aggregates with the original, since every use
+    /// of a constant builds its value anew.
+    pub fn deep_copy(&self) -> Self {
+        match self {
+            ConstValue::Scalar(literal) => ConstValue::Scalar(literal.clone()),
+            ConstValue::Aggregate(values) => {
+                ConstValue::aggregate(values.borrow().iter().map(ConstValue::deep_copy).collect())
+            }
+        }
+    }
+}
+
+/// What running a statement did besides its effects on locals.
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(ConstValue),
+}
+
+/// Evaluates constant initializers at compile time by interpreting their
+/// HIR, including the bodies of the `const fn`s they call.
+///
+/// Integer arithmetic is checked against the range of its type instead of
+/// wrapping as it does at run time, and evaluation is bounded by
+/// `STEP_LIMIT` and `CALL_DEPTH_LIMIT` so that a runaway `const fn` is
+/// reported rather than hanging the compiler.
+///
+/// Values are the scalars and strings of `HirLiteral`, and structs and
+/// arrays built from them, which const functions may also read and write.
+/// The discriminants of enum variants are constants too, so they may be
+/// computed like any other.
+pub struct ConstEvaluator<'a> {
+    functions: &'a [HirFunction],
+    /// Name and initializer of each `const` item.
+    constants: &'a [(String, HirExpr)],
+    values: Vec<Option<ConstValue>>,
+    /// Constants whose evaluation is in progress, to detect cycles.
+    pending: Vec<usize>,
+    steps: usize,
+    depth: usize,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    pub fn new(functions: &'a [HirFunction], constants: &'a [(String, HirExpr)]) -> Self {
+        Self {
+            functions,
+            constants,
+            values: vec![None; constants.len()],
+            pending: Vec::new(),
+            steps: 0,
+            depth: 0,
+        }
+    }
+
+    /// The value of the `const` item with the given index.
+    pub fn constant(&mut self, id: usize) -> Result<ConstValue, String> {
+        self.steps = 0;
+        self.constant_value(id)
+            .map_err(|reason| format!("Cannot evaluate constant '{}': {}", self.constants[id].0, reason))
+    }
+
+    /// Evaluates the initializer of another item, such as a `static`, which
+    /// `item` names in diagnostics.
+    pub fn evaluate(&mut self, expr: &HirExpr, item: &str) -> Result<ConstValue, String> {
+        self.steps = 0;
+        self.expr(expr, &mut Vec::new())
+            .map_err(|reason| format!("Cannot evaluate {}: {}", item, reason))
+    }
+
+    fn constant_value(&mut self, id: usize) -> Result<ConstValue, String> {
+        if let Some(value) = &self.values[id] {
+            return Ok(value.clone());
+        }
+        let (name, initializer) = &self.constants[id];
+        if self.pending.contains(&id) {
+            return Err(format!("the value of '{}' depends on itself", name));
+        }
+        self.pending.push(id);
+        let value = self.expr(initializer, &mut Vec::new());
+        self.pending.pop();
+        let value = value?;
+        self.values[id] = Some(value.clone());
+        Ok(value)
+    }
+
+    fn call(&mut self, function: usize, args: Vec<ConstValue>) -> Result<ConstValue, String> {
+        let callee = &self.functions[function];
+        if !callee.is_const {
+            return Err(format!("'{}' is not a const function", callee.name));
+        }
+        if self.depth == CALL_DEPTH_LIMIT {
+            return Err(format!("const function calls nest deeper than {}", CALL_DEPTH_LIMIT));
+        }
+        let mut locals = vec![None; callee.locals.len()];
+        for (local, value) in locals.iter_mut().zip(args) {
+            *local = Some(value);
+        }
+
+        self.depth += 1;
+        let flow = self.block(&callee.body, &mut locals);
+        self.depth -= 1;
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(ConstValue::Scalar(HirLiteral::Null)),
+        }
+    }
+
+    fn block(&mut self, body: &[HirStmt], locals: &mut Vec<Option<ConstValue>>) -> Result<Flow, String> {
+        for statement in body {
+            match &statement.kind {
+                HirStmtKind::Let { local, value } => {
+                    locals[*local] = match value {
+                        Some(value) => Some(self.expr(value, locals)?),
+                        None => None,
+                    };
+                }
+                HirStmtKind::Expr(expr) => match self.effect(expr, locals)? {
+                    Flow::Next => {}
+                    flow => return Ok(flow),
+                },
+                HirStmtKind::Return(value) => {
+                    let value = match value {
+                        Some(value) => self.expr(value, locals)?,
+                        None => ConstValue::Scalar(HirLiteral::Null),
+                    };
+                    return Ok(Flow::Return(value));
+                }
+                HirStmtKind::Loop { body, continuing } => loop {
+                    // Counted so that an empty infinite loop still runs out of steps.
+                    self.step()?;
+                    match self.block(body, locals)? {
+                        Flow::Next | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                    }
+                    match self.block(continuing, locals)? {
+                        Flow::Next => {}
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                },
+                HirStmtKind::Break => return Ok(Flow::Break),
+                HirStmtKind::Continue => return Ok(Flow::Continue),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Evaluates an expression statement, where an `if` may run a branch
+    /// that jumps.
+    fn effect(&mut self, expr: &HirExpr, locals: &mut Vec<Option<ConstValue>>) -> Result<Flow, String> {
+        match expr {
+            HirExpr::If { condition, then_branch, else_branch, .. } => {
+                let branch = if boolean(self.expr(condition, locals)?)? { then_branch } else { else_branch };
+                self.effect(branch, locals)
+            }
+            HirExpr::Block { statements, value, .. } => {
+                match self.block(statements, locals)? {
+                    Flow::Next => {}
+                    flow => return Ok(flow),
+                }
+                if let Some(value) = value {
+                    self.expr(value, locals)?;
+                }
+                Ok(Flow::Next)
+            }
+            _ => {
+                self.expr(expr, locals)?;
+                Ok(Flow::Next)
+            }
+        }
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return Err(format!("evaluation takes more than {} steps", STEP_LIMIT));
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &HirExpr, locals: &mut Vec<Option<ConstValue>>) -> Result<ConstValue, String> {
+        self.step()?;
+
+        match expr {
+            HirExpr::Literal { value, .. } => Ok(ConstValue::Scalar(value.clone())),
+            HirExpr::Const { id, .. } => Ok(self.constant_value(*id)?.deep_copy()),
+            HirExpr::Variable { place, .. } => match place {
+                HirPlace::Local(local) => locals[*local]
+                    .clone()
+                    .ok_or_else(|| "a variable is read before it is assigned".to_string()),
+                HirPlace::Captured(_) => Err("captured variables are not constant".to_string()),
+                HirPlace::Static(_) => Err("reading a static is not a constant operation".to_string()),
+            },
+            HirExpr::Assign { place, value, .. } => {
+                let value = self.expr(value, locals)?;
+                match place {
+                    HirPlace::Local(local) => locals[*local] = Some(value.clone()),
+                    _ => return Err("assigning to a static is not a constant operation".to_string()),
+                }
+                Ok(value)
+            }
+            HirExpr::Binary { op, left, right, .. } => {
+                let left = scalar(self.expr(left, locals)?)?;
+                let right = scalar(self.expr(right, locals)?)?;
+                binary(op, left, right).map(ConstValue::Scalar)
+            }
+            HirExpr::Unary { op, expr: operand, .. } => {
+                let value = scalar(self.expr(operand, locals)?)?;
+                let value = match (op, value) {
+                    // A negative literal such as `-128` is one value even if
+                    // its magnitude alone does not fit in the type.
+                    (HirUnaryOp::Negate, HirLiteral::Integer(bits, int))
+                        if matches!(**operand, HirExpr::Literal { .. }) =>
+                    {
+                        Ok(HirLiteral::Integer(bits.wrapping_neg(), int))
+                    }
+                    (HirUnaryOp::Negate, HirLiteral::Integer(bits, int)) => {
+                        let value = decode(bits, int);
+                        checked(-value, int).ok_or_else(|| format!("integer overflow: -{} does not fit in {}", value, int.name()))
+                    }
+                    (HirUnaryOp::Negate, HirLiteral::Float(value, float)) => Ok(HirLiteral::Float(-value, float)),
+                    (HirUnaryOp::Not, HirLiteral::Boolean(value)) => Ok(HirLiteral::Boolean(!value)),
+                    (op, value) => Err(format!("cannot apply {:?} to {:?}", op, value)),
+                };
+                value.map(ConstValue::Scalar)
+            }
+            HirExpr::Logical { op, left, right, .. } => {
+                let left = boolean(self.expr(left, locals)?)?;
+                let value = match (op, left) {
+                    (HirLogicalOp::And, false) => false,
+                    (HirLogicalOp::Or, true) => true,
+                    _ => boolean(self.expr(right, locals)?)?,
+                };
+                Ok(ConstValue::Scalar(HirLiteral::Boolean(value)))
+            }
+            HirExpr::If { condition, then_branch, else_branch, .. } => {
+                if boolean(self.expr(condition, locals)?)? {
+                    self.expr(then_branch, locals)
+                } else {
+                    self.expr(else_branch, locals)
+                }
+            }
+            HirExpr::Block { statements, value, .. } => {
+                if !matches!(self.block(statements, locals)?, Flow::Next) {
+                    return Err("control flow leaves the middle of an expression".to_string());
+                }
+                match value {
+                    Some(value) => self.expr(value, locals),
+                    None => Ok(ConstValue::Scalar(HirLiteral::Null)),
+                }
+            }
+            HirExpr::Call { function, args, .. } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.expr(arg, locals)?);
+                }
+                self.call(*function, values)
+            }
+            HirExpr::Cast { expr: operand, expr_type, .. } => {
+                let value = scalar(self.expr(operand, locals)?)?;
+                cast(value, expr_type).map(ConstValue::Scalar)
+            }
+            HirExpr::CallIndirect { .. } => Err("calling a function value is not a constant operation".to_string()),
+            HirExpr::Closure { .. } => Err("creating a closure is not a constant operation".to_string()),
+            HirExpr::Aggregate { elements, .. } => {
+                let mut values = Vec::new();
+                for element in elements {
+                    values.push(self.expr(element, locals)?);
+                }
+                Ok(ConstValue::aggregate(values))
+            }
+            HirExpr::Field { object, index, .. } => {
+                let fields = aggregate(self.expr(object, locals)?)?;
+                let value = fields.borrow()[*index].clone();
+                Ok(value)
+            }
+            HirExpr::Index { array, index, .. } => {
+                let (elements, index) = self.element(array, index, locals)?;
+                let value = elements.borrow()[index].clone();
+                Ok(value)
+            }
+            HirExpr::SetField { object, index, value, .. } => {
+                let fields = aggregate(self.expr(object, locals)?)?;
+                let value = self.expr(value, locals)?;
+                fields.borrow_mut()[*index] = value.clone();
+                Ok(value)
+            }
+            HirExpr::SetIndex { array, index, value, .. } => {
+                let (elements, index) = self.element(array, index, locals)?;
+                let value = self.expr(value, locals)?;
+                elements.borrow_mut()[index] = value.clone();
+                Ok(value)
+            }
+        }
+    }
+
+    /// The elements of an array and an index into them, which must be in
+    /// bounds.
+    fn element(
+        &mut self,
+        array: &HirExpr,
+        index: &HirExpr,
+        locals: &mut Vec<Option<ConstValue>>,
+    ) -> Result<(Elements, usize), String> {
+        let elements = aggregate(self.expr(array, locals)?)?;
+        let index = match scalar(self.expr(index, locals)?)? {
+            HirLiteral::Integer(bits, int) => decode(bits, int),
+            other => return Err(format!("expected an index, found {:?}", other)),
+        };
+        let length = elements.borrow().len();
+        match usize::try_from(index) {
+            Ok(index) if index < length => Ok((elements, index)),
+            _ => Err(format!("index {} is out of bounds for an array of length {}", index, length)),
+        }
+    }
+}
+
+fn binary(op: &HirBinaryOp, left: HirLiteral, right: HirLiteral) -> Result<HirLiteral, String> {
+    let symbol = match op {
+        HirBinaryOp::Add => "+",
+        HirBinaryOp::Subtract => "-",
+        HirBinaryOp::Multiply => "*",
+        HirBinaryOp::Divide => "/",
+        HirBinaryOp::Remainder => "%",
+        HirBinaryOp::Equal => "==",
+        HirBinaryOp::NotEqual => "!=",
+        HirBinaryOp::GreaterThan => ">",
+        HirBinaryOp::LessThan => "<",
+        HirBinaryOp::GreaterThanOrEqual => ">=",
+        HirBinaryOp::LessThanOrEqual => "<=",
+    };
+    let comparison = |ordering: Option<std::cmp::Ordering>| {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        let result = match op {
+            HirBinaryOp::Equal => ordering == Some(Equal),
+            HirBinaryOp::NotEqual => ordering != Some(Equal),
+            HirBinaryOp::GreaterThan => ordering == Some(Greater),
+            HirBinaryOp::LessThan => ordering == Some(Less),
+            HirBinaryOp::GreaterThanOrEqual => matches!(ordering, Some(Greater | Equal)),
+            HirBinaryOp::LessThanOrEqual => matches!(ordering, Some(Less | Equal)),
+            _ => unreachable!("arithmetic is handled separately"),
+        };
+        Ok(HirLiteral::Boolean(result))
+    };
+    let arithmetic = matches!(
+        op,
+        HirBinaryOp::Add | HirBinaryOp::Subtract | HirBinaryOp::Multiply | HirBinaryOp::Divide | HirBinaryOp::Remainder
+    );
+
+    match (left, right) {
+        (HirLiteral::Integer(left, int), HirLiteral::Integer(right, _)) => {
+            let (left, right) = (decode(left, int), decode(right, int));
+            if !arithmetic {
+                return comparison(left.partial_cmp(&right));
+            }
+            if right == 0 && matches!(op, HirBinaryOp::Divide | HirBinaryOp::Remainder) {
+                return Err(format!("division by zero in `{} {} 0`", left, symbol));
+            }
+            let result = match op {
+                HirBinaryOp::Add => left.checked_add(right),
+                HirBinaryOp::Subtract => left.checked_sub(right),
+                HirBinaryOp::Multiply => left.checked_mul(right),
+                HirBinaryOp::Divide => left.checked_div(right),
+                _ => left.checked_rem(right),
+            };
+            result.and_then(|result| checked(result, int)).ok_or_else(|| {
+                format!(
+                    "integer overflow: `{} {} {}` does not fit in {}",
+                    left,
+                    symbol,
+                    right,
+                    int.name()
+                )
+            })
+        }
+        (HirLiteral::Float(left, float), HirLiteral::Float(right, _)) => {
+            let result = match op {
+                HirBinaryOp::Add => left + right,
+                HirBinaryOp::Subtract => left - right,
+                HirBinaryOp::Multiply => left * right,
+                HirBinaryOp::Divide => left / right,
+                HirBinaryOp::Remainder => remainder(left, right, float),
+                _ => return comparison(left.partial_cmp(&right)),
+            };
+            Ok(HirLiteral::Float(round(result, float), float))
+        }
+        (HirLiteral::String(left), HirLiteral::String(right)) => match op {
+            HirBinaryOp::Add => Ok(HirLiteral::String(left + &right)),
+            _ if !arithmetic => comparison(left.partial_cmp(&right)),
+            _ => Err(format!("cannot apply '{}' to strings", symbol)),
+        },
+        (HirLiteral::Boolean(left), HirLiteral::Boolean(right)) if !arithmetic => comparison(left.partial_cmp(&right)),
+        (left, right) => Err(format!("cannot apply '{}' to {:?} and {:?}", symbol, left, right)),
+    }
+}
+
+/// Converts a value as `expr as ty` does at run time: integers wrap, and
+/// floats saturate at the bounds of an integer type.
+pub fn cast(value: HirLiteral, ty: &Type) -> Result<HirLiteral, String> {
+    match (value, ty.representation()) {
+        (HirLiteral::Integer(bits, from), Type::Integer(int)) => Ok(wrap(decode(bits, from), *int)),
+        (HirLiteral::Boolean(value), Type::Integer(int)) => Ok(wrap(value as i128, *int)),
+        (HirLiteral::Float(value, _), Type::Integer(int)) => {
+            let min = if int.is_signed() { -(int.min_magnitude() as i128) } else { 0 };
+            // `as` saturates at the bounds of i128 and maps NaN to zero.
+            let value = (value as i128).clamp(min, int.max() as i128);
+            Ok(wrap(value, *int))
+        }
+        // Rounded once, straight to the target type.
+        (HirLiteral::Integer(bits, from), Type::Float(FloatType::F32)) => {
+            Ok(HirLiteral::Float(decode(bits, from) as f32 as f64, FloatType::F32))
+        }
+        (HirLiteral::Integer(bits, from), Type::Float(FloatType::F64)) => {
+            Ok(HirLiteral::Float(decode(bits, from) as f64, FloatType::F64))
+        }
+        // The value is only as precise as its own type, whatever it was computed in.
+        (HirLiteral::Float(value, from), Type::Float(float)) => {
+            Ok(HirLiteral::Float(round(round(value, from), *float), *float))
+        }
+        (value, _) => Ok(value),
+    }
+}
+
+fn boolean(value: ConstValue) -> Result<bool, String> {
+    match scalar(value)? {
+        HirLiteral::Boolean(value) => Ok(value),
+        other => Err(format!("expected a bool, found {:?}", other)),
+    }
+}
+
+fn scalar(value: ConstValue) -> Result<HirLiteral, String> {
+    match value {
+        ConstValue::Scalar(literal) => Ok(literal),
+        ConstValue::Aggregate(_) => Err("expected a scalar, found a struct or array".to_string()),
+    }
+}
+
+fn aggregate(value: ConstValue) -> Result<Elements, String> {
+    match value {
+        ConstValue::Aggregate(values) => Ok(values),
+        ConstValue::Scalar(literal) => Err(format!("expected a struct or array, found {:?}", literal)),
+    }
+}
+
+/// The value of an integer literal's bits, read as its type.
+pub fn decode(bits: u64, int: IntType) -> i128 {
+    let unused = 64 - int.bits();
+    if int.is_signed() {
+        ((bits << unused) as i64 >> unused) as i128
+    } else {
+        ((bits << unused) >> unused) as i128
+    }
+}
+
+/// `value` as an integer of type `int`, if it is in range.
+fn checked(value: i128, int: IntType) -> Option<HirLiteral> {
+    let min = if int.is_signed() { -(int.min_magnitude() as i128) } else { 0 };
+    (min..=int.max() as i128)
+        .contains(&value)
+        .then_some(HirLiteral::Integer(value as u64, int))
+}
+
+/// `value` truncated to the width of `int`.
+pub fn wrap(value: i128, int: IntType) -> HirLiteral {
+    HirLiteral::Integer(decode(value as u64, int) as u64, int)
+}
+
+/// The float remainder as the generated code computes it, since WebAssembly
+/// has none: `a - trunc(a / b) * b`, rounding each step to the type. This
+/// is not always `fmod`, which is exact.
+pub fn remainder(left: f64, right: f64, float: FloatType) -> f64 {
+    let truncated = round(left / right, float).trunc();
+    round(left - round(truncated * right, float), float)
+}
+
+/// `value` rounded to the precision of `float`.
+pub fn round(value: f64, float: FloatType) -> f64 {
+    match float {
+        FloatType::F32 => value as f32 as f64,
+        FloatType::F64 => value,
+    }
+}
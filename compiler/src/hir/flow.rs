@@ -0,0 +1,397 @@
+use crate::diagnostics::Diagnostic;
+use crate::hir::hir::{HirExpr, HirFunction, HirOrigin, HirPlace, HirStmt, HirStmtKind};
+use crate::semantic::symbol_table::Type;
+
+/// What a point in a function body does to a local.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowEvent {
+    /// The local is given a value.
+    Assign(usize),
+    /// `let x;` leaves the local without a value, even if a previous
+    /// iteration of a loop assigned it.
+    Unassign(usize),
+    /// The value of the local is used, including by a closure capturing it.
+    Read(usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FlowBlock {
+    /// What the block does to locals, in order, each with the innermost
+    /// statement or expression it comes from.
+    pub events: Vec<(FlowEvent, Option<HirOrigin>)>,
+    pub successors: Vec<usize>,
+    /// The origins of the statements that start in this block.
+    pub statements: Vec<Option<HirOrigin>>,
+}
+
+/// The control-flow graph of a HIR function body, reduced to what the
+/// checks in this module need: which locals each block assigns and reads,
+/// and where control can go next. Short-circuit operators split blocks
+/// just as `if` does, so an assignment in only one branch of either is not
+/// a definite assignment.
+#[derive(Debug, Clone)]
+pub struct FlowGraph {
+    pub blocks: Vec<FlowBlock>,
+    /// The block that falls off the end of the body.
+    pub end: usize,
+}
+
+impl FlowGraph {
+    pub const ENTRY: usize = 0;
+
+    pub fn build(function: &HirFunction) -> Result<Self, Diagnostic> {
+        let mut builder = FlowBuilder {
+            blocks: vec![FlowBlock::default()],
+            current: Self::ENTRY,
+            loops: Vec::new(),
+            counting: true,
+            origin: None,
+        };
+        builder.body(&function.body)?;
+        Ok(Self {
+            end: builder.current,
+            blocks: builder.blocks,
+        })
+    }
+
+    /// Whether each block can be reached from the entry.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = vec![Self::ENTRY];
+        while let Some(block) = pending.pop() {
+            if !std::mem::replace(&mut reachable[block], true) {
+                pending.extend(&self.blocks[block].successors);
+            }
+        }
+        reachable
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (block, data) in self.blocks.iter().enumerate() {
+            for &successor in &data.successors {
+                predecessors[successor].push(block);
+            }
+        }
+        predecessors
+    }
+}
+
+struct FlowBuilder {
+    blocks: Vec<FlowBlock>,
+    current: usize,
+    /// Where `continue` and `break` go in each enclosing loop.
+    loops: Vec<(usize, usize)>,
+    /// Whether statements count towards `FlowBlock::statements`, which
+    /// they do unless lowering added them, as it adds a `for` loop's
+    /// increment after the body.
+    counting: bool,
+    /// The innermost statement or expression being visited that has an origin.
+    origin: Option<HirOrigin>,
+}
+
+impl FlowBuilder {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(FlowBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.blocks[from].successors.push(to);
+    }
+
+    fn event(&mut self, event: FlowEvent) {
+        self.blocks[self.current].events.push((event, self.origin));
+    }
+
+    /// Visits a statement or expression from `origin`, when it has one, as
+    /// the innermost place events come from.
+    fn at<T>(&mut self, origin: Option<HirOrigin>, visit: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = self.origin;
+        self.origin = origin.or(outer);
+        let result = visit(self);
+        self.origin = outer;
+        result
+    }
+
+    /// Continues in a fresh block that nothing jumps to, after a jump.
+    fn detach(&mut self) {
+        self.current = self.new_block();
+    }
+
+    fn body(&mut self, body: &[HirStmt]) -> Result<(), Diagnostic> {
+        body.iter().try_for_each(|statement| self.at(statement.origin, |builder| builder.stmt(statement)))
+    }
+
+    fn stmt(&mut self, statement: &HirStmt) -> Result<(), Diagnostic> {
+        if self.counting {
+            self.blocks[self.current].statements.push(statement.origin);
+        }
+        match &statement.kind {
+            HirStmtKind::Let { local, value } => match value {
+                Some(value) => {
+                    self.expr(value)?;
+                    self.event(FlowEvent::Assign(*local));
+                }
+                None => self.event(FlowEvent::Unassign(*local)),
+            },
+            HirStmtKind::Expr(expr) => self.expr(expr)?,
+            HirStmtKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+                self.detach();
+            }
+            HirStmtKind::Loop { body, continuing } => {
+                let (body_block, exit_block) = (self.new_block(), self.new_block());
+                let continue_block = if continuing.is_empty() { body_block } else { self.new_block() };
+                self.edge(self.current, body_block);
+                self.current = body_block;
+                self.loops.push((continue_block, exit_block));
+                self.body(body)?;
+                self.loops.pop();
+                self.edge(self.current, continue_block);
+                if !continuing.is_empty() {
+                    self.current = continue_block;
+                    let counting = std::mem::replace(&mut self.counting, false);
+                    self.body(continuing)?;
+                    self.counting = counting;
+                    self.edge(self.current, body_block);
+                }
+                // Only `break` leaves the loop.
+                self.current = exit_block;
+            }
+            HirStmtKind::Break | HirStmtKind::Continue => {
+                let is_break = matches!(statement.kind, HirStmtKind::Break);
+                let keyword = if is_break { "break" } else { "continue" };
+                let &(continue_block, exit_block) = self
+                    .loops
+                    .last()
+                    .ok_or_else(|| error(format!("'{}' outside of a loop", keyword), self.origin, "not in a loop"))?;
+                let target = if is_break { exit_block } else { continue_block };
+                self.edge(self.current, target);
+                self.detach();
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &HirExpr) -> Result<(), Diagnostic> {
+        self.at(expr.origin(), |builder| builder.expr_events(expr))
+    }
+
+    fn expr_events(&mut self, expr: &HirExpr) -> Result<(), Diagnostic> {
+        match expr {
+            HirExpr::Variable { place, .. } => {
+                if let HirPlace::Local(local) = place {
+                    self.event(FlowEvent::Read(*local));
+                }
+            }
+            HirExpr::Assign { place, value, .. } => {
+                self.expr(value)?;
+                if let HirPlace::Local(local) = place {
+                    self.event(FlowEvent::Assign(*local));
+                }
+            }
+            HirExpr::Binary { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)?;
+            }
+            HirExpr::Logical { left, right, .. } => {
+                self.expr(left)?;
+                let (right_block, join_block) = (self.new_block(), self.new_block());
+                self.edge(self.current, right_block);
+                self.edge(self.current, join_block);
+                self.current = right_block;
+                self.expr(right)?;
+                self.edge(self.current, join_block);
+                self.current = join_block;
+            }
+            HirExpr::If { condition, then_branch, else_branch, .. } => {
+                self.expr(condition)?;
+                let (then_block, else_block, join_block) = (self.new_block(), self.new_block(), self.new_block());
+                self.edge(self.current, then_block);
+                self.edge(self.current, else_block);
+                for (block, branch) in [(then_block, then_branch), (else_block, else_branch)] {
+                    self.current = block;
+                    self.expr(branch)?;
+                    self.edge(self.current, join_block);
+                }
+                self.current = join_block;
+            }
+            HirExpr::Block { statements, value, .. } => {
+                self.body(statements)?;
+                if let Some(value) = value {
+                    self.expr(value)?;
+                }
+            }
+            HirExpr::Unary { expr, .. } | HirExpr::Cast { expr, .. } => self.expr(expr)?,
+            HirExpr::Call { args, .. } => args.iter().try_for_each(|arg| self.expr(arg))?,
+            HirExpr::CallIndirect { callee, args, .. } => {
+                self.expr(callee)?;
+                args.iter().try_for_each(|arg| self.expr(arg))?;
+            }
+            HirExpr::Closure { captures, .. } => {
+                for capture in captures {
+                    if let HirPlace::Local(local) = capture.place {
+                        self.event(FlowEvent::Read(local));
+                    }
+                }
+            }
+            HirExpr::Aggregate { elements, .. } => elements.iter().try_for_each(|element| self.expr(element))?,
+            HirExpr::Field { object, .. } => self.expr(object)?,
+            HirExpr::Index { array, index, .. } => {
+                self.expr(array)?;
+                self.expr(index)?;
+            }
+            HirExpr::SetField { object, value, .. } => {
+                self.expr(object)?;
+                self.expr(value)?;
+            }
+            HirExpr::SetIndex { array, index, value, .. } => {
+                self.expr(array)?;
+                self.expr(index)?;
+                self.expr(value)?;
+            }
+            HirExpr::Literal { .. } | HirExpr::Const { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// Checks a function body on its control-flow graph: every local must be
+/// assigned on all paths before it is read, and an immutable one declared
+/// without a value at most once, a function that returns a value must not
+/// fall off its end, and `break` and `continue` must be inside a loop.
+/// Returns a warning if the body contains unreachable code.
+///
+/// Diagnostics are labelled at the statement or expression at fault when
+/// it has an origin; the caller can point the others at the function.
+pub fn check_function(function: &HirFunction) -> Result<Vec<Diagnostic>, Diagnostic> {
+    let graph = FlowGraph::build(function).map_err(|mut error| {
+        error.message = format!("{} in function '{}'", error.message, function.name);
+        error
+    })?;
+    let reachable = graph.reachable();
+
+    check_assignments(function, &graph, &reachable)?;
+    if reachable[graph.end] && function.return_type != Type::Void {
+        return Err(Diagnostic::error(format!("Not all paths in function '{}' return a value", function.name)));
+    }
+
+    let mut warnings = Vec::new();
+    let unreachable: Vec<&Option<HirOrigin>> = (0..graph.blocks.len())
+        .filter(|&block| !reachable[block])
+        .flat_map(|block| &graph.blocks[block].statements)
+        .collect();
+    if !unreachable.is_empty() {
+        let warning = Diagnostic::warning(format!("Unreachable code in function '{}'", function.name));
+        // The first unreachable statement in the source is where the dead code starts.
+        let first = unreachable.into_iter().flatten().min_by_key(|origin| (origin.span.line, origin.span.column));
+        warnings.push(match first {
+            Some(origin) => warning.with_label(origin.span, "unreachable statement"),
+            None => warning,
+        });
+    }
+    Ok(warnings)
+}
+
+/// An error labelled at `origin` when it is known.
+fn error(message: String, origin: Option<HirOrigin>, label: &str) -> Diagnostic {
+    let error = Diagnostic::error(message);
+    match origin {
+        Some(origin) => error.with_label(origin.span, label),
+        None => error,
+    }
+}
+
+/// Checks that every local is assigned on all paths before it is read, and
+/// that a local assigned once is not assigned on a path where it may
+/// already have been.
+fn check_assignments(function: &HirFunction, graph: &FlowGraph, reachable: &[bool]) -> Result<(), Diagnostic> {
+    let definitely = assigned_on_entry(function, graph, reachable, true);
+    let possibly = assigned_on_entry(function, graph, reachable, false);
+    for (block, data) in graph.blocks.iter().enumerate().filter(|&(block, _)| reachable[block]) {
+        let mut assigned = definitely[block].clone();
+        let mut maybe_assigned = possibly[block].clone();
+        for &(event, origin) in &data.events {
+            let name = &function.locals[local_of(event)].name;
+            match event {
+                FlowEvent::Assign(local) if function.locals[local].assign_once && maybe_assigned[local] => {
+                    let message = format!("Cannot assign twice to immutable variable '{}'", name);
+                    return Err(error(message, origin, "assigned again here"));
+                }
+                FlowEvent::Assign(local) => {
+                    assigned[local] = true;
+                    maybe_assigned[local] = true;
+                }
+                FlowEvent::Unassign(local) => {
+                    assigned[local] = false;
+                    maybe_assigned[local] = false;
+                }
+                FlowEvent::Read(local) if !assigned[local] => {
+                    let message = format!("Variable '{}' may be used before it is assigned", name);
+                    return Err(error(message, origin, "used here"));
+                }
+                FlowEvent::Read(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn local_of(event: FlowEvent) -> usize {
+    match event {
+        FlowEvent::Assign(local) | FlowEvent::Unassign(local) | FlowEvent::Read(local) => local,
+    }
+}
+
+/// Forward dataflow over the locals that are assigned on entry to each
+/// block, starting from the parameters: on every path to it when
+/// `all_paths`, otherwise on some path.
+fn assigned_on_entry(function: &HirFunction, graph: &FlowGraph, reachable: &[bool], all_paths: bool) -> Vec<Vec<bool>> {
+    let locals = function.locals.len();
+    let predecessors = graph.predecessors();
+    let transfer = |block: &FlowBlock, mut assigned: Vec<bool>| {
+        for &(event, _) in &block.events {
+            match event {
+                FlowEvent::Assign(local) => assigned[local] = true,
+                FlowEvent::Unassign(local) => assigned[local] = false,
+                FlowEvent::Read(_) => {}
+            }
+        }
+        assigned
+    };
+
+    let parameters: Vec<bool> = (0..locals).map(|local| local < function.params).collect();
+    // Until a block is visited its output constrains nothing.
+    let mut outputs = vec![vec![all_paths; locals]; graph.blocks.len()];
+    let mut inputs = outputs.clone();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in (0..graph.blocks.len()).filter(|&block| reachable[block]) {
+            let mut input = if block == FlowGraph::ENTRY {
+                parameters.clone()
+            } else {
+                vec![all_paths; locals]
+            };
+            for &predecessor in predecessors[block].iter().filter(|&&predecessor| reachable[predecessor]) {
+                for (assigned, incoming) in input.iter_mut().zip(&outputs[predecessor]) {
+                    if all_paths {
+                        *assigned &= *incoming;
+                    } else {
+                        *assigned |= *incoming;
+                    }
+                }
+            }
+            let output = transfer(&graph.blocks[block], input.clone());
+            inputs[block] = input;
+            if output != outputs[block] {
+                outputs[block] = output;
+                changed = true;
+            }
+        }
+    }
+    inputs
+}
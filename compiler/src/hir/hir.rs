@@ -1,22 +1,387 @@
-use crate::semantic::symbol_table::Type;
+use std::collections::HashMap;
+
+use crate::diagnostics::Span;
+use crate::hir::const_eval::ConstValue;
+use crate::parser::ast::NodeId;
+use crate::semantic::analyzer::CaptureMode;
+use crate::semantic::attributes::Attributes;
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+
+#[derive(Debug, Clone)]
+pub struct HirProgram {
+    pub functions: Vec<HirFunction>,
+    pub consts: Vec<HirConst>,
+    pub statics: Vec<HirStatic>,
+    pub structs: Vec<HirStruct>,
+    pub enums: Vec<HirEnum>,
+    pub entry: usize,
+    /// The declaration each item was lowered from. The implicit entry
+    /// function has none, and a closure adapter has that of the function
+    /// item it wraps.
+    pub origins: HashMap<DefId, HirOrigin>,
+}
+
+impl HirProgram {
+    pub fn origin(&self, def: DefId) -> Option<&HirOrigin> {
+        self.origins.get(&def)
+    }
+}
+
+/// Identifies an item by its kind and its index in the matching list of
+/// `HirProgram`, which is also how HIR expressions refer to items.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DefId {
+    Function(usize),
+    Const(usize),
+    Static(usize),
+    Struct(usize),
+    Enum(usize),
+}
+
+/// The AST node an item, statement or expression was lowered from and
+/// where diagnostics about it point: an item's name, where a lambda starts,
+/// or the span of the statement or expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HirOrigin {
+    pub node: NodeId,
+    pub span: Span,
+}
+
+/// A `const` item with the value its initializer evaluated to.
+#[derive(Debug, Clone)]
+pub struct HirConst {
+    pub def_id: DefId,
+    pub name: String,
+    pub ty: Type,
+    pub value: ConstValue,
+}
+
+/// A `static` item with its constant initial value.
+#[derive(Debug, Clone)]
+pub struct HirStatic {
+    pub def_id: DefId,
+    pub name: String,
+    pub ty: Type,
+    pub mutable: bool,
+    pub value: HirLiteral,
+}
+
+/// A `struct` item. Its values are heap objects holding the fields in
+/// declaration order.
+#[derive(Debug, Clone)]
+pub struct HirStruct {
+    pub def_id: DefId,
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+/// An `enum` item. Its values are the discriminants of its variants, which
+/// are `const` items.
+#[derive(Debug, Clone)]
+pub struct HirEnum {
+    pub def_id: DefId,
+    pub name: String,
+    /// Each variant with the index of its discriminant in `HirProgram::consts`.
+    pub variants: Vec<(String, usize)>,
+}
+
+/// Function items come first in `HirProgram::functions`, in declaration
+/// order, followed by the implicit entry function and lifted closures.
+#[derive(Debug, Clone)]
+pub struct HirFunction {
+    pub def_id: DefId,
+    pub name: String,
+    /// The first `params` locals are the parameters.
+    pub params: usize,
+    pub locals: Vec<HirLocal>,
+    pub return_type: Type,
+    /// Layout of the environment a lifted closure receives as a hidden first
+    /// argument; `None` for functions that are only called directly.
+    pub env: Option<Vec<HirEnvSlot>>,
+    pub body: Vec<HirStmt>,
+    pub attributes: Attributes,
+    /// A `const fn`, which constant evaluation may call.
+    pub is_const: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct HirLocal {
+    pub name: String,
+    pub ty: Type,
+    /// Stored in a heap cell because a closure captures it by reference.
+    pub boxed: bool,
+    /// An immutable variable declared without a value, which may be
+    /// assigned once on any path.
+    pub assign_once: bool,
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, Clone)]
+pub struct HirEnvSlot {
+    pub ty: Type,
+    pub mode: CaptureMode,
+}
+
+/// A statement, with the source statement it was lowered from. Statements
+/// lowering adds, such as the `break` that ends a `while` loop, have none.
+#[derive(Debug, Clone)]
+pub struct HirStmt {
+    pub kind: HirStmtKind,
+    pub origin: Option<HirOrigin>,
+}
+
+impl HirStmt {
+    pub fn new(kind: HirStmtKind, origin: Option<HirOrigin>) -> Self {
+        Self { kind, origin }
+    }
+
+    /// A statement lowering adds, with no source statement of its own.
+    pub fn synthesized(kind: HirStmtKind) -> Self {
+        Self::new(kind, None)
+    }
+}
+
+/// Lowering desugars `if`, `while` and `for` into `Loop`, `HirExpr::If`
+/// and `HirExpr::Block`, so only these forms remain.
+#[derive(Debug, Clone)]
+pub enum HirStmtKind {
+    Let {
+        local: usize,
+        value: Option<HirExpr>,
+    },
+    Expr(HirExpr),
+    Return(Option<HirExpr>),
+    /// Runs `body` repeatedly until a `Break`. `continuing` runs after each
+    /// pass through the body, including one ended by `Continue`; it holds
+    /// the increment of a `for` loop.
+    Loop {
+        body: Vec<HirStmt>,
+        continuing: Vec<HirStmt>,
+    },
+    Break,
+    Continue,
+}
+
+/// Where a variable lives in the function being lowered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HirPlace {
+    Local(usize),
+    /// A slot of the closure environment.
+    Captured(usize),
+    /// A `static` item, by index into `HirProgram::statics`.
+    Static(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct HirCapture {
+    pub place: HirPlace,
+    pub mode: CaptureMode,
+}
 
 #[derive(Debug, Clone)]
 pub enum HirExpr {
+    Assign {
+        place: HirPlace,
+        value: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
     Binary {
         op: HirBinaryOp,
         left: Box<HirExpr>,
         right: Box<HirExpr>,
         expr_type: Type,
+        origin: Option<HirOrigin>,
     },
     Literal {
         value: HirLiteral,
         expr_type: Type,
+        origin: Option<HirOrigin>,
     },
     Unary {
         op: HirUnaryOp,
         expr: Box<HirExpr>,
         expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    Logical {
+        op: HirLogicalOp,
+        left: Box<HirExpr>,
+        right: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// An `if` statement, whose branches are `void` blocks, or a ternary.
+    If {
+        condition: Box<HirExpr>,
+        then_branch: Box<HirExpr>,
+        else_branch: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Runs `statements`, then evaluates to `value`, or to nothing when it
+    /// is absent and `expr_type` is `void`.
+    Block {
+        statements: Vec<HirStmt>,
+        value: Option<Box<HirExpr>>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    Variable {
+        place: HirPlace,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// The value of a `const` item, by index into `HirProgram::consts`.
+    Const {
+        id: usize,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// A direct call to the function at the given index.
+    Call {
+        function: usize,
+        args: Vec<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Converts a numeric or bool value to the numeric type `expr_type`.
+    Cast {
+        expr: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
     },
+    /// A call through a closure value.
+    CallIndirect {
+        callee: Box<HirExpr>,
+        args: Vec<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Allocates the environment of a lifted closure and fills it from the
+    /// enclosing function's variables.
+    Closure {
+        function: usize,
+        captures: Vec<HirCapture>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Allocates a struct or array holding `elements`, which are evaluated
+    /// in order. The fields of a struct are in declaration order.
+    Aggregate {
+        elements: Vec<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Reads the field at `index` of a struct.
+    Field {
+        object: Box<HirExpr>,
+        index: usize,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Reads an element of an array, trapping when `index`, an `i64`, is out
+    /// of bounds.
+    Index {
+        array: Box<HirExpr>,
+        index: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Stores `value` in the field at `index` of a struct and evaluates to it.
+    SetField {
+        object: Box<HirExpr>,
+        index: usize,
+        value: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+    /// Stores `value` in an element of an array and evaluates to it.
+    SetIndex {
+        array: Box<HirExpr>,
+        index: Box<HirExpr>,
+        value: Box<HirExpr>,
+        expr_type: Type,
+        origin: Option<HirOrigin>,
+    },
+}
+
+impl HirExpr {
+    pub fn ty(&self) -> &Type {
+        match self {
+            HirExpr::Assign { expr_type, .. }
+            | HirExpr::Binary { expr_type, .. }
+            | HirExpr::Literal { expr_type, .. }
+            | HirExpr::Unary { expr_type, .. }
+            | HirExpr::Logical { expr_type, .. }
+            | HirExpr::If { expr_type, .. }
+            | HirExpr::Block { expr_type, .. }
+            | HirExpr::Variable { expr_type, .. }
+            | HirExpr::Const { expr_type, .. }
+            | HirExpr::Call { expr_type, .. }
+            | HirExpr::Cast { expr_type, .. }
+            | HirExpr::CallIndirect { expr_type, .. }
+            | HirExpr::Closure { expr_type, .. }
+            | HirExpr::Aggregate { expr_type, .. }
+            | HirExpr::Field { expr_type, .. }
+            | HirExpr::Index { expr_type, .. }
+            | HirExpr::SetField { expr_type, .. }
+            | HirExpr::SetIndex { expr_type, .. } => expr_type,
+        }
+    }
+
+    /// The source expression this was lowered from. Expressions lowering
+    /// adds, such as implicit conversions, have none.
+    pub fn origin(&self) -> Option<HirOrigin> {
+        match self {
+            HirExpr::Assign { origin, .. }
+            | HirExpr::Binary { origin, .. }
+            | HirExpr::Literal { origin, .. }
+            | HirExpr::Unary { origin, .. }
+            | HirExpr::Logical { origin, .. }
+            | HirExpr::If { origin, .. }
+            | HirExpr::Block { origin, .. }
+            | HirExpr::Variable { origin, .. }
+            | HirExpr::Const { origin, .. }
+            | HirExpr::Call { origin, .. }
+            | HirExpr::Cast { origin, .. }
+            | HirExpr::CallIndirect { origin, .. }
+            | HirExpr::Closure { origin, .. }
+            | HirExpr::Aggregate { origin, .. }
+            | HirExpr::Field { origin, .. }
+            | HirExpr::Index { origin, .. }
+            | HirExpr::SetField { origin, .. }
+            | HirExpr::SetIndex { origin, .. } => *origin,
+        }
+    }
+
+    /// Records where the expression was lowered from, unless a more
+    /// precise origin is already known.
+    pub fn or_origin(mut self, origin: HirOrigin) -> Self {
+        match &mut self {
+            HirExpr::Assign { origin: slot, .. }
+            | HirExpr::Binary { origin: slot, .. }
+            | HirExpr::Literal { origin: slot, .. }
+            | HirExpr::Unary { origin: slot, .. }
+            | HirExpr::Logical { origin: slot, .. }
+            | HirExpr::If { origin: slot, .. }
+            | HirExpr::Block { origin: slot, .. }
+            | HirExpr::Variable { origin: slot, .. }
+            | HirExpr::Const { origin: slot, .. }
+            | HirExpr::Call { origin: slot, .. }
+            | HirExpr::Cast { origin: slot, .. }
+            | HirExpr::CallIndirect { origin: slot, .. }
+            | HirExpr::Closure { origin: slot, .. }
+            | HirExpr::Aggregate { origin: slot, .. }
+            | HirExpr::Field { origin: slot, .. }
+            | HirExpr::Index { origin: slot, .. }
+            | HirExpr::SetField { origin: slot, .. }
+            | HirExpr::SetIndex { origin: slot, .. } => {
+                slot.get_or_insert(origin);
+            }
+        }
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +390,7 @@ pub enum HirBinaryOp {
     Subtract,
     Multiply,
     Divide,
+    Remainder,
     Equal,
     NotEqual,
     GreaterThan,
@@ -33,6 +399,12 @@ pub enum HirBinaryOp {
     LessThanOrEqual,
 }
 
+#[derive(Debug, Clone)]
+pub enum HirLogicalOp {
+    And,
+    Or,
+}
+
 #[derive(Debug, Clone)]
 pub enum HirUnaryOp {
     Negate,
@@ -41,7 +413,10 @@ pub enum HirUnaryOp {
 
 #[derive(Debug, Clone)]
 pub enum HirLiteral {
-    Number(f64),
+    /// An integer of the given type, as the bits of its two's complement
+    /// representation.
+    Integer(u64, IntType),
+    Float(f64, FloatType),
     String(String),
     Boolean(bool),
     Null,
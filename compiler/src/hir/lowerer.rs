@@ -1,48 +1,752 @@
-use crate::hir::hir::{HirBinaryOp, HirExpr, HirLiteral, HirUnaryOp};
-use crate::parser::ast::Expr;
-use crate::semantic::symbol_table::Type;
+use std::collections::HashMap;
 
-pub struct HirLowerer;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::hir::const_eval::{round, ConstEvaluator, ConstValue};
+use crate::hir::flow;
+use crate::hir::hir::{
+    DefId, HirBinaryOp, HirCapture, HirConst, HirEnum, HirEnvSlot, HirExpr, HirFunction, HirLiteral, HirLocal,
+    HirLogicalOp, HirOrigin, HirPlace, HirProgram, HirStatic, HirStmt, HirStmtKind, HirStruct, HirUnaryOp,
+};
+use crate::hir::validate;
+use crate::lexer::token::Token;
+use crate::parser::ast::{Expr, Param, Stmt};
+use crate::semantic::analyzer::{struct_fields, SemanticAnalyzer};
+use crate::semantic::attributes::Attributes;
+use crate::semantic::lints::{at_level, DEAD_CODE};
+use crate::semantic::operators::{binary_typing, BinaryOperator};
+use crate::semantic::symbol_table::{FloatType, IntType, ScopeId, SymbolKind, Type};
 
-impl HirLowerer {
+/// Per-function state while its body is lowered.
+struct FunctionBuilder {
+    name: String,
+    locals: Vec<HirLocal>,
+    /// Analyzer variable ids of this function's locals.
+    variable_locals: HashMap<usize, usize>,
+    /// Analyzer variable ids of captured variables, mapped to environment slots.
+    variable_slots: HashMap<usize, usize>,
+    body: Vec<HirStmt>,
+}
+
+impl FunctionBuilder {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            locals: Vec::new(),
+            variable_locals: HashMap::new(),
+            variable_slots: HashMap::new(),
+            body: Vec::new(),
+        }
+    }
+}
+
+/// Lowers a checked program to HIR, converting every lambda into a lifted
+/// function plus an environment built at the point of creation.
+///
+/// Declarations are visited in exactly the order `SemanticAnalyzer` visits
+/// them, so its variable and lambda numbering can be used directly, and
+/// identifiers are looked up in its resolution table.
+pub struct HirLowerer<'a> {
+    analysis: Option<&'a SemanticAnalyzer>,
+    functions: Vec<Option<HirFunction>>,
+    builders: Vec<FunctionBuilder>,
+    next_variable: usize,
+    next_lambda: usize,
+    /// Closure adapters for function items used as values.
+    adapters: HashMap<usize, usize>,
+    origins: HashMap<DefId, HirOrigin>,
+}
+
+impl<'a> HirLowerer<'a> {
     pub fn new() -> Self {
-        Self
+        Self {
+            analysis: None,
+            functions: Vec::new(),
+            builders: Vec::new(),
+            next_variable: 0,
+            next_lambda: 0,
+            adapters: HashMap::new(),
+            origins: HashMap::new(),
+        }
+    }
+
+    /// Lowers a checked program. Unreachable code found by the control-flow
+    /// checks is reported to `sink` at the level the analysis was configured
+    /// with for the `dead_code` lint. A type that analysis left unresolved is
+    /// reported as an internal error.
+    pub fn lower_program(
+        &mut self,
+        statements: &[Stmt],
+        analysis: &'a SemanticAnalyzer,
+        sink: &mut DiagnosticSink,
+    ) -> Result<HirProgram, Diagnostic> {
+        let program = self
+            .lower_items(statements, analysis, sink)
+            .map_err(|error| sink.fail(error.or_code(codes::LOWERING)))?;
+        validate::check_types(&program).map_err(|error| {
+            sink.fail(Diagnostic::error(format!("Internal error: HIR {}", error)).with_code(codes::INTERNAL))
+        })?;
+        Ok(program)
+    }
+
+    fn lower_items(
+        &mut self,
+        statements: &[Stmt],
+        analysis: &'a SemanticAnalyzer,
+        sink: &mut DiagnosticSink,
+    ) -> Result<HirProgram, Diagnostic> {
+        self.analysis = Some(analysis);
+
+        let mut main_item = None;
+        for statement in statements {
+            if let Stmt::Function { name, .. } = statement {
+                let id = self.functions.len();
+                self.functions.push(None);
+                if name == "main" {
+                    main_item = Some(id);
+                }
+            }
+        }
+
+        // Initializers are lowered in the order the analyzer checks them and
+        // evaluated once the functions they may call are lowered.
+        let mut static_initializers = Vec::new();
+        for statement in statements {
+            if let Stmt::Static { initializer, span, id, .. } = statement {
+                let origin = HirOrigin { node: *id, span: *span };
+                self.origins.insert(DefId::Static(static_initializers.len()), origin);
+                static_initializers.push(self.lower(initializer)?);
+            }
+        }
+        let mut const_initializers = Vec::new();
+        for statement in statements {
+            match statement {
+                Stmt::Const { name, initializer, span, id, .. } => {
+                    let origin = HirOrigin { node: *id, span: *span };
+                    self.origins.insert(DefId::Const(const_initializers.len()), origin);
+                    const_initializers.push((name.clone(), self.lower(initializer)?));
+                }
+                Stmt::Enum { variants, id, .. } => {
+                    for (index, variant) in variants.iter().enumerate() {
+                        let const_id = const_initializers.len();
+                        let (name, ty) = {
+                            let info = &analysis.consts[const_id];
+                            (info.name.clone(), info.ty.clone())
+                        };
+                        self.origins.insert(DefId::Const(const_id), HirOrigin { node: *id, span: variant.span });
+                        let discriminant = match &variant.value {
+                            Some(value) => self.lower(value)?,
+                            None => implicit_discriminant(const_id, index == 0, ty),
+                        };
+                        const_initializers.push((name, discriminant));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut structs = Vec::new();
+        let mut enums = Vec::new();
+        for statement in statements {
+            match statement {
+                Stmt::Struct { span, id, .. } => {
+                    let info = &analysis.structs[structs.len()];
+                    let def_id = DefId::Struct(structs.len());
+                    self.origins.insert(def_id, HirOrigin { node: *id, span: *span });
+                    structs.push(HirStruct {
+                        def_id,
+                        name: info.name.clone(),
+                        fields: info.fields.clone(),
+                    });
+                }
+                Stmt::Enum { span, id, .. } => {
+                    let info = &analysis.enums[enums.len()];
+                    let def_id = DefId::Enum(enums.len());
+                    self.origins.insert(def_id, HirOrigin { node: *id, span: *span });
+                    enums.push(HirEnum {
+                        def_id,
+                        name: info.name.clone(),
+                        variants: info.variants.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let has_top_level_code = statements.iter().any(|statement| !statement.is_item());
+        let entry = match main_item {
+            Some(id) if !has_top_level_code => id,
+            _ => {
+                let id = self.functions.len();
+                self.functions.push(None);
+                id
+            }
+        };
+
+        self.builders.push(FunctionBuilder::new("main".to_string()));
+        let mut return_type = Type::Void;
+        for (index, statement) in statements.iter().enumerate() {
+            match statement {
+                // The value of a trailing expression is the program's result.
+                Stmt::Expression { expression, .. } if index == statements.len() - 1 => {
+                    let value = self.lower(expression)?;
+                    return_type = value.ty().clone();
+                    let kind = if return_type == Type::Void {
+                        HirStmtKind::Expr(value)
+                    } else {
+                        HirStmtKind::Return(Some(value))
+                    };
+                    self.push(kind, statement);
+                }
+                _ => self.lower_stmt(statement)?,
+            }
+        }
+        let builder = self.builders.pop().expect("entry builder was pushed above");
+        if self.functions[entry].is_none() {
+            self.functions[entry] = Some(HirFunction {
+                def_id: DefId::Function(entry),
+                name: builder.name,
+                params: 0,
+                locals: builder.locals,
+                return_type,
+                env: None,
+                body: builder.body,
+                attributes: Attributes::default(),
+                is_const: false,
+            });
+        }
+
+        let functions = self
+            .functions
+            .drain(..)
+            .enumerate()
+            .map(|(id, function)| function.ok_or_else(|| format!("Function {} was never lowered", id)))
+            .collect::<Result<Vec<_>, String>>()?;
+        for (&item, &adapter) in &self.adapters {
+            if let Some(&origin) = self.origins.get(&DefId::Function(item)) {
+                self.origins.insert(DefId::Function(adapter), origin);
+            }
+        }
+        let analysis = self.analysis()?;
+        for function in &functions {
+            let level = analysis.lint_levels.level(&DEAD_CODE, function.attributes.lint_level(&DEAD_CODE));
+            // Flow diagnostics not labelled at a statement point at the
+            // function or closure they are about; top-level code has no
+            // single place to point at.
+            let origin = self.origins.get(&function.def_id).copied();
+            let locate = |diagnostic: Diagnostic| match origin {
+                Some(origin) => diagnostic.or_label(origin.span),
+                None => diagnostic,
+            };
+            let warnings =
+                flow::check_function(function).map_err(|error| locate(error.with_code(codes::CONTROL_FLOW)))?;
+            for warning in warnings {
+                match at_level(&DEAD_CODE, level, locate(warning)) {
+                    Some(error) if error.is_error() => return Err(error),
+                    Some(warning) => sink.emit(warning),
+                    None => {}
+                }
+            }
+        }
+
+        let mut evaluator = ConstEvaluator::new(&functions, &const_initializers);
+        let mut consts = Vec::new();
+        for (id, info) in analysis.consts.iter().enumerate() {
+            let def_id = DefId::Const(id);
+            consts.push(HirConst {
+                def_id,
+                name: info.name.clone(),
+                ty: info.ty.clone(),
+                value: evaluator
+                    .constant(id)
+                    .map_err(|error| const_evaluation(error, self.origins.get(&def_id)))?,
+            });
+        }
+        let mut statics = Vec::new();
+        for (id, (info, initializer)) in analysis.statics.iter().zip(&static_initializers).enumerate() {
+            let def_id = DefId::Static(id);
+            statics.push(HirStatic {
+                def_id,
+                name: info.name.clone(),
+                ty: info.ty.clone(),
+                mutable: info.mutable,
+                value: match evaluator.evaluate(initializer, &format!("static '{}'", info.name)) {
+                    Ok(ConstValue::Scalar(value)) => value,
+                    Ok(ConstValue::Aggregate(_)) => return Err(format!("Static '{}' cannot hold a struct or array", info.name).into()),
+                    Err(error) => return Err(const_evaluation(error, self.origins.get(&def_id))),
+                },
+            });
+        }
+
+        Ok(HirProgram {
+            functions,
+            consts,
+            statics,
+            structs,
+            enums,
+            entry,
+            origins: std::mem::take(&mut self.origins),
+        })
+    }
+
+    fn lower_stmt(&mut self, statement: &Stmt) -> Result<(), String> {
+        match statement {
+            Stmt::Block { statements, .. } => {
+                for statement in statements {
+                    self.lower_stmt(statement)?;
+                }
+            }
+            Stmt::Expression { expression, .. } => {
+                let expression = self.lower_effect(expression)?;
+                self.push(HirStmtKind::Expr(expression), statement);
+            }
+            Stmt::Function { name, params, body, span, id: node, .. } => {
+                let analysis = self.analysis()?;
+                let id = match analysis.symbol_table.lookup_in(ScopeId::ROOT, name).map(|symbol| &symbol.kind) {
+                    Some(SymbolKind::Function { id }) => *id,
+                    _ => return Err(format!("Undefined function '{}'", name)),
+                };
+                let return_type = match &analysis.functions[id] {
+                    Type::Function { return_type, .. } => (**return_type).clone(),
+                    other => return Err(format!("Function '{}' has non-function type {:?}", name, other)),
+                };
+                let attributes = analysis.function_attributes[id].clone();
+                let def_id = DefId::Function(id);
+                self.origins.insert(def_id, HirOrigin { node: *node, span: *span });
+                let mut function = self.lower_function(def_id, name.clone(), params, body, return_type, None)?;
+                function.attributes = attributes;
+                function.is_const = analysis.const_functions[id];
+                self.functions[id] = Some(function);
+            }
+            Stmt::Let { name, initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => Some(self.lower(initializer)?),
+                    None => None,
+                };
+                let local = self.declare(name)?;
+                self.push(HirStmtKind::Let { local, value }, statement);
+            }
+            Stmt::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => Some(self.lower(value)?),
+                    None => None,
+                };
+                self.push(HirStmtKind::Return(value), statement);
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                let condition = self.lower(condition)?;
+                let then_body = self.lower_body(then_branch)?;
+                let else_body = match else_branch {
+                    Some(else_branch) => self.lower_body(else_branch)?,
+                    None => Vec::new(),
+                };
+                let branch = HirExpr::If {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(void_block(then_body)),
+                    else_branch: Box::new(void_block(else_body)),
+                    expr_type: Type::Void,
+                    origin: Some(origin(statement)),
+                };
+                self.push(HirStmtKind::Expr(branch), statement);
+            }
+            Stmt::While { condition, body, .. } => self.lower_loop(statement, Some(condition), None, body)?,
+            Stmt::For { initializer, condition, increment, body, .. } => {
+                if let Some(initializer) = initializer {
+                    self.lower_stmt(initializer)?;
+                }
+                self.lower_loop(statement, condition.as_ref(), increment.as_ref(), body)?;
+            }
+            Stmt::Break { .. } => self.push(HirStmtKind::Break, statement),
+            Stmt::Continue { .. } => self.push(HirStmtKind::Continue, statement),
+            // Constants, statics, structs and enums are collected up front,
+            // and types are fully resolved by the analyzer.
+            Stmt::Const { .. }
+            | Stmt::Static { .. }
+            | Stmt::TypeAlias { .. }
+            | Stmt::TypeDef { .. }
+            | Stmt::Struct { .. }
+            | Stmt::Enum { .. } => {}
+            Stmt::Conditional { .. } => unreachable!("'#if' is resolved before analysis"),
+        }
+        Ok(())
+    }
+
+    /// Adds a statement lowered from `statement` to the body being built.
+    fn push(&mut self, kind: HirStmtKind, statement: &Stmt) {
+        self.builder().body.push(HirStmt::new(kind, Some(origin(statement))));
+    }
+
+    /// Lowers the statements of a nested block, such as a branch of an `if`,
+    /// into a body of their own.
+    fn lower_body(&mut self, statements: &[Stmt]) -> Result<Vec<HirStmt>, String> {
+        let outer = std::mem::take(&mut self.builder().body);
+        let result = statements.iter().try_for_each(|statement| self.lower_stmt(statement));
+        let body = std::mem::replace(&mut self.builder().body, outer);
+        result.map(|_| body)
+    }
+
+    /// Lowers a `while` or `for` loop to a `Loop` that first breaks out
+    /// when `condition` is false.
+    fn lower_loop(
+        &mut self,
+        statement: &Stmt,
+        condition: Option<&Expr>,
+        increment: Option<&Expr>,
+        body: &[Stmt],
+    ) -> Result<(), String> {
+        let mut loop_body = Vec::new();
+        if let Some(condition) = condition {
+            let condition = self.lower(condition)?;
+            // `while true` is left only through `break`, like a `for` without a condition.
+            if !matches!(condition, HirExpr::Literal { value: HirLiteral::Boolean(true), .. }) {
+                loop_body.push(HirStmt::synthesized(HirStmtKind::Expr(HirExpr::If {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(void_block(Vec::new())),
+                    else_branch: Box::new(void_block(vec![HirStmt::synthesized(HirStmtKind::Break)])),
+                    expr_type: Type::Void,
+                    origin: None,
+                })));
+            }
+        }
+        let continuing = match increment {
+            Some(increment) => vec![HirStmt::synthesized(HirStmtKind::Expr(self.lower_effect(increment)?))],
+            None => Vec::new(),
+        };
+        loop_body.extend(self.lower_body(body)?);
+        self.push(
+            HirStmtKind::Loop {
+                body: loop_body,
+                continuing,
+            },
+            statement,
+        );
+        Ok(())
+    }
+
+    fn lower_function(
+        &mut self,
+        def_id: DefId,
+        name: String,
+        params: &[Param],
+        body: &[Stmt],
+        return_type: Type,
+        env: Option<(Vec<HirEnvSlot>, HashMap<usize, usize>)>,
+    ) -> Result<HirFunction, String> {
+        let mut builder = FunctionBuilder::new(name);
+        let env = env.map(|(slots, variable_slots)| {
+            builder.variable_slots = variable_slots;
+            slots
+        });
+        self.builders.push(builder);
+        for param in params {
+            self.declare(&param.name)?;
+        }
+        for statement in body {
+            self.lower_stmt(statement)?;
+        }
+        let builder = self.builders.pop().expect("function builder was pushed above");
+
+        Ok(HirFunction {
+            def_id,
+            name: builder.name,
+            params: params.len(),
+            locals: builder.locals,
+            return_type,
+            env,
+            body: builder.body,
+            attributes: Attributes::default(),
+            is_const: false,
+        })
+    }
+
+    fn lower_lambda(&mut self, params: &[Param], body: &[Stmt], origin: HirOrigin) -> Result<HirExpr, String> {
+        let id = self.next_lambda;
+        self.next_lambda += 1;
+        let analysis = self.analysis()?;
+        let expr_type = analysis.lambdas[id].ty.clone();
+        let return_type = match &expr_type {
+            Type::Function { return_type, .. } => (**return_type).clone(),
+            other => return Err(format!("Lambda has non-function type {:?}", other)),
+        };
+
+        let mut captures = Vec::new();
+        let mut slots = Vec::new();
+        let mut variable_slots = HashMap::new();
+        for capture in analysis.captures(id) {
+            captures.push(HirCapture {
+                place: self.place_of(capture.variable)?,
+                mode: capture.mode,
+            });
+            variable_slots.insert(capture.variable, slots.len());
+            slots.push(HirEnvSlot {
+                ty: capture.ty,
+                mode: capture.mode,
+            });
+        }
+
+        // The lifted function is numbered before the lambdas in its body.
+        let function_id = self.functions.len();
+        self.functions.push(None);
+        let def_id = DefId::Function(function_id);
+        self.origins.insert(def_id, origin);
+        let name = format!("{}::lambda#{}", self.builder().name, id);
+        let env = Some((slots, variable_slots));
+        self.functions[function_id] = Some(self.lower_function(def_id, name, params, body, return_type, env)?);
+
+        Ok(HirExpr::Closure {
+            function: function_id,
+            captures,
+            expr_type,
+            origin: None,
+        })
+    }
+
+    /// Wraps a function item in a closure with an empty environment so it
+    /// can be passed around like any other function value.
+    fn item_closure(&mut self, item: usize, name: &str) -> Result<HirExpr, String> {
+        let expr_type = self.analysis()?.functions[item].clone();
+        if let Some(&adapter) = self.adapters.get(&item) {
+            return Ok(HirExpr::Closure {
+                function: adapter,
+                captures: Vec::new(),
+                expr_type,
+                origin: None,
+            });
+        }
+
+        let (params, return_type) = match &expr_type {
+            Type::Function { params, return_type } => (params.clone(), (**return_type).clone()),
+            other => return Err(format!("Function '{}' has non-function type {:?}", name, other)),
+        };
+        let locals: Vec<HirLocal> = params
+            .iter()
+            .enumerate()
+            .map(|(index, ty)| HirLocal {
+                name: format!("arg{}", index),
+                ty: ty.clone(),
+                boxed: false,
+                assign_once: false,
+                attributes: Attributes::default(),
+            })
+            .collect();
+        let args = locals
+            .iter()
+            .enumerate()
+            .map(|(index, local)| HirExpr::Variable {
+                place: HirPlace::Local(index),
+                expr_type: local.ty.clone(),
+                origin: None,
+            })
+            .collect();
+        let call = HirExpr::Call {
+            function: item,
+            args,
+            expr_type: return_type.clone(),
+            origin: None,
+        };
+        let body = if return_type == Type::Void {
+            vec![
+                HirStmt::synthesized(HirStmtKind::Expr(call)),
+                HirStmt::synthesized(HirStmtKind::Return(None)),
+            ]
+        } else {
+            vec![HirStmt::synthesized(HirStmtKind::Return(Some(call)))]
+        };
+
+        let adapter = self.functions.len();
+        self.functions.push(Some(HirFunction {
+            def_id: DefId::Function(adapter),
+            name: format!("{}::closure", name),
+            params: params.len(),
+            locals,
+            return_type,
+            env: Some(Vec::new()),
+            body,
+            attributes: Attributes::default(),
+            is_const: false,
+        }));
+        self.adapters.insert(item, adapter);
+
+        Ok(HirExpr::Closure {
+            function: adapter,
+            captures: Vec::new(),
+            expr_type,
+            origin: None,
+        })
+    }
+
+    fn declare(&mut self, name: &str) -> Result<usize, String> {
+        let variable = self.next_variable;
+        self.next_variable += 1;
+        let info = self
+            .analysis()?
+            .variables
+            .get(variable)
+            .ok_or_else(|| format!("Variable '{}' was not declared during analysis", name))?
+            .clone();
+
+        let builder = self.builder();
+        let local = builder.locals.len();
+        builder.locals.push(HirLocal {
+            name: name.to_string(),
+            ty: info.ty.clone(),
+            boxed: info.is_boxed(),
+            assign_once: info.deferred && !info.mutable,
+            attributes: info.attributes.clone(),
+        });
+        builder.variable_locals.insert(variable, local);
+        Ok(local)
+    }
+
+    /// Declares a local that holds an intermediate value of a desugared
+    /// expression rather than a source variable.
+    fn temporary(&mut self, name: String, ty: Type) -> Result<usize, String> {
+        let builder = self
+            .builders
+            .last_mut()
+            .ok_or_else(|| format!("Temporary '{}' needs an enclosing function", name))?;
+        let local = builder.locals.len();
+        builder.locals.push(HirLocal {
+            name,
+            ty,
+            boxed: false,
+            assign_once: false,
+            attributes: Attributes::default(),
+        });
+        Ok(local)
+    }
+
+    fn place_of(&mut self, variable: usize) -> Result<HirPlace, String> {
+        let builder = self.builder();
+        if let Some(&local) = builder.variable_locals.get(&variable) {
+            Ok(HirPlace::Local(local))
+        } else if let Some(&slot) = builder.variable_slots.get(&variable) {
+            Ok(HirPlace::Captured(slot))
+        } else {
+            Err(format!("Variable {} is not visible in '{}'", variable, builder.name))
+        }
+    }
+
+    /// The declaration an identifier expression refers to.
+    fn lookup(&self, expr: &Expr) -> Result<Option<SymbolKind>, String> {
+        Ok(self.analysis()?.resolution(expr).map(|resolution| resolution.symbol.clone()))
+    }
+
+    fn builder(&mut self) -> &mut FunctionBuilder {
+        self.builders.last_mut().expect("lowering happens inside a function")
+    }
+
+    fn analysis(&self) -> Result<&'a SemanticAnalyzer, String> {
+        self.analysis
+            .ok_or_else(|| "HIR lowering of declarations requires semantic analysis results".to_string())
+    }
+
+    fn analyzed_type(&self, expr: &Expr) -> Result<Type, String> {
+        self.analysis()?
+            .expr_type(expr)
+            .cloned()
+            .ok_or_else(|| format!("Expression {:?} was not typed during analysis", expr))
     }
 
-    pub fn lower(&self, expr: &Expr) -> Result<HirExpr, String> {
+    /// Lowers an expression, recording it as the origin of the HIR
+    /// expression it becomes.
+    pub fn lower(&mut self, expr: &Expr) -> Result<HirExpr, String> {
+        Ok(self.lower_expr(expr)?.or_origin(HirOrigin { node: expr.id(), span: expr.span() }))
+    }
+
+    fn lower_expr(&mut self, expr: &Expr) -> Result<HirExpr, String> {
         match expr {
-            Expr::Binary { left, operator, right } => {
+            Expr::Assign { name, value, .. } => {
+                let value = self.lower(value)?;
+                let (place, expr_type) = self.assignment_place(expr, name)?;
+                Ok(HirExpr::Assign {
+                    place,
+                    value: Box::new(value),
+                    expr_type,
+                    origin: None,
+                })
+            }
+            // `x += y` is `x = x + y`.
+            Expr::CompoundAssign { name, operator, value, .. } => {
+                let value = self.lower(value)?;
+                let (place, expr_type) = self.assignment_place(expr, name)?;
+                let current = HirExpr::Variable {
+                    place: place.clone(),
+                    expr_type: expr_type.clone(),
+                    origin: None,
+                };
+                Ok(HirExpr::Assign {
+                    place,
+                    value: Box::new(binary(operator, current, value, expr_type.clone())?),
+                    expr_type,
+                    origin: None,
+                })
+            }
+            Expr::Binary { left, operator, right, .. } => {
                 let left = self.lower(left)?;
                 let right = self.lower(right)?;
-
-                let op = match operator {
-                    crate::lexer::token::Token::Operation(op) => match op {
-                        crate::lexer::token::Operation::Add => HirBinaryOp::Add,
-                        crate::lexer::token::Operation::Subtract => HirBinaryOp::Subtract,
-                        crate::lexer::token::Operation::Multiply => HirBinaryOp::Multiply,
-                        crate::lexer::token::Operation::Divide => HirBinaryOp::Divide,
-                        crate::lexer::token::Operation::IfEqual => HirBinaryOp::Equal,
-                        crate::lexer::token::Operation::NotEqual => HirBinaryOp::NotEqual,
-                        crate::lexer::token::Operation::Greater => HirBinaryOp::GreaterThan,
-                        crate::lexer::token::Operation::Less => HirBinaryOp::LessThan,
-                        crate::lexer::token::Operation::GreaterEqual => HirBinaryOp::GreaterThanOrEqual,
-                        crate::lexer::token::Operation::LessEqual => HirBinaryOp::LessThanOrEqual,
-                        _ => return Err(format!("Invalid binary operator: {:?}", op)),
-                    },
-                    _ => return Err(format!("Invalid binary operator: {:?}", operator)),
+                binary(operator, left, right, self.analyzed_type(expr)?)
+            }
+            Expr::Call { callee, arguments, .. } => {
+                // A conversion such as `UserId(5)` leaves the representation unchanged.
+                if self.analysis()?.is_conversion(expr) {
+                    let [argument] = arguments.as_slice() else {
+                        return Err(format!("Conversion {:?} must have exactly one argument", expr));
+                    };
+                    return Ok(convert(self.lower(argument)?, &self.analyzed_type(expr)?));
+                }
+                let direct = match self.lookup(callee)? {
+                    Some(SymbolKind::Function { id }) => Some(id),
+                    _ => None,
+                };
+                let callee = match direct {
+                    Some(_) => None,
+                    None => Some(self.lower(callee)?),
                 };
+                let mut args = Vec::new();
+                for argument in arguments {
+                    args.push(self.lower(argument)?);
+                }
 
-                Ok(HirExpr::Binary {
-                    op,
-                    left: Box::new(left),
-                    right: Box::new(right),
-                    expr_type: Type::Float, // TODO: Get type from semantic analyzer
+                let expr_type = self.analyzed_type(expr)?;
+                match (direct, callee) {
+                    (Some(function), _) => Ok(HirExpr::Call {
+                        function,
+                        args,
+                        expr_type,
+                        origin: None,
+                    }),
+                    (None, Some(callee)) => {
+                        if !matches!(callee.ty().unaliased(), Type::Function { .. }) {
+                            return Err(format!("Cannot call a value of type {:?}", callee.ty()));
+                        }
+                        Ok(HirExpr::CallIndirect {
+                            callee: Box::new(callee),
+                            args,
+                            expr_type,
+                            origin: None,
+                        })
+                    }
+                    (None, None) => unreachable!("indirect callees are lowered above"),
+                }
+            }
+            Expr::Cast { expr: operand, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
+                Ok(HirExpr::Cast {
+                    expr: Box::new(self.lower(operand)?),
+                    expr_type,
+                    origin: None,
                 })
             }
-            Expr::Grouping { expression } => self.lower(expression),
-            Expr::Literal { value } => {
+            Expr::Grouping { expression, .. } => self.lower(expression),
+            Expr::Lambda { params, body, span, id, .. } => {
+                self.lower_lambda(params, body, HirOrigin { node: *id, span: *span })
+            }
+            Expr::Literal { value, .. } => {
                 let (literal, expr_type) = match value {
-                    crate::lexer::token::Token::Number(n) => (HirLiteral::Number(*n), Type::Float),
+                    crate::lexer::token::Token::Integer(_) | crate::lexer::token::Token::Number(_) => {
+                        let expr_type = self.analyzed_type(expr)?;
+                        (numeric_literal(value, &expr_type)?, expr_type)
+                    }
                     crate::lexer::token::Token::String(s) => (HirLiteral::String(s.clone()), Type::String),
                     crate::lexer::token::Token::Reserved(r) => match r {
                         crate::lexer::token::Reserved::True => (HirLiteral::Boolean(true), Type::Boolean),
@@ -55,9 +759,43 @@ impl HirLowerer {
                 Ok(HirExpr::Literal {
                     value: literal,
                     expr_type,
+                    origin: None,
+                })
+            }
+            Expr::Logical { left, operator, right, .. } => {
+                let left = self.lower(left)?;
+                let right = self.lower(right)?;
+
+                let op = match operator {
+                    crate::lexer::token::Token::Operation(crate::lexer::token::Operation::And) => HirLogicalOp::And,
+                    crate::lexer::token::Token::Operation(crate::lexer::token::Operation::Or) => HirLogicalOp::Or,
+                    _ => return Err(format!("Invalid logical operator: {:?}", operator)),
+                };
+
+                Ok(HirExpr::Logical {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    expr_type: self.analyzed_type(expr)?,
+                    origin: None,
+                })
+            }
+            Expr::Ternary { condition, then_branch, else_branch, .. } => {
+                let condition = self.lower(condition)?;
+                let then_branch = self.lower(then_branch)?;
+                let else_branch = self.lower(else_branch)?;
+                let expr_type = self.analyzed_type(expr)?;
+
+                Ok(HirExpr::If {
+                    condition: Box::new(condition),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                    expr_type,
+                    origin: None,
                 })
             }
-            Expr::Unary { operator, right } => {
+            Expr::Unary { operator, right, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
                 let expr = self.lower(right)?;
 
                 let op = match operator {
@@ -72,9 +810,393 @@ impl HirLowerer {
                 Ok(HirExpr::Unary {
                     op,
                     expr: Box::new(expr),
-                    expr_type: Type::Float, // TODO: Get type from semantic analyzer
+                    expr_type,
+                    origin: None,
+                })
+            }
+            Expr::Update { name, operator, prefix, .. } => self.lower_update(expr, name, operator, *prefix),
+            Expr::StructLiteral { name, fields, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
+                let declared = struct_fields(&expr_type)
+                    .ok_or_else(|| format!("Struct literal has non-struct type {}", expr_type))?
+                    .to_vec();
+                let mut values = Vec::new();
+                for field in fields {
+                    let index = field_index(&declared, &field.name)?;
+                    values.push((index, convert(self.lower(&field.value)?, &declared[index].1)));
+                }
+                if values.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+                    return Ok(HirExpr::Aggregate {
+                        elements: values.into_iter().map(|(_, value)| value).collect(),
+                        expr_type,
+                        origin: None,
+                    });
+                }
+                // Fields written out of declaration order are still evaluated
+                // in the order they are written.
+                let mut statements = Vec::new();
+                let mut elements = vec![None; declared.len()];
+                for (index, value) in values {
+                    let (field, ty) = &declared[index];
+                    let local = self.temporary(format!("{}.{}", name, field), ty.clone())?;
+                    statements.push(HirStmt::synthesized(HirStmtKind::Let { local, value: Some(value) }));
+                    elements[index] = Some(HirExpr::Variable {
+                        place: HirPlace::Local(local),
+                        expr_type: ty.clone(),
+                        origin: None,
+                    });
+                }
+                Ok(HirExpr::Block {
+                    statements,
+                    value: Some(Box::new(HirExpr::Aggregate {
+                        elements: elements.into_iter().flatten().collect(),
+                        expr_type: expr_type.clone(),
+                        origin: None,
+                    })),
+                    expr_type,
+                    origin: None,
+                })
+            }
+            Expr::ArrayLiteral { elements, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
+                let Type::Array { element, .. } = expr_type.unaliased() else {
+                    return Err(format!("Array literal has non-array type {}", expr_type));
+                };
+                let element = (**element).clone();
+                let mut values = Vec::new();
+                for value in elements {
+                    values.push(convert(self.lower(value)?, &element));
+                }
+                Ok(HirExpr::Aggregate {
+                    elements: values,
+                    expr_type,
+                    origin: None,
+                })
+            }
+            Expr::EnumVariant { enum_name, variant, .. } => {
+                let id = self
+                    .analysis()?
+                    .enums
+                    .iter()
+                    .find(|info| info.name == *enum_name)
+                    .and_then(|info| info.variants.iter().find(|(name, _)| name == variant))
+                    .map(|(_, id)| *id)
+                    .ok_or_else(|| format!("Undefined variant '{}::{}'", enum_name, variant))?;
+                Ok(HirExpr::Const {
+                    id,
+                    expr_type: self.analyzed_type(expr)?,
+                    origin: None,
+                })
+            }
+            Expr::Field { object, name, .. } => {
+                let object = self.lower(object)?;
+                let index = self.field_of(&object, name)?;
+                Ok(HirExpr::Field {
+                    object: Box::new(object),
+                    index,
+                    expr_type: self.analyzed_type(expr)?,
+                    origin: None,
                 })
             }
+            Expr::Index { object, index, .. } => Ok(HirExpr::Index {
+                array: Box::new(self.lower(object)?),
+                index: Box::new(convert(self.lower(index)?, &Type::Integer(IntType::I64))),
+                expr_type: self.analyzed_type(expr)?,
+                origin: None,
+            }),
+            Expr::SetField { object, name, operator, value, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
+                let mut object = self.lower(object)?;
+                let index = self.field_of(&object, name)?;
+                let mut statements = Vec::new();
+                let value = self.lower(value)?;
+                // `a.x += y` is `a.x = a.x + y` with `a` evaluated once.
+                let value = match operator {
+                    Some(operator) => {
+                        object = self.bind(object, format!("{}.object", name), &mut statements)?;
+                        let current = HirExpr::Field {
+                            object: Box::new(object.clone()),
+                            index,
+                            expr_type: expr_type.clone(),
+                            origin: None,
+                        };
+                        binary(operator, current, value, expr_type.clone())?
+                    }
+                    None => convert(value, &expr_type),
+                };
+                let store = HirExpr::SetField {
+                    object: Box::new(object),
+                    index,
+                    value: Box::new(value),
+                    expr_type: expr_type.clone(),
+                    origin: None,
+                };
+                Ok(with_statements(statements, store))
+            }
+            Expr::SetIndex { object, index, operator, value, .. } => {
+                let expr_type = self.analyzed_type(expr)?;
+                let mut array = self.lower(object)?;
+                let mut index = convert(self.lower(index)?, &Type::Integer(IntType::I64));
+                let mut statements = Vec::new();
+                let value = self.lower(value)?;
+                // `a[i] += y` is `a[i] = a[i] + y` with `a` and `i` evaluated once.
+                let value = match operator {
+                    Some(operator) => {
+                        array = self.bind(array, "array".to_string(), &mut statements)?;
+                        index = self.bind(index, "index".to_string(), &mut statements)?;
+                        let current = HirExpr::Index {
+                            array: Box::new(array.clone()),
+                            index: Box::new(index.clone()),
+                            expr_type: expr_type.clone(),
+                            origin: None,
+                        };
+                        binary(operator, current, value, expr_type.clone())?
+                    }
+                    None => convert(value, &expr_type),
+                };
+                let store = HirExpr::SetIndex {
+                    array: Box::new(array),
+                    index: Box::new(index),
+                    value: Box::new(value),
+                    expr_type: expr_type.clone(),
+                    origin: None,
+                };
+                Ok(with_statements(statements, store))
+            }
+            Expr::Variable { name, .. } => match self.lookup(expr)? {
+                Some(SymbolKind::Variable { id: variable, .. }) => {
+                    let expr_type = self.analysis()?.variables[variable].ty.clone();
+                    Ok(HirExpr::Variable {
+                        place: self.place_of(variable)?,
+                        expr_type,
+                        origin: None,
+                    })
+                }
+                Some(SymbolKind::Function { id: item }) => self.item_closure(item, name),
+                Some(SymbolKind::Static { id }) => Ok(HirExpr::Variable {
+                    place: HirPlace::Static(id),
+                    expr_type: self.analysis()?.statics[id].ty.clone(),
+                    origin: None,
+                }),
+                Some(SymbolKind::Const { id }) => Ok(HirExpr::Const {
+                    id,
+                    expr_type: self.analysis()?.consts[id].ty.clone(),
+                    origin: None,
+                }),
+                None => Err(format!("Undefined variable '{}'", name)),
+            },
+        }
+    }
+
+    /// Lowers an expression whose value is discarded, where `x++` can
+    /// update `x` in place just as `++x` does.
+    fn lower_effect(&mut self, expr: &Expr) -> Result<HirExpr, String> {
+        match expr {
+            Expr::Update { name, operator, span, id, .. } => Ok(self
+                .lower_update(expr, name, operator, true)?
+                .or_origin(HirOrigin { node: *id, span: *span })),
+            _ => self.lower(expr),
+        }
+    }
+
+    /// `++x` is `x = x + 1`. `x++` keeps the old value in a temporary,
+    /// assigns it plus one to `x` and evaluates to the temporary.
+    fn lower_update(&mut self, expr: &Expr, name: &str, operator: &Token, prefix: bool) -> Result<HirExpr, String> {
+        let (place, expr_type) = self.assignment_place(expr, name)?;
+        let variable = |place: HirPlace| HirExpr::Variable {
+            place,
+            expr_type: expr_type.clone(),
+            origin: None,
+        };
+        let one = HirExpr::Literal {
+            value: numeric_literal(&Token::Integer(1), &expr_type)?,
+            expr_type: expr_type.clone(),
+            origin: None,
+        };
+        if prefix {
+            let value = binary(operator, variable(place.clone()), one, expr_type.clone())?;
+            return Ok(HirExpr::Assign {
+                place,
+                value: Box::new(value),
+                expr_type,
+                origin: None,
+            });
+        }
+        let old = self.temporary(format!("{}.old", name), expr_type.clone())?;
+        let value = binary(operator, variable(HirPlace::Local(old)), one, expr_type.clone())?;
+        Ok(HirExpr::Block {
+            statements: vec![
+                HirStmt::synthesized(HirStmtKind::Let {
+                    local: old,
+                    value: Some(variable(place.clone())),
+                }),
+                HirStmt::synthesized(HirStmtKind::Expr(HirExpr::Assign {
+                    place,
+                    value: Box::new(value),
+                    expr_type: expr_type.clone(),
+                    origin: None,
+                })),
+            ],
+            value: Some(Box::new(variable(HirPlace::Local(old)))),
+            expr_type,
+            origin: None,
+        })
+    }
+
+    /// Stores `value` in a new temporary, adding its declaration to
+    /// `statements`, and returns a read of the temporary.
+    fn bind(&mut self, value: HirExpr, name: String, statements: &mut Vec<HirStmt>) -> Result<HirExpr, String> {
+        let ty = value.ty().clone();
+        let local = self.temporary(name, ty.clone())?;
+        statements.push(HirStmt::synthesized(HirStmtKind::Let { local, value: Some(value) }));
+        Ok(HirExpr::Variable {
+            place: HirPlace::Local(local),
+            expr_type: ty,
+            origin: None,
+        })
+    }
+
+    /// The index of field `name` of the struct `object` evaluates to.
+    fn field_of(&self, object: &HirExpr, name: &str) -> Result<usize, String> {
+        let fields = struct_fields(object.ty()).ok_or_else(|| format!("Type {} has no fields", object.ty()))?;
+        field_index(fields, name)
+    }
+
+    /// The variable or static an assignment to `name` writes, and its type.
+    fn assignment_place(&mut self, expr: &Expr, name: &str) -> Result<(HirPlace, Type), String> {
+        match self.lookup(expr)? {
+            Some(SymbolKind::Variable { id, .. }) => Ok((self.place_of(id)?, self.analysis()?.variables[id].ty.clone())),
+            Some(SymbolKind::Static { id }) => Ok((HirPlace::Static(id), self.analysis()?.statics[id].ty.clone())),
+            _ => Err(format!("Cannot assign to '{}'", name)),
+        }
+    }
+}
+
+/// A binary operation of `expr_type`, converting operands of different
+/// numeric types to their common type.
+fn binary(operator: &Token, left: HirExpr, right: HirExpr, expr_type: Type) -> Result<HirExpr, String> {
+    let operator = BinaryOperator::from_token(operator)
+        .ok_or_else(|| format!("Invalid binary operator: {:?}", operator))?;
+    let op = match operator {
+        BinaryOperator::Add => HirBinaryOp::Add,
+        BinaryOperator::Subtract => HirBinaryOp::Subtract,
+        BinaryOperator::Multiply => HirBinaryOp::Multiply,
+        BinaryOperator::Divide => HirBinaryOp::Divide,
+        BinaryOperator::Remainder => HirBinaryOp::Remainder,
+        BinaryOperator::Equal => HirBinaryOp::Equal,
+        BinaryOperator::NotEqual => HirBinaryOp::NotEqual,
+        BinaryOperator::Greater => HirBinaryOp::GreaterThan,
+        BinaryOperator::Less => HirBinaryOp::LessThan,
+        BinaryOperator::GreaterEqual => HirBinaryOp::GreaterThanOrEqual,
+        BinaryOperator::LessEqual => HirBinaryOp::LessThanOrEqual,
+        BinaryOperator::And | BinaryOperator::Or => return Err(format!("Invalid binary operator: {}", operator)),
+    };
+    let typing = binary_typing(operator, left.ty(), right.ty())?;
+    Ok(HirExpr::Binary {
+        op,
+        left: Box::new(convert(left, &typing.operands)),
+        right: Box::new(convert(right, &typing.operands)),
+        expr_type,
+        origin: None,
+    })
+}
+
+/// The position of field `name` among a struct's fields.
+fn field_index(fields: &[(String, Type)], name: &str) -> Result<usize, String> {
+    fields
+        .iter()
+        .position(|(field, _)| field == name)
+        .ok_or_else(|| format!("Undefined field '{}'", name))
+}
+
+/// `expr` run after `statements`, or `expr` itself when there are none.
+fn with_statements(statements: Vec<HirStmt>, expr: HirExpr) -> HirExpr {
+    if statements.is_empty() {
+        return expr;
+    }
+    let expr_type = expr.ty().clone();
+    HirExpr::Block {
+        statements,
+        value: Some(Box::new(expr)),
+        expr_type,
+        origin: None,
+    }
+}
+
+/// Where a statement is written, as the origin of what it lowers to.
+fn origin(statement: &Stmt) -> HirOrigin {
+    HirOrigin {
+        node: statement.id(),
+        span: statement.span(),
+    }
+}
+
+/// The branch of an `if` statement, which has no value.
+fn void_block(statements: Vec<HirStmt>) -> HirExpr {
+    HirExpr::Block {
+        statements,
+        value: None,
+        expr_type: Type::Void,
+        origin: None,
+    }
+}
+
+/// Applies an implicit arithmetic conversion when the value needs one.
+fn convert(expr: HirExpr, ty: &Type) -> HirExpr {
+    if expr.ty() == ty {
+        expr
+    } else {
+        HirExpr::Cast {
+            expr: Box::new(expr),
+            expr_type: ty.clone(),
+            origin: None,
         }
     }
 }
+
+/// The HIR literal for a number token given the type analysis chose for it.
+fn numeric_literal(token: &Token, ty: &Type) -> Result<HirLiteral, String> {
+    match (token, ty.representation()) {
+        (Token::Integer(value), Type::Integer(int)) => Ok(HirLiteral::Integer(*value, *int)),
+        // Rounded to the literal's type, as the value it stands for at run time.
+        (Token::Integer(value), Type::Float(FloatType::F32)) => Ok(HirLiteral::Float(*value as f32 as f64, FloatType::F32)),
+        (Token::Integer(value), Type::Float(float)) => Ok(HirLiteral::Float(*value as f64, *float)),
+        (Token::Number(value), Type::Float(float)) => Ok(HirLiteral::Float(round(*value, *float), *float)),
+        _ => Err(format!("Literal {} cannot have type {}", token, ty)),
+    }
+}
+
+/// An error from evaluating a constant or a static initializer, pointing
+/// at the item when its declaration is known.
+/// The discriminant of an enum variant declared without one: 0 for the
+/// first variant and otherwise one more than the variant before, whose
+/// discriminant is the constant before it.
+fn implicit_discriminant(id: usize, first: bool, ty: Type) -> HirExpr {
+    let literal = |value: u64| HirExpr::Literal {
+        value: HirLiteral::Integer(value, IntType::I64),
+        expr_type: ty.clone(),
+        origin: None,
+    };
+    if first {
+        return literal(0);
+    }
+    HirExpr::Binary {
+        op: HirBinaryOp::Add,
+        left: Box::new(HirExpr::Const {
+            id: id - 1,
+            expr_type: ty.clone(),
+            origin: None,
+        }),
+        right: Box::new(literal(1)),
+        expr_type: ty.clone(),
+        origin: None,
+    }
+}
+
+fn const_evaluation(error: String, origin: Option<&HirOrigin>) -> Diagnostic {
+    let error = Diagnostic::error(error).with_code(codes::CONST_EVALUATION);
+    match origin {
+        Some(origin) => error.with_label(origin.span, "evaluated here"),
+        None => error,
+    }
+}
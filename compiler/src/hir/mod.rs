@@ -1,2 +1,6 @@
+pub mod conditional;
+pub mod const_eval;
+pub mod flow;
 pub mod hir;
 pub mod lowerer;
+pub mod validate;
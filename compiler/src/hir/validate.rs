@@ -0,0 +1,96 @@
+use crate::hir::hir::{HirExpr, HirFunction, HirProgram, HirStmt, HirStmtKind};
+use crate::semantic::symbol_table::Type;
+
+/// Checks that every type in a lowered program was resolved by analysis.
+/// Lowering takes types from the analyzer as they are, so a type still being
+/// inferred here means the two disagree about the program, which is a
+/// compiler bug; the error names the first place it was found.
+pub fn check_types(program: &HirProgram) -> Result<(), String> {
+    for function in &program.functions {
+        check_function(function).map_err(|error| format!("in function '{}': {}", function.name, error))?;
+    }
+    for item in &program.consts {
+        check_type(&item.ty, || format!("const '{}'", item.name))?;
+    }
+    for item in &program.statics {
+        check_type(&item.ty, || format!("static '{}'", item.name))?;
+    }
+    Ok(())
+}
+
+fn check_function(function: &HirFunction) -> Result<(), String> {
+    check_type(&function.return_type, || "the return type".to_string())?;
+    for local in &function.locals {
+        check_type(&local.ty, || format!("local '{}'", local.name))?;
+    }
+    for (index, slot) in function.env.iter().flatten().enumerate() {
+        check_type(&slot.ty, || format!("environment slot {}", index))?;
+    }
+    check_body(&function.body)
+}
+
+fn check_body(body: &[HirStmt]) -> Result<(), String> {
+    for statement in body {
+        match &statement.kind {
+            HirStmtKind::Let { value, .. } => value.iter().try_for_each(check_expr)?,
+            HirStmtKind::Expr(expr) => check_expr(expr)?,
+            HirStmtKind::Return(value) => value.iter().try_for_each(check_expr)?,
+            HirStmtKind::Loop { body, continuing } => {
+                check_body(body)?;
+                check_body(continuing)?;
+            }
+            HirStmtKind::Break | HirStmtKind::Continue => {}
+        }
+    }
+    Ok(())
+}
+
+fn check_expr(expr: &HirExpr) -> Result<(), String> {
+    check_type(expr.ty(), || format!("expression {:?}", expr))?;
+    match expr {
+        HirExpr::Assign { value, .. } => check_expr(value),
+        HirExpr::Binary { left, right, .. } | HirExpr::Logical { left, right, .. } => {
+            check_expr(left)?;
+            check_expr(right)
+        }
+        HirExpr::Unary { expr, .. } | HirExpr::Cast { expr, .. } => check_expr(expr),
+        HirExpr::If { condition, then_branch, else_branch, .. } => {
+            check_expr(condition)?;
+            check_expr(then_branch)?;
+            check_expr(else_branch)
+        }
+        HirExpr::Block { statements, value, .. } => {
+            check_body(statements)?;
+            value.iter().try_for_each(|value| check_expr(value))
+        }
+        HirExpr::Call { args, .. } => args.iter().try_for_each(check_expr),
+        HirExpr::CallIndirect { callee, args, .. } => {
+            check_expr(callee)?;
+            args.iter().try_for_each(check_expr)
+        }
+        HirExpr::Aggregate { elements, .. } => elements.iter().try_for_each(check_expr),
+        HirExpr::Field { object, .. } => check_expr(object),
+        HirExpr::Index { array, index, .. } => {
+            check_expr(array)?;
+            check_expr(index)
+        }
+        HirExpr::SetField { object, value, .. } => {
+            check_expr(object)?;
+            check_expr(value)
+        }
+        HirExpr::SetIndex { array, index, value, .. } => {
+            check_expr(array)?;
+            check_expr(index)?;
+            check_expr(value)
+        }
+        HirExpr::Literal { .. } | HirExpr::Variable { .. } | HirExpr::Const { .. } | HirExpr::Closure { .. } => Ok(()),
+    }
+}
+
+fn check_type(ty: &Type, site: impl FnOnce() -> String) -> Result<(), String> {
+    if ty.is_resolved() {
+        Ok(())
+    } else {
+        Err(format!("{} has the unresolved type {:?}", site(), ty))
+    }
+}
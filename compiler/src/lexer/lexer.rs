@@ -1,3 +1,4 @@
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
 use crate::lexer::token::{Operation, Punctuation, Reserved, Token, TokenInfo};
 
 pub struct Lexer {
@@ -17,23 +18,35 @@ impl Lexer {
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<TokenInfo>, String> {
+    /// Splits the input into tokens. Every invalid token is reported to
+    /// `sink`, and the first one is returned as the error.
+    pub fn tokenize(&mut self, sink: &mut DiagnosticSink) -> Result<Vec<TokenInfo>, Diagnostic> {
         let mut tokens = Vec::new();
+        let mut first_error = None;
 
         while !self.is_at_end() {
             let token_info = self.next_token();
             // Skip whitespace tokens for cleaner output
             if !matches!(token_info.token, Token::Whitespace) {
                 if let Token::Invalid(msg) = &token_info.token {
-                    return Err(format!(
+                    let error = Diagnostic::error(format!(
                         "Invalid token at line {}, column {}: {}",
                         token_info.line, token_info.column, msg
-                    ));
+                    ))
+                    .with_code(codes::INVALID_TOKEN)
+                    .with_label(token_info.span(), "");
+                    sink.emit(error.clone());
+                    first_error.get_or_insert(error);
+                    continue;
                 }
                 tokens.push(token_info);
             }
         }
 
+        if let Some(error) = first_error {
+            return Err(error);
+        }
+
         tokens.push(TokenInfo::new(
             Token::Eof,
             String::new(),
@@ -70,6 +83,9 @@ impl Lexer {
                 if self.peek() == '=' {
                     self.advance();
                     Token::Operation(Operation::IfEqual)
+                } else if self.peek() == '>' {
+                    self.advance();
+                    Token::Punctuation(Punctuation::FatArrow)
                 } else {
                     Token::Operation(Operation::Assign)
                 }
@@ -99,14 +115,64 @@ impl Lexer {
                 }
             }
 
+            '&' => {
+                if self.peek() == '&' {
+                    self.advance();
+                    Token::Operation(Operation::And)
+                } else {
+                    Token::Invalid(ch.to_string())
+                }
+            }
+            '|' => {
+                if self.peek() == '|' {
+                    self.advance();
+                    Token::Operation(Operation::Or)
+                } else {
+                    Token::Punctuation(Punctuation::Pipe)
+                }
+            }
+
             // Single character operators
-            '+' => Token::Operation(Operation::Add),
-            '-' => Token::Operation(Operation::Subtract),
-            '*' => Token::Operation(Operation::Multiply),
+            '+' => {
+                if self.peek() == '+' {
+                    self.advance();
+                    Token::Operation(Operation::Increment)
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Token::Operation(Operation::AddAssign)
+                } else {
+                    Token::Operation(Operation::Add)
+                }
+            }
+            '-' => {
+                if self.peek() == '>' {
+                    self.advance();
+                    Token::Punctuation(Punctuation::Arrow)
+                } else if self.peek() == '-' {
+                    self.advance();
+                    Token::Operation(Operation::Decrement)
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Token::Operation(Operation::SubtractAssign)
+                } else {
+                    Token::Operation(Operation::Subtract)
+                }
+            }
+            '*' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Token::Operation(Operation::MultiplyAssign)
+                } else {
+                    Token::Operation(Operation::Multiply)
+                }
+            }
             '%' => {
                 if self.peek() == '%' {
                     self.advance();
                     Token::Operation(Operation::Remainder)
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Token::Operation(Operation::ModuloAssign)
                 } else {
                     Token::Operation(Operation::Modulo)
                 }
@@ -120,6 +186,9 @@ impl Lexer {
                 } else if self.peek() == '*' {
                     self.advance();
                     Token::Punctuation(Punctuation::CommentBlkStr)
+                } else if self.peek() == '=' {
+                    self.advance();
+                    Token::Operation(Operation::DivideAssign)
                 } else {
                     Token::Operation(Operation::Divide)
                 }
@@ -139,6 +208,7 @@ impl Lexer {
             '?' => Token::Punctuation(Punctuation::QuestionMark),
             '#' => Token::Punctuation(Punctuation::Hashtag),
             '@' => Token::Punctuation(Punctuation::At),
+            '$' => Token::Punctuation(Punctuation::Dollar),
 
             // String literals
             '"' | '\'' => self.string(ch),
@@ -209,6 +279,12 @@ impl Lexer {
             while self.peek().is_ascii_digit() {
                 self.advance();
             }
+        } else {
+            let number_str: String = self.input[start..self.current].iter().collect();
+            return match number_str.parse::<u64>() {
+                Ok(num) => Token::Integer(num),
+                Err(_) => Token::Invalid(format!("Integer literal too large: {}", number_str)),
+            };
         }
 
         let number_str: String = self.input[start..self.current].iter().collect();
@@ -259,6 +335,10 @@ impl Lexer {
             "import" => Some(Reserved::Import),
             "export" => Some(Reserved::Export),
             "define" | "Define" => Some(Reserved::Define),
+            "macro" => Some(Reserved::Macro),
+            "as" => Some(Reserved::As),
+            "mut" => Some(Reserved::Mut),
+            "const" => Some(Reserved::Const),
 
             _ => None,
         };
@@ -1,3 +1,5 @@
+use crate::diagnostics::Span;
+
 #[allow(dead_code, unused_variables)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Punctuation {
@@ -17,6 +19,38 @@ pub enum Punctuation {
     CommentBlkStr = 13, // /*
     CommentBlkEnd = 14, // *\
     At = 15,            // @
+    Pipe = 16,          // |
+    FatArrow = 17,      // =>
+    Arrow = 18,         // ->
+    Dollar = 19,        // $
+}
+
+impl Punctuation {
+    /// How the punctuation is written.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Punctuation::OpenParen => "(",
+            Punctuation::CloseParen => ")",
+            Punctuation::OpenBrace => "{",
+            Punctuation::CloseBrace => "}",
+            Punctuation::OpenBracket => "[",
+            Punctuation::CloseBracket => "]",
+            Punctuation::Comma => ",",
+            Punctuation::Semicolon => ";",
+            Punctuation::Dot => ".",
+            Punctuation::Colon => ":",
+            Punctuation::QuestionMark => "?",
+            Punctuation::Comment => "//",
+            Punctuation::Hashtag => "#",
+            Punctuation::CommentBlkStr => "/*",
+            Punctuation::CommentBlkEnd => "*/",
+            Punctuation::At => "@",
+            Punctuation::Pipe => "|",
+            Punctuation::FatArrow => "=>",
+            Punctuation::Arrow => "->",
+            Punctuation::Dollar => "$",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,6 +69,46 @@ pub enum Operation {
     Not,          // !
     Modulo,       // %
     Remainder,    // %%
+    And,          // &&
+    Or,           // ||
+    AddAssign,      // +=
+    SubtractAssign, // -=
+    MultiplyAssign, // *=
+    DivideAssign,   // /=
+    ModuloAssign,   // %=
+    Increment,      // ++
+    Decrement,      // --
+}
+
+impl Operation {
+    /// How the operator is written.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operation::Add => "+",
+            Operation::Subtract => "-",
+            Operation::Multiply => "*",
+            Operation::Divide => "/",
+            Operation::Assign => "=",
+            Operation::IfEqual => "==",
+            Operation::NotEqual => "!=",
+            Operation::Greater => ">",
+            Operation::Less => "<",
+            Operation::GreaterEqual => ">=",
+            Operation::LessEqual => "<=",
+            Operation::Not => "!",
+            Operation::Modulo => "%",
+            Operation::Remainder => "%%",
+            Operation::And => "&&",
+            Operation::Or => "||",
+            Operation::AddAssign => "+=",
+            Operation::SubtractAssign => "-=",
+            Operation::MultiplyAssign => "*=",
+            Operation::DivideAssign => "/=",
+            Operation::ModuloAssign => "%=",
+            Operation::Increment => "++",
+            Operation::Decrement => "--",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -72,6 +146,54 @@ pub enum Reserved {
     StructField,
     TypeAlias,
     TypeDef,
+    As,
+    Mut,
+    Const,
+}
+
+impl Reserved {
+    /// The lowercase spelling of the keyword. The lexer also accepts a
+    /// capitalized one for some keywords.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Reserved::Null => "null",
+            Reserved::Void => "void",
+            Reserved::Let => "let",
+            Reserved::Fn => "fn",
+            Reserved::If => "if",
+            Reserved::Else => "else",
+            Reserved::While => "while",
+            Reserved::For => "for",
+            Reserved::Continue => "continue",
+            Reserved::Break => "break",
+            Reserved::Return => "return",
+            Reserved::Public => "public",
+            Reserved::Private => "private",
+            Reserved::Static => "static",
+            Reserved::Print => "print",
+            Reserved::True => "true",
+            Reserved::False => "false",
+            Reserved::Define => "define",
+            Reserved::Macro => "macro",
+            Reserved::Struct => "struct",
+            Reserved::Enum => "enum",
+            Reserved::Union => "union",
+            Reserved::Type => "type",
+            Reserved::Trait => "trait",
+            Reserved::Impl => "impl",
+            Reserved::Module => "module",
+            Reserved::Use => "use",
+            Reserved::Import => "import",
+            Reserved::Export => "export",
+            Reserved::EnumVariant => "variant",
+            Reserved::StructField => "field",
+            Reserved::TypeAlias => "alias",
+            Reserved::TypeDef => "def",
+            Reserved::As => "as",
+            Reserved::Mut => "mut",
+            Reserved::Const => "const",
+        }
+    }
 }
 
 use std::fmt::{Display, Formatter};
@@ -79,6 +201,8 @@ use std::fmt::{Display, Formatter};
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Identifier(String),
+    /// A literal written without a fractional part, typed by its context.
+    Integer(u64),
     Number(f64),
     String(String),
     Reserved(Reserved),
@@ -90,18 +214,29 @@ pub enum Token {
     Invalid(String),
 }
 
+impl Token {
+    /// The token quoted for an error message, such as "`;`".
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Eof => self.to_string(),
+            _ => format!("`{}`", self),
+        }
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Token::Identifier(s) => write!(f, "{}", s),
+            Token::Integer(n) => write!(f, "{}", n),
             Token::Number(n) => write!(f, "{}", n),
-            Token::String(s) => write!(f, "{}", s),
-            Token::Reserved(r) => write!(f, "{:?}", r),
-            Token::Operation(o) => write!(f, "{:?}", o),
-            Token::Punctuation(p) => write!(f, "{:?}", p),
+            Token::String(s) => write!(f, "{:?}", s),
+            Token::Reserved(r) => write!(f, "{}", r.keyword()),
+            Token::Operation(o) => write!(f, "{}", o.symbol()),
+            Token::Punctuation(p) => write!(f, "{}", p.symbol()),
             Token::Whitespace => write!(f, " "),
             Token::Newline => write!(f, "\n"),
-            Token::Eof => write!(f, "Eof"),
+            Token::Eof => write!(f, "end of input"),
             Token::Invalid(s) => write!(f, "Invalid({})", s),
         }
     }
@@ -116,6 +251,9 @@ pub struct TokenInfo {
     pub line: usize,
     #[allow(dead_code)]
     pub column: usize,
+    /// Made up by a macro expansion rather than written anywhere, such as
+    /// the parentheses that keep a pasted expression grouped.
+    pub synthesized: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +270,11 @@ impl TokenInfo {
             lexeme,
             line,
             column,
+            synthesized: false,
         }
     }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.line, self.column, self.lexeme.chars().count())
+    }
 }
@@ -1,3 +1,8 @@
+// Stages fail with a `Diagnostic`, which carries labels, notes and fixes.
+// It is only built when compilation fails, so its size does not matter.
+#![allow(clippy::result_large_err)]
+
+pub mod diagnostics;
 pub mod lexer;
 pub mod parser;
 pub mod semantic;
@@ -6,18 +11,138 @@ pub mod mir;
 pub mod lir;
 pub mod codegen;
 
-use crate::codegen::codegen::CodeGenerator;
+#[cfg(test)]
+mod tests;
+
+use crate::codegen::wat::WatGenerator;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::hir::conditional::resolve_conditionals;
+use crate::hir::hir::HirProgram;
+use crate::hir::lowerer::HirLowerer;
 use crate::lexer::lexer::Lexer;
+use crate::lexer::token::TokenInfo;
+use crate::lir::lir::LirModule;
+use crate::lir::lowerer::LirLowerer;
+use crate::mir::lowerer::MirLowerer;
+use crate::mir::mir::MirModule;
+use crate::mir::passes::{OptLevel, PassManager};
+use crate::parser::ast::Stmt;
 use crate::parser::pratt_parser::Parser;
 use crate::semantic::analyzer::SemanticAnalyzer;
+use crate::semantic::lints::LintLevels;
+
+/// The stage whose output `compile` returns. Compilation stops after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Hir,
+    Mir,
+    Lir,
+    Wat,
+    Wasm,
+}
+
+impl Emit {
+    pub const ALL: [Emit; 7] = [Emit::Tokens, Emit::Ast, Emit::Hir, Emit::Mir, Emit::Lir, Emit::Wat, Emit::Wasm];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Emit::Tokens => "tokens",
+            Emit::Ast => "ast",
+            Emit::Hir => "hir",
+            Emit::Mir => "mir",
+            Emit::Lir => "lir",
+            Emit::Wat => "wat",
+            Emit::Wasm => "wasm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Emit> {
+        Emit::ALL.into_iter().find(|emit| emit.name() == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub emit: Emit,
+    pub lint_levels: LintLevels,
+    pub opt_level: OptLevel,
+}
+
+impl CompileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for CompileOptions {
+    /// Options that compile to WebAssembly text with the default lint levels
+    /// and no optimization.
+    fn default() -> Self {
+        Self {
+            emit: Emit::Wat,
+            lint_levels: LintLevels::new(),
+            opt_level: OptLevel::O0,
+        }
+    }
+}
+
+/// The output of the stage `CompileOptions::emit` chose.
+#[derive(Debug)]
+pub enum CompileOutput {
+    Tokens(Vec<TokenInfo>),
+    Ast(Vec<Stmt>),
+    Hir(HirProgram),
+    Mir(MirModule),
+    Lir(LirModule),
+    Wat(String),
+    Wasm(Vec<u8>),
+}
 
-pub fn compile(source: &str) -> Result<String, String> {
-    let mut lexer = Lexer::new(source);
-    let tokens = lexer.tokenize()?;
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse()?;
-    let mut semantic_analyzer = SemanticAnalyzer::new();
-    semantic_analyzer.analyze(&ast)?;
-    let mut codegen = CodeGenerator::new();
-    codegen.codegen(ast)
+/// Runs every stage up to the one `options` asks for: lexing, parsing,
+/// semantic analysis, lowering to HIR and MIR, optimizing the MIR, lowering
+/// to LIR, and generating WebAssembly. Every error and warning of every stage is reported to
+/// `sink`; the first error is also returned.
+pub fn compile(source: &str, options: &CompileOptions, sink: &mut DiagnosticSink) -> Result<CompileOutput, Diagnostic> {
+    let tokens = Lexer::new(source).tokenize(sink)?;
+    if options.emit == Emit::Tokens {
+        return Ok(CompileOutput::Tokens(tokens));
+    }
+    let ast = Parser::new(tokens).parse(sink)?;
+    if options.emit == Emit::Ast {
+        return Ok(CompileOutput::Ast(ast));
+    }
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.lint_levels = options.lint_levels.clone();
+    let ast = resolve_conditionals(ast, &mut analyzer, sink)?;
+    analyzer.analyze_program(&ast, sink)?;
+    let hir = HirLowerer::new().lower_program(&ast, &analyzer, sink)?;
+    if options.emit == Emit::Hir {
+        return Ok(CompileOutput::Hir(hir));
+    }
+    let mut mir = MirLowerer::new().lower(hir, sink)?;
+    let mut passes = PassManager::for_level(options.opt_level);
+    passes.run(&mut mir).map_err(|error| {
+        sink.fail(Diagnostic::error(format!("Internal error: {}", error)).with_code(codes::INTERNAL))
+    })?;
+    for remark in passes.remarks() {
+        sink.remark(remark.clone());
+    }
+    if options.emit == Emit::Mir {
+        return Ok(CompileOutput::Mir(mir));
+    }
+    let lir = LirLowerer::new().lower(mir, sink)?;
+    if options.emit == Emit::Lir {
+        return Ok(CompileOutput::Lir(lir));
+    }
+    let text = WatGenerator::new().generate(&lir, sink)?;
+    if options.emit == Emit::Wat {
+        return Ok(CompileOutput::Wat(text));
+    }
+    let binary = wat::parse_str(&text).map_err(|error| {
+        let error = Diagnostic::error(format!("Generated WebAssembly does not assemble: {}", error));
+        sink.fail(error.with_code(codes::LOWERING))
+    })?;
+    Ok(CompileOutput::Wasm(binary))
 }
@@ -1,9 +1,66 @@
+use crate::semantic::attributes::Attributes;
+
+/// WebAssembly value types.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LirType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl LirType {
+    pub fn is_float(self) -> bool {
+        matches!(self, LirType::F32 | LirType::F64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LirModule {
+    pub functions: Vec<LirFunction>,
+    /// Functions reachable through `CallIndirect`, by table slot.
+    pub table: Vec<usize>,
+    /// Initial contents of linear memory: one segment per `static`, then one
+    /// per distinct string literal.
+    pub data: Vec<LirData>,
+    /// First address past the data segments, where the heap begins.
+    pub heap_start: u32,
+    pub entry: usize,
+}
+
+/// Bytes placed at a fixed address before the program runs.
+#[derive(Debug, Clone)]
+pub struct LirData {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LirFunction {
+    pub name: String,
+    /// Registers `0..params` hold the arguments.
+    pub params: usize,
+    pub registers: Vec<LirType>,
+    pub result: Option<LirType>,
+    pub blocks: Vec<LirBlock>,
+    pub attributes: Attributes,
+}
+
+#[derive(Debug, Clone)]
+pub struct LirBlock {
+    pub label: usize,
+    pub instructions: Vec<LirInstruction>,
+    pub terminator: LirTerminator,
+}
+
 #[derive(Debug, Clone)]
 pub struct LirInstruction {
     pub kind: LirInstructionKind,
-    pub dest: LirOperand,
-    pub src1: LirOperand,
-    pub src2: Option<LirOperand>,
+    /// The type operated on: the operand type of arithmetic and comparisons,
+    /// or the type of the value moved, loaded or stored.
+    pub ty: LirType,
+    pub dest: Option<usize>,
+    pub operands: Vec<LirOperand>,
 }
 
 #[derive(Debug, Clone)]
@@ -12,11 +69,102 @@ pub enum LirInstructionKind {
     Sub,
     Mul,
     Div,
+    /// The remainder of truncating division, with the sign of the dividend.
+    Rem,
+    /// Unsigned variants of integer division, remainder and comparisons.
+    DivU,
+    RemU,
     Mov,
+    Neg,
+    Not,
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    GtU,
+    LtU,
+    GeU,
+    LeU,
+    /// Float minimum and maximum.
+    Min,
+    Max,
+    /// Converts the operand from the given type to `ty`. Integer sources are
+    /// read as signed or unsigned, and integer targets are produced by
+    /// saturating, truncating conversion from floats.
+    Convert { from: LirType, signed: bool },
+    /// Keeps the low `bits` bits of an integer, sign- or zero-extending them
+    /// to the full width. Narrow integer types are kept in this form.
+    Wrap { bits: u32, signed: bool },
+    Call(usize),
+    /// Calls the closure in the first operand through the function table,
+    /// passing the closure itself ahead of the remaining operands.
+    CallIndirect,
+    /// Bump-allocates the given number of bytes on the heap.
+    Alloc(u32),
+    /// Loads from the address in the operand plus the offset.
+    Load(u32),
+    /// Stores the second operand at the address in the first plus the offset.
+    Store(u32),
+    /// The address of the element of the array in the first operand at the
+    /// index in the second, an `i64`; traps when the index is out of bounds.
+    Element,
+    /// Allocates a string holding the bytes of the first string operand
+    /// followed by those of the second.
+    Concat,
+    /// Whether the two string operands hold the same bytes.
+    StringEq,
+}
+
+#[derive(Debug, Clone)]
+pub enum LirTerminator {
+    Return(Option<LirOperand>),
+    Jump(usize),
+    /// Jumps to the first label when the operand is non-zero, otherwise to the second.
+    Branch(LirOperand, usize, usize),
 }
 
 #[derive(Debug, Clone)]
 pub enum LirOperand {
     Register(usize),
-    Constant(f64),
+    Constant(LirConstant),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LirConstant {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl LirConstant {
+    pub fn ty(&self) -> LirType {
+        match self {
+            LirConstant::I32(_) => LirType::I32,
+            LirConstant::I64(_) => LirType::I64,
+            LirConstant::F32(_) => LirType::F32,
+            LirConstant::F64(_) => LirType::F64,
+        }
+    }
+
+    /// The little-endian bytes of the constant, as stored in memory.
+    pub fn to_le_bytes(self) -> Vec<u8> {
+        match self {
+            LirConstant::I32(value) => value.to_le_bytes().to_vec(),
+            LirConstant::I64(value) => value.to_le_bytes().to_vec(),
+            LirConstant::F32(value) => value.to_le_bytes().to_vec(),
+            LirConstant::F64(value) => value.to_le_bytes().to_vec(),
+        }
+    }
+
+    pub fn zero(ty: LirType) -> Self {
+        match ty {
+            LirType::I32 => LirConstant::I32(0),
+            LirType::I64 => LirConstant::I64(0),
+            LirType::F32 => LirConstant::F32(0.0),
+            LirType::F64 => LirConstant::F64(0.0),
+        }
+    }
 }
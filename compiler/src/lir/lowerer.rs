@@ -1,57 +1,579 @@
-use crate::lir::lir::{LirInstruction, LirInstructionKind, LirOperand};
-use crate::mir::mir::{MirBasicBlock, MirBinaryOp, MirInstructionKind, MirOperand};
+use std::collections::HashMap;
 
-pub struct LirLowerer;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::hir::hir::HirLiteral;
+use crate::lir::lir::{
+    LirBlock, LirConstant, LirData, LirFunction, LirInstruction, LirInstructionKind, LirModule, LirOperand,
+    LirTerminator, LirType,
+};
+use crate::mir::mir::{
+    MirBinaryOp, MirBody, MirFunction, MirInstructionKind, MirModule, MirOperand, MirTerminator, MirUnaryOp,
+};
+use crate::mir::ssa::out_of_ssa;
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+
+/// Closures, heap cells, structs and arrays are laid out as 8-byte slots. A
+/// closure stores its function table slot first, followed by one slot per
+/// captured value; a struct or array stores its number of fields or elements
+/// first, as an `i32`, followed by one slot per field or element.
+const SLOT_SIZE: u32 = 8;
+
+/// Statics occupy one slot each from this address on; address 0 is kept
+/// free to act as a null pointer.
+const DATA_START: u32 = 8;
+
+/// A string is a pointer to its length in bytes, an `i32`, followed by the
+/// bytes themselves. Literals live in the data segments; concatenation
+/// allocates new strings on the heap.
+const STRING_HEADER: u32 = 4;
+
+pub struct LirLowerer {
+    table: Vec<usize>,
+    /// The address and type of each static.
+    statics: Vec<(u32, LirType)>,
+    /// The address of each distinct string literal.
+    strings: HashMap<String, u32>,
+}
 
 impl LirLowerer {
     pub fn new() -> Self {
-        Self
+        Self {
+            table: Vec::new(),
+            statics: Vec::new(),
+            strings: HashMap::new(),
+        }
     }
 
-    pub fn lower(&self, blocks: Vec<MirBasicBlock>) -> Result<Vec<LirInstruction>, String> {
-        let mut instructions = Vec::new();
+    pub fn lower(&mut self, module: MirModule, sink: &mut DiagnosticSink) -> Result<LirModule, Diagnostic> {
+        self.lower_module(module)
+            .map_err(|error| sink.fail(Diagnostic::error(error).with_code(codes::LOWERING)))
+    }
+
+    fn lower_module(&mut self, module: MirModule) -> Result<LirModule, String> {
+        let mut data = Vec::new();
+        self.statics.clear();
+        // String literals are laid out after the statics, which may hold them.
+        let mut address = DATA_START + SLOT_SIZE * module.statics.len() as u32;
+        self.strings.clear();
+        for text in string_literals(&module) {
+            if self.strings.contains_key(text) {
+                continue;
+            }
+            let mut bytes = (text.len() as u32).to_le_bytes().to_vec();
+            bytes.extend(text.as_bytes());
+            self.strings.insert(text.clone(), address);
+            data.push(LirData { offset: address, bytes });
+            address = align(address + STRING_HEADER + text.len() as u32, STRING_HEADER);
+        }
+        let heap_start = align(address, SLOT_SIZE);
+
+        for (index, item) in module.statics.into_iter().enumerate() {
+            let offset = DATA_START + SLOT_SIZE * index as u32;
+            let ty = lir_type(&item.ty).unwrap_or(LirType::I32);
+            let bytes = match self.lower_operand_as(MirOperand::Literal(item.value), ty) {
+                Ok(LirOperand::Constant(constant)) => constant.to_le_bytes(),
+                _ => return Err(format!("Static '{}' cannot be stored in the data segment", item.name)),
+            };
+            data.push(LirData { offset, bytes });
+            self.statics.push((offset, ty));
+        }
+
+        let mut functions = Vec::new();
+        for function in module.functions {
+            functions.push(self.lower_function(function)?);
+        }
+
+        Ok(LirModule {
+            functions,
+            table: std::mem::take(&mut self.table),
+            data,
+            heap_start,
+            entry: module.entry,
+        })
+    }
+
+    fn lower_function(&mut self, mut function: MirFunction) -> Result<LirFunction, String> {
+        // LIR has no phis, so the body leaves SSA form here.
+        out_of_ssa(&mut function.body);
+        let mut registers = register_types(&function.body);
+        let result = lir_type(&function.return_type);
+        let mut lir_blocks = Vec::new();
+
+        for (label, block) in function.body.blocks.into_iter().enumerate() {
+            let mut instructions = Vec::new();
 
-        for block in blocks {
             for instruction in block.instructions {
+                let dest = match instruction.dest {
+                    MirOperand::Register(register) => register,
+                    MirOperand::Literal(_) => return Err("Instruction destination must be a register".to_string()),
+                };
+                let value_type = lir_type(&instruction.ty);
+
                 match instruction.kind {
-                    MirInstructionKind::BinaryOp(op, src1, src2) => {
+                    MirInstructionKind::BinaryOp(op, src1, src2) if *instruction.ty.representation() == Type::String => {
                         let kind = match op {
-                            MirBinaryOp::Add => LirInstructionKind::Add,
-                            MirBinaryOp::Subtract => LirInstructionKind::Sub,
-                            MirBinaryOp::Multiply => LirInstructionKind::Mul,
-                            MirBinaryOp::Divide => LirInstructionKind::Div,
-                            _ => return Err(format!("Invalid binary operator: {:?}", op)),
+                            MirBinaryOp::Add => LirInstructionKind::Concat,
+                            MirBinaryOp::Equal | MirBinaryOp::NotEqual => LirInstructionKind::StringEq,
+                            op => return Err(format!("Invalid string operation: {}", op.name())),
+                        };
+                        let negate = matches!(op, MirBinaryOp::NotEqual);
+                        instructions.push(LirInstruction {
+                            kind,
+                            ty: LirType::I32,
+                            dest: Some(dest),
+                            operands: vec![self.lower_operand(src1)?, self.lower_operand(src2)?],
+                        });
+                        if negate {
+                            instructions.push(LirInstruction {
+                                kind: LirInstructionKind::Not,
+                                ty: LirType::I32,
+                                dest: Some(dest),
+                                operands: vec![LirOperand::Register(dest)],
+                            });
+                        }
+                    }
+                    MirInstructionKind::BinaryOp(op, src1, src2) => {
+                        let int = int_type(&instruction.ty);
+                        let unsigned = int.is_some_and(|int| !int.is_signed());
+                        let kind = match (op, unsigned) {
+                            (MirBinaryOp::Add, _) => LirInstructionKind::Add,
+                            (MirBinaryOp::Subtract, _) => LirInstructionKind::Sub,
+                            (MirBinaryOp::Multiply, _) => LirInstructionKind::Mul,
+                            (MirBinaryOp::Divide, false) => LirInstructionKind::Div,
+                            (MirBinaryOp::Divide, true) => LirInstructionKind::DivU,
+                            (MirBinaryOp::Remainder, false) => LirInstructionKind::Rem,
+                            (MirBinaryOp::Remainder, true) => LirInstructionKind::RemU,
+                            (MirBinaryOp::Equal, _) => LirInstructionKind::Eq,
+                            (MirBinaryOp::NotEqual, _) => LirInstructionKind::Ne,
+                            (MirBinaryOp::GreaterThan, false) => LirInstructionKind::Gt,
+                            (MirBinaryOp::GreaterThan, true) => LirInstructionKind::GtU,
+                            (MirBinaryOp::LessThan, false) => LirInstructionKind::Lt,
+                            (MirBinaryOp::LessThan, true) => LirInstructionKind::LtU,
+                            (MirBinaryOp::GreaterThanOrEqual, false) => LirInstructionKind::Ge,
+                            (MirBinaryOp::GreaterThanOrEqual, true) => LirInstructionKind::GeU,
+                            (MirBinaryOp::LessThanOrEqual, false) => LirInstructionKind::Le,
+                            (MirBinaryOp::LessThanOrEqual, true) => LirInstructionKind::LeU,
                         };
-                        let dest = self.lower_operand(instruction.dest)?;
+                        let arithmetic = matches!(
+                            kind,
+                            LirInstructionKind::Add
+                                | LirInstructionKind::Sub
+                                | LirInstructionKind::Mul
+                                | LirInstructionKind::Div
+                                | LirInstructionKind::DivU
+                                | LirInstructionKind::Rem
+                                | LirInstructionKind::RemU
+                        );
                         let src1 = self.lower_operand(src1)?;
                         let src2 = self.lower_operand(src2)?;
                         instructions.push(LirInstruction {
                             kind,
-                            dest,
-                            src1,
-                            src2: Some(src2),
+                            ty: operand_type(&src1, &registers),
+                            dest: Some(dest),
+                            operands: vec![src1, src2],
+                        });
+                        if arithmetic {
+                            instructions.extend(int.and_then(|int| wrap(dest, int)));
+                        }
+                    }
+                    MirInstructionKind::UnaryOp(op, src) => {
+                        let kind = match op {
+                            MirUnaryOp::Negate => LirInstructionKind::Neg,
+                            MirUnaryOp::Not => LirInstructionKind::Not,
+                        };
+                        let negate = matches!(kind, LirInstructionKind::Neg);
+                        let src = self.lower_operand(src)?;
+                        instructions.push(LirInstruction {
+                            kind,
+                            ty: operand_type(&src, &registers),
+                            dest: Some(dest),
+                            operands: vec![src],
+                        });
+                        if negate {
+                            instructions.extend(int_type(&instruction.ty).and_then(|int| wrap(dest, int)));
+                        }
+                    }
+                    MirInstructionKind::Cast(value, source) => {
+                        let value = self.lower_operand(value)?;
+                        instructions.extend(cast(dest, value, &source, &instruction.ty, &mut registers)?);
+                    }
+                    MirInstructionKind::Load(src) => {
+                        let ty = registers[dest];
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Mov,
+                            ty,
+                            dest: Some(dest),
+                            operands: vec![self.lower_operand_as(src, ty)?],
+                        });
+                    }
+                    MirInstructionKind::Call(function, args) => {
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Call(function),
+                            ty: value_type.unwrap_or(LirType::I32),
+                            dest: value_type.map(|_| dest),
+                            operands: self.lower_operands(args)?,
+                        });
+                    }
+                    MirInstructionKind::CallIndirect(callee, args) => {
+                        let mut operands = vec![self.lower_operand(callee)?];
+                        operands.extend(self.lower_operands(args)?);
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::CallIndirect,
+                            ty: value_type.unwrap_or(LirType::I32),
+                            dest: value_type.map(|_| dest),
+                            operands,
+                        });
+                    }
+                    MirInstructionKind::MakeClosure(function, captures) => {
+                        let slot = self.table_slot(function);
+                        instructions.push(alloc(dest, SLOT_SIZE * (captures.len() as u32 + 1)));
+                        instructions.push(store(dest, 0, LirOperand::Constant(LirConstant::I32(slot)), LirType::I32));
+                        for (index, capture) in captures.into_iter().enumerate() {
+                            let capture = self.lower_operand(capture)?;
+                            let ty = operand_type(&capture, &registers);
+                            instructions.push(store(dest, SLOT_SIZE * (index as u32 + 1), capture, ty));
+                        }
+                    }
+                    MirInstructionKind::LoadCapture(closure, slot) => {
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Load(SLOT_SIZE * (slot as u32 + 1)),
+                            ty: registers[dest],
+                            dest: Some(dest),
+                            operands: vec![self.lower_operand(closure)?],
+                        });
+                    }
+                    MirInstructionKind::MakeCell(value) => {
+                        let ty = match &instruction.ty {
+                            Type::Reference(inner) => lir_type(inner).unwrap_or(LirType::I32),
+                            other => return Err(format!("Cell must have a reference type, found {:?}", other)),
+                        };
+                        instructions.push(alloc(dest, SLOT_SIZE));
+                        instructions.push(store(dest, 0, self.lower_operand_as(value, ty)?, ty));
+                    }
+                    MirInstructionKind::LoadCell(cell) => {
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Load(0),
+                            ty: registers[dest],
+                            dest: Some(dest),
+                            operands: vec![self.lower_operand(cell)?],
+                        });
+                    }
+                    MirInstructionKind::StoreCell(cell, value) => {
+                        let value = self.lower_operand(value)?;
+                        let ty = operand_type(&value, &registers);
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Store(0),
+                            ty,
+                            dest: None,
+                            operands: vec![self.lower_operand(cell)?, value],
+                        });
+                    }
+                    MirInstructionKind::LoadStatic(id) => {
+                        let (offset, _) = self.statics[id];
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Load(offset),
+                            ty: registers[dest],
+                            dest: Some(dest),
+                            operands: vec![LirOperand::Constant(LirConstant::I32(0))],
                         });
                     }
-                    MirInstructionKind::UnaryOp(_, _) => {
-                        // TODO: Handle unary operations
+                    MirInstructionKind::StoreStatic(id, value) => {
+                        let (offset, ty) = self.statics[id];
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Store(offset),
+                            ty,
+                            dest: None,
+                            operands: vec![
+                                LirOperand::Constant(LirConstant::I32(0)),
+                                self.lower_operand_as(value, ty)?,
+                            ],
+                        });
+                    }
+                    MirInstructionKind::MakeAggregate(elements) => {
+                        let count = LirOperand::Constant(LirConstant::I32(elements.len() as i32));
+                        instructions.push(alloc(dest, SLOT_SIZE * (elements.len() as u32 + 1)));
+                        instructions.push(store(dest, 0, count, LirType::I32));
+                        for (index, element) in elements.into_iter().enumerate() {
+                            let element = self.lower_operand(element)?;
+                            let ty = operand_type(&element, &registers);
+                            instructions.push(store(dest, SLOT_SIZE * (index as u32 + 1), element, ty));
+                        }
+                    }
+                    MirInstructionKind::LoadField(object, index) => {
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Load(SLOT_SIZE * (index as u32 + 1)),
+                            ty: registers[dest],
+                            dest: Some(dest),
+                            operands: vec![self.lower_operand(object)?],
+                        });
                     }
-                    MirInstructionKind::Load(_) => {
-                        // TODO: Handle load operations
+                    MirInstructionKind::StoreField(object, index, value) => {
+                        let value = self.lower_operand(value)?;
+                        let ty = operand_type(&value, &registers);
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Store(SLOT_SIZE * (index as u32 + 1)),
+                            ty,
+                            dest: None,
+                            operands: vec![self.lower_operand(object)?, value],
+                        });
                     }
+                    MirInstructionKind::LoadElement(array, index) => {
+                        let address = registers.len();
+                        registers.push(LirType::I32);
+                        instructions.push(element(address, self.lower_operand(array)?, self.lower_operand(index)?));
+                        instructions.push(LirInstruction {
+                            kind: LirInstructionKind::Load(0),
+                            ty: registers[dest],
+                            dest: Some(dest),
+                            operands: vec![LirOperand::Register(address)],
+                        });
+                    }
+                    MirInstructionKind::StoreElement(array, index, value) => {
+                        let address = registers.len();
+                        registers.push(LirType::I32);
+                        instructions.push(element(address, self.lower_operand(array)?, self.lower_operand(index)?));
+                        let value = self.lower_operand(value)?;
+                        let ty = operand_type(&value, &registers);
+                        instructions.push(store(address, 0, value, ty));
+                    }
+                    MirInstructionKind::Phi(_) => return Err("Phis must be removed before lowering to LIR".to_string()),
                 }
             }
+
+            let terminator = match block.terminator {
+                MirTerminator::Return(value) => match result {
+                    Some(ty) => LirTerminator::Return(Some(self.lower_operand_as(value, ty)?)),
+                    None => LirTerminator::Return(None),
+                },
+                MirTerminator::Goto(target) => LirTerminator::Jump(target.index()),
+                MirTerminator::Branch(condition, then_block, else_block) => {
+                    LirTerminator::Branch(self.lower_operand(condition)?, then_block.index(), else_block.index())
+                }
+            };
+
+            lir_blocks.push(LirBlock {
+                label,
+                instructions,
+                terminator,
+            });
         }
 
-        Ok(instructions)
+        Ok(LirFunction {
+            name: function.name,
+            params: function.body.args,
+            registers,
+            result,
+            blocks: lir_blocks,
+            attributes: function.attributes,
+        })
+    }
+
+    fn table_slot(&mut self, function: usize) -> i32 {
+        let slot = match self.table.iter().position(|&entry| entry == function) {
+            Some(slot) => slot,
+            None => {
+                self.table.push(function);
+                self.table.len() - 1
+            }
+        };
+        slot as i32
+    }
+
+    fn lower_operands(&self, operands: Vec<MirOperand>) -> Result<Vec<LirOperand>, String> {
+        operands.into_iter().map(|operand| self.lower_operand(operand)).collect()
+    }
+
+    /// Lowers an operand where a value of type `ty` is required; `null` stands
+    /// for "no value" and becomes that type's zero.
+    fn lower_operand_as(&self, operand: MirOperand, ty: LirType) -> Result<LirOperand, String> {
+        match operand {
+            MirOperand::Literal(HirLiteral::Null) => Ok(LirOperand::Constant(LirConstant::zero(ty))),
+            operand => self.lower_operand(operand),
+        }
     }
 
     fn lower_operand(&self, operand: MirOperand) -> Result<LirOperand, String> {
         match operand {
             MirOperand::Literal(literal) => match literal {
-                crate::hir::hir::HirLiteral::Number(n) => Ok(LirOperand::Constant(n)),
-                _ => Err(format!("Invalid literal type: {:?}", literal)),
+                HirLiteral::Integer(value, int) if int.bits() <= 32 => {
+                    Ok(LirOperand::Constant(LirConstant::I32(value as i32)))
+                }
+                HirLiteral::Integer(value, _) => Ok(LirOperand::Constant(LirConstant::I64(value as i64))),
+                HirLiteral::Float(value, FloatType::F32) => Ok(LirOperand::Constant(LirConstant::F32(value as f32))),
+                HirLiteral::Float(value, FloatType::F64) => Ok(LirOperand::Constant(LirConstant::F64(value))),
+                HirLiteral::Boolean(b) => Ok(LirOperand::Constant(LirConstant::I32(b as i32))),
+                HirLiteral::Null => Ok(LirOperand::Constant(LirConstant::I32(0))),
+                HirLiteral::String(text) => match self.strings.get(&text) {
+                    Some(&address) => Ok(LirOperand::Constant(LirConstant::I32(address as i32))),
+                    None => Err(format!("String literal {:?} has no data segment", text)),
+                },
             },
             MirOperand::Register(r) => Ok(LirOperand::Register(r)),
         }
     }
 }
+
+/// The WebAssembly type of a value of the given type, or `None` for `Void`.
+fn lir_type(ty: &Type) -> Option<LirType> {
+    match ty.representation() {
+        Type::Integer(int) if int.bits() <= 32 => Some(LirType::I32),
+        Type::Integer(_) => Some(LirType::I64),
+        Type::Float(FloatType::F32) => Some(LirType::F32),
+        Type::Float(FloatType::F64) => Some(LirType::F64),
+        Type::Boolean
+        | Type::String
+        | Type::Null
+        | Type::Function { .. }
+        | Type::Reference(_)
+        | Type::Struct { .. }
+        | Type::Array { .. } => Some(LirType::I32),
+        Type::Void => None,
+        Type::Alias { .. } | Type::Nominal { .. } => unreachable!("representation() looks through aliases and def types"),
+        Type::Variable(_) => unreachable!("types are fully inferred before lowering"),
+    }
+}
+
+fn int_type(ty: &Type) -> Option<IntType> {
+    match ty.representation() {
+        Type::Integer(int) => Some(*int),
+        _ => None,
+    }
+}
+
+/// Integers narrower than 32 bits live in an `i32`; after arithmetic the
+/// result is brought back into the type's range.
+fn wrap(register: usize, int: IntType) -> Option<LirInstruction> {
+    (int.bits() < 32).then(|| LirInstruction {
+        kind: LirInstructionKind::Wrap {
+            bits: int.bits(),
+            signed: int.is_signed(),
+        },
+        ty: LirType::I32,
+        dest: Some(register),
+        operands: vec![LirOperand::Register(register)],
+    })
+}
+
+/// Converts `value` from `from` to `to` with the semantics of `as`: integers
+/// are truncated or sign- or zero-extended according to the source type,
+/// floats convert to integers rounding toward zero and saturating at the
+/// target's bounds, with NaN becoming zero, and bools convert to 0 or 1.
+fn cast(
+    dest: usize,
+    mut value: LirOperand,
+    from: &Type,
+    to: &Type,
+    registers: &mut Vec<LirType>,
+) -> Result<Vec<LirInstruction>, String> {
+    let (Some(source), Some(target)) = (lir_type(from), lir_type(to)) else {
+        return Err(format!("Cannot cast {} to {}", from, to));
+    };
+    let mut instructions = Vec::new();
+    let signed = match (from.representation(), to.representation()) {
+        (Type::Float(_), Type::Integer(int)) => {
+            // The saturating conversion only saturates at 32 or 64 bits, so
+            // narrower targets clamp the float first.
+            if int.bits() < 32 {
+                let min = if int.is_signed() { -(int.min_magnitude() as f64) } else { 0.0 };
+                let clamped = registers.len();
+                registers.push(source);
+                for (kind, bound) in [(LirInstructionKind::Max, min), (LirInstructionKind::Min, int.max() as f64)] {
+                    let bound = match source {
+                        LirType::F32 => LirConstant::F32(bound as f32),
+                        _ => LirConstant::F64(bound),
+                    };
+                    instructions.push(LirInstruction {
+                        kind,
+                        ty: source,
+                        dest: Some(clamped),
+                        operands: vec![value, LirOperand::Constant(bound)],
+                    });
+                    value = LirOperand::Register(clamped);
+                }
+            }
+            int.is_signed()
+        }
+        (Type::Integer(int), _) => int.is_signed(),
+        _ => false,
+    };
+
+    let kind = if source == target {
+        LirInstructionKind::Mov
+    } else {
+        LirInstructionKind::Convert { from: source, signed }
+    };
+    instructions.push(LirInstruction {
+        kind,
+        ty: target,
+        dest: Some(dest),
+        operands: vec![value],
+    });
+    if let (Type::Integer(_), Type::Integer(int)) = (from.representation(), to.representation()) {
+        instructions.extend(wrap(dest, *int));
+    }
+    Ok(instructions)
+}
+
+fn operand_type(operand: &LirOperand, registers: &[LirType]) -> LirType {
+    match operand {
+        LirOperand::Register(register) => registers[*register],
+        LirOperand::Constant(constant) => constant.ty(),
+    }
+}
+
+/// The WebAssembly type of each register. Registers that hold no value,
+/// such as the results of `Void` calls, default to `i32`.
+fn register_types(body: &MirBody) -> Vec<LirType> {
+    body.locals.iter().map(|local| lir_type(&local.ty).unwrap_or(LirType::I32)).collect()
+}
+
+/// The string literals the module uses, in order of first use, with repeats.
+fn string_literals(module: &MirModule) -> Vec<&String> {
+    let statics = module.statics.iter().map(|item| &item.value);
+    let operands = module.functions.iter().flat_map(|function| &function.body.blocks).flat_map(|block| {
+        let reads = block.instructions.iter().flat_map(|instruction| instruction.kind.operands());
+        reads.chain(block.terminator.operand()).filter_map(|operand| match operand {
+            MirOperand::Literal(literal) => Some(literal),
+            MirOperand::Register(_) => None,
+        })
+    });
+    statics
+        .chain(operands)
+        .filter_map(|literal| match literal {
+            HirLiteral::String(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rounds `address` up to a multiple of `alignment`.
+fn align(address: u32, alignment: u32) -> u32 {
+    address.div_ceil(alignment) * alignment
+}
+
+fn alloc(dest: usize, size: u32) -> LirInstruction {
+    LirInstruction {
+        kind: LirInstructionKind::Alloc(size),
+        ty: LirType::I32,
+        dest: Some(dest),
+        operands: Vec::new(),
+    }
+}
+
+fn element(dest: usize, array: LirOperand, index: LirOperand) -> LirInstruction {
+    LirInstruction {
+        kind: LirInstructionKind::Element,
+        ty: LirType::I32,
+        dest: Some(dest),
+        operands: vec![array, index],
+    }
+}
+
+fn store(address: usize, offset: u32, value: LirOperand, ty: LirType) -> LirInstruction {
+    LirInstruction {
+        kind: LirInstructionKind::Store(offset),
+        ty,
+        dest: None,
+        operands: vec![LirOperand::Register(address), value],
+    }
+}
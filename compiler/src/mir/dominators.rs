@@ -0,0 +1,160 @@
+use crate::mir::mir::{BlockId, MirBody};
+
+/// The dominator tree of a body. A block dominates another when every path
+/// from the entry to the other block passes through it. Blocks that cannot
+/// be reached from the entry are not part of the tree.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// The immediate dominator of each reachable block, with the entry
+    /// block as its own.
+    idoms: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+    reverse_postorder: Vec<BlockId>,
+}
+
+impl Dominators {
+    /// Computes the tree with the iterative algorithm of Cooper, Harvey and
+    /// Kennedy, which visits blocks in reverse postorder until nothing changes.
+    pub fn new(body: &MirBody) -> Self {
+        let reverse_postorder = reverse_postorder(body);
+        let mut order = vec![usize::MAX; body.blocks.len()];
+        for (position, id) in reverse_postorder.iter().enumerate() {
+            order[id.index()] = position;
+        }
+        let predecessors = body.predecessors();
+
+        let mut idoms = vec![None; body.blocks.len()];
+        idoms[BlockId::ENTRY.index()] = Some(BlockId::ENTRY);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in &reverse_postorder[1..] {
+                let mut processed = predecessors[id.index()].iter().filter(|pred| idoms[pred.index()].is_some());
+                let Some(&first) = processed.next() else {
+                    continue;
+                };
+                let idom = processed.fold(first, |idom, &pred| intersect(&idoms, &order, idom, pred));
+                if idoms[id.index()] != Some(idom) {
+                    idoms[id.index()] = Some(idom);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); body.blocks.len()];
+        for &id in &reverse_postorder[1..] {
+            if let Some(idom) = idoms[id.index()] {
+                children[idom.index()].push(id);
+            }
+        }
+        Self {
+            idoms,
+            children,
+            reverse_postorder,
+        }
+    }
+
+    /// The closest block that strictly dominates `id`; `None` for the entry
+    /// and unreachable blocks.
+    pub fn immediate_dominator(&self, id: BlockId) -> Option<BlockId> {
+        self.idoms[id.index()].filter(|_| id != BlockId::ENTRY)
+    }
+
+    pub fn is_reachable(&self, id: BlockId) -> bool {
+        self.idoms[id.index()].is_some()
+    }
+
+    /// Whether `a` dominates `b`. Every block dominates itself, and an
+    /// unreachable block dominates nothing and is dominated by nothing.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        if !self.is_reachable(a) || !self.is_reachable(b) {
+            return false;
+        }
+        let mut current = b;
+        loop {
+            if current == a {
+                return true;
+            }
+            match self.immediate_dominator(current) {
+                Some(idom) => current = idom,
+                None => return false,
+            }
+        }
+    }
+
+    /// The blocks `id` immediately dominates, in reverse postorder.
+    pub fn children(&self, id: BlockId) -> &[BlockId] {
+        &self.children[id.index()]
+    }
+
+    /// The reachable blocks, each after all of its predecessors except those
+    /// that reach it through a back edge.
+    pub fn reverse_postorder(&self) -> &[BlockId] {
+        &self.reverse_postorder
+    }
+
+    /// The dominance frontier of each block, indexed by block: the blocks
+    /// where its dominance ends, which it does not strictly dominate but
+    /// one of whose predecessors it dominates. These are where a value
+    /// written in the block may meet other values.
+    pub fn frontiers(&self, body: &MirBody) -> Vec<Vec<BlockId>> {
+        let mut frontiers = vec![Vec::new(); body.blocks.len()];
+        for (index, predecessors) in body.predecessors().into_iter().enumerate() {
+            let id = BlockId(index);
+            if predecessors.len() < 2 || !self.is_reachable(id) {
+                continue;
+            }
+            for pred in predecessors.into_iter().filter(|&pred| self.is_reachable(pred)) {
+                let mut runner = pred;
+                while Some(runner) != self.immediate_dominator(id) {
+                    let frontier: &mut Vec<BlockId> = &mut frontiers[runner.index()];
+                    if !frontier.contains(&id) {
+                        frontier.push(id);
+                    }
+                    match self.immediate_dominator(runner) {
+                        Some(idom) => runner = idom,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+/// The nearest common dominator of two processed blocks: walks up from
+/// whichever comes later in reverse postorder until the two meet.
+fn intersect(idoms: &[Option<BlockId>], order: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while order[a.index()] > order[b.index()] {
+            a = idoms[a.index()].expect("processed blocks have a dominator");
+        }
+        while order[b.index()] > order[a.index()] {
+            b = idoms[b.index()].expect("processed blocks have a dominator");
+        }
+    }
+    a
+}
+
+fn reverse_postorder(body: &MirBody) -> Vec<BlockId> {
+    let mut visited = vec![false; body.blocks.len()];
+    let mut postorder = Vec::new();
+    // Each entry is a block and how many of its successors have been visited.
+    let mut stack = vec![(BlockId::ENTRY, 0)];
+    visited[BlockId::ENTRY.index()] = true;
+    while let Some((id, next)) = stack.pop() {
+        let successors = body.successors(id);
+        match successors.get(next) {
+            Some(&successor) => {
+                stack.push((id, next + 1));
+                if !visited[successor.index()] {
+                    visited[successor.index()] = true;
+                    stack.push((successor, 0));
+                }
+            }
+            None => postorder.push(id),
+        }
+    }
+    postorder.reverse();
+    postorder
+}
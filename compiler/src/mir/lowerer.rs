@@ -1,32 +1,377 @@
-use crate::hir::hir::{HirBinaryOp, HirExpr, HirUnaryOp};
-use crate::mir::mir::{MirBasicBlock, MirBinaryOp, MirInstruction, MirInstructionKind, MirOperand, MirTerminator, MirUnaryOp};
+use crate::diagnostics::{codes, Diagnostic, DiagnosticSink};
+use crate::hir::const_eval::ConstValue;
+use crate::hir::hir::{
+    HirBinaryOp, HirEnvSlot, HirExpr, HirFunction, HirLiteral, HirLogicalOp, HirPlace, HirProgram, HirStmt, HirStmtKind,
+    HirUnaryOp,
+};
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBinaryOp, MirBody, MirFunction, MirInstruction, MirInstructionKind, MirLocal, MirModule,
+    MirOperand, MirStatic, MirTerminator, MirUnaryOp,
+};
+use crate::mir::ssa::into_ssa;
+use crate::semantic::analyzer::CaptureMode;
+use crate::semantic::symbol_table::Type;
 
-pub struct MirLowerer {
+/// A block under construction; its terminator is filled in once control flow
+/// out of it is known.
+struct PendingBlock {
     instructions: Vec<MirInstruction>,
-    registers: usize,
+    terminator: Option<MirTerminator>,
+}
+
+/// How the function being lowered stores its HIR locals.
+struct LocalSlot {
+    register: usize,
+    ty: Type,
+    boxed: bool,
+}
+
+pub struct MirLowerer {
+    blocks: Vec<PendingBlock>,
+    current_block: BlockId,
+    /// The registers of the function being lowered, each typed by the
+    /// instructions that write it.
+    registers: Vec<MirLocal>,
+    locals: Vec<LocalSlot>,
+    env: Option<(MirOperand, Vec<HirEnvSlot>)>,
+    static_types: Vec<Type>,
+    /// Values of `const` items, which uses are replaced with.
+    consts: Vec<ConstValue>,
+    /// Continue and exit blocks of the loops around the current statement,
+    /// where `continue` and `break` jump.
+    loops: Vec<(BlockId, BlockId)>,
 }
 
 impl MirLowerer {
     pub fn new() -> Self {
         Self {
-            instructions: Vec::new(),
-            registers: 0,
+            blocks: Vec::new(),
+            current_block: BlockId::ENTRY,
+            registers: Vec::new(),
+            locals: Vec::new(),
+            env: None,
+            static_types: Vec::new(),
+            consts: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn lower(&mut self, program: HirProgram, sink: &mut DiagnosticSink) -> Result<MirModule, Diagnostic> {
+        self.lower_program(program)
+            .map_err(|error| sink.fail(Diagnostic::error(error).with_code(codes::LOWERING)))
+    }
+
+    fn lower_program(&mut self, program: HirProgram) -> Result<MirModule, String> {
+        self.static_types = program.statics.iter().map(|item| item.ty.clone()).collect();
+        self.consts = program.consts.into_iter().map(|item| item.value).collect();
+        let mut functions = Vec::new();
+        for function in program.functions {
+            functions.push(self.lower_function(function)?);
         }
+        let statics = program
+            .statics
+            .into_iter()
+            .map(|item| MirStatic {
+                name: item.name,
+                ty: item.ty,
+                value: item.value,
+            })
+            .collect();
+        Ok(MirModule {
+            functions,
+            statics,
+            entry: program.entry,
+        })
     }
 
-    pub fn lower(&mut self, expr: HirExpr) -> Result<Vec<MirBasicBlock>, String> {
-        let result = self.lower_expr(expr)?;
-        let terminator = MirTerminator::Return(result);
-        let block = MirBasicBlock {
-            instructions: self.instructions.drain(..).collect(),
-            terminator,
+    fn lower_function(&mut self, function: HirFunction) -> Result<MirFunction, String> {
+        self.blocks.clear();
+        self.registers.clear();
+        self.locals.clear();
+        self.env = None;
+
+        let param_types: Vec<Type> = function.locals[..function.params].iter().map(|local| local.ty.clone()).collect();
+        if let Some(slots) = function.env.clone() {
+            let closure_type = Type::Function {
+                params: param_types.clone(),
+                return_type: Box::new(function.return_type.clone()),
+            };
+            let env = self.new_register();
+            self.registers[register_index(&env)].ty = closure_type;
+            self.env = Some((env, slots));
+        }
+
+        let entry = self.new_block();
+        self.switch_to(entry);
+        let mut param_registers = Vec::new();
+        for param in &function.locals[..function.params] {
+            let register = self.new_register_index();
+            self.registers[register].ty = param.ty.clone();
+            param_registers.push(register);
+        }
+        for (index, local) in function.locals.iter().enumerate() {
+            let register = match param_registers.get(index) {
+                // A boxed parameter is moved into a cell on entry.
+                Some(&param) if local.boxed => {
+                    let cell = self.new_register();
+                    self.emit(MirInstruction {
+                        kind: MirInstructionKind::MakeCell(MirOperand::Register(param)),
+                        dest: cell.clone(),
+                        ty: Type::Reference(Box::new(local.ty.clone())),
+                    });
+                    register_index(&cell)
+                }
+                Some(&param) => param,
+                None => {
+                    let register = self.new_register_index();
+                    self.registers[register].ty = match local.boxed {
+                        true => Type::Reference(Box::new(local.ty.clone())),
+                        false => local.ty.clone(),
+                    };
+                    register
+                }
+            };
+            if let Some(&param) = param_registers.get(index) {
+                self.registers[param].name = Some(local.name.clone());
+            }
+            self.registers[register].name = Some(local.name.clone());
+            self.locals.push(LocalSlot {
+                register,
+                ty: local.ty.clone(),
+                boxed: local.boxed,
+            });
+        }
+
+        self.lower_body(function.body)?;
+        if !self.is_terminated() {
+            self.terminate(MirTerminator::Return(MirOperand::Literal(HirLiteral::Null)));
+        }
+
+        let blocks = self
+            .blocks
+            .drain(..)
+            .enumerate()
+            .map(|(id, block)| {
+                let terminator = block
+                    .terminator
+                    .ok_or_else(|| format!("Basic block {} has no terminator", id))?;
+                Ok(MirBasicBlock {
+                    instructions: block.instructions,
+                    terminator,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let mut body = MirBody {
+            locals: std::mem::take(&mut self.registers),
+            args: param_registers.len() + self.env.is_some() as usize,
+            blocks,
         };
-        Ok(vec![block])
+        into_ssa(&mut body);
+        Ok(MirFunction {
+            name: function.name,
+            return_type: function.return_type,
+            is_closure: self.env.is_some(),
+            body,
+            attributes: function.attributes,
+        })
+    }
+
+    fn lower_body(&mut self, body: Vec<HirStmt>) -> Result<(), String> {
+        for statement in body {
+            self.reopen();
+            self.lower_stmt(statement)?;
+        }
+        Ok(())
+    }
+
+    /// Continues in a new block if the current one already ends in a jump.
+    /// Anything after a jump is unreachable but still needs a block.
+    fn reopen(&mut self) {
+        if self.is_terminated() {
+            let next = self.new_block();
+            self.switch_to(next);
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.blocks[self.current_block.index()].terminator.is_some()
+    }
+
+    /// Ends the current block with a jump to `target` unless it already
+    /// ends in a jump of its own.
+    fn goto(&mut self, target: BlockId) {
+        if !self.is_terminated() {
+            self.terminate(MirTerminator::Goto(target));
+        }
+    }
+
+    fn lower_stmt(&mut self, statement: HirStmt) -> Result<(), String> {
+        match statement.kind {
+            HirStmtKind::Let { local, value } => {
+                if let Some(value) = value {
+                    let value = self.lower_expr(value)?;
+                    self.write_local(local, value, true);
+                } else if self.locals[local].boxed {
+                    self.write_local(local, MirOperand::Literal(HirLiteral::Null), true);
+                }
+            }
+            HirStmtKind::Expr(expr) => {
+                self.lower_expr(expr)?;
+            }
+            HirStmtKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.lower_expr(value)?,
+                    None => MirOperand::Literal(HirLiteral::Null),
+                };
+                self.terminate(MirTerminator::Return(value));
+            }
+            HirStmtKind::Loop { body, continuing } => {
+                let body_block = self.new_block();
+                // Without a continuing part, `continue` starts the next pass directly.
+                let continue_block = if continuing.is_empty() { body_block } else { self.new_block() };
+                let exit_block = self.new_block();
+                self.terminate(MirTerminator::Goto(body_block));
+
+                self.switch_to(body_block);
+                self.loops.push((continue_block, exit_block));
+                let result = self.lower_body(body);
+                self.loops.pop();
+                result?;
+                self.goto(continue_block);
+                if !continuing.is_empty() {
+                    self.switch_to(continue_block);
+                    self.lower_body(continuing)?;
+                    self.goto(body_block);
+                }
+                self.switch_to(exit_block);
+            }
+            HirStmtKind::Break | HirStmtKind::Continue => {
+                let &(continue_block, exit_block) = self
+                    .loops
+                    .last()
+                    .ok_or_else(|| "'break' or 'continue' outside of a loop".to_string())?;
+                let target = match statement.kind {
+                    HirStmtKind::Break => exit_block,
+                    _ => continue_block,
+                };
+                self.terminate(MirTerminator::Goto(target));
+            }
+        }
+        Ok(())
+    }
+
+    fn read_place(&mut self, place: &HirPlace, ty: Type) -> MirOperand {
+        let dest = self.new_register();
+        let kind = match place {
+            HirPlace::Local(local) => {
+                let slot = &self.locals[*local];
+                let register = MirOperand::Register(slot.register);
+                if slot.boxed {
+                    MirInstructionKind::LoadCell(register)
+                } else {
+                    MirInstructionKind::Load(register)
+                }
+            }
+            HirPlace::Captured(slot) => match self.capture_mode(*slot) {
+                CaptureMode::ByValue => MirInstructionKind::LoadCapture(self.env_operand(), *slot),
+                CaptureMode::ByReference => MirInstructionKind::LoadCell(self.cell_of(place)),
+            },
+            HirPlace::Static(id) => MirInstructionKind::LoadStatic(*id),
+        };
+        self.emit(MirInstruction {
+            kind,
+            dest: dest.clone(),
+            ty,
+        });
+        dest
+    }
+
+    fn write_place(&mut self, place: &HirPlace, value: MirOperand) {
+        match place {
+            HirPlace::Local(local) => self.write_local(*local, value, false),
+            HirPlace::Captured(_) => {
+                let cell = self.cell_of(place);
+                self.store_cell(cell, value);
+            }
+            HirPlace::Static(id) => {
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::StoreStatic(*id, value),
+                    dest,
+                    ty: Type::Void,
+                });
+            }
+        }
+    }
+
+    fn write_local(&mut self, local: usize, value: MirOperand, initialize: bool) {
+        let slot = &self.locals[local];
+        let register = MirOperand::Register(slot.register);
+        let ty = slot.ty.clone();
+        if !slot.boxed {
+            self.emit(MirInstruction {
+                kind: MirInstructionKind::Load(value),
+                dest: register,
+                ty,
+            });
+        } else if initialize {
+            self.emit(MirInstruction {
+                kind: MirInstructionKind::MakeCell(value),
+                dest: register,
+                ty: Type::Reference(Box::new(ty)),
+            });
+        } else {
+            self.store_cell(register, value);
+        }
+    }
+
+    fn store_cell(&mut self, cell: MirOperand, value: MirOperand) {
+        let dest = self.new_register();
+        self.emit(MirInstruction {
+            kind: MirInstructionKind::StoreCell(cell, value),
+            dest,
+            ty: Type::Void,
+        });
+    }
+
+    /// The heap cell behind a variable that is shared by reference.
+    fn cell_of(&mut self, place: &HirPlace) -> MirOperand {
+        match place {
+            HirPlace::Local(local) => MirOperand::Register(self.locals[*local].register),
+            HirPlace::Captured(slot) => {
+                let ty = self.env_slots()[*slot].ty.clone();
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::LoadCapture(self.env_operand(), *slot),
+                    dest: dest.clone(),
+                    ty: Type::Reference(Box::new(ty)),
+                });
+                dest
+            }
+            HirPlace::Static(_) => unreachable!("statics are never shared through cells"),
+        }
+    }
+
+    fn capture_mode(&self, slot: usize) -> CaptureMode {
+        self.env_slots()[slot].mode
+    }
+
+    fn env_slots(&self) -> &[HirEnvSlot] {
+        self.env.as_ref().map(|(_, slots)| slots.as_slice()).unwrap_or(&[])
+    }
+
+    fn env_operand(&self) -> MirOperand {
+        self.env
+            .as_ref()
+            .map(|(operand, _)| operand.clone())
+            .expect("captured variables only occur in closures")
     }
 
     fn lower_expr(&mut self, expr: HirExpr) -> Result<MirOperand, String> {
         match expr {
-            HirExpr::Binary { op, left, right, expr_type } => {
+            HirExpr::Binary { op, left, right, expr_type, .. } => {
+                let operand_type = left.ty().clone();
                 let left = self.lower_expr(*left)?;
                 let right = self.lower_expr(*right)?;
                 let op = match op {
@@ -34,6 +379,7 @@ impl MirLowerer {
                     HirBinaryOp::Subtract => MirBinaryOp::Subtract,
                     HirBinaryOp::Multiply => MirBinaryOp::Multiply,
                     HirBinaryOp::Divide => MirBinaryOp::Divide,
+                    HirBinaryOp::Remainder => MirBinaryOp::Remainder,
                     HirBinaryOp::Equal => MirBinaryOp::Equal,
                     HirBinaryOp::NotEqual => MirBinaryOp::NotEqual,
                     HirBinaryOp::GreaterThan => MirBinaryOp::GreaterThan,
@@ -41,35 +387,313 @@ impl MirLowerer {
                     HirBinaryOp::GreaterThanOrEqual => MirBinaryOp::GreaterThanOrEqual,
                     HirBinaryOp::LessThanOrEqual => MirBinaryOp::LessThanOrEqual,
                 };
+                let ty = match op {
+                    MirBinaryOp::Add
+                    | MirBinaryOp::Subtract
+                    | MirBinaryOp::Multiply
+                    | MirBinaryOp::Divide
+                    | MirBinaryOp::Remainder => expr_type,
+                    _ => operand_type,
+                };
                 let dest = self.new_register();
-                self.instructions.push(MirInstruction {
+                self.emit(MirInstruction {
                     kind: MirInstructionKind::BinaryOp(op, left, right),
                     dest: dest.clone(),
-                    ty: expr_type,
+                    ty,
                 });
                 Ok(dest)
             }
             HirExpr::Literal { value, .. } => Ok(MirOperand::Literal(value)),
-            HirExpr::Unary { op, expr, expr_type } => {
+            HirExpr::Const { id, expr_type, .. } => {
+                let value = self.consts[id].clone();
+                Ok(self.constant(&value, &expr_type))
+            }
+            HirExpr::Variable { place, expr_type, .. } => Ok(self.read_place(&place, expr_type)),
+            HirExpr::Assign { place, value, .. } => {
+                let value = self.lower_expr(*value)?;
+                self.write_place(&place, value.clone());
+                Ok(value)
+            }
+            HirExpr::Call { function, args, expr_type, .. } => {
+                let args = self.lower_args(args)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::Call(function, args),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::Cast { expr, expr_type, .. } => {
+                let source = expr.ty().clone();
+                let value = self.lower_expr(*expr)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::Cast(value, source),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::CallIndirect { callee, args, expr_type, .. } => {
+                let callee = self.lower_expr(*callee)?;
+                let args = self.lower_args(args)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::CallIndirect(callee, args),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::Closure { function, captures, expr_type, .. } => {
+                let mut values = Vec::new();
+                for capture in captures {
+                    let value = match capture.mode {
+                        CaptureMode::ByValue => {
+                            let ty = self.place_type(&capture.place);
+                            self.read_place(&capture.place, ty)
+                        }
+                        CaptureMode::ByReference => self.cell_of(&capture.place),
+                    };
+                    values.push(value);
+                }
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::MakeClosure(function, values),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::Unary { op, expr, expr_type, .. } => {
                 let expr = self.lower_expr(*expr)?;
                 let op = match op {
                     HirUnaryOp::Negate => MirUnaryOp::Negate,
                     HirUnaryOp::Not => MirUnaryOp::Not,
                 };
                 let dest = self.new_register();
-                self.instructions.push(MirInstruction {
+                self.emit(MirInstruction {
                     kind: MirInstructionKind::UnaryOp(op, expr),
                     dest: dest.clone(),
                     ty: expr_type,
                 });
                 Ok(dest)
             }
+            HirExpr::Logical { op, left, right, expr_type, .. } => {
+                // `a && b` only evaluates `b` when `a` is true, `a || b` only
+                // when `a` is false. Either way the left value is the result
+                // when the right operand is skipped.
+                let left = self.lower_expr(*left)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::Load(left.clone()),
+                    dest: dest.clone(),
+                    ty: expr_type.clone(),
+                });
+
+                let right_block = self.new_block();
+                let join_block = self.new_block();
+                let terminator = match op {
+                    HirLogicalOp::And => MirTerminator::Branch(left, right_block, join_block),
+                    HirLogicalOp::Or => MirTerminator::Branch(left, join_block, right_block),
+                };
+                self.terminate(terminator);
+
+                self.switch_to(right_block);
+                let right = self.lower_expr(*right)?;
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::Load(right),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                self.terminate(MirTerminator::Goto(join_block));
+
+                self.switch_to(join_block);
+                Ok(dest)
+            }
+            HirExpr::If { condition, then_branch, else_branch, expr_type, .. } => {
+                let condition = self.lower_expr(*condition)?;
+                let dest = (expr_type != Type::Void).then(|| self.new_register());
+                // An empty branch, such as a missing `else`, jumps straight to the join block.
+                let then_block = (!is_empty_block(&then_branch)).then(|| self.new_block());
+                let else_block = (!is_empty_block(&else_branch)).then(|| self.new_block());
+                let join_block = self.new_block();
+                self.terminate(MirTerminator::Branch(
+                    condition,
+                    then_block.unwrap_or(join_block),
+                    else_block.unwrap_or(join_block),
+                ));
+
+                for (block, branch) in [(then_block, *then_branch), (else_block, *else_branch)] {
+                    let Some(block) = block else {
+                        continue;
+                    };
+                    self.switch_to(block);
+                    let value = self.lower_expr(branch)?;
+                    if let Some(dest) = &dest {
+                        // A branch that jumped away has no value.
+                        if !self.is_terminated() {
+                            self.emit(MirInstruction {
+                                kind: MirInstructionKind::Load(value),
+                                dest: dest.clone(),
+                                ty: expr_type.clone(),
+                            });
+                        }
+                    }
+                    self.goto(join_block);
+                }
+
+                self.switch_to(join_block);
+                Ok(dest.unwrap_or(MirOperand::Literal(HirLiteral::Null)))
+            }
+            HirExpr::Block { statements, value, .. } => {
+                self.lower_body(statements)?;
+                match value {
+                    Some(value) => {
+                        self.reopen();
+                        self.lower_expr(*value)
+                    }
+                    None => Ok(MirOperand::Literal(HirLiteral::Null)),
+                }
+            }
+            HirExpr::Aggregate { elements, expr_type, .. } => {
+                let elements = self.lower_args(elements)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::MakeAggregate(elements),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::Field { object, index, expr_type, .. } => {
+                let object = self.lower_expr(*object)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::LoadField(object, index),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::Index { array, index, expr_type, .. } => {
+                let array = self.lower_expr(*array)?;
+                let index = self.lower_expr(*index)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::LoadElement(array, index),
+                    dest: dest.clone(),
+                    ty: expr_type,
+                });
+                Ok(dest)
+            }
+            HirExpr::SetField { object, index, value, .. } => {
+                let object = self.lower_expr(*object)?;
+                let value = self.lower_expr(*value)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::StoreField(object, index, value.clone()),
+                    dest,
+                    ty: Type::Void,
+                });
+                Ok(value)
+            }
+            HirExpr::SetIndex { array, index, value, .. } => {
+                let array = self.lower_expr(*array)?;
+                let index = self.lower_expr(*index)?;
+                let value = self.lower_expr(*value)?;
+                let dest = self.new_register();
+                self.emit(MirInstruction {
+                    kind: MirInstructionKind::StoreElement(array, index, value.clone()),
+                    dest,
+                    ty: Type::Void,
+                });
+                Ok(value)
+            }
+        }
+    }
+
+    /// The value of a constant. A struct or array is built anew at each use,
+    /// as a literal of it would be.
+    fn constant(&mut self, value: &ConstValue, ty: &Type) -> MirOperand {
+        let values = match value {
+            ConstValue::Scalar(literal) => return MirOperand::Literal(literal.clone()),
+            ConstValue::Aggregate(values) => values.borrow(),
+        };
+        let types = match ty.representation() {
+            Type::Struct { fields } => fields.iter().map(|(_, ty)| ty.clone()).collect(),
+            Type::Array { element, .. } => vec![(**element).clone(); values.len()],
+            other => unreachable!("an aggregate constant has type {}", other),
+        };
+        let elements = values.iter().zip(&types).map(|(value, ty)| self.constant(value, ty)).collect();
+        let dest = self.new_register();
+        self.emit(MirInstruction {
+            kind: MirInstructionKind::MakeAggregate(elements),
+            dest: dest.clone(),
+            ty: ty.clone(),
+        });
+        dest
+    }
+
+    fn lower_args(&mut self, args: Vec<HirExpr>) -> Result<Vec<MirOperand>, String> {
+        args.into_iter().map(|arg| self.lower_expr(arg)).collect()
+    }
+
+    fn place_type(&self, place: &HirPlace) -> Type {
+        match place {
+            HirPlace::Local(local) => self.locals[*local].ty.clone(),
+            HirPlace::Captured(slot) => self.env_slots()[*slot].ty.clone(),
+            HirPlace::Static(id) => self.static_types[*id].clone(),
         }
     }
 
+    fn new_register_index(&mut self) -> usize {
+        register_index(&self.new_register())
+    }
+
+    /// A new register, typed by the first instruction that writes it.
     fn new_register(&mut self) -> MirOperand {
-        let register = MirOperand::Register(self.registers);
-        self.registers += 1;
-        register
+        self.registers.push(MirLocal {
+            ty: Type::Void,
+            name: None,
+        });
+        MirOperand::Register(self.registers.len() - 1)
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(PendingBlock {
+            instructions: Vec::new(),
+            terminator: None,
+        });
+        BlockId(self.blocks.len() - 1)
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current_block = block;
+    }
+
+    fn emit(&mut self, instruction: MirInstruction) {
+        if let MirOperand::Register(dest) = instruction.dest {
+            if self.registers[dest].ty == Type::Void {
+                self.registers[dest].ty = instruction.result_type();
+            }
+        }
+        self.blocks[self.current_block.index()].instructions.push(instruction);
+    }
+
+    fn terminate(&mut self, terminator: MirTerminator) {
+        self.blocks[self.current_block.index()].terminator = Some(terminator);
+    }
+}
+
+fn is_empty_block(expr: &HirExpr) -> bool {
+    matches!(expr, HirExpr::Block { statements, value: None, .. } if statements.is_empty())
+}
+
+fn register_index(operand: &MirOperand) -> usize {
+    match operand {
+        MirOperand::Register(register) => *register,
+        MirOperand::Literal(_) => unreachable!("new_register always returns a register"),
     }
 }
@@ -1,12 +1,158 @@
+use std::fmt::{Display, Formatter};
+
 use crate::hir::hir::HirLiteral;
+use crate::semantic::attributes::Attributes;
 use crate::semantic::symbol_table::Type;
 
+#[derive(Debug, Clone)]
+pub struct MirModule {
+    pub functions: Vec<MirFunction>,
+    pub statics: Vec<MirStatic>,
+    pub entry: usize,
+}
+
+/// A global stored in linear memory, initialized before the program runs.
+#[derive(Debug, Clone)]
+pub struct MirStatic {
+    pub name: String,
+    pub ty: Type,
+    pub value: HirLiteral,
+}
+
+#[derive(Debug, Clone)]
+pub struct MirFunction {
+    pub name: String,
+    pub return_type: Type,
+    pub is_closure: bool,
+    pub body: MirBody,
+    pub attributes: Attributes,
+}
+
+/// Identifies a basic block by its index in `MirBody::blocks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+impl BlockId {
+    /// Where execution of a body starts.
+    pub const ENTRY: BlockId = BlockId(0);
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+/// The control-flow graph of a function. Its registers are the locals, and
+/// arguments arrive in the first `args` of them; a closure's own value,
+/// which doubles as the pointer to its environment, is passed first.
+///
+/// Bodies are in SSA form from lowering until LIR lowering: each register is
+/// written by one instruction, or is an argument, and values that depend on
+/// the path taken meet in phis. The entry block has no predecessors.
+#[derive(Debug, Clone)]
+pub struct MirBody {
+    pub locals: Vec<MirLocal>,
+    pub args: usize,
+    pub blocks: Vec<MirBasicBlock>,
+}
+
+impl MirBody {
+    /// A body with no locals and no blocks.
+    pub fn empty() -> Self {
+        Self {
+            locals: Vec::new(),
+            args: 0,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn block(&self, id: BlockId) -> &MirBasicBlock {
+        &self.blocks[id.index()]
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len()).map(BlockId)
+    }
+
+    /// The types of the arguments, in order.
+    pub fn arg_types(&self) -> impl Iterator<Item = &Type> {
+        self.locals[..self.args].iter().map(|local| &local.ty)
+    }
+
+    pub fn successors(&self, id: BlockId) -> Vec<BlockId> {
+        self.block(id).terminator.successors()
+    }
+
+    /// Makes every instruction and terminator that reads `register` read
+    /// `value` instead. Returns whether there were any.
+    pub fn replace_uses(&mut self, register: usize, value: &MirOperand) -> bool {
+        let mut replaced = false;
+        for block in &mut self.blocks {
+            let reads = block.instructions.iter_mut().flat_map(|instruction| instruction.kind.operands_mut());
+            for operand in reads.chain(block.terminator.operand_mut()) {
+                if operand.register() == Some(register) {
+                    *operand = value.clone();
+                    replaced = true;
+                }
+            }
+        }
+        replaced
+    }
+
+    /// The blocks that jump to each block, indexed by block. A block that
+    /// branches to the same target twice is listed once.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for id in self.block_ids() {
+            for successor in self.successors(id) {
+                if !predecessors[successor.index()].contains(&id) {
+                    predecessors[successor.index()].push(id);
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+/// A register of a function body. `name` is that of the source variable
+/// it holds, if any.
+#[derive(Debug, Clone)]
+pub struct MirLocal {
+    pub ty: Type,
+    pub name: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct MirBasicBlock {
     pub instructions: Vec<MirInstruction>,
     pub terminator: MirTerminator,
 }
 
+impl MirBasicBlock {
+    /// The phis at the start of the block.
+    pub fn phis(&self) -> &[MirInstruction] {
+        let count = self.instructions.iter().take_while(|instruction| instruction.is_phi()).count();
+        &self.instructions[..count]
+    }
+
+    /// Drops the inputs the block's phis have for `pred`, once control can
+    /// no longer come from there.
+    pub fn remove_phi_inputs(&mut self, pred: BlockId) {
+        for phi in self.instructions.iter_mut() {
+            if let MirInstructionKind::Phi(inputs) = &mut phi.kind {
+                inputs.retain(|(input, _)| *input != pred);
+            }
+        }
+    }
+}
+
+/// `ty` is the type of the value written to `dest`, except for comparisons,
+/// where it is the type of the operands compared.
 #[derive(Debug, Clone)]
 pub struct MirInstruction {
     pub kind: MirInstructionKind,
@@ -14,18 +160,169 @@ pub struct MirInstruction {
     pub ty: Type,
 }
 
+impl MirInstruction {
+    pub fn is_phi(&self) -> bool {
+        matches!(self.kind, MirInstructionKind::Phi(_))
+    }
+
+    /// The type of the value written to `dest`: `bool` for comparisons and
+    /// `void` for instructions that write nothing.
+    pub fn result_type(&self) -> Type {
+        match &self.kind {
+            MirInstructionKind::BinaryOp(op, _, _) if op.is_comparison() => Type::Boolean,
+            MirInstructionKind::StoreCell(..)
+            | MirInstructionKind::StoreStatic(..)
+            | MirInstructionKind::StoreField(..)
+            | MirInstructionKind::StoreElement(..) => Type::Void,
+            _ => self.ty.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MirInstructionKind {
     BinaryOp(MirBinaryOp, MirOperand, MirOperand),
     UnaryOp(MirUnaryOp, MirOperand),
+    /// Copies the operand into `dest`.
     Load(MirOperand),
+    Call(usize, Vec<MirOperand>),
+    /// Converts the operand, a value of the given type, to `ty`.
+    Cast(MirOperand, Type),
+    /// Calls a closure value, which is passed to the callee ahead of the arguments.
+    CallIndirect(MirOperand, Vec<MirOperand>),
+    /// Allocates a closure of the given function with the operands as its environment.
+    MakeClosure(usize, Vec<MirOperand>),
+    /// Reads an environment slot of a closure.
+    LoadCapture(MirOperand, usize),
+    /// Allocates a heap cell holding the operand; `ty` is the `Reference` type.
+    MakeCell(MirOperand),
+    LoadCell(MirOperand),
+    /// Writes the second operand through the cell in the first; `dest` is unused.
+    StoreCell(MirOperand, MirOperand),
+    /// Reads the `static` with the given index.
+    LoadStatic(usize),
+    /// Writes the operand to the `static` with the given index; `dest` is unused.
+    StoreStatic(usize, MirOperand),
+    /// Allocates a struct or array of type `ty` holding the operands, the
+    /// fields of a struct in declaration order.
+    MakeAggregate(Vec<MirOperand>),
+    /// Reads the field with the given index of a struct.
+    LoadField(MirOperand, usize),
+    /// Writes the last operand to the field with the given index of the
+    /// struct in the first; `dest` is unused.
+    StoreField(MirOperand, usize, MirOperand),
+    /// Reads the element of an array at the index in the second operand, an
+    /// `i64`, trapping when it is out of bounds.
+    LoadElement(MirOperand, MirOperand),
+    /// Writes the third operand to an element of an array as `LoadElement`
+    /// reads one; `dest` is unused.
+    StoreElement(MirOperand, MirOperand, MirOperand),
+    /// Takes the operand paired with the block control came from. Phis come
+    /// first in their block and have one input for each predecessor.
+    Phi(Vec<(BlockId, MirOperand)>),
+}
+
+impl MirInstructionKind {
+    /// The operands the instruction reads.
+    pub fn operands(&self) -> Vec<&MirOperand> {
+        match self {
+            MirInstructionKind::BinaryOp(_, left, right)
+            | MirInstructionKind::StoreCell(left, right)
+            | MirInstructionKind::StoreField(left, _, right)
+            | MirInstructionKind::LoadElement(left, right) => vec![left, right],
+            MirInstructionKind::UnaryOp(_, operand)
+            | MirInstructionKind::Load(operand)
+            | MirInstructionKind::Cast(operand, _)
+            | MirInstructionKind::LoadCapture(operand, _)
+            | MirInstructionKind::MakeCell(operand)
+            | MirInstructionKind::LoadCell(operand)
+            | MirInstructionKind::StoreStatic(_, operand)
+            | MirInstructionKind::LoadField(operand, _) => vec![operand],
+            MirInstructionKind::StoreElement(array, index, value) => vec![array, index, value],
+            MirInstructionKind::Call(_, args)
+            | MirInstructionKind::MakeClosure(_, args)
+            | MirInstructionKind::MakeAggregate(args) => args.iter().collect(),
+            MirInstructionKind::CallIndirect(callee, args) => std::iter::once(callee).chain(args).collect(),
+            MirInstructionKind::LoadStatic(_) => Vec::new(),
+            MirInstructionKind::Phi(inputs) => inputs.iter().map(|(_, operand)| operand).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut MirOperand> {
+        match self {
+            MirInstructionKind::BinaryOp(_, left, right)
+            | MirInstructionKind::StoreCell(left, right)
+            | MirInstructionKind::StoreField(left, _, right)
+            | MirInstructionKind::LoadElement(left, right) => vec![left, right],
+            MirInstructionKind::UnaryOp(_, operand)
+            | MirInstructionKind::Load(operand)
+            | MirInstructionKind::Cast(operand, _)
+            | MirInstructionKind::LoadCapture(operand, _)
+            | MirInstructionKind::MakeCell(operand)
+            | MirInstructionKind::LoadCell(operand)
+            | MirInstructionKind::StoreStatic(_, operand)
+            | MirInstructionKind::LoadField(operand, _) => vec![operand],
+            MirInstructionKind::StoreElement(array, index, value) => vec![array, index, value],
+            MirInstructionKind::Call(_, args)
+            | MirInstructionKind::MakeClosure(_, args)
+            | MirInstructionKind::MakeAggregate(args) => args.iter_mut().collect(),
+            MirInstructionKind::CallIndirect(callee, args) => std::iter::once(callee).chain(args).collect(),
+            MirInstructionKind::LoadStatic(_) => Vec::new(),
+            MirInstructionKind::Phi(inputs) => inputs.iter_mut().map(|(_, operand)| operand).collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum MirTerminator {
     Return(MirOperand),
-    Goto(usize),
-    Branch(MirOperand, usize, usize),
+    Goto(BlockId),
+    /// Goes to the first block if the condition is true and to the second otherwise.
+    Branch(MirOperand, BlockId, BlockId),
+}
+
+impl MirTerminator {
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            MirTerminator::Return(_) => Vec::new(),
+            MirTerminator::Goto(target) => vec![*target],
+            MirTerminator::Branch(_, then_block, else_block) if then_block == else_block => vec![*then_block],
+            MirTerminator::Branch(_, then_block, else_block) => vec![*then_block, *else_block],
+        }
+    }
+
+    /// The operand the terminator reads, if any.
+    pub fn operand(&self) -> Option<&MirOperand> {
+        match self {
+            MirTerminator::Return(operand) | MirTerminator::Branch(operand, _, _) => Some(operand),
+            MirTerminator::Goto(_) => None,
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> Option<&mut MirOperand> {
+        match self {
+            MirTerminator::Return(operand) | MirTerminator::Branch(operand, _, _) => Some(operand),
+            MirTerminator::Goto(_) => None,
+        }
+    }
+
+    /// The blocks the terminator may jump to, one for each place it names one.
+    pub fn targets_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            MirTerminator::Return(_) => Vec::new(),
+            MirTerminator::Goto(target) => vec![target],
+            MirTerminator::Branch(_, then_block, else_block) => vec![then_block, else_block],
+        }
+    }
+
+    /// Sends every jump to `from` to `to` instead.
+    pub fn retarget(&mut self, from: BlockId, to: BlockId) {
+        for target in self.targets_mut() {
+            if *target == from {
+                *target = to;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,12 +331,22 @@ pub enum MirOperand {
     Register(usize),
 }
 
+impl MirOperand {
+    pub fn register(&self) -> Option<usize> {
+        match self {
+            MirOperand::Register(register) => Some(*register),
+            MirOperand::Literal(_) => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MirBinaryOp {
     Add,
     Subtract,
     Multiply,
     Divide,
+    Remainder,
     Equal,
     NotEqual,
     GreaterThan,
@@ -48,8 +355,66 @@ pub enum MirBinaryOp {
     LessThanOrEqual,
 }
 
+impl MirBinaryOp {
+    pub const ALL: [MirBinaryOp; 11] = [
+        MirBinaryOp::Add,
+        MirBinaryOp::Subtract,
+        MirBinaryOp::Multiply,
+        MirBinaryOp::Divide,
+        MirBinaryOp::Remainder,
+        MirBinaryOp::Equal,
+        MirBinaryOp::NotEqual,
+        MirBinaryOp::GreaterThan,
+        MirBinaryOp::LessThan,
+        MirBinaryOp::GreaterThanOrEqual,
+        MirBinaryOp::LessThanOrEqual,
+    ];
+
+    /// The mnemonic of the operation in the MIR text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MirBinaryOp::Add => "add",
+            MirBinaryOp::Subtract => "sub",
+            MirBinaryOp::Multiply => "mul",
+            MirBinaryOp::Divide => "div",
+            MirBinaryOp::Remainder => "rem",
+            MirBinaryOp::Equal => "eq",
+            MirBinaryOp::NotEqual => "ne",
+            MirBinaryOp::GreaterThan => "gt",
+            MirBinaryOp::LessThan => "lt",
+            MirBinaryOp::GreaterThanOrEqual => "ge",
+            MirBinaryOp::LessThanOrEqual => "le",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.name() == name)
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        !matches!(
+            self,
+            MirBinaryOp::Add | MirBinaryOp::Subtract | MirBinaryOp::Multiply | MirBinaryOp::Divide | MirBinaryOp::Remainder
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum MirUnaryOp {
     Negate,
     Not,
 }
+
+impl MirUnaryOp {
+    /// The mnemonic of the operation in the MIR text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MirUnaryOp::Negate => "neg",
+            MirUnaryOp::Not => "not",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [MirUnaryOp::Negate, MirUnaryOp::Not].into_iter().find(|op| op.name() == name)
+    }
+}
@@ -1,2 +1,8 @@
 pub mod mir;
 pub mod lowerer;
+pub mod dominators;
+pub mod ssa;
+pub mod passes;
+pub mod verify;
+pub mod printer;
+pub mod parser;
@@ -0,0 +1,610 @@
+use std::collections::HashMap;
+
+use crate::hir::const_eval::{round, wrap};
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBinaryOp, MirBody, MirFunction, MirInstruction, MirInstructionKind, MirLocal, MirModule,
+    MirOperand, MirStatic, MirTerminator, MirUnaryOp,
+};
+use crate::semantic::attributes::{Attributes, InlineHint};
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+
+/// Reads a module in the text format `MirModule` is displayed in; see the
+/// printer for the format. `//` starts a comment that runs to the end of
+/// the line. The module is only checked for being well formed as text:
+/// whether it is valid MIR is up to `verify`.
+pub fn parse_module(text: &str) -> Result<MirModule, String> {
+    MirParser::new(text).module().map_err(|error| error.to_string())
+}
+
+/// A parse error and the line it was found on.
+#[derive(Debug)]
+struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+type Parse<T> = Result<T, ParseError>;
+
+/// The suffixes of typed literals, longest first so that `isize` is not
+/// taken for `size` with an `i` in front.
+const SUFFIXES: [&str; 12] = ["isize", "usize", "i16", "i32", "i64", "u16", "u32", "u64", "f32", "f64", "i8", "u8"];
+
+struct MirParser {
+    chars: Vec<char>,
+    position: usize,
+    /// The index of each function and static, by name, found before
+    /// parsing so that bodies can refer to items defined after them.
+    functions: HashMap<String, usize>,
+    statics: HashMap<String, usize>,
+}
+
+impl MirParser {
+    fn new(text: &str) -> Self {
+        let mut functions = HashMap::new();
+        let mut statics = HashMap::new();
+        for line in text.lines() {
+            let line = line.trim_start();
+            if let Some(rest) = line.strip_prefix("fn @") {
+                functions.insert(item_name(rest), functions.len());
+            } else if let Some(rest) = line.strip_prefix("static @") {
+                statics.insert(item_name(rest), statics.len());
+            }
+        }
+        Self {
+            chars: text.chars().collect(),
+            position: 0,
+            functions,
+            statics,
+        }
+    }
+
+    fn module(&mut self) -> Parse<MirModule> {
+        let mut module = MirModule {
+            functions: Vec::new(),
+            statics: Vec::new(),
+            entry: usize::MAX,
+        };
+        loop {
+            self.skip_space();
+            if self.position == self.chars.len() {
+                break;
+            }
+            if self.eat("static") {
+                module.statics.push(self.static_item()?);
+            } else if self.eat("fn") {
+                let (function, entry) = self.function()?;
+                if entry {
+                    if module.entry != usize::MAX {
+                        return Err(self.error("a second function is marked 'entry'"));
+                    }
+                    module.entry = module.functions.len();
+                }
+                module.functions.push(function);
+            } else {
+                return Err(self.error("expected 'fn' or 'static'"));
+            }
+        }
+        if module.entry == usize::MAX {
+            return Err(self.error("no function is marked 'entry'"));
+        }
+        Ok(module)
+    }
+
+    fn static_item(&mut self) -> Parse<MirStatic> {
+        let name = self.item()?;
+        self.expect(":")?;
+        let ty = self.ty()?;
+        self.expect("=")?;
+        let value = self.literal()?;
+        Ok(MirStatic { name, ty, value })
+    }
+
+    /// A function after `fn`, and whether it is marked `entry`.
+    fn function(&mut self) -> Parse<(MirFunction, bool)> {
+        let name = self.item()?;
+        let mut body = MirBody::empty();
+        self.expect("(")?;
+        if !self.eat(")") {
+            loop {
+                body.locals.push(self.local(body.locals.len())?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        body.args = body.locals.len();
+        self.expect("->")?;
+        let return_type = self.ty()?;
+
+        let (mut entry, mut is_closure) = (false, false);
+        let mut attributes = Attributes::default();
+        while !self.eat("{") {
+            match self.word()?.as_str() {
+                "entry" => entry = true,
+                "closure" => is_closure = true,
+                "inline" => attributes.inline = Some(InlineHint::Always),
+                "noinline" => attributes.inline = Some(InlineHint::Never),
+                "export" => attributes.export = Some(self.string()?),
+                flag => return Err(self.error(format!("unknown flag '{}'", flag))),
+            }
+        }
+        while self.eat("let") {
+            body.locals.push(self.local(body.locals.len())?);
+        }
+        while !self.eat("}") {
+            body.blocks.push(self.block(body.blocks.len())?);
+        }
+        let function = MirFunction {
+            name,
+            return_type,
+            is_closure,
+            body,
+            attributes,
+        };
+        Ok((function, entry))
+    }
+
+    /// `%n: type`, optionally followed by the name of the variable, where
+    /// `n` must be `register`.
+    fn local(&mut self, register: usize) -> Parse<MirLocal> {
+        let found = self.register()?;
+        if found != register {
+            return Err(self.error(format!("expected %{} to be declared next, found %{}", register, found)));
+        }
+        self.expect(":")?;
+        let ty = self.ty()?;
+        self.skip_space();
+        let name = if self.peek() == Some('"') { Some(self.string()?) } else { None };
+        Ok(MirLocal { ty, name })
+    }
+
+    fn block(&mut self, index: usize) -> Parse<MirBasicBlock> {
+        let id = self.block_id()?;
+        if id.index() != index {
+            return Err(self.error(format!("expected {} to come next, found {}", BlockId(index), id)));
+        }
+        self.expect(":")?;
+        let mut instructions = Vec::new();
+        loop {
+            self.skip_space();
+            if self.peek() == Some('%') {
+                instructions.push(self.instruction()?);
+                continue;
+            }
+            let terminator = self.terminator()?;
+            return Ok(MirBasicBlock {
+                instructions,
+                terminator,
+            });
+        }
+    }
+
+    fn instruction(&mut self) -> Parse<MirInstruction> {
+        let dest = MirOperand::Register(self.register()?);
+        self.expect("=")?;
+        let operation = self.word()?;
+        let ty = self.ty()?;
+        let kind = match operation.as_str() {
+            "copy" => MirInstructionKind::Load(self.operand()?),
+            "cast" => {
+                // `cast from %n to ty`: the type read first is the operand's.
+                let operand = self.operand()?;
+                self.expect_word("to")?;
+                let to = self.ty()?;
+                let instruction = MirInstruction {
+                    kind: MirInstructionKind::Cast(operand, ty),
+                    dest,
+                    ty: to,
+                };
+                return Ok(instruction);
+            }
+            "call" => {
+                let callee = self.function_ref()?;
+                MirInstructionKind::Call(callee, self.operand_list()?)
+            }
+            "call_indirect" => {
+                let callee = self.operand()?;
+                MirInstructionKind::CallIndirect(callee, self.operand_list()?)
+            }
+            "closure" => {
+                let callee = self.function_ref()?;
+                MirInstructionKind::MakeClosure(callee, self.operand_list()?)
+            }
+            "capture" => {
+                let closure = self.operand()?;
+                self.expect(",")?;
+                MirInstructionKind::LoadCapture(closure, self.number()?)
+            }
+            "make_cell" => MirInstructionKind::MakeCell(self.operand()?),
+            "load_cell" => MirInstructionKind::LoadCell(self.operand()?),
+            "store_cell" => {
+                let cell = self.operand()?;
+                self.expect(",")?;
+                MirInstructionKind::StoreCell(cell, self.operand()?)
+            }
+            "load_static" => MirInstructionKind::LoadStatic(self.static_ref()?),
+            "store_static" => {
+                let index = self.static_ref()?;
+                self.expect(",")?;
+                MirInstructionKind::StoreStatic(index, self.operand()?)
+            }
+            "aggregate" => MirInstructionKind::MakeAggregate(self.operand_list()?),
+            "field" => {
+                let object = self.operand()?;
+                self.expect(",")?;
+                MirInstructionKind::LoadField(object, self.number()?)
+            }
+            "set_field" => {
+                let object = self.operand()?;
+                self.expect(",")?;
+                let index = self.number()?;
+                self.expect(",")?;
+                MirInstructionKind::StoreField(object, index, self.operand()?)
+            }
+            "element" => {
+                let array = self.operand()?;
+                self.expect(",")?;
+                MirInstructionKind::LoadElement(array, self.operand()?)
+            }
+            "set_element" => {
+                let array = self.operand()?;
+                self.expect(",")?;
+                let index = self.operand()?;
+                self.expect(",")?;
+                MirInstructionKind::StoreElement(array, index, self.operand()?)
+            }
+            "phi" => {
+                let mut inputs = Vec::new();
+                self.expect("[")?;
+                if !self.eat("]") {
+                    loop {
+                        let pred = self.block_id()?;
+                        self.expect(":")?;
+                        inputs.push((pred, self.operand()?));
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                MirInstructionKind::Phi(inputs)
+            }
+            name => {
+                if let Some(op) = MirBinaryOp::from_name(name) {
+                    let left = self.operand()?;
+                    self.expect(",")?;
+                    MirInstructionKind::BinaryOp(op, left, self.operand()?)
+                } else if let Some(op) = MirUnaryOp::from_name(name) {
+                    MirInstructionKind::UnaryOp(op, self.operand()?)
+                } else {
+                    return Err(self.error(format!("unknown instruction '{}'", name)));
+                }
+            }
+        };
+        Ok(MirInstruction { kind, dest, ty })
+    }
+
+    fn terminator(&mut self) -> Parse<MirTerminator> {
+        match self.word()?.as_str() {
+            "return" => Ok(MirTerminator::Return(self.operand()?)),
+            "goto" => Ok(MirTerminator::Goto(self.block_id()?)),
+            "br" => {
+                let condition = self.operand()?;
+                self.expect(",")?;
+                let then_block = self.block_id()?;
+                self.expect(",")?;
+                Ok(MirTerminator::Branch(condition, then_block, self.block_id()?))
+            }
+            word => Err(self.error(format!("expected an instruction or a terminator, found '{}'", word))),
+        }
+    }
+
+    fn ty(&mut self) -> Parse<Type> {
+        // Struct fields are not named in the text, so they are named by
+        // their position.
+        if self.eat("{") {
+            let mut fields = Vec::new();
+            if !self.eat("}") {
+                loop {
+                    fields.push((fields.len().to_string(), self.ty()?));
+                    if self.eat("}") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            return Ok(Type::Struct { fields });
+        }
+        if self.eat("[") {
+            let element = Box::new(self.ty()?);
+            self.expect(";")?;
+            let length = self.number()? as u64;
+            self.expect("]")?;
+            return Ok(Type::Array { element, length });
+        }
+        let word = self.word()?;
+        match word.as_str() {
+            "bool" => Ok(Type::Boolean),
+            "string" => Ok(Type::String),
+            "null" => Ok(Type::Null),
+            "void" => Ok(Type::Void),
+            "ref" => Ok(Type::Reference(Box::new(self.ty()?))),
+            "fn" => {
+                let mut params = Vec::new();
+                self.expect("(")?;
+                if !self.eat(")") {
+                    loop {
+                        params.push(self.ty()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                self.expect("->")?;
+                let return_type = Box::new(self.ty()?);
+                Ok(Type::Function { params, return_type })
+            }
+            name => IntType::from_name(name)
+                .map(Type::Integer)
+                .or_else(|| FloatType::from_name(name).map(Type::Float))
+                .ok_or_else(|| self.error(format!("unknown type '{}'", name))),
+        }
+    }
+
+    fn operand(&mut self) -> Parse<MirOperand> {
+        self.skip_space();
+        if self.peek() == Some('%') {
+            return Ok(MirOperand::Register(self.register()?));
+        }
+        Ok(MirOperand::Literal(self.literal()?))
+    }
+
+    fn operand_list(&mut self) -> Parse<Vec<MirOperand>> {
+        let mut operands = Vec::new();
+        self.expect("(")?;
+        if !self.eat(")") {
+            loop {
+                operands.push(self.operand()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        Ok(operands)
+    }
+
+    fn literal(&mut self) -> Parse<HirLiteral> {
+        self.skip_space();
+        if self.peek() == Some('"') {
+            return Ok(HirLiteral::String(self.string()?));
+        }
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            let exponent_sign = (c == '-' || c == '+') && matches!(self.chars.get(self.position - 1), Some('e' | 'E'));
+            if !(c.is_ascii_alphanumeric() || c == '.' || (c == '-' && self.position == start) || exponent_sign) {
+                break;
+            }
+            self.position += 1;
+        }
+        let token: String = self.chars[start..self.position].iter().collect();
+        match token.as_str() {
+            "true" => return Ok(HirLiteral::Boolean(true)),
+            "false" => return Ok(HirLiteral::Boolean(false)),
+            "null" => return Ok(HirLiteral::Null),
+            "" => return Err(self.error("expected an operand")),
+            _ => {}
+        }
+        let suffix = SUFFIXES.into_iter().find(|suffix| token.len() > suffix.len() && token.ends_with(suffix));
+        let digits = &token[..token.len() - suffix.map_or(0, str::len)];
+        let is_float = digits.contains(['.', 'e', 'E']) || digits.ends_with("inf") || digits.ends_with("NaN");
+        match suffix.and_then(FloatType::from_name).or((suffix.is_none() && is_float).then_some(FloatType::F64)) {
+            Some(float) => match digits.parse::<f64>() {
+                Ok(value) => Ok(HirLiteral::Float(round(value, float), float)),
+                Err(_) => Err(self.error(format!("invalid float literal '{}'", token))),
+            },
+            None => {
+                let int = suffix.and_then(IntType::from_name).unwrap_or(IntType::I64);
+                let value: i128 = digits.parse().map_err(|_| self.error(format!("invalid literal '{}'", token)))?;
+                let min = if int.is_signed() { -(int.min_magnitude() as i128) } else { 0 };
+                if value < min || value > int.max() as i128 {
+                    return Err(self.error(format!("{} does not fit in {}", value, int.name())));
+                }
+                Ok(wrap(value, int))
+            }
+        }
+    }
+
+    /// A double-quoted string with the escapes of Rust's debug formatting.
+    fn string(&mut self) -> Parse<String> {
+        self.expect("\"")?;
+        let mut text = String::new();
+        loop {
+            let Some(c) = self.next() else {
+                return Err(self.error("unterminated string"));
+            };
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escaped = match self.next() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"' | '\'')) => c,
+                        Some('u') => {
+                            self.expect("{")?;
+                            let start = self.position;
+                            while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                                self.position += 1;
+                            }
+                            let digits: String = self.chars[start..self.position].iter().collect();
+                            self.expect("}")?;
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error(format!("invalid escape '\\u{{{}}}'", digits)))?
+                        }
+                        other => return Err(self.error(format!("invalid escape '\\{}'", other.unwrap_or(' ')))),
+                    };
+                    text.push(escaped);
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn register(&mut self) -> Parse<usize> {
+        self.expect("%")?;
+        self.number()
+    }
+
+    fn block_id(&mut self) -> Parse<BlockId> {
+        self.skip_space();
+        if !self.rest_starts_with("bb") {
+            return Err(self.error("expected a block"));
+        }
+        self.position += 2;
+        Ok(BlockId(self.number()?))
+    }
+
+    fn number(&mut self) -> Parse<usize> {
+        self.skip_space();
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().map_err(|_| self.error("expected a number"))
+    }
+
+    /// `@name` after `fn` or `static`.
+    fn item(&mut self) -> Parse<String> {
+        self.expect("@")?;
+        let rest: String = self.chars[self.position..].iter().collect();
+        let name = item_name(&rest);
+        if name.is_empty() {
+            return Err(self.error("expected a name after '@'"));
+        }
+        self.position += name.chars().count();
+        Ok(name)
+    }
+
+    fn function_ref(&mut self) -> Parse<usize> {
+        let name = self.item()?;
+        self.functions.get(&name).copied().ok_or_else(|| self.error(format!("unknown function '@{}'", name)))
+    }
+
+    fn static_ref(&mut self) -> Parse<usize> {
+        let name = self.item()?;
+        self.statics.get(&name).copied().ok_or_else(|| self.error(format!("unknown static '@{}'", name)))
+    }
+
+    /// A run of letters, digits and underscores.
+    fn word(&mut self) -> Parse<String> {
+        self.skip_space();
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.error("expected a word"));
+        }
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn expect_word(&mut self, word: &str) -> Parse<()> {
+        let start = self.position;
+        if self.word().ok().as_deref() == Some(word) {
+            return Ok(());
+        }
+        self.position = start;
+        Err(self.error(format!("expected '{}'", word)))
+    }
+
+    /// Consumes `text` if it comes next. A word is only taken whole.
+    fn eat(&mut self, text: &str) -> bool {
+        self.skip_space();
+        if !self.rest_starts_with(text) {
+            return false;
+        }
+        let end = self.position + text.chars().count();
+        let is_word = text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if is_word && self.chars.get(end).is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            return false;
+        }
+        self.position = end;
+        true
+    }
+
+    fn expect(&mut self, text: &str) -> Parse<()> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", text)))
+        }
+    }
+
+    fn rest_starts_with(&self, text: &str) -> bool {
+        text.chars().enumerate().all(|(offset, c)| self.chars.get(self.position + offset) == Some(&c))
+    }
+
+    fn skip_space(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.position += 1;
+            }
+            if !self.rest_starts_with("//") {
+                return;
+            }
+            while self.peek().is_some_and(|c| c != '\n') {
+                self.position += 1;
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        let line = self.chars[..self.position.min(self.chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// The name at the start of `text`: letters, digits, `_` and `#`, and `::`
+/// between parts, as in `main::lambda#0`.
+fn item_name(text: &str) -> String {
+    let mut name = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '#' {
+            name.push(c);
+        } else if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            name.push_str("::");
+        } else {
+            break;
+        }
+    }
+    name
+}
@@ -0,0 +1,135 @@
+use crate::hir::const_eval::{cast, decode, remainder, round, wrap};
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{MirBinaryOp, MirBody, MirInstructionKind, MirOperand, MirUnaryOp};
+use crate::mir::passes::{Pass, PassContext};
+use crate::semantic::symbol_table::Type;
+
+/// Replaces operations on literals with a copy of their result.
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "const-fold"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        let mut changed = false;
+        for instruction in body.blocks.iter_mut().flat_map(|block| &mut block.instructions) {
+            if matches!(instruction.kind, MirInstructionKind::Load(_)) {
+                continue;
+            }
+            let operands: Option<Vec<&HirLiteral>> = instruction
+                .kind
+                .operands()
+                .into_iter()
+                .map(|operand| match operand {
+                    MirOperand::Literal(HirLiteral::Null) | MirOperand::Register(_) => None,
+                    MirOperand::Literal(literal) => Some(literal),
+                })
+                .collect();
+            if let Some(value) = operands.and_then(|operands| fold(&instruction.kind, &instruction.ty, &operands)) {
+                // A comparison's `ty` is that of its operands; the copy's is that of the result.
+                instruction.ty = instruction.result_type();
+                instruction.kind = MirInstructionKind::Load(MirOperand::Literal(value));
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// The value an instruction computes from the given values of its operands,
+/// with the semantics of the generated code: integers wrap around, floats
+/// round to their type after each operation, and `%` on floats is
+/// `a - trunc(a / b) * b`. Instructions that would trap, such as a division
+/// by zero, and those that are not pure computations are not folded.
+pub fn fold(kind: &MirInstructionKind, ty: &Type, operands: &[&HirLiteral]) -> Option<HirLiteral> {
+    match (kind, operands) {
+        (MirInstructionKind::Load(_), [value]) => Some((*value).clone()),
+        (MirInstructionKind::BinaryOp(op, _, _), [left, right]) => binary(op, left, right),
+        (MirInstructionKind::UnaryOp(op, _), [value]) => match (op, value) {
+            (MirUnaryOp::Negate, HirLiteral::Integer(bits, int)) => Some(wrap(-decode(*bits, *int), *int)),
+            (MirUnaryOp::Negate, HirLiteral::Float(value, float)) => Some(HirLiteral::Float(-value, *float)),
+            (MirUnaryOp::Not, HirLiteral::Boolean(value)) => Some(HirLiteral::Boolean(!value)),
+            _ => None,
+        },
+        (MirInstructionKind::Cast(_, _), [value]) => match (value, ty.representation()) {
+            (HirLiteral::Integer(..) | HirLiteral::Float(..), Type::Integer(_) | Type::Float(_))
+            | (HirLiteral::Boolean(_), Type::Integer(_)) => cast((*value).clone(), ty).ok(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn binary(op: &MirBinaryOp, left: &HirLiteral, right: &HirLiteral) -> Option<HirLiteral> {
+    let compare = |ordering: Option<std::cmp::Ordering>| {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        let result = match op {
+            MirBinaryOp::Equal => ordering == Some(Equal),
+            MirBinaryOp::NotEqual => ordering != Some(Equal),
+            MirBinaryOp::GreaterThan => ordering == Some(Greater),
+            MirBinaryOp::LessThan => ordering == Some(Less),
+            MirBinaryOp::GreaterThanOrEqual => matches!(ordering, Some(Greater | Equal)),
+            MirBinaryOp::LessThanOrEqual => matches!(ordering, Some(Less | Equal)),
+            _ => unreachable!("arithmetic is handled separately"),
+        };
+        Some(HirLiteral::Boolean(result))
+    };
+
+    match (left, right) {
+        (HirLiteral::Integer(left, int), HirLiteral::Integer(right, _)) => {
+            let (left, right) = (decode(*left, *int), decode(*right, *int));
+            if op.is_comparison() {
+                return compare(left.partial_cmp(&right));
+            }
+            let result = match op {
+                MirBinaryOp::Add => left + right,
+                MirBinaryOp::Subtract => left - right,
+                // The product of two 64-bit values can overflow i128, but
+                // its low bits, which are all that is kept, are right.
+                MirBinaryOp::Multiply => left.wrapping_mul(right),
+                MirBinaryOp::Divide | MirBinaryOp::Remainder if right == 0 => return None,
+                MirBinaryOp::Divide => {
+                    let quotient = left / right;
+                    // Dividing the smallest 32- or 64-bit value by -1 traps;
+                    // narrower types compute in 32 bits and wrap.
+                    if int.bits() >= 32 && quotient > int.max() as i128 {
+                        return None;
+                    }
+                    quotient
+                }
+                _ => left % right,
+            };
+            Some(wrap(result, *int))
+        }
+        (HirLiteral::Float(left, float), HirLiteral::Float(right, _)) => {
+            let (left, right, float) = (*left, *right, *float);
+            let result = match op {
+                MirBinaryOp::Add => left + right,
+                MirBinaryOp::Subtract => left - right,
+                MirBinaryOp::Multiply => left * right,
+                MirBinaryOp::Divide => left / right,
+                MirBinaryOp::Remainder => remainder(left, right, float),
+                _ => return compare(left.partial_cmp(&right)),
+            };
+            Some(HirLiteral::Float(round(result, float), float))
+        }
+        (HirLiteral::Boolean(left), HirLiteral::Boolean(right)) if op.is_comparison() => {
+            compare(left.partial_cmp(right))
+        }
+        _ => None,
+    }
+}
+
+/// Whether two literals are the same value, bit for bit.
+pub fn identical(a: &HirLiteral, b: &HirLiteral) -> bool {
+    match (a, b) {
+        (HirLiteral::Integer(a, a_type), HirLiteral::Integer(b, b_type)) => a == b && a_type == b_type,
+        (HirLiteral::Float(a, a_type), HirLiteral::Float(b, b_type)) => a.to_bits() == b.to_bits() && a_type == b_type,
+        (HirLiteral::String(a), HirLiteral::String(b)) => a == b,
+        (HirLiteral::Boolean(a), HirLiteral::Boolean(b)) => a == b,
+        (HirLiteral::Null, HirLiteral::Null) => true,
+        _ => false,
+    }
+}
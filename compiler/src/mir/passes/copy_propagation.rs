@@ -0,0 +1,68 @@
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{MirBody, MirInstructionKind, MirOperand};
+use crate::mir::passes::const_fold::identical;
+use crate::mir::passes::{Pass, PassContext};
+
+/// Makes the readers of a copy read what was copied, and does the same for
+/// phis whose inputs are all one value (or the phi itself, around a loop).
+/// The copies are left for dead code elimination.
+///
+/// `null` is not propagated: it stands for a value of whatever type the
+/// register it is copied into has, which the reader would lose.
+pub struct CopyPropagation;
+
+impl Pass for CopyPropagation {
+    fn name(&self) -> &'static str {
+        "copy-propagation"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        let mut changed = false;
+        loop {
+            let copies: Vec<(usize, MirOperand)> = body
+                .blocks
+                .iter()
+                .flat_map(|block| &block.instructions)
+                .filter_map(|instruction| {
+                    let dest = instruction.dest.register()?;
+                    Some((dest, copied(&instruction.kind, dest)?))
+                })
+                .collect();
+            // A source may itself be a copy replaced earlier in the round,
+            // which is still correct and is taken care of by the next round.
+            let mut replaced = false;
+            for (dest, source) in copies {
+                replaced |= body.replace_uses(dest, &source);
+            }
+            if !replaced {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+/// The value an instruction writing `dest` copies, if it is a copy.
+fn copied(kind: &MirInstructionKind, dest: usize) -> Option<MirOperand> {
+    let source = match kind {
+        MirInstructionKind::Load(source) => source.clone(),
+        MirInstructionKind::Phi(inputs) => {
+            let mut values = inputs.iter().map(|(_, value)| value).filter(|value| value.register() != Some(dest));
+            let first = values.next()?;
+            if !values.all(|value| same_operand(value, first)) {
+                return None;
+            }
+            first.clone()
+        }
+        _ => return None,
+    };
+    (!matches!(source, MirOperand::Literal(HirLiteral::Null))).then_some(source)
+}
+
+fn same_operand(a: &MirOperand, b: &MirOperand) -> bool {
+    match (a, b) {
+        (MirOperand::Register(a), MirOperand::Register(b)) => a == b,
+        (MirOperand::Literal(a), MirOperand::Literal(b)) => identical(a, b),
+        _ => false,
+    }
+}
@@ -0,0 +1,83 @@
+use crate::hir::const_eval::decode;
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{MirBinaryOp, MirBody, MirInstruction, MirInstructionKind, MirOperand};
+use crate::mir::passes::{Pass, PassContext};
+use crate::semantic::symbol_table::Type;
+
+/// Removes instructions whose results are never used and that do nothing
+/// else. Starting from what must run, the terminators and instructions with
+/// effects, it marks the instructions producing their operands, and keeps
+/// only what it marked; unused phis that feed each other around a loop go
+/// too.
+pub struct DeadCodeElimination;
+
+impl Pass for DeadCodeElimination {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        let mut definitions = vec![None; body.locals.len()];
+        for (block_index, block) in body.blocks.iter().enumerate() {
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                if let Some(dest) = instruction.dest.register() {
+                    definitions[dest] = Some((block_index, index));
+                }
+            }
+        }
+
+        let mut live: Vec<Vec<bool>> = body.blocks.iter().map(|block| vec![false; block.instructions.len()]).collect();
+        let mut worklist: Vec<&MirOperand> = Vec::new();
+        for (block_index, block) in body.blocks.iter().enumerate() {
+            worklist.extend(block.terminator.operand());
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                if has_effects(instruction) {
+                    live[block_index][index] = true;
+                    worklist.extend(instruction.kind.operands());
+                }
+            }
+        }
+        while let Some(operand) = worklist.pop() {
+            let Some((block_index, index)) = operand.register().and_then(|register| definitions[register]) else {
+                continue;
+            };
+            if !live[block_index][index] {
+                live[block_index][index] = true;
+                worklist.extend(body.blocks[block_index].instructions[index].kind.operands());
+            }
+        }
+
+        let mut changed = false;
+        for (block, live) in body.blocks.iter_mut().zip(live) {
+            let mut live = live.into_iter();
+            let before = block.instructions.len();
+            block.instructions.retain(|_| live.next().unwrap_or(true));
+            changed |= block.instructions.len() != before;
+        }
+        changed
+    }
+}
+
+/// Whether running an instruction matters beyond the value it writes: calls,
+/// stores, and integer divisions that may trap.
+pub fn has_effects(instruction: &MirInstruction) -> bool {
+    match &instruction.kind {
+        MirInstructionKind::Call(..)
+        | MirInstructionKind::CallIndirect(..)
+        | MirInstructionKind::StoreCell(..)
+        | MirInstructionKind::StoreStatic(..)
+        | MirInstructionKind::StoreField(..)
+        | MirInstructionKind::StoreElement(..) => true,
+        // An index out of bounds traps.
+        MirInstructionKind::LoadElement(..) => true,
+        MirInstructionKind::BinaryOp(MirBinaryOp::Divide | MirBinaryOp::Remainder, _, divisor) => match divisor {
+            // Only a zero divisor, or -1 for the smallest value, traps.
+            MirOperand::Literal(HirLiteral::Integer(bits, int)) => {
+                *bits == 0 || (int.is_signed() && decode(*bits, *int) == -1)
+            }
+            MirOperand::Literal(_) => false,
+            MirOperand::Register(_) => !matches!(instruction.ty.representation(), Type::Float(_)),
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::hir::hir::HirLiteral;
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{BlockId, MirBinaryOp, MirBody, MirInstruction, MirInstructionKind, MirOperand};
+use crate::mir::passes::{Pass, PassContext};
+
+/// Removes computations that repeat one that dominates them, making their
+/// readers read the earlier result. Blocks are visited in reverse
+/// postorder, so that a computation is seen before those it dominates, and
+/// two computations are the same when they apply the same operation to
+/// operands with the same value numbers: a register that repeats another
+/// is numbered as that one. Only pure instructions are numbered; see
+/// `is_pure`.
+pub struct GlobalValueNumbering;
+
+impl Pass for GlobalValueNumbering {
+    fn name(&self) -> &'static str {
+        "gvn"
+    }
+
+    fn run(&mut self, body: &mut MirBody, context: &mut PassContext) -> bool {
+        let dominators = Dominators::new(body);
+        let mut leaders: HashMap<usize, usize> = HashMap::new();
+        let mut available: HashMap<String, Vec<(BlockId, usize)>> = HashMap::new();
+        let mut redundant: Vec<(BlockId, usize)> = Vec::new();
+        for &id in dominators.reverse_postorder() {
+            for (index, instruction) in body.block(id).instructions.iter().enumerate() {
+                let (Some(dest), Some(key)) = (instruction.dest.register(), value_key(instruction, &leaders)) else {
+                    continue;
+                };
+                let candidates = available.entry(key).or_default();
+                match candidates.iter().find(|(block, _)| dominators.dominates(*block, id)) {
+                    Some(&(_, leader)) => {
+                        leaders.insert(dest, leader);
+                        redundant.push((id, index));
+                    }
+                    None => candidates.push((id, dest)),
+                }
+            }
+        }
+        if redundant.is_empty() {
+            return false;
+        }
+
+        // Later instructions first, so that the indices stay valid.
+        for &(id, index) in redundant.iter().rev() {
+            body.blocks[id.index()].instructions.remove(index);
+        }
+        for (&dest, &leader) in &leaders {
+            body.replace_uses(dest, &MirOperand::Register(leader));
+        }
+        let noun = if redundant.len() == 1 { "computation" } else { "computations" };
+        context.remark(self.name(), format!("removed {} redundant {}", redundant.len(), noun));
+        true
+    }
+}
+
+/// Whether an instruction only computes a value from its operands, so that
+/// it can be dropped when unused, removed when repeated, or moved to where
+/// its operands are available. Integer divisions are pure but may trap,
+/// which matters when moving them; see `dce::has_effects`.
+pub fn is_pure(instruction: &MirInstruction) -> bool {
+    matches!(
+        instruction.kind,
+        MirInstructionKind::BinaryOp(..)
+            | MirInstructionKind::UnaryOp(..)
+            | MirInstructionKind::Cast(..)
+            | MirInstructionKind::Load(_)
+            // Environment slots are written once, when the closure is made.
+            | MirInstructionKind::LoadCapture(..)
+    )
+}
+
+/// What an instruction computes, in terms of the value numbers of its
+/// operands; `None` for instructions that are not numbered. Copies are left
+/// to copy propagation.
+fn value_key(instruction: &MirInstruction, leaders: &HashMap<usize, usize>) -> Option<String> {
+    if !is_pure(instruction) {
+        return None;
+    }
+    let number = |operand: &MirOperand| operand_key(operand, leaders);
+    let operation = match &instruction.kind {
+        MirInstructionKind::BinaryOp(op, left, right) => {
+            let (mut left, mut right) = (number(left), number(right));
+            if is_commutative(op) && left > right {
+                std::mem::swap(&mut left, &mut right);
+            }
+            format!("{:?} {} {}", op, left, right)
+        }
+        MirInstructionKind::UnaryOp(op, operand) => format!("{:?} {}", op, number(operand)),
+        MirInstructionKind::Cast(operand, from) => format!("cast {} {:?}", number(operand), from),
+        MirInstructionKind::LoadCapture(closure, slot) => format!("capture {} {}", number(closure), slot),
+        _ => return None,
+    };
+    Some(format!("{} : {:?}", operation, instruction.ty))
+}
+
+fn operand_key(operand: &MirOperand, leaders: &HashMap<usize, usize>) -> String {
+    match operand {
+        MirOperand::Register(register) => format!("%{}", leaders.get(register).unwrap_or(register)),
+        // Floats by their bits, so that 0.0 and -0.0 differ.
+        MirOperand::Literal(HirLiteral::Float(value, float)) => format!("{:#x}:{:?}", value.to_bits(), float),
+        MirOperand::Literal(literal) => format!("{:?}", literal),
+    }
+}
+
+fn is_commutative(op: &MirBinaryOp) -> bool {
+    matches!(op, MirBinaryOp::Add | MirBinaryOp::Multiply | MirBinaryOp::Equal | MirBinaryOp::NotEqual)
+}
@@ -0,0 +1,170 @@
+use crate::mir::mir::{BlockId, MirBasicBlock, MirBody, MirInstruction, MirInstructionKind, MirOperand, MirTerminator};
+use crate::mir::passes::{Pass, PassContext};
+use crate::semantic::attributes::InlineHint;
+
+/// Replaces direct calls to small functions with a copy of the callee's
+/// body. A function is small when its cost, roughly the number of
+/// instructions it runs, is at most `THRESHOLD`; `@inline` functions are
+/// inlined whatever their cost and `@noinline` ones never are. Recursive
+/// functions are not inlined, and a caller stops taking callees once it
+/// costs more than `MAX_CALLER_COST`.
+///
+/// The block holding the call is split after it: the arguments are copied
+/// into the callee's argument registers, the callee's returns jump to the
+/// rest of the block, and a phi there takes the value returned. Calls in
+/// the inlined blocks are left for the next round of the pipeline.
+pub struct Inliner;
+
+impl Inliner {
+    pub const THRESHOLD: usize = 12;
+    pub const MAX_CALLER_COST: usize = 400;
+}
+
+impl Pass for Inliner {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&mut self, body: &mut MirBody, context: &mut PassContext) -> bool {
+        let functions = context.functions;
+        let mut changed = false;
+        let mut worklist: Vec<BlockId> = body.block_ids().collect();
+        while let Some(id) = worklist.pop() {
+            let instructions = body.block(id).instructions.iter().enumerate();
+            let calls: Vec<(usize, usize, usize)> = instructions
+                .filter_map(|(index, instruction)| match &instruction.kind {
+                    MirInstructionKind::Call(callee, args) => Some((index, *callee, args.len())),
+                    _ => None,
+                })
+                .collect();
+            for (index, callee, arity) in calls {
+                let function = &functions[callee];
+                if function.is_closure || (callee != context.function && function.body.args != arity) {
+                    continue;
+                }
+                match self.decide(context, callee, cost(body)) {
+                    Ok(cost) => {
+                        let message = format!("inlined '{}' (cost {})", function.name, cost);
+                        worklist.push(inline_call(body, id, index, &function.body));
+                        context.remark(self.name(), message);
+                        changed = true;
+                        // The rest of the block moved to the continuation.
+                        break;
+                    }
+                    Err(reason) => {
+                        context.missed(self.name(), format!("did not inline '{}': {}", function.name, reason));
+                    }
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl Inliner {
+    /// The cost of inlining a call to `callee` into a caller costing
+    /// `caller_cost`, or why it should not be inlined.
+    fn decide(&self, context: &PassContext, callee: usize, caller_cost: usize) -> Result<usize, String> {
+        let function = &context.functions[callee];
+        if callee == context.function || calls(&function.body, callee) {
+            return Err("it is recursive".to_string());
+        }
+        let cost = cost(&function.body);
+        match function.attributes.inline {
+            Some(InlineHint::Never) => Err("it is marked @noinline".to_string()),
+            Some(InlineHint::Always) => Ok(cost),
+            None if cost > Self::THRESHOLD => {
+                Err(format!("its cost {} is over the threshold of {}", cost, Self::THRESHOLD))
+            }
+            None if caller_cost + cost > Self::MAX_CALLER_COST => {
+                Err(format!("the caller would cost more than {}", Self::MAX_CALLER_COST))
+            }
+            None => Ok(cost),
+        }
+    }
+}
+
+/// The instructions a body runs besides its phis, and one for each branch.
+pub fn cost(body: &MirBody) -> usize {
+    body.blocks
+        .iter()
+        .map(|block| {
+            let instructions = block.instructions.len() - block.phis().len();
+            instructions + usize::from(matches!(block.terminator, MirTerminator::Branch(..)))
+        })
+        .sum()
+}
+
+fn calls(body: &MirBody, function: usize) -> bool {
+    let mut instructions = body.blocks.iter().flat_map(|block| &block.instructions);
+    instructions.any(|instruction| matches!(instruction.kind, MirInstructionKind::Call(callee, _) if callee == function))
+}
+
+/// Inlines `callee` in place of the call at `index` in block `id`, and
+/// returns the block the instructions after the call moved to.
+fn inline_call(body: &mut MirBody, id: BlockId, index: usize, callee: &MirBody) -> BlockId {
+    let register_offset = body.locals.len();
+    let block_offset = body.blocks.len();
+    let continuation = BlockId(block_offset + callee.blocks.len());
+    body.locals.extend(callee.locals.iter().cloned());
+
+    let block = &mut body.blocks[id.index()];
+    let mut rest = block.instructions.split_off(index);
+    let call = rest.remove(0);
+    let MirInstructionKind::Call(_, args) = call.kind else {
+        unreachable!("inlining an instruction that is not a call");
+    };
+    for (register, arg) in args.into_iter().enumerate() {
+        block.instructions.push(MirInstruction {
+            kind: MirInstructionKind::Load(arg),
+            dest: MirOperand::Register(register_offset + register),
+            ty: callee.locals[register].ty.clone(),
+        });
+    }
+    let terminator = std::mem::replace(&mut block.terminator, MirTerminator::Goto(BlockId(block_offset)));
+
+    let renumber = |operand: &mut MirOperand| {
+        if let MirOperand::Register(register) = operand {
+            *register += register_offset;
+        }
+    };
+    let mut returned = Vec::new();
+    for (index, callee_block) in callee.blocks.iter().enumerate() {
+        let mut block = callee_block.clone();
+        for instruction in &mut block.instructions {
+            renumber(&mut instruction.dest);
+            instruction.kind.operands_mut().into_iter().for_each(renumber);
+            if let MirInstructionKind::Phi(inputs) = &mut instruction.kind {
+                inputs.iter_mut().for_each(|(pred, _)| pred.0 += block_offset);
+            }
+        }
+        if let Some(operand) = block.terminator.operand_mut() {
+            renumber(operand);
+        }
+        block.terminator.targets_mut().into_iter().for_each(|target| target.0 += block_offset);
+        if let MirTerminator::Return(value) = &block.terminator {
+            returned.push((BlockId(block_offset + index), value.clone()));
+            block.terminator = MirTerminator::Goto(continuation);
+        }
+        body.blocks.push(block);
+    }
+
+    for successor in terminator.successors() {
+        for phi in body.blocks[successor.index()].instructions.iter_mut() {
+            if let MirInstructionKind::Phi(inputs) = &mut phi.kind {
+                inputs.iter_mut().filter(|(pred, _)| *pred == id).for_each(|(pred, _)| *pred = continuation);
+            }
+        }
+    }
+    let result = MirInstruction {
+        kind: MirInstructionKind::Phi(returned),
+        dest: call.dest,
+        ty: call.ty,
+    };
+    rest.insert(0, result);
+    body.blocks.push(MirBasicBlock {
+        instructions: rest,
+        terminator,
+    });
+    continuation
+}
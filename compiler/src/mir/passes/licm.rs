@@ -0,0 +1,194 @@
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{BlockId, MirBasicBlock, MirBody, MirInstruction, MirInstructionKind, MirOperand, MirTerminator};
+use crate::mir::passes::dce::has_effects;
+use crate::mir::passes::gvn::is_pure;
+use crate::mir::passes::{Pass, PassContext};
+
+/// A loop of the control-flow graph: a header that dominates the rest of
+/// the loop, and the blocks that can get back to the header without
+/// leaving through it.
+#[derive(Debug, Clone)]
+pub struct NaturalLoop {
+    pub header: BlockId,
+    /// The blocks of the loop, the header included, in block order.
+    pub blocks: Vec<BlockId>,
+}
+
+impl NaturalLoop {
+    pub fn contains(&self, id: BlockId) -> bool {
+        self.blocks.binary_search(&id).is_ok()
+    }
+}
+
+/// The natural loops of a body, innermost first. A back edge is a jump to
+/// a block that dominates the jumping one; the loops of back edges to the
+/// same header are one loop.
+pub fn natural_loops(body: &MirBody, dominators: &Dominators) -> Vec<NaturalLoop> {
+    let predecessors = body.predecessors();
+    let mut loops: Vec<NaturalLoop> = Vec::new();
+    for &id in dominators.reverse_postorder() {
+        let latches: Vec<BlockId> = predecessors[id.index()]
+            .iter()
+            .copied()
+            .filter(|&pred| dominators.dominates(id, pred))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+        let mut blocks = vec![id];
+        let mut worklist = latches;
+        while let Some(block) = worklist.pop() {
+            if blocks.contains(&block) {
+                continue;
+            }
+            blocks.push(block);
+            let preds = predecessors[block.index()].iter();
+            worklist.extend(preds.copied().filter(|&pred| dominators.is_reachable(pred)));
+        }
+        blocks.sort();
+        loops.push(NaturalLoop { header: id, blocks });
+    }
+    loops.sort_by_key(|natural_loop| natural_loop.blocks.len());
+    loops
+}
+
+/// Moves computations whose operands do not change around a loop out of
+/// it, into a preheader: a block outside the loop that only jumps to the
+/// header, made for the loop if it has none. Loops are visited innermost
+/// first, so that what leaves an inner loop can go on to leave the loop
+/// around it.
+///
+/// Only pure instructions that cannot trap are moved, since the loop may
+/// not have run them at all.
+pub struct LoopInvariantCodeMotion;
+
+impl Pass for LoopInvariantCodeMotion {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn run(&mut self, body: &mut MirBody, context: &mut PassContext) -> bool {
+        let mut changed = false;
+        // Each time something moves, the loops are found again, since a new
+        // preheader is part of the loop around it.
+        'search: loop {
+            let dominators = Dominators::new(body);
+            for natural_loop in natural_loops(body, &dominators) {
+                let invariant = invariant_instructions(body, &natural_loop);
+                if invariant.is_empty() {
+                    continue;
+                }
+                let preheader = preheader(body, &natural_loop);
+                let mut hoisted: Vec<(BlockId, usize, MirInstruction)> = Vec::new();
+                let mut positions = invariant.clone();
+                positions.sort_by(|a, b| b.cmp(a));
+                for (id, index) in positions {
+                    let instruction = body.blocks[id.index()].instructions.remove(index);
+                    hoisted.push((id, index, instruction));
+                }
+                // Back in the order they were found, which puts definitions first.
+                for position in &invariant {
+                    let found = hoisted.iter().position(|(id, index, _)| (*id, *index) == *position);
+                    let (_, _, instruction) = hoisted.swap_remove(found.expect("invariant instructions were removed"));
+                    body.blocks[preheader.index()].instructions.push(instruction);
+                }
+                let noun = if invariant.len() == 1 { "instruction" } else { "instructions" };
+                let header = natural_loop.header;
+                context.remark(self.name(), format!("hoisted {} {} out of the loop at {}", invariant.len(), noun, header));
+                changed = true;
+                continue 'search;
+            }
+            return changed;
+        }
+    }
+}
+
+/// The instructions of a loop that compute the same value on every trip
+/// and can be moved out of it, each after those it reads.
+fn invariant_instructions(body: &MirBody, natural_loop: &NaturalLoop) -> Vec<(BlockId, usize)> {
+    let mut defined_in_loop = vec![false; body.locals.len()];
+    for &id in &natural_loop.blocks {
+        for instruction in &body.block(id).instructions {
+            if let Some(dest) = instruction.dest.register() {
+                defined_in_loop[dest] = true;
+            }
+        }
+    }
+
+    let mut invariant = Vec::new();
+    let mut found = true;
+    while found {
+        found = false;
+        for &id in &natural_loop.blocks {
+            for (index, instruction) in body.block(id).instructions.iter().enumerate() {
+                let Some(dest) = instruction.dest.register() else {
+                    continue;
+                };
+                if !defined_in_loop[dest] || !is_pure(instruction) || has_effects(instruction) {
+                    continue;
+                }
+                let operands = instruction.kind.operands();
+                if operands.iter().all(|operand| operand.register().is_none_or(|register| !defined_in_loop[register])) {
+                    // Its readers in the loop now read a value from outside it.
+                    defined_in_loop[dest] = false;
+                    invariant.push((id, index));
+                    found = true;
+                }
+            }
+        }
+    }
+    invariant
+}
+
+/// The block outside the loop that jumps to its header and nowhere else,
+/// adding one if there is none. The header's phis take what came from
+/// outside the loop from the new block, which merges it with phis of its
+/// own when it came from more than one block.
+fn preheader(body: &mut MirBody, natural_loop: &NaturalLoop) -> BlockId {
+    let header = natural_loop.header;
+    let outside: Vec<BlockId> = body.predecessors()[header.index()]
+        .iter()
+        .copied()
+        .filter(|&pred| !natural_loop.contains(pred))
+        .collect();
+    if let [pred] = outside[..] {
+        if body.successors(pred) == [header] {
+            return pred;
+        }
+    }
+
+    let preheader = BlockId(body.blocks.len());
+    let mut phis = Vec::new();
+    for index in 0..body.block(header).phis().len() {
+        let MirInstructionKind::Phi(inputs) = &mut body.blocks[header.index()].instructions[index].kind else {
+            unreachable!("phis come first in their block");
+        };
+        let (entering, staying): (Vec<_>, Vec<_>) = inputs.drain(..).partition(|(pred, _)| outside.contains(pred));
+        *inputs = staying;
+        let value = match &entering[..] {
+            [(_, value)] => value.clone(),
+            _ => {
+                let phi = &body.blocks[header.index()].instructions[index];
+                let register = body.locals.len();
+                body.locals.push(body.locals[phi.dest.register().expect("phis write a register")].clone());
+                phis.push(MirInstruction {
+                    kind: MirInstructionKind::Phi(entering),
+                    dest: MirOperand::Register(register),
+                    ty: phi.ty.clone(),
+                });
+                MirOperand::Register(register)
+            }
+        };
+        if let MirInstructionKind::Phi(inputs) = &mut body.blocks[header.index()].instructions[index].kind {
+            inputs.push((preheader, value));
+        }
+    }
+    for &pred in &outside {
+        body.blocks[pred.index()].terminator.retarget(header, preheader);
+    }
+    body.blocks.push(MirBasicBlock {
+        instructions: phis,
+        terminator: MirTerminator::Goto(header),
+    });
+    preheader
+}
@@ -0,0 +1,212 @@
+pub mod const_fold;
+pub mod copy_propagation;
+pub mod dce;
+pub mod gvn;
+pub mod inline;
+pub mod licm;
+pub mod sccp;
+pub mod simplify_cfg;
+
+use crate::diagnostics::Remark;
+use crate::mir::mir::{MirBody, MirFunction, MirModule};
+use crate::mir::passes::const_fold::ConstantFolding;
+use crate::mir::passes::copy_propagation::CopyPropagation;
+use crate::mir::passes::dce::DeadCodeElimination;
+use crate::mir::passes::gvn::GlobalValueNumbering;
+use crate::mir::passes::inline::Inliner;
+use crate::mir::passes::licm::LoopInvariantCodeMotion;
+use crate::mir::passes::sccp::Sccp;
+use crate::mir::passes::simplify_cfg::{RemoveUnreachableBlocks, SimplifyCfg};
+use crate::mir::verify::verify;
+
+/// How much optimization to do, as chosen with `-O0`, `-O1` or `-O2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+}
+
+impl OptLevel {
+    pub const ALL: [OptLevel; 3] = [OptLevel::O0, OptLevel::O1, OptLevel::O2];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<OptLevel> {
+        OptLevel::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+/// What a pass can see besides the body it rewrites.
+pub struct PassContext<'a> {
+    /// The functions of the module. The one being rewritten has an empty
+    /// body while the pass runs.
+    pub functions: &'a [MirFunction],
+    /// The index of the function being rewritten.
+    pub function: usize,
+    /// Where passes report what they did, and what they chose not to do.
+    pub remarks: &'a mut Vec<Remark>,
+}
+
+impl PassContext<'_> {
+    /// Reports a change `pass` made to the function being rewritten.
+    pub fn remark(&mut self, pass: &'static str, message: impl Into<String>) {
+        self.report(pass, message.into(), true);
+    }
+
+    /// Reports a change `pass` considered and did not make, and why.
+    pub fn missed(&mut self, pass: &'static str, message: impl Into<String>) {
+        self.report(pass, message.into(), false);
+    }
+
+    fn report(&mut self, pass: &'static str, message: String, applied: bool) {
+        let function = self.functions.get(self.function).map(|function| function.name.clone()).unwrap_or_default();
+        self.remarks.push(Remark {
+            pass,
+            function,
+            message,
+            applied,
+        });
+    }
+}
+
+/// A transformation of MIR bodies. Passes take bodies in SSA form and leave
+/// them in SSA form.
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Rewrites `body` and returns whether anything changed.
+    fn run(&mut self, body: &mut MirBody, context: &mut PassContext) -> bool;
+}
+
+/// Runs a pipeline of passes over every function of a module, repeating the
+/// pipeline while it keeps changing something, up to `MAX_ROUNDS` times.
+///
+/// In debug builds every body is verified before the pipeline and after
+/// each pass, so that a pass that breaks a body is caught, and named, at
+/// once rather than by whatever stage trips over the body later.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    remarks: Vec<Remark>,
+}
+
+impl PassManager {
+    pub const MAX_ROUNDS: usize = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The pipeline for an optimization level. `-O1` folds constants and
+    /// cleans up after itself. `-O2` first propagates constants along the
+    /// paths that can actually run, also removes redundant computations and
+    /// hoists them out of loops, and last inlines small functions, which it
+    /// measures after the rest of the pipeline has shrunk them. What was
+    /// inlined is optimized in its new place by the next round.
+    pub fn for_level(level: OptLevel) -> Self {
+        let mut manager = Self::new();
+        if level >= OptLevel::O2 {
+            manager.add(Sccp::new());
+        }
+        if level >= OptLevel::O1 {
+            manager.add(ConstantFolding);
+            manager.add(CopyPropagation);
+        }
+        if level >= OptLevel::O2 {
+            manager.add(GlobalValueNumbering);
+            manager.add(LoopInvariantCodeMotion);
+        }
+        if level >= OptLevel::O1 {
+            manager.add(DeadCodeElimination);
+            manager.add(SimplifyCfg);
+            manager.add(RemoveUnreachableBlocks);
+        }
+        if level >= OptLevel::O2 {
+            manager.add(Inliner);
+        }
+        manager
+    }
+
+    /// A pipeline of the passes with the given names, in that order, as
+    /// golden tests list them.
+    pub fn from_names(names: &[&str]) -> Result<Self, String> {
+        let mut manager = Self::new();
+        for name in names {
+            match *name {
+                "sccp" => manager.add(Sccp::new()),
+                "const-fold" => manager.add(ConstantFolding),
+                "copy-propagation" => manager.add(CopyPropagation),
+                "gvn" => manager.add(GlobalValueNumbering),
+                "licm" => manager.add(LoopInvariantCodeMotion),
+                "dce" => manager.add(DeadCodeElimination),
+                "simplify-cfg" => manager.add(SimplifyCfg),
+                "remove-unreachable-blocks" => manager.add(RemoveUnreachableBlocks),
+                "inline" => manager.add(Inliner),
+                name => return Err(format!("unknown pass '{}'", name)),
+            }
+        }
+        Ok(manager)
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) {
+        self.passes.push(Box::new(pass));
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// What the passes reported, in the order they reported it. A remark
+    /// repeated by a later round is only kept once.
+    pub fn remarks(&self) -> &[Remark] {
+        &self.remarks
+    }
+
+    /// Fails only in debug builds, with the function and pass whose body
+    /// does not verify.
+    pub fn run(&mut self, module: &mut MirModule) -> Result<(), String> {
+        for function in &module.functions {
+            check(function, "MIR lowering")?;
+        }
+        for _ in 0..Self::MAX_ROUNDS {
+            let mut changed = false;
+            for pass in &mut self.passes {
+                for function in 0..module.functions.len() {
+                    let mut body = std::mem::replace(&mut module.functions[function].body, MirBody::empty());
+                    let mut remarks = Vec::new();
+                    let mut context = PassContext {
+                        functions: &module.functions,
+                        function,
+                        remarks: &mut remarks,
+                    };
+                    changed |= pass.run(&mut body, &mut context);
+                    module.functions[function].body = body;
+                    check(&module.functions[function], pass.name())?;
+                    for remark in remarks {
+                        if !self.remarks.contains(&remark) {
+                            self.remarks.push(remark);
+                        }
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check(function: &MirFunction, after: &str) -> Result<(), String> {
+    if !cfg!(debug_assertions) {
+        return Ok(());
+    }
+    verify(&function.body).map_err(|error| format!("MIR of '{}' is invalid after {}: {}", function.name, after, error))
+}
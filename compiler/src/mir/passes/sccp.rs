@@ -0,0 +1,210 @@
+use std::collections::HashSet;
+
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{BlockId, MirBody, MirInstruction, MirInstructionKind, MirOperand, MirTerminator};
+use crate::mir::passes::const_fold::{fold, identical};
+use crate::mir::passes::{Pass, PassContext};
+
+/// What is known about the value of a register.
+#[derive(Debug, Clone)]
+enum Value {
+    /// Nothing writes it on a path found to run so far.
+    Unknown,
+    Constant(HirLiteral),
+    /// It may hold more than one value.
+    Varying,
+}
+
+/// Sparse conditional constant propagation (Wegman and Zadeck). Registers
+/// are assumed constant until shown otherwise, and only the edges found to
+/// be taken are followed, so a value that is constant on every path that
+/// can run is found even when it flows around a loop or through a branch
+/// that never goes one way. Constant registers are then replaced with their
+/// values and branches on known conditions with jumps; what is left unused
+/// or unreachable is removed by other passes.
+#[derive(Default)]
+pub struct Sccp {
+    values: Vec<Value>,
+    executable: Vec<bool>,
+    /// The edges found to be taken, as (from, to).
+    edges: HashSet<(BlockId, BlockId)>,
+}
+
+impl Sccp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn analyze(&mut self, body: &MirBody) {
+        self.values = vec![Value::Unknown; body.locals.len()];
+        for value in &mut self.values[..body.args] {
+            *value = Value::Varying;
+        }
+        self.executable = vec![false; body.blocks.len()];
+        self.edges.clear();
+
+        // The instructions reading each register, as (block, index), with
+        // `None` for the terminator.
+        let mut readers = vec![Vec::new(); body.locals.len()];
+        for id in body.block_ids() {
+            let block = body.block(id);
+            for (index, instruction) in block.instructions.iter().enumerate() {
+                for register in instruction.kind.operands().into_iter().filter_map(MirOperand::register) {
+                    readers[register].push((id, Some(index)));
+                }
+            }
+            if let Some(register) = block.terminator.operand().and_then(MirOperand::register) {
+                readers[register].push((id, None));
+            }
+        }
+
+        let mut flow = vec![(None, BlockId::ENTRY)];
+        let mut changed_registers = Vec::new();
+        while !flow.is_empty() || !changed_registers.is_empty() {
+            while let Some((from, to)) = flow.pop() {
+                if let Some(from) = from {
+                    if !self.edges.insert((from, to)) {
+                        continue;
+                    }
+                }
+                let first_visit = !self.executable[to.index()];
+                self.executable[to.index()] = true;
+                let block = body.block(to);
+                for (index, instruction) in block.instructions.iter().enumerate() {
+                    // A newly taken edge only changes the phis, unless the
+                    // block is running for the first time.
+                    if first_visit || instruction.is_phi() {
+                        self.visit(body, to, index, &mut changed_registers);
+                    }
+                }
+                if first_visit {
+                    flow.extend(self.taken(&block.terminator).into_iter().map(|next| (Some(to), next)));
+                }
+            }
+            while let Some(register) = changed_registers.pop() {
+                for &(id, index) in &readers[register] {
+                    if !self.executable[id.index()] {
+                        continue;
+                    }
+                    match index {
+                        Some(index) => self.visit(body, id, index, &mut changed_registers),
+                        None => {
+                            let taken = self.taken(&body.block(id).terminator);
+                            flow.extend(taken.into_iter().map(|next| (Some(id), next)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, body: &MirBody, id: BlockId, index: usize, changed: &mut Vec<usize>) {
+        let instruction = &body.block(id).instructions[index];
+        let Some(dest) = instruction.dest.register() else {
+            return;
+        };
+        let value = self.evaluate(instruction, id);
+        let lowered = match (&self.values[dest], &value) {
+            (Value::Varying, _) | (_, Value::Unknown) => false,
+            (Value::Constant(old), Value::Constant(new)) => !identical(old, new),
+            _ => true,
+        };
+        if lowered {
+            // Values only ever go down: unknown, then constant, then varying.
+            self.values[dest] = match self.values[dest] {
+                Value::Unknown => value,
+                _ => Value::Varying,
+            };
+            changed.push(dest);
+        }
+    }
+
+    fn evaluate(&self, instruction: &MirInstruction, id: BlockId) -> Value {
+        if let MirInstructionKind::Phi(inputs) = &instruction.kind {
+            let mut result = Value::Unknown;
+            for (pred, operand) in inputs {
+                if self.edges.contains(&(*pred, id)) {
+                    result = meet(result, self.operand(operand));
+                }
+            }
+            return result;
+        }
+        let mut literals = Vec::new();
+        for operand in instruction.kind.operands() {
+            match self.operand(operand) {
+                Value::Unknown => return Value::Unknown,
+                Value::Varying => return Value::Varying,
+                Value::Constant(literal) => literals.push(literal),
+            }
+        }
+        let literals: Vec<&HirLiteral> = literals.iter().collect();
+        match fold(&instruction.kind, &instruction.ty, &literals) {
+            Some(literal) => Value::Constant(literal),
+            None => Value::Varying,
+        }
+    }
+
+    fn operand(&self, operand: &MirOperand) -> Value {
+        match operand {
+            // `null` stands for a value of any type, which folding would lose.
+            MirOperand::Literal(HirLiteral::Null) => Value::Varying,
+            MirOperand::Literal(literal) => Value::Constant(literal.clone()),
+            MirOperand::Register(register) => self.values[*register].clone(),
+        }
+    }
+
+    /// The successors a terminator is known to go to so far.
+    fn taken(&self, terminator: &MirTerminator) -> Vec<BlockId> {
+        match terminator {
+            MirTerminator::Branch(condition, then_block, else_block) => match self.operand(condition) {
+                Value::Unknown => Vec::new(),
+                Value::Constant(HirLiteral::Boolean(true)) => vec![*then_block],
+                Value::Constant(HirLiteral::Boolean(false)) => vec![*else_block],
+                _ => vec![*then_block, *else_block],
+            },
+            terminator => terminator.successors(),
+        }
+    }
+}
+
+fn meet(a: Value, b: Value) -> Value {
+    match (a, b) {
+        (Value::Unknown, value) | (value, Value::Unknown) => value,
+        (Value::Constant(a), Value::Constant(b)) if identical(&a, &b) => Value::Constant(a),
+        _ => Value::Varying,
+    }
+}
+
+impl Pass for Sccp {
+    fn name(&self) -> &'static str {
+        "sccp"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        self.analyze(body);
+        let mut changed = false;
+        for register in 0..body.locals.len() {
+            let Value::Constant(literal) = &self.values[register] else {
+                continue;
+            };
+            changed |= body.replace_uses(register, &MirOperand::Literal(literal.clone()));
+        }
+        for id in body.block_ids() {
+            if !self.executable[id.index()] {
+                continue;
+            }
+            let MirTerminator::Branch(_, then_block, else_block) = body.block(id).terminator else {
+                continue;
+            };
+            let taken = self.taken(&body.block(id).terminator);
+            if let [target] = taken[..] {
+                body.blocks[id.index()].terminator = MirTerminator::Goto(target);
+                for untaken in [then_block, else_block].into_iter().filter(|&block| block != target) {
+                    body.blocks[untaken.index()].remove_phi_inputs(id);
+                }
+                changed |= then_block != else_block;
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,217 @@
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{BlockId, MirBasicBlock, MirBody, MirInstruction, MirInstructionKind, MirOperand, MirTerminator};
+use crate::mir::passes::{Pass, PassContext};
+
+/// Simplifies the shape of the control-flow graph until nothing changes:
+///
+/// - a branch on a literal, or to the same block either way, becomes a jump;
+/// - a block that is the only successor of its only predecessor is merged
+///   into it, its phis becoming copies;
+/// - jumps to an empty block that only jumps on go straight to where it
+///   jumps, unless that would give a phi two inputs from one block.
+///
+/// The blocks this leaves unreachable are removed as it goes, so that they
+/// do not count as predecessors.
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        let mut changed = false;
+        loop {
+            let removed = remove_unreachable_blocks(body);
+            let folded = fold_branches(body);
+            let merged = merge_blocks(body);
+            let forwarded = forward_jumps(body);
+            if !(removed || folded || merged || forwarded) {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+fn fold_branches(body: &mut MirBody) -> bool {
+    let mut changed = false;
+    for id in body.block_ids() {
+        let (taken, untaken) = match &body.block(id).terminator {
+            MirTerminator::Branch(_, then_block, else_block) if then_block == else_block => (*then_block, None),
+            MirTerminator::Branch(MirOperand::Literal(HirLiteral::Boolean(true)), then_block, else_block) => {
+                (*then_block, Some(*else_block))
+            }
+            MirTerminator::Branch(MirOperand::Literal(HirLiteral::Boolean(false)), then_block, else_block) => {
+                (*else_block, Some(*then_block))
+            }
+            _ => continue,
+        };
+        body.blocks[id.index()].terminator = MirTerminator::Goto(taken);
+        if let Some(untaken) = untaken.filter(|&untaken| untaken != taken) {
+            body.blocks[untaken.index()].remove_phi_inputs(id);
+        }
+        changed = true;
+    }
+    changed
+}
+
+/// Merges one block into its only predecessor, if some block allows it.
+fn merge_blocks(body: &mut MirBody) -> bool {
+    let predecessors = body.predecessors();
+    let merge = body.block_ids().find_map(|id| {
+        let MirTerminator::Goto(next) = body.block(id).terminator else {
+            return None;
+        };
+        (next != id && next != BlockId::ENTRY && predecessors[next.index()] == [id]).then_some((id, next))
+    });
+    let Some((id, next)) = merge else {
+        return false;
+    };
+
+    let removed = std::mem::replace(
+        &mut body.blocks[next.index()],
+        MirBasicBlock {
+            instructions: Vec::new(),
+            terminator: MirTerminator::Return(MirOperand::Literal(HirLiteral::Null)),
+        },
+    );
+    for successor in removed.terminator.successors() {
+        rename_phi_inputs(&mut body.blocks[successor.index()], next, id);
+    }
+    let block = &mut body.blocks[id.index()];
+    block.instructions.extend(removed.instructions.into_iter().map(|instruction| match instruction.kind {
+        // With one predecessor, a phi is a copy of its only input.
+        MirInstructionKind::Phi(mut inputs) => {
+            let value = inputs.pop().map_or(MirOperand::Literal(HirLiteral::Null), |(_, value)| value);
+            MirInstruction {
+                kind: MirInstructionKind::Load(value),
+                ..instruction
+            }
+        }
+        _ => instruction,
+    }));
+    block.terminator = removed.terminator;
+    true
+}
+
+/// Sends the jumps to one empty forwarding block past it, if some block
+/// allows it.
+fn forward_jumps(body: &mut MirBody) -> bool {
+    let predecessors = body.predecessors();
+    for id in body.block_ids().filter(|&id| id != BlockId::ENTRY) {
+        let Some(target) = forwards_to(body.block(id)) else {
+            continue;
+        };
+        // Chains are forwarded from their end, and cycles of empty blocks,
+        // which loop forever, are left alone.
+        if target == id || forwards_to(body.block(target)).is_some() {
+            continue;
+        }
+        let has_phis = !body.block(target).phis().is_empty();
+        let movable: Vec<BlockId> = predecessors[id.index()]
+            .iter()
+            .copied()
+            .filter(|pred| !has_phis || !predecessors[target.index()].contains(pred))
+            .collect();
+        if movable.is_empty() {
+            continue;
+        }
+        for &pred in &movable {
+            body.blocks[pred.index()].terminator.retarget(id, target);
+            for phi in body.blocks[target.index()].instructions.iter_mut() {
+                if let MirInstructionKind::Phi(inputs) = &mut phi.kind {
+                    let value = inputs.iter().find(|(input, _)| *input == id).map(|(_, value)| value.clone());
+                    inputs.extend(value.map(|value| (pred, value)));
+                }
+            }
+        }
+        if movable.len() == predecessors[id.index()].len() {
+            body.blocks[target.index()].remove_phi_inputs(id);
+        }
+        return true;
+    }
+    false
+}
+
+/// Where a block with nothing to do but jump jumps.
+fn forwards_to(block: &MirBasicBlock) -> Option<BlockId> {
+    match block.terminator {
+        MirTerminator::Goto(target) if block.instructions.is_empty() => Some(target),
+        _ => None,
+    }
+}
+
+fn rename_phi_inputs(block: &mut MirBasicBlock, from: BlockId, to: BlockId) {
+    for phi in block.instructions.iter_mut() {
+        if let MirInstructionKind::Phi(inputs) = &mut phi.kind {
+            for (input, _) in inputs.iter_mut() {
+                if *input == from {
+                    *input = to;
+                }
+            }
+        }
+    }
+}
+
+/// Removes the blocks that cannot be reached from the entry and the phi
+/// inputs that came from them, numbering the remaining blocks in order.
+pub struct RemoveUnreachableBlocks;
+
+impl Pass for RemoveUnreachableBlocks {
+    fn name(&self) -> &'static str {
+        "remove-unreachable-blocks"
+    }
+
+    fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+        remove_unreachable_blocks(body)
+    }
+}
+
+/// Removes the blocks that cannot be reached from the entry; see
+/// `RemoveUnreachableBlocks`.
+pub fn remove_unreachable_blocks(body: &mut MirBody) -> bool {
+    let mut reachable = vec![false; body.blocks.len()];
+    let mut worklist = vec![BlockId::ENTRY];
+    reachable[BlockId::ENTRY.index()] = true;
+    while let Some(id) = worklist.pop() {
+        for successor in body.successors(id) {
+            if !reachable[successor.index()] {
+                reachable[successor.index()] = true;
+                worklist.push(successor);
+            }
+        }
+    }
+    if reachable.iter().all(|&reachable| reachable) {
+        return false;
+    }
+
+    let mut renumbered = Vec::new();
+    let mut next = 0;
+    for &reachable in &reachable {
+        renumbered.push(reachable.then_some(BlockId(next)));
+        next += reachable as usize;
+    }
+    let blocks = std::mem::take(&mut body.blocks);
+    for (mut block, reachable) in blocks.into_iter().zip(&reachable) {
+        if !reachable {
+            continue;
+        }
+        for target in block.terminator.targets_mut() {
+            *target = renumbered[target.index()].expect("successors of reachable blocks are reachable");
+        }
+        for phi in block.instructions.iter_mut() {
+            if let MirInstructionKind::Phi(inputs) = &mut phi.kind {
+                inputs.retain_mut(|(pred, _)| match renumbered[pred.index()] {
+                    Some(renumbered) => {
+                        *pred = renumbered;
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+        body.blocks.push(block);
+    }
+    true
+}
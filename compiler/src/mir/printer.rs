@@ -0,0 +1,199 @@
+use std::fmt::{Display, Formatter, Result};
+
+use crate::hir::const_eval::decode;
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{MirFunction, MirInstruction, MirInstructionKind, MirModule, MirOperand, MirTerminator};
+use crate::semantic::attributes::InlineHint;
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+
+/// Prints a module in the MIR text format, which `parser::parse_module`
+/// reads back:
+///
+/// ```text
+/// static @total: i64 = 0
+///
+/// fn @scale(%0: f64 "x") -> f64 inline {
+///     let %1: bool
+///     let %2: f64
+/// bb0:
+///     %1 = gt f64 %0, 2.0
+///     br %1, bb1, bb2
+/// bb1:
+///     %2 = mul f64 %0, 0.5
+///     goto bb2
+/// ...
+/// }
+/// ```
+///
+/// Registers are `%n` and blocks `bbn`; functions and statics are named
+/// with `@`. Each instruction names its operation and its type, which for a
+/// comparison is that of its operands. Literals of types other than `i64`,
+/// `f64` and `bool` carry their type as a suffix, as in `255u8`. Types are
+/// printed as they are represented, without aliases or `def` names, so a
+/// struct is the list of its field types, as in `{i64, f64}`, and of
+/// a function's attributes only those later stages read are printed.
+impl Display for MirModule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for item in &self.statics {
+            writeln!(f, "static @{}: {} = {}", item.name, mir_type(&item.ty), Literal(&item.value))?;
+        }
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 || !self.statics.is_empty() {
+                writeln!(f)?;
+            }
+            write_function(f, self, function, index == self.entry)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_function(f: &mut Formatter<'_>, module: &MirModule, function: &MirFunction, entry: bool) -> Result {
+    let body = &function.body;
+    let local = |register: usize| {
+        let local = &body.locals[register];
+        match &local.name {
+            Some(name) => format!("%{}: {} {:?}", register, mir_type(&local.ty), name),
+            None => format!("%{}: {}", register, mir_type(&local.ty)),
+        }
+    };
+    let params: Vec<String> = (0..body.args).map(local).collect();
+    write!(f, "fn @{}({}) -> {}", function.name, params.join(", "), mir_type(&function.return_type))?;
+    if entry {
+        write!(f, " entry")?;
+    }
+    if function.is_closure {
+        write!(f, " closure")?;
+    }
+    match function.attributes.inline {
+        Some(InlineHint::Always) => write!(f, " inline")?,
+        Some(InlineHint::Never) => write!(f, " noinline")?,
+        None => {}
+    }
+    if let Some(name) = &function.attributes.export {
+        write!(f, " export {:?}", name)?;
+    }
+    writeln!(f, " {{")?;
+    for register in body.args..body.locals.len() {
+        writeln!(f, "    let {}", local(register))?;
+    }
+    for id in body.block_ids() {
+        writeln!(f, "{}:", id)?;
+        let block = body.block(id);
+        for instruction in &block.instructions {
+            writeln!(f, "    {}", Instruction { module, instruction })?;
+        }
+        writeln!(f, "    {}", block.terminator)?;
+    }
+    writeln!(f, "}}")
+}
+
+/// An instruction with the module it belongs to, for the names of the
+/// functions and statics it refers to.
+struct Instruction<'a> {
+    module: &'a MirModule,
+    instruction: &'a MirInstruction,
+}
+
+impl Display for Instruction<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let instruction = self.instruction;
+        let function = |index: usize| match self.module.functions.get(index) {
+            Some(function) => function.name.clone(),
+            None => index.to_string(),
+        };
+        let item = |index: usize| match self.module.statics.get(index) {
+            Some(item) => item.name.clone(),
+            None => index.to_string(),
+        };
+        let list = |operands: &[MirOperand]| operands.iter().map(MirOperand::to_string).collect::<Vec<_>>().join(", ");
+        let ty = mir_type(&instruction.ty);
+        write!(f, "{} = ", instruction.dest)?;
+        match &instruction.kind {
+            MirInstructionKind::BinaryOp(op, left, right) => write!(f, "{} {} {}, {}", op.name(), ty, left, right),
+            MirInstructionKind::UnaryOp(op, operand) => write!(f, "{} {} {}", op.name(), ty, operand),
+            MirInstructionKind::Load(operand) => write!(f, "copy {} {}", ty, operand),
+            MirInstructionKind::Cast(operand, from) => write!(f, "cast {} {} to {}", mir_type(from), operand, ty),
+            MirInstructionKind::Call(callee, args) => write!(f, "call {} @{}({})", ty, function(*callee), list(args)),
+            MirInstructionKind::CallIndirect(callee, args) => write!(f, "call_indirect {} {}({})", ty, callee, list(args)),
+            MirInstructionKind::MakeClosure(callee, env) => {
+                write!(f, "closure {} @{}({})", ty, function(*callee), list(env))
+            }
+            MirInstructionKind::LoadCapture(closure, slot) => write!(f, "capture {} {}, {}", ty, closure, slot),
+            MirInstructionKind::MakeCell(operand) => write!(f, "make_cell {} {}", ty, operand),
+            MirInstructionKind::LoadCell(cell) => write!(f, "load_cell {} {}", ty, cell),
+            MirInstructionKind::StoreCell(cell, value) => write!(f, "store_cell {} {}, {}", ty, cell, value),
+            MirInstructionKind::LoadStatic(index) => write!(f, "load_static {} @{}", ty, item(*index)),
+            MirInstructionKind::StoreStatic(index, value) => write!(f, "store_static {} @{}, {}", ty, item(*index), value),
+            MirInstructionKind::MakeAggregate(elements) => write!(f, "aggregate {} ({})", ty, list(elements)),
+            MirInstructionKind::LoadField(object, index) => write!(f, "field {} {}, {}", ty, object, index),
+            MirInstructionKind::StoreField(object, index, value) => {
+                write!(f, "set_field {} {}, {}, {}", ty, object, index, value)
+            }
+            MirInstructionKind::LoadElement(array, index) => write!(f, "element {} {}, {}", ty, array, index),
+            MirInstructionKind::StoreElement(array, index, value) => {
+                write!(f, "set_element {} {}, {}, {}", ty, array, index, value)
+            }
+            MirInstructionKind::Phi(inputs) => {
+                let inputs: Vec<String> = inputs.iter().map(|(pred, value)| format!("{}: {}", pred, value)).collect();
+                write!(f, "phi {} [{}]", ty, inputs.join(", "))
+            }
+        }
+    }
+}
+
+impl Display for MirTerminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            MirTerminator::Return(value) => write!(f, "return {}", value),
+            MirTerminator::Goto(target) => write!(f, "goto {}", target),
+            MirTerminator::Branch(condition, then_block, else_block) => {
+                write!(f, "br {}, {}, {}", condition, then_block, else_block)
+            }
+        }
+    }
+}
+
+impl Display for MirOperand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            MirOperand::Register(register) => write!(f, "%{}", register),
+            MirOperand::Literal(literal) => write!(f, "{}", Literal(literal)),
+        }
+    }
+}
+
+/// A literal as the text format writes it: exactly enough to read back the
+/// same value of the same type.
+struct Literal<'a>(&'a HirLiteral);
+
+impl Display for Literal<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            HirLiteral::Integer(bits, IntType::I64) => write!(f, "{}", decode(*bits, IntType::I64)),
+            HirLiteral::Integer(bits, int) => write!(f, "{}{}", decode(*bits, *int), int.name()),
+            // Debug formatting is the shortest text that reads back as the same value.
+            HirLiteral::Float(value, FloatType::F64) => write!(f, "{:?}", value),
+            HirLiteral::Float(value, float) => write!(f, "{:?}{}", value, float.name()),
+            HirLiteral::String(text) => write!(f, "{:?}", text),
+            HirLiteral::Boolean(value) => write!(f, "{}", value),
+            HirLiteral::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// A type as the text format writes it: as it is represented.
+pub fn mir_type(ty: &Type) -> String {
+    match ty.representation() {
+        Type::Function { params, return_type } => {
+            let params: Vec<String> = params.iter().map(mir_type).collect();
+            format!("fn({}) -> {}", params.join(", "), mir_type(return_type))
+        }
+        Type::Struct { fields } => {
+            let fields: Vec<String> = fields.iter().map(|(_, ty)| mir_type(ty)).collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+        Type::Array { element, length } => format!("[{}; {}]", mir_type(element), length),
+        Type::Reference(inner) => format!("ref {}", mir_type(inner)),
+        ty => ty.to_string(),
+    }
+}
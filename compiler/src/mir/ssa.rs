@@ -0,0 +1,296 @@
+use crate::hir::hir::HirLiteral;
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBody, MirInstruction, MirInstructionKind, MirLocal, MirOperand, MirTerminator,
+};
+use crate::semantic::symbol_table::Type;
+
+/// Puts a body in SSA form with the algorithm of Cytron et al. Phis are
+/// placed on the iterated dominance frontiers of the blocks that write a
+/// register, for registers that are read in a block other than the one
+/// writing them ("semi-pruned" SSA). A walk of the dominator tree then gives
+/// every write a register of its own; the first write keeps the original.
+///
+/// A read that no write reaches becomes `null`. Blocks that cannot be
+/// reached are emptied, so no phi has an input from them.
+pub fn into_ssa(body: &mut MirBody) {
+    let dominators = Dominators::new(body);
+    for id in body.block_ids() {
+        if !dominators.is_reachable(id) {
+            body.blocks[id.index()] = MirBasicBlock {
+                instructions: Vec::new(),
+                terminator: MirTerminator::Return(MirOperand::Literal(HirLiteral::Null)),
+            };
+        }
+    }
+    let phis = insert_phis(body, &dominators);
+    Renamer::new(body, phis).rename(body, &dominators);
+}
+
+/// Inserts phis with `null` inputs and returns the register each phi of each
+/// block is for.
+fn insert_phis(body: &mut MirBody, dominators: &Dominators) -> Vec<Vec<usize>> {
+    let frontiers = dominators.frontiers(body);
+    let predecessors = body.predecessors();
+
+    // The blocks writing each register, and whether it is read anywhere it
+    // may not have been written in the same block.
+    let mut writers = vec![Vec::new(); body.locals.len()];
+    let mut global = vec![false; body.locals.len()];
+    for blocks in &mut writers[..body.args] {
+        blocks.push(BlockId::ENTRY);
+    }
+    for id in body.block_ids() {
+        let mut written = vec![false; body.locals.len()];
+        let block = body.block(id);
+        for instruction in &block.instructions {
+            for operand in instruction.kind.operands() {
+                if let Some(register) = operand.register().filter(|&register| !written[register]) {
+                    global[register] = true;
+                }
+            }
+            if let Some(dest) = instruction.dest.register() {
+                written[dest] = true;
+                if writers[dest].last() != Some(&id) {
+                    writers[dest].push(id);
+                }
+            }
+        }
+        if let Some(register) = block.terminator.operand().and_then(MirOperand::register) {
+            global[register] |= !written[register];
+        }
+    }
+
+    let mut phis: Vec<Vec<usize>> = vec![Vec::new(); body.blocks.len()];
+    for register in (0..body.locals.len()).filter(|&register| global[register]) {
+        let mut worklist = writers[register].clone();
+        let mut has_phi = vec![false; body.blocks.len()];
+        while let Some(id) = worklist.pop() {
+            for &frontier in &frontiers[id.index()] {
+                if has_phi[frontier.index()] {
+                    continue;
+                }
+                has_phi[frontier.index()] = true;
+                phis[frontier.index()].push(register);
+                if !writers[register].contains(&frontier) {
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+
+    for (index, registers) in phis.iter().enumerate() {
+        let inputs: Vec<(BlockId, MirOperand)> = predecessors[index]
+            .iter()
+            .map(|&pred| (pred, MirOperand::Literal(HirLiteral::Null)))
+            .collect();
+        let new_phis: Vec<MirInstruction> = registers
+            .iter()
+            .map(|&register| MirInstruction {
+                kind: MirInstructionKind::Phi(inputs.clone()),
+                dest: MirOperand::Register(register),
+                ty: body.locals[register].ty.clone(),
+            })
+            .collect();
+        body.blocks[index].instructions.splice(0..0, new_phis);
+    }
+    phis
+}
+
+/// Gives each write its own register while walking the dominator tree. The
+/// register a read sees is the latest version on the stack of its original.
+struct Renamer {
+    /// The original register of each phi of each block.
+    phis: Vec<Vec<usize>>,
+    stacks: Vec<Vec<usize>>,
+    /// Whether the original register already names a version.
+    used: Vec<bool>,
+}
+
+impl Renamer {
+    fn new(body: &MirBody, phis: Vec<Vec<usize>>) -> Self {
+        let mut renamer = Self {
+            phis,
+            stacks: vec![Vec::new(); body.locals.len()],
+            used: vec![false; body.locals.len()],
+        };
+        for register in 0..body.args {
+            renamer.used[register] = true;
+            renamer.stacks[register].push(register);
+        }
+        renamer
+    }
+
+    fn rename(&mut self, body: &mut MirBody, dominators: &Dominators) {
+        // The walk is iterative; `None` marks where a block's subtree ends
+        // and the versions it pushed are popped.
+        let mut pending = vec![Some(BlockId::ENTRY)];
+        let mut pushed: Vec<Vec<usize>> = Vec::new();
+        while let Some(next) = pending.pop() {
+            let Some(id) = next else {
+                for original in pushed.pop().unwrap_or_default() {
+                    self.stacks[original].pop();
+                }
+                continue;
+            };
+            pushed.push(self.rename_block(body, id));
+            pending.push(None);
+            pending.extend(dominators.children(id).iter().rev().map(|&child| Some(child)));
+        }
+    }
+
+    /// Renames the reads and writes of a block and fills in its successors'
+    /// phi inputs. Returns the originals of the versions it pushed.
+    fn rename_block(&mut self, body: &mut MirBody, id: BlockId) -> Vec<usize> {
+        let mut defined = Vec::new();
+        let mut instructions = std::mem::take(&mut body.blocks[id.index()].instructions);
+        for instruction in &mut instructions {
+            if !instruction.is_phi() {
+                for operand in instruction.kind.operands_mut() {
+                    self.rename_read(operand);
+                }
+            }
+            if let Some(original) = instruction.dest.register() {
+                let version = self.new_version(body, original);
+                instruction.dest = MirOperand::Register(version);
+                defined.push(original);
+            }
+        }
+        body.blocks[id.index()].instructions = instructions;
+        if let Some(operand) = body.blocks[id.index()].terminator.operand_mut() {
+            self.rename_read(operand);
+        }
+
+        for successor in body.successors(id) {
+            let phis = body.blocks[successor.index()].instructions.iter_mut();
+            for (phi, &original) in phis.zip(&self.phis[successor.index()]) {
+                let MirInstructionKind::Phi(inputs) = &mut phi.kind else {
+                    unreachable!("phis come first in their block");
+                };
+                for (pred, operand) in inputs.iter_mut() {
+                    if *pred == id {
+                        *operand = self.current(original);
+                    }
+                }
+            }
+        }
+        defined
+    }
+
+    fn rename_read(&self, operand: &mut MirOperand) {
+        if let Some(original) = operand.register() {
+            *operand = self.current(original);
+        }
+    }
+
+    fn current(&self, original: usize) -> MirOperand {
+        match self.stacks[original].last() {
+            Some(&version) => MirOperand::Register(version),
+            None => MirOperand::Literal(HirLiteral::Null),
+        }
+    }
+
+    fn new_version(&mut self, body: &mut MirBody, original: usize) -> usize {
+        let version = if self.used[original] {
+            body.locals.push(body.locals[original].clone());
+            self.stacks.push(Vec::new());
+            self.used.push(true);
+            body.locals.len() - 1
+        } else {
+            self.used[original] = true;
+            original
+        };
+        self.stacks[original].push(version);
+        version
+    }
+}
+
+/// Replaces the phis of a body with copies at the end of the predecessors.
+/// An edge from a block with several successors to one with phis is split
+/// first, so the copies only run when control takes that edge. The copies
+/// into one block happen at once, so they are ordered to read every value
+/// before it is overwritten, going through a new register when they form a
+/// cycle.
+pub fn out_of_ssa(body: &mut MirBody) {
+    for id in body.block_ids().collect::<Vec<_>>() {
+        let count = body.block(id).phis().len();
+        if count == 0 {
+            continue;
+        }
+        let phis: Vec<MirInstruction> = body.blocks[id.index()].instructions.drain(..count).collect();
+        for pred in body.predecessors()[id.index()].clone() {
+            let copies = phis
+                .iter()
+                .map(|phi| {
+                    let MirInstructionKind::Phi(inputs) = &phi.kind else {
+                        unreachable!("phis come first in their block");
+                    };
+                    let (_, value) = inputs
+                        .iter()
+                        .find(|(input, _)| *input == pred)
+                        .expect("a phi has an input for each predecessor");
+                    let dest = phi.dest.register().expect("phis write a register");
+                    (dest, value.clone(), phi.ty.clone())
+                })
+                .collect();
+            let target = if body.successors(pred).len() > 1 { split_edge(body, pred, id) } else { pred };
+            let copies = sequentialize(body, copies);
+            body.blocks[target.index()].instructions.extend(copies);
+        }
+    }
+}
+
+/// Puts a new block on the edge from `from` to `to` and returns it.
+fn split_edge(body: &mut MirBody, from: BlockId, to: BlockId) -> BlockId {
+    let middle = BlockId(body.blocks.len());
+    body.blocks.push(MirBasicBlock {
+        instructions: Vec::new(),
+        terminator: MirTerminator::Goto(to),
+    });
+    body.blocks[from.index()].terminator.retarget(to, middle);
+    middle
+}
+
+/// Orders copies that all happen at once, `dest <- value`, so that each
+/// value is read before its register is written.
+fn sequentialize(body: &mut MirBody, mut pending: Vec<(usize, MirOperand, Type)>) -> Vec<MirInstruction> {
+    pending.retain(|(dest, value, _)| value.register() != Some(*dest));
+    let mut copies = Vec::new();
+    while !pending.is_empty() {
+        let ready = (0..pending.len()).find(|&index| {
+            let dest = pending[index].0;
+            pending.iter().all(|(_, value, _)| value.register() != Some(dest))
+        });
+        match ready {
+            Some(index) => {
+                let (dest, value, ty) = pending.remove(index);
+                copies.push(copy(dest, value, ty));
+            }
+            None => {
+                // Every remaining destination is still to be read: save one
+                // of them and read the saved copy instead.
+                let (dest, ty) = (pending[0].0, pending[0].2.clone());
+                body.locals.push(MirLocal {
+                    ty: ty.clone(),
+                    name: None,
+                });
+                let saved = body.locals.len() - 1;
+                copies.push(copy(saved, MirOperand::Register(dest), ty));
+                for (_, value, _) in &mut pending {
+                    if value.register() == Some(dest) {
+                        *value = MirOperand::Register(saved);
+                    }
+                }
+            }
+        }
+    }
+    copies
+}
+
+fn copy(dest: usize, value: MirOperand, ty: Type) -> MirInstruction {
+    MirInstruction {
+        kind: MirInstructionKind::Load(value),
+        dest: MirOperand::Register(dest),
+        ty,
+    }
+}
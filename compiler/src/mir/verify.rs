@@ -0,0 +1,237 @@
+use crate::hir::hir::HirLiteral;
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{BlockId, MirBody, MirInstruction, MirInstructionKind, MirOperand, MirTerminator, MirUnaryOp};
+use crate::semantic::symbol_table::{IntType, Type};
+
+/// Checks that a body is well formed MIR in SSA form:
+///
+/// - every register read is written by exactly one instruction, or is an
+///   argument, and its definition dominates the read (for a phi input, the
+///   end of the block the input comes from);
+/// - phis come first in their block and have one input for each
+///   predecessor, and the entry block has no predecessors;
+/// - every jump goes to a block of the body, and branches test a `bool`;
+/// - operands and results have the types `MirInstruction::ty` says.
+///
+/// A body that fails is a compiler bug; the error names the first problem
+/// found and where.
+pub fn verify(body: &MirBody) -> Result<(), String> {
+    if body.blocks.is_empty() {
+        return Err("the body has no blocks".to_string());
+    }
+    let mut definitions: Vec<Option<(BlockId, usize)>> = vec![None; body.locals.len()];
+    for id in body.block_ids() {
+        let block = body.block(id);
+        let phis = block.phis().len();
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            let at = || format!("{}: instruction {}", id, index);
+            if instruction.is_phi() && index >= phis {
+                return Err(format!("{} is a phi after other instructions", at()));
+            }
+            let Some(dest) = instruction.dest.register() else {
+                return Err(format!("{} writes to a literal", at()));
+            };
+            if dest >= body.locals.len() {
+                return Err(format!("{} writes %{}, which is not a local", at(), dest));
+            }
+            if dest < body.args {
+                return Err(format!("{} writes the argument %{}", at(), dest));
+            }
+            if definitions[dest].replace((id, index)).is_some() {
+                return Err(format!("{} writes %{}, which is already written", at(), dest));
+            }
+            check_types(body, instruction).map_err(|error| format!("{}: {}", at(), error))?;
+        }
+        let terminator = &block.terminator;
+        for target in terminator.successors() {
+            if target.index() >= body.blocks.len() {
+                return Err(format!("{}: jumps to {}, which does not exist", id, target));
+            }
+        }
+        if let MirTerminator::Branch(condition, _, _) = terminator {
+            check_operand(body, condition)
+                .and_then(|ty| expect(ty.as_ref(), &Type::Boolean, "the condition"))
+                .map_err(|error| format!("{}: terminator: {}", id, error))?;
+        }
+    }
+
+    let predecessors = body.predecessors();
+    if !predecessors[BlockId::ENTRY.index()].is_empty() {
+        return Err(format!("{} is the entry block but is jumped to", BlockId::ENTRY));
+    }
+    let dominators = Dominators::new(body);
+    let dominates = |register: usize, id: BlockId, index: usize| -> Result<(), String> {
+        if register < body.args || !dominators.is_reachable(id) {
+            return Ok(());
+        }
+        match definitions[register] {
+            None => Err(format!("reads %{}, which is never written", register)),
+            Some((block, at)) if block == id && at < index => Ok(()),
+            Some((block, _)) if block != id && dominators.dominates(block, id) => Ok(()),
+            Some((block, at)) => {
+                Err(format!("reads %{} before it is written, by instruction {} of {}", register, at, block))
+            }
+        }
+    };
+    for id in body.block_ids() {
+        let block = body.block(id);
+        for (index, instruction) in block.instructions.iter().enumerate() {
+            let at = || format!("{}: instruction {}", id, index);
+            match &instruction.kind {
+                MirInstructionKind::Phi(inputs) => {
+                    let mut sources: Vec<BlockId> = inputs.iter().map(|(pred, _)| *pred).collect();
+                    sources.sort();
+                    let mut expected = predecessors[id.index()].clone();
+                    expected.sort();
+                    if sources != expected {
+                        return Err(format!(
+                            "{} has inputs from {} but the block's predecessors are {}",
+                            at(),
+                            blocks(&sources),
+                            blocks(&expected)
+                        ));
+                    }
+                    for (pred, value) in inputs {
+                        // The value only has to be available at the end of the block it comes from.
+                        let Some(register) = value.register() else {
+                            continue;
+                        };
+                        let end = body.block(*pred).instructions.len();
+                        dominates(register, *pred, end)
+                            .map_err(|error| format!("{}: input from {} {}", at(), pred, error))?;
+                    }
+                }
+                kind => {
+                    for register in kind.operands().into_iter().filter_map(MirOperand::register) {
+                        dominates(register, id, index).map_err(|error| format!("{} {}", at(), error))?;
+                    }
+                }
+            }
+        }
+        if let Some(register) = block.terminator.operand().and_then(MirOperand::register) {
+            dominates(register, id, block.instructions.len()).map_err(|error| format!("{}: terminator {}", id, error))?;
+        }
+    }
+    Ok(())
+}
+
+fn blocks(ids: &[BlockId]) -> String {
+    if ids.is_empty() {
+        return "none".to_string();
+    }
+    ids.iter().map(BlockId::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// Checks the operands an instruction reads and the register it writes
+/// against its type.
+fn check_types(body: &MirBody, instruction: &MirInstruction) -> Result<(), String> {
+    let ty = &instruction.ty;
+    match &instruction.kind {
+        MirInstructionKind::BinaryOp(_, left, right) => {
+            expect(check_operand(body, left)?.as_ref(), ty, "the left operand")?;
+            expect(check_operand(body, right)?.as_ref(), ty, "the right operand")?;
+        }
+        MirInstructionKind::UnaryOp(op, operand) => {
+            let expected = match op {
+                MirUnaryOp::Negate => ty,
+                MirUnaryOp::Not => &Type::Boolean,
+            };
+            expect(check_operand(body, operand)?.as_ref(), expected, "the operand")?;
+        }
+        MirInstructionKind::Load(operand) => expect(check_operand(body, operand)?.as_ref(), ty, "the operand")?,
+        MirInstructionKind::Cast(operand, from) => expect(check_operand(body, operand)?.as_ref(), from, "the operand")?,
+        MirInstructionKind::Phi(inputs) => {
+            for (pred, value) in inputs {
+                expect(check_operand(body, value)?.as_ref(), ty, &format!("the input from {}", pred))?;
+            }
+        }
+        MirInstructionKind::MakeAggregate(elements) => {
+            let slots = slot_types(ty).ok_or_else(|| format!("makes a value of type {}, which is not a struct or array", ty))?;
+            if slots.len() != elements.len() {
+                return Err(format!("makes a {} from {} values", ty, elements.len()));
+            }
+            for (index, (element, slot)) in elements.iter().zip(&slots).enumerate() {
+                expect(check_operand(body, element)?.as_ref(), slot, &format!("value {}", index))?;
+            }
+        }
+        MirInstructionKind::LoadField(object, index) => {
+            expect(Some(&field_type(body, object, *index)?), ty, "the field")?;
+        }
+        MirInstructionKind::StoreField(object, index, value) => {
+            let field = field_type(body, object, *index)?;
+            expect(check_operand(body, value)?.as_ref(), &field, "the value")?;
+        }
+        MirInstructionKind::LoadElement(array, index) => {
+            expect(Some(&element_type(body, array, index)?), ty, "the element")?;
+        }
+        MirInstructionKind::StoreElement(array, index, value) => {
+            let element = element_type(body, array, index)?;
+            expect(check_operand(body, value)?.as_ref(), &element, "the value")?;
+        }
+        kind => {
+            for operand in kind.operands() {
+                check_operand(body, operand)?;
+            }
+        }
+    }
+    let dest = instruction.dest.register().map(|register| &body.locals[register].ty);
+    expect(dest, &instruction.result_type(), "the destination")
+}
+
+/// The types of the values a struct or array is made of, in order.
+fn slot_types(ty: &Type) -> Option<Vec<Type>> {
+    match ty.representation() {
+        Type::Struct { fields } => Some(fields.iter().map(|(_, ty)| ty.clone()).collect()),
+        Type::Array { element, length } => Some(vec![(**element).clone(); *length as usize]),
+        _ => None,
+    }
+}
+
+fn field_type(body: &MirBody, object: &MirOperand, index: usize) -> Result<Type, String> {
+    let ty = check_operand(body, object)?.ok_or("reads a field of null")?;
+    match ty.representation() {
+        Type::Struct { fields } => match fields.get(index) {
+            Some((_, field)) => Ok(field.clone()),
+            None => Err(format!("reads field {} of {}, which has {} fields", index, ty, fields.len())),
+        },
+        _ => Err(format!("reads a field of {}, which is not a struct", ty)),
+    }
+}
+
+fn element_type(body: &MirBody, array: &MirOperand, index: &MirOperand) -> Result<Type, String> {
+    expect(check_operand(body, index)?.as_ref(), &Type::Integer(IntType::I64), "the index")?;
+    let ty = check_operand(body, array)?.ok_or("reads an element of null")?;
+    match ty.representation() {
+        Type::Array { element, .. } => Ok((**element).clone()),
+        _ => Err(format!("reads an element of {}, which is not an array", ty)),
+    }
+}
+
+/// The type of an operand, or `None` for `null`, which takes the type of
+/// wherever it goes.
+fn check_operand(body: &MirBody, operand: &MirOperand) -> Result<Option<Type>, String> {
+    match operand {
+        MirOperand::Register(register) => match body.locals.get(*register) {
+            Some(local) => Ok(Some(local.ty.clone())),
+            None => Err(format!("reads %{}, which is not a local", register)),
+        },
+        MirOperand::Literal(literal) => Ok(match literal {
+            HirLiteral::Integer(_, int) => Some(Type::Integer(*int)),
+            HirLiteral::Float(_, float) => Some(Type::Float(*float)),
+            HirLiteral::String(_) => Some(Type::String),
+            HirLiteral::Boolean(_) => Some(Type::Boolean),
+            HirLiteral::Null => None,
+        }),
+    }
+}
+
+/// Types match when they are represented alike, since `def` types only
+/// differ from their underlying types before lowering.
+fn expect(found: Option<&Type>, expected: &Type, what: &str) -> Result<(), String> {
+    match found {
+        Some(found) if found.representation() != expected.representation() => {
+            Err(format!("{} has type {} but {} was expected", what, found, expected))
+        }
+        _ => Ok(()),
+    }
+}
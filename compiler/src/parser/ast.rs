@@ -1,20 +1,503 @@
+use crate::diagnostics::Span;
 use crate::lexer::token::Token;
 
+/// Identifies an expression, statement or parameter of a parsed program.
+/// The parser numbers nodes in the order it finishes them, continuing
+/// through macro expansions, so ids are unique within one parse and
+/// stay the same when the same source is parsed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
 #[derive(Debug)]
 pub enum Expr {
+    Assign {
+        name: String,
+        value: Box<Expr>,
+        /// Where `name` is written.
+        span: Span,
+        id: NodeId,
+    },
     Binary {
         left: Box<Expr>,
         operator: Token,
         right: Box<Expr>,
+        /// Where the operator is written.
+        span: Span,
+        id: NodeId,
+    },
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+        id: NodeId,
+    },
+    /// `name += value` and the like; `operator` is the arithmetic operator
+    /// that `+=` stands for.
+    CompoundAssign {
+        name: String,
+        operator: Token,
+        value: Box<Expr>,
+        /// Where `name` is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `[a, b, c]`
+    ArrayLiteral {
+        elements: Vec<Expr>,
+        /// Where the `[` is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `expr as ty`
+    Cast {
+        expr: Box<Expr>,
+        ty: TypeExpr,
+        /// Where `as` is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `Name::Variant`
+    EnumVariant {
+        enum_name: String,
+        variant: String,
+        /// Where the variant is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `object.name`
+    Field {
+        object: Box<Expr>,
+        name: String,
+        /// Where the field name is written.
+        span: Span,
+        id: NodeId,
     },
     Grouping {
         expression: Box<Expr>,
+        /// Wraps the expansion of a macro call, or a pasted expression
+        /// fragment, rather than parentheses written in the source.
+        from_macro: bool,
+        id: NodeId,
+    },
+    /// `object[index]`
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        /// Where the `[` is written.
+        span: Span,
+        id: NodeId,
+    },
+    Lambda {
+        params: Vec<Param>,
+        return_type: Option<TypeExpr>,
+        body: Vec<Stmt>,
+        /// Where the `|`, `||` or `fn` that starts the lambda is written.
+        span: Span,
+        id: NodeId,
     },
     Literal {
         value: Token,
+        span: Span,
+        id: NodeId,
+    },
+    Logical {
+        left: Box<Expr>,
+        operator: Token,
+        right: Box<Expr>,
+        /// Where the operator is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `object.name = value`, or with `operator` set, `object.name += value`
+    /// and the like.
+    SetField {
+        object: Box<Expr>,
+        name: String,
+        operator: Option<Token>,
+        value: Box<Expr>,
+        /// Where the field name is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `object[index] = value`, or with `operator` set, `object[index] += value`
+    /// and the like.
+    SetIndex {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        operator: Option<Token>,
+        value: Box<Expr>,
+        /// Where the `[` is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `Name { field: value, ... }`
+    StructLiteral {
+        name: String,
+        fields: Vec<FieldInitializer>,
+        /// Where the struct name is written.
+        span: Span,
+        id: NodeId,
+    },
+    Ternary {
+        condition: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+        /// Where the `?` is written.
+        span: Span,
+        id: NodeId,
     },
     Unary {
         operator: Token,
         right: Box<Expr>,
+        /// Where the operator is written.
+        span: Span,
+        id: NodeId,
+    },
+    /// `++name`, `name++`, `--name` or `name--`; `operator` is `+` or `-`.
+    Update {
+        name: String,
+        operator: Token,
+        /// The prefix forms evaluate to the new value, the postfix ones to
+        /// the old.
+        prefix: bool,
+        /// Where `name` is written.
+        span: Span,
+        id: NodeId,
+    },
+    Variable {
+        name: String,
+        span: Span,
+        id: NodeId,
+    },
+}
+
+#[derive(Debug)]
+pub enum Stmt {
+    Block {
+        statements: Vec<Stmt>,
+        /// Where the `{` is written.
+        span: Span,
+        id: NodeId,
+    },
+    Expression {
+        expression: Expr,
+        id: NodeId,
+    },
+    /// `fn name(...)`, or `const fn name(...)` for a function that constant
+    /// expressions may call.
+    Function {
+        attributes: Vec<Attribute>,
+        is_const: bool,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        params: Vec<Param>,
+        return_type: Option<TypeExpr>,
+        body: Vec<Stmt>,
+        id: NodeId,
+    },
+    /// `const NAME: ty = expr;`, a value computed at compile time.
+    Const {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        ty: TypeExpr,
+        initializer: Expr,
+        id: NodeId,
+    },
+    /// `let name` binds immutably and `let mut name` reassignably.
+    Let {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        mutable: bool,
+        type_annotation: Option<TypeExpr>,
+        initializer: Option<Expr>,
+        id: NodeId,
+    },
+    Return {
+        value: Option<Expr>,
+        /// Where `return` is written; the other statements that start with
+        /// a keyword point at it too.
+        span: Span,
+        id: NodeId,
+    },
+    /// `if condition { ... } else { ... }`. An `else if` is an `If` that is
+    /// the only statement of the else branch.
+    If {
+        condition: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Option<Vec<Stmt>>,
+        span: Span,
+        id: NodeId,
+    },
+    While {
+        condition: Expr,
+        body: Vec<Stmt>,
+        span: Span,
+        id: NodeId,
+    },
+    /// `for initializer; condition; increment { ... }`, each clause optional.
+    /// The initializer's variables are scoped to the loop.
+    For {
+        initializer: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        increment: Option<Expr>,
+        body: Vec<Stmt>,
+        span: Span,
+        id: NodeId,
+    },
+    Break {
+        span: Span,
+        id: NodeId,
+    },
+    Continue {
+        span: Span,
+        id: NodeId,
+    },
+    /// `static [mut] NAME: ty = constant;`, a global stored in linear memory.
+    Static {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        mutable: bool,
+        type_annotation: Option<TypeExpr>,
+        initializer: Expr,
+        id: NodeId,
+    },
+    /// `alias Name<params> = ty;`
+    TypeAlias {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        params: Vec<String>,
+        ty: TypeExpr,
+        id: NodeId,
+    },
+    /// `def Name = ty;`
+    TypeDef {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        ty: TypeExpr,
+        id: NodeId,
+    },
+    /// `struct Name { field: ty, ... }`
+    Struct {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        fields: Vec<StructField>,
+        id: NodeId,
+    },
+    /// `enum Name { Variant, Variant = value, ... }`. Variants without a
+    /// value take the one after the previous variant's, starting at 0.
+    Enum {
+        attributes: Vec<Attribute>,
+        name: String,
+        /// Where the name is written.
+        span: Span,
+        variants: Vec<EnumVariant>,
+        id: NodeId,
+    },
+    /// `#if condition { ... } #else { ... }`: the statements of the branch a
+    /// constant condition selects, chosen before the program is analyzed.
+    Conditional {
+        condition: Expr,
+        then_branch: Vec<Stmt>,
+        else_branch: Vec<Stmt>,
+        /// Where the `#` is written.
+        span: Span,
+        id: NodeId,
+    },
+}
+
+#[derive(Debug)]
+pub struct StructField {
+    pub name: String,
+    /// Where the name is written.
+    pub span: Span,
+    pub ty: TypeExpr,
+}
+
+#[derive(Debug)]
+pub struct EnumVariant {
+    pub name: String,
+    /// Where the name is written.
+    pub span: Span,
+    pub value: Option<Expr>,
+}
+
+/// `name: value` in a struct literal.
+#[derive(Debug)]
+pub struct FieldInitializer {
+    pub name: String,
+    /// Where the name is written.
+    pub span: Span,
+    pub value: Expr,
+}
+
+/// An `@name` or `@name(arguments)` annotation on the declaration that follows it.
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub arguments: Vec<AttributeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeArgument {
+    Identifier(String),
+    String(String),
+}
+
+impl Expr {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expr::ArrayLiteral { id, .. }
+            | Expr::Assign { id, .. }
+            | Expr::Binary { id, .. }
+            | Expr::Call { id, .. }
+            | Expr::CompoundAssign { id, .. }
+            | Expr::Cast { id, .. }
+            | Expr::EnumVariant { id, .. }
+            | Expr::Field { id, .. }
+            | Expr::Grouping { id, .. }
+            | Expr::Index { id, .. }
+            | Expr::Lambda { id, .. }
+            | Expr::Literal { id, .. }
+            | Expr::Logical { id, .. }
+            | Expr::SetField { id, .. }
+            | Expr::SetIndex { id, .. }
+            | Expr::StructLiteral { id, .. }
+            | Expr::Ternary { id, .. }
+            | Expr::Unary { id, .. }
+            | Expr::Update { id, .. }
+            | Expr::Variable { id, .. } => *id,
+        }
+    }
+
+    /// Where diagnostics about the expression point: its operator, name or
+    /// literal, or for a call, its callee.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Call { callee, .. } => callee.span(),
+            Expr::Grouping { expression, .. } => expression.span(),
+            Expr::ArrayLiteral { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::CompoundAssign { span, .. }
+            | Expr::Cast { span, .. }
+            | Expr::EnumVariant { span, .. }
+            | Expr::Field { span, .. }
+            | Expr::Index { span, .. }
+            | Expr::Lambda { span, .. }
+            | Expr::Literal { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::SetField { span, .. }
+            | Expr::SetIndex { span, .. }
+            | Expr::StructLiteral { span, .. }
+            | Expr::Ternary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Update { span, .. }
+            | Expr::Variable { span, .. } => *span,
+        }
+    }
+}
+
+impl Stmt {
+    pub fn id(&self) -> NodeId {
+        match self {
+            Stmt::Block { id, .. }
+            | Stmt::Expression { id, .. }
+            | Stmt::Function { id, .. }
+            | Stmt::Const { id, .. }
+            | Stmt::Let { id, .. }
+            | Stmt::Return { id, .. }
+            | Stmt::If { id, .. }
+            | Stmt::While { id, .. }
+            | Stmt::For { id, .. }
+            | Stmt::Break { id, .. }
+            | Stmt::Continue { id, .. }
+            | Stmt::Static { id, .. }
+            | Stmt::TypeAlias { id, .. }
+            | Stmt::TypeDef { id, .. }
+            | Stmt::Struct { id, .. }
+            | Stmt::Enum { id, .. }
+            | Stmt::Conditional { id, .. } => *id,
+        }
+    }
+
+    /// Where diagnostics about the statement point: the name it declares,
+    /// the keyword it starts with, or its expression.
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Expression { expression, .. } => expression.span(),
+            Stmt::Block { span, .. }
+            | Stmt::Function { span, .. }
+            | Stmt::Const { span, .. }
+            | Stmt::Let { span, .. }
+            | Stmt::Return { span, .. }
+            | Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::For { span, .. }
+            | Stmt::Break { span, .. }
+            | Stmt::Continue { span, .. }
+            | Stmt::Static { span, .. }
+            | Stmt::TypeAlias { span, .. }
+            | Stmt::TypeDef { span, .. }
+            | Stmt::Struct { span, .. }
+            | Stmt::Enum { span, .. }
+            | Stmt::Conditional { span, .. } => *span,
+        }
+    }
+
+    /// Whether the statement declares an item rather than running code.
+    pub fn is_item(&self) -> bool {
+        matches!(
+            self,
+            Stmt::Function { .. }
+                | Stmt::Const { .. }
+                | Stmt::Static { .. }
+                | Stmt::TypeAlias { .. }
+                | Stmt::TypeDef { .. }
+                | Stmt::Struct { .. }
+                | Stmt::Enum { .. }
+                | Stmt::Conditional { .. }
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Param {
+    pub name: String,
+    /// Where the name is written.
+    pub span: Span,
+    pub mutable: bool,
+    pub type_annotation: Option<TypeExpr>,
+    pub id: NodeId,
+}
+
+/// A type as written in source, resolved to a `Type` during semantic analysis.
+#[derive(Debug, Clone)]
+pub enum TypeExpr {
+    Named(String),
+    /// `Name<args>`, a use of a generic alias.
+    Generic {
+        name: String,
+        args: Vec<TypeExpr>,
+    },
+    Function {
+        params: Vec<TypeExpr>,
+        return_type: Box<TypeExpr>,
+    },
+    /// `[element; length]`
+    Array {
+        element: Box<TypeExpr>,
+        length: u64,
     },
 }
@@ -0,0 +1,528 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lexer::token::{Operation, Punctuation, Reserved, Token, TokenInfo};
+
+/// Expansions nested deeper than this are reported as runaway recursion.
+pub const RECURSION_LIMIT: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FragmentKind {
+    Expr,
+    Ident,
+    Ty,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    ZeroOrMore, // *
+    OneOrMore,  // +
+    ZeroOrOne,  // ?
+}
+
+/// One element of the pattern a macro rule matches against its arguments.
+#[derive(Debug, Clone)]
+pub enum MacroPattern {
+    Token(Token),
+    /// `$name:kind`
+    Fragment { name: String, kind: FragmentKind },
+    /// `$( ... ) separator? repeat`
+    Repetition {
+        elements: Vec<MacroPattern>,
+        separator: Option<Token>,
+        repeat: Repeat,
+    },
+}
+
+/// One element of the tokens a macro rule expands to.
+#[derive(Debug, Clone)]
+pub enum MacroTemplate {
+    Token(TokenInfo),
+    /// `$name`
+    Fragment(String),
+    Repetition {
+        elements: Vec<MacroTemplate>,
+        separator: Option<TokenInfo>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroRule {
+    pub pattern: Vec<MacroPattern>,
+    pub template: Vec<MacroTemplate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroDefinition {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    pub rules: Vec<MacroRule>,
+}
+
+/// The macros defined so far, shared by a parser and the parsers it starts
+/// for expansions so that every expansion gets its own hygiene mark.
+#[derive(Debug, Clone, Default)]
+pub struct MacroTable {
+    pub definitions: HashMap<String, MacroDefinition>,
+    pub expansions: usize,
+}
+
+/// The tokens a pattern variable matched: one fragment, or one binding per
+/// iteration of the repetition it appears in.
+#[derive(Debug, Clone)]
+pub enum Binding {
+    Fragment { tokens: Vec<TokenInfo>, kind: FragmentKind },
+    Repeated(Vec<Binding>),
+}
+
+pub type Bindings = HashMap<String, Binding>;
+
+impl MacroRule {
+    pub fn new(pattern: &[TokenInfo], template: &[TokenInfo]) -> Result<Self, String> {
+        let pattern = parse_pattern(pattern)?;
+        let template = parse_template(template)?;
+
+        let mut names = Vec::new();
+        pattern_variables(&pattern, &mut names);
+        let mut seen = HashSet::new();
+        for name in &names {
+            if !seen.insert(name) {
+                return Err(format!("Macro pattern binds '${}' more than once", name));
+            }
+        }
+        let mut used = Vec::new();
+        template_variables(&template, &mut used);
+        if let Some(unbound) = used.iter().find(|name| !names.contains(name)) {
+            return Err(format!("Macro body uses '${}', which its pattern does not bind", unbound));
+        }
+
+        Ok(Self { pattern, template })
+    }
+}
+
+fn parse_pattern(tokens: &[TokenInfo]) -> Result<Vec<MacroPattern>, String> {
+    let mut elements = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if tokens[index].token != Token::Punctuation(Punctuation::Dollar) {
+            elements.push(MacroPattern::Token(tokens[index].token.clone()));
+            index += 1;
+            continue;
+        }
+        match tokens.get(index + 1).map(|token| &token.token) {
+            Some(Token::Identifier(name)) => {
+                let kind = match (tokens.get(index + 2).map(|t| &t.token), tokens.get(index + 3).map(|t| &t.token)) {
+                    (Some(Token::Punctuation(Punctuation::Colon)), Some(Token::Identifier(kind))) => match kind.as_str() {
+                        "expr" => FragmentKind::Expr,
+                        "ident" => FragmentKind::Ident,
+                        "ty" => FragmentKind::Ty,
+                        "block" => FragmentKind::Block,
+                        _ => return Err(format!("Unknown fragment kind '{}'; expected expr, ident, ty or block", kind)),
+                    },
+                    _ => return Err(format!("Expected ':' and a fragment kind after '${}'", name)),
+                };
+                elements.push(MacroPattern::Fragment { name: name.clone(), kind });
+                index += 4;
+            }
+            Some(Token::Punctuation(Punctuation::OpenParen)) => {
+                let close = matching_close(tokens, index + 1)?;
+                let inner = parse_pattern(&tokens[index + 2..close])?;
+                let (separator, repeat, next) = repetition_suffix(tokens, close + 1)?;
+                elements.push(MacroPattern::Repetition {
+                    elements: inner,
+                    separator: separator.map(|separator| separator.token),
+                    repeat,
+                });
+                index = next;
+            }
+            _ => return Err("Expected a fragment or '(' after '$' in macro pattern".to_string()),
+        }
+    }
+    Ok(elements)
+}
+
+fn parse_template(tokens: &[TokenInfo]) -> Result<Vec<MacroTemplate>, String> {
+    let mut elements = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        if tokens[index].token != Token::Punctuation(Punctuation::Dollar) {
+            elements.push(MacroTemplate::Token(tokens[index].clone()));
+            index += 1;
+            continue;
+        }
+        match tokens.get(index + 1).map(|token| &token.token) {
+            Some(Token::Identifier(name)) => {
+                elements.push(MacroTemplate::Fragment(name.clone()));
+                index += 2;
+            }
+            Some(Token::Punctuation(Punctuation::OpenParen)) => {
+                let close = matching_close(tokens, index + 1)?;
+                let inner = parse_template(&tokens[index + 2..close])?;
+                let (separator, _, next) = repetition_suffix(tokens, close + 1)?;
+                elements.push(MacroTemplate::Repetition {
+                    elements: inner,
+                    separator,
+                });
+                index = next;
+            }
+            _ => return Err("Expected a variable or '(' after '$' in macro body".to_string()),
+        }
+    }
+    Ok(elements)
+}
+
+/// Parses the optional separator and the `*`, `+` or `?` after `$( ... )`,
+/// returning them with the index of the token that follows.
+fn repetition_suffix(tokens: &[TokenInfo], index: usize) -> Result<(Option<TokenInfo>, Repeat, usize), String> {
+    let repeat = |token: &Token| match token {
+        Token::Operation(Operation::Multiply) => Some(Repeat::ZeroOrMore),
+        Token::Operation(Operation::Add) => Some(Repeat::OneOrMore),
+        Token::Punctuation(Punctuation::QuestionMark) => Some(Repeat::ZeroOrOne),
+        _ => None,
+    };
+    let missing = || "Expected '*', '+' or '?' after macro repetition".to_string();
+
+    let first = tokens.get(index).ok_or_else(missing)?;
+    if let Some(repeat) = repeat(&first.token) {
+        return Ok((None, repeat, index + 1));
+    }
+    let second = tokens.get(index + 1).ok_or_else(missing)?;
+    let repeat = repeat(&second.token).ok_or_else(missing)?;
+    Ok((Some(first.clone()), repeat, index + 2))
+}
+
+/// The index of the bracket closing the one at `open`.
+pub fn matching_close(tokens: &[TokenInfo], open: usize) -> Result<usize, String> {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token.token {
+            Token::Punctuation(Punctuation::OpenParen | Punctuation::OpenBrace | Punctuation::OpenBracket) => depth += 1,
+            Token::Punctuation(Punctuation::CloseParen | Punctuation::CloseBrace | Punctuation::CloseBracket) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("Unbalanced brackets in macro".to_string())
+}
+
+fn pattern_variables(pattern: &[MacroPattern], names: &mut Vec<String>) {
+    for element in pattern {
+        match element {
+            MacroPattern::Token(_) => {}
+            MacroPattern::Fragment { name, .. } => names.push(name.clone()),
+            MacroPattern::Repetition { elements, .. } => pattern_variables(elements, names),
+        }
+    }
+}
+
+fn template_variables(template: &[MacroTemplate], names: &mut Vec<String>) {
+    for element in template {
+        match element {
+            MacroTemplate::Token(_) => {}
+            MacroTemplate::Fragment(name) => names.push(name.clone()),
+            MacroTemplate::Repetition { elements, .. } => template_variables(elements, names),
+        }
+    }
+}
+
+/// Matches a whole invocation against a pattern. `measure` returns how many
+/// tokens at the start of a slice form a fragment of the given kind.
+pub fn match_pattern(
+    pattern: &[MacroPattern],
+    tokens: &[TokenInfo],
+    measure: &dyn Fn(&[TokenInfo], FragmentKind) -> Option<usize>,
+) -> Option<Bindings> {
+    let mut bindings = Bindings::new();
+    match match_sequence(pattern, tokens, 0, &mut bindings, measure) {
+        Some(end) if end == tokens.len() => Some(bindings),
+        _ => None,
+    }
+}
+
+fn match_sequence(
+    pattern: &[MacroPattern],
+    tokens: &[TokenInfo],
+    mut position: usize,
+    bindings: &mut Bindings,
+    measure: &dyn Fn(&[TokenInfo], FragmentKind) -> Option<usize>,
+) -> Option<usize> {
+    for element in pattern {
+        match element {
+            MacroPattern::Token(expected) => {
+                if tokens.get(position).map(|token| &token.token) != Some(expected) {
+                    return None;
+                }
+                position += 1;
+            }
+            MacroPattern::Fragment { name, kind } => {
+                let length = measure(&tokens[position..], *kind).filter(|&length| length > 0)?;
+                bindings.insert(
+                    name.clone(),
+                    Binding::Fragment {
+                        tokens: tokens[position..position + length].to_vec(),
+                        kind: *kind,
+                    },
+                );
+                position += length;
+            }
+            MacroPattern::Repetition {
+                elements,
+                separator,
+                repeat,
+            } => {
+                let mut iterations = Vec::new();
+                loop {
+                    let mut start = position;
+                    if let (Some(separator), false) = (separator, iterations.is_empty()) {
+                        if tokens.get(start).map(|token| &token.token) != Some(separator) {
+                            break;
+                        }
+                        start += 1;
+                    }
+                    let mut iteration = Bindings::new();
+                    match match_sequence(elements, tokens, start, &mut iteration, measure) {
+                        // An iteration that consumes nothing would repeat forever.
+                        Some(end) if end > start => {
+                            position = end;
+                            iterations.push(iteration);
+                        }
+                        _ => break,
+                    }
+                    if *repeat == Repeat::ZeroOrOne {
+                        break;
+                    }
+                }
+                if *repeat == Repeat::OneOrMore && iterations.is_empty() {
+                    return None;
+                }
+
+                let mut names = Vec::new();
+                pattern_variables(elements, &mut names);
+                for name in names {
+                    let values = iterations
+                        .iter_mut()
+                        .filter_map(|iteration| iteration.remove(&name))
+                        .collect();
+                    bindings.insert(name, Binding::Repeated(values));
+                }
+            }
+        }
+    }
+    Some(position)
+}
+
+/// Produces the tokens of a template, each marked with whether it was
+/// written in the macro definition rather than passed in by the caller.
+pub fn transcribe(
+    template: &[MacroTemplate],
+    bindings: &Bindings,
+    output: &mut Vec<(TokenInfo, bool)>,
+) -> Result<(), String> {
+    for element in template {
+        match element {
+            MacroTemplate::Token(token) => output.push((token.clone(), true)),
+            MacroTemplate::Fragment(name) => match bindings.get(name) {
+                Some(Binding::Fragment { tokens, kind }) => {
+                    // Expressions keep their grouping wherever they are pasted.
+                    let grouped = *kind == FragmentKind::Expr && tokens.len() > 1;
+                    if grouped {
+                        output.push((punctuation(&tokens[0], Punctuation::OpenParen, "("), true));
+                    }
+                    output.extend(tokens.iter().map(|token| (token.clone(), false)));
+                    if grouped {
+                        output.push((punctuation(&tokens[0], Punctuation::CloseParen, ")"), true));
+                    }
+                }
+                Some(Binding::Repeated(_)) => {
+                    return Err(format!("'${}' is repeated and must be used inside '$( ... )'", name))
+                }
+                None => return Err(format!("Macro body uses '${}', which its pattern does not bind", name)),
+            },
+            MacroTemplate::Repetition { elements, separator } => {
+                let mut names = Vec::new();
+                template_variables(elements, &mut names);
+                let mut count = None;
+                for name in &names {
+                    if let Some(Binding::Repeated(values)) = bindings.get(name) {
+                        match count {
+                            Some(count) if count != values.len() => {
+                                return Err(format!(
+                                    "Macro repetition mixes variables matched {} and {} times",
+                                    count,
+                                    values.len()
+                                ))
+                            }
+                            _ => count = Some(values.len()),
+                        }
+                    }
+                }
+                let count = count.ok_or_else(|| "Macro repetition contains no repeated variables".to_string())?;
+
+                for index in 0..count {
+                    if let (Some(separator), true) = (separator, index > 0) {
+                        output.push((separator.clone(), true));
+                    }
+                    let mut iteration = bindings.clone();
+                    for name in &names {
+                        if let Some(Binding::Repeated(values)) = bindings.get(name) {
+                            iteration.insert(name.clone(), values[index].clone());
+                        }
+                    }
+                    transcribe(elements, &iteration, output)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn punctuation(at: &TokenInfo, punctuation: Punctuation, lexeme: &str) -> TokenInfo {
+    TokenInfo {
+        synthesized: true,
+        ..TokenInfo::new(Token::Punctuation(punctuation), lexeme.to_string(), at.line, at.column)
+    }
+}
+
+/// A local the macro body declares, renamed wherever it is in scope.
+struct HygienicBinding {
+    name: String,
+    /// Delimiter depth the binding lives at; closing that block ends its scope.
+    depth: usize,
+    /// Whether a `;` or `,` at `depth` also ends the scope, as for the
+    /// parameters of a lambda whose body is a bare expression.
+    ends_at_separator: bool,
+}
+
+/// Renames the local variables a macro body declares so that they can
+/// neither capture nor shadow variables the caller passed in.
+///
+/// Only locals introduced by the definition itself are renamed: `let`
+/// bindings and lambda or function parameters. An occurrence is renamed only
+/// where that binding is in scope, so names the body refers to before its
+/// own `let` (or outside the lambda or function declaring them) still
+/// resolve at the call site.
+pub fn apply_hygiene(tokens: Vec<(TokenInfo, bool)>, mark: usize) -> Vec<TokenInfo> {
+    let mut rename = vec![false; tokens.len()];
+    let mut scopes: Vec<HygienicBinding> = Vec::new();
+    let mut depth = 0;
+    // `let` bindings come into scope at the `;` ending their statement, so
+    // that the initializer still sees any outer variable of the same name.
+    let mut pending_lets: Vec<(String, usize)> = Vec::new();
+    let mut pipe_params: Option<Vec<String>> = None;
+    // Parenthesis depth inside a `fn` parameter list, when in one.
+    let mut fn_params: Option<usize> = None;
+    let mut declared_fn_params: Vec<String> = Vec::new();
+    // Parameters of a `fn` whose body has not opened yet.
+    let mut waiting_fn_params: Option<Vec<String>> = None;
+    for (index, (token, from_definition)) in tokens.iter().enumerate() {
+        let mut previous = index.checked_sub(1).map(|previous| &tokens[previous].0.token);
+        // A binding marked `mut` declares the name all the same.
+        if previous == Some(&Token::Reserved(Reserved::Mut)) && matches!(token.token, Token::Identifier(_)) {
+            previous = index.checked_sub(2).map(|previous| &tokens[previous].0.token);
+        }
+        let next = tokens.get(index + 1).map(|(token, _)| &token.token);
+        // Parameter names directly follow the opening delimiter or a comma.
+        let starts_param = matches!(
+            previous,
+            Some(Token::Punctuation(Punctuation::Pipe | Punctuation::OpenParen | Punctuation::Comma))
+        );
+        match &token.token {
+            Token::Punctuation(Punctuation::Pipe) if fn_params.is_none() => match pipe_params.take() {
+                None => pipe_params = Some(Vec::new()),
+                Some(names) => {
+                    // A block body scopes the parameters to the block; a bare
+                    // expression body ends at the next separator.
+                    let block_body = next == Some(&Token::Punctuation(Punctuation::OpenBrace));
+                    for name in names {
+                        scopes.push(HygienicBinding {
+                            name,
+                            depth: if block_body { depth + 1 } else { depth },
+                            ends_at_separator: !block_body,
+                        });
+                    }
+                }
+            },
+            Token::Punctuation(Punctuation::OpenParen | Punctuation::OpenBrace | Punctuation::OpenBracket) => {
+                depth += 1;
+                if token.token == Token::Punctuation(Punctuation::OpenParen) {
+                    let after_fn = index >= 1 && tokens[index - 1].0.token == Token::Reserved(Reserved::Fn)
+                        || index >= 2 && tokens[index - 2].0.token == Token::Reserved(Reserved::Fn);
+                    fn_params = match fn_params {
+                        Some(params) => Some(params + 1),
+                        None if after_fn => Some(1),
+                        None => None,
+                    };
+                }
+                if token.token == Token::Punctuation(Punctuation::OpenBrace) {
+                    for name in waiting_fn_params.take().unwrap_or_default() {
+                        scopes.push(HygienicBinding { name, depth, ends_at_separator: false });
+                    }
+                }
+            }
+            Token::Punctuation(Punctuation::CloseParen | Punctuation::CloseBrace | Punctuation::CloseBracket) => {
+                if token.token == Token::Punctuation(Punctuation::CloseParen) {
+                    if fn_params == Some(1) {
+                        waiting_fn_params = Some(std::mem::take(&mut declared_fn_params));
+                    }
+                    fn_params = fn_params.and_then(|params| params.checked_sub(1)).filter(|&params| params > 0);
+                }
+                depth = depth.saturating_sub(1);
+                scopes.retain(|binding| binding.depth <= depth);
+                pending_lets.retain(|(_, at)| *at <= depth);
+            }
+            Token::Punctuation(Punctuation::Semicolon | Punctuation::Comma) => {
+                scopes.retain(|binding| !(binding.ends_at_separator && binding.depth == depth));
+                if token.token == Token::Punctuation(Punctuation::Semicolon) {
+                    let (ready, waiting) = pending_lets.drain(..).partition(|(_, at)| *at == depth);
+                    pending_lets = waiting;
+                    for (name, depth) in ready {
+                        scopes.push(HygienicBinding { name, depth, ends_at_separator: false });
+                    }
+                }
+            }
+            Token::Identifier(name) if *from_definition => {
+                let after_let = previous == Some(&Token::Reserved(Reserved::Let));
+                let pipe_param = pipe_params.is_some()
+                    && fn_params.is_none()
+                    && starts_param
+                    && matches!(
+                        next,
+                        Some(Token::Punctuation(Punctuation::Colon | Punctuation::Comma | Punctuation::Pipe))
+                    );
+                // Untyped `fn` parameters look like the parameters of a function type, so only
+                // annotated ones are recognised.
+                let fn_param =
+                    fn_params == Some(1) && starts_param && matches!(next, Some(Token::Punctuation(Punctuation::Colon)));
+                if after_let {
+                    pending_lets.push((name.clone(), depth));
+                } else if pipe_param {
+                    pipe_params.get_or_insert_with(Vec::new).push(name.clone());
+                } else if fn_param {
+                    declared_fn_params.push(name.clone());
+                }
+                rename[index] = after_let || pipe_param || fn_param || scopes.iter().any(|binding| binding.name == *name);
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+        .into_iter()
+        .zip(rename)
+        .map(|((mut token, _), rename)| {
+            if let Token::Identifier(name) = &token.token {
+                if rename {
+                    let renamed = format!("{}#{}", name, mark);
+                    token.lexeme = renamed.clone();
+                    token.token = Token::Identifier(renamed);
+                }
+            }
+            token
+        })
+        .collect()
+}
@@ -1,2 +1,3 @@
 pub mod ast;
+pub mod macros;
 pub mod pratt_parser;
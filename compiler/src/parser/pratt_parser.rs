@@ -1,39 +1,1032 @@
-use crate::lexer::token::{Token, TokenInfo};
-use crate::parser::ast::Expr;
+use crate::diagnostics::{closest_match, codes, did_you_mean, Diagnostic, DiagnosticSink, Fix, Span};
+use crate::lexer::token::{Operation, Punctuation, Reserved, Token, TokenInfo};
+use crate::parser::ast::{
+    Attribute, AttributeArgument, EnumVariant, Expr, FieldInitializer, NodeId, Param, Stmt, StructField, TypeExpr,
+};
+use crate::parser::macros::{
+    apply_hygiene, match_pattern, matching_close, transcribe, FragmentKind, MacroDefinition, MacroRule, MacroTable,
+    RECURSION_LIMIT,
+};
 
 #[derive(PartialEq, PartialOrd)]
 enum Precedence {
     None,
-    Assignment, // =
-    Or,         // or
-    And,        // and
+    Assignment, // = += -= *= /= %=
+    Ternary,    // ?:
+    Or,         // ||
+    And,        // &&
     Equality,   // == !=
     Comparison, // < > <= >=
     Term,       // + -
-    Factor,     // * /
-    Unary,      // ! -
-    Call,       // . ()
+    Factor,     // * / %
+    Cast,       // as
+    Unary,      // ! - ++ --
+    Call,       // . () [] and postfix ++ --
     Primary,
 }
 
+impl Precedence {
+    /// The next tighter level. The right operand of a left-associative
+    /// operator is parsed at it, so that `a - b - c` groups as `(a - b) - c`.
+    fn next(self) -> Precedence {
+        match self {
+            Precedence::None => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Ternary,
+            Precedence::Ternary => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Cast,
+            Precedence::Cast => Precedence::Unary,
+            Precedence::Unary => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Keywords that start a statement, which a misspelling turns into an
+/// identifier.
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "let", "fn", "return", "if", "else", "while", "for", "break", "continue", "const", "static", "alias", "def", "macro",
+    "struct", "enum",
+];
+
+/// The arithmetic operator a compound assignment such as `+=` applies.
+fn compound_operator(operation: &Operation) -> Option<Operation> {
+    match operation {
+        Operation::AddAssign => Some(Operation::Add),
+        Operation::SubtractAssign => Some(Operation::Subtract),
+        Operation::MultiplyAssign => Some(Operation::Multiply),
+        Operation::DivideAssign => Some(Operation::Divide),
+        Operation::ModuloAssign => Some(Operation::Modulo),
+        _ => None,
+    }
+}
+
 pub struct Parser {
     tokens: Vec<TokenInfo>,
     current: usize,
+    macros: MacroTable,
+    /// How many macro expansions enclose the tokens being parsed.
+    depth: usize,
+    /// The keyword a misspelled statement was probably meant to start
+    /// with, found while the current declaration failed to parse.
+    suggestion: Option<Fix>,
+    /// The id the next node parsed gets. Nested parsers for macro
+    /// expansions continue from it and hand it back.
+    next_id: u32,
+    /// The diagnostic for an error in a macro expansion, labelled at the
+    /// invocation that led to it. Only the outermost parser records one.
+    expansion_error: Option<Diagnostic>,
+    /// Set while parsing the condition of an `if`, `while` or `for`, where
+    /// `Name {` starts the body rather than a struct literal.
+    no_struct_literal: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<TokenInfo>) -> Self {
-        Self { tokens, current: 0 }
+        // Line breaks carry no meaning to the grammar.
+        let tokens = tokens
+            .into_iter()
+            .filter(|token_info| token_info.token != Token::Newline)
+            .collect();
+        Self {
+            tokens,
+            current: 0,
+            macros: MacroTable::default(),
+            depth: 0,
+            suggestion: None,
+            next_id: 0,
+            expansion_error: None,
+            no_struct_literal: false,
+        }
+    }
+
+    /// A parser for the tokens of a macro expansion or argument.
+    fn nested(mut tokens: Vec<TokenInfo>, macros: MacroTable, depth: usize, next_id: u32) -> Self {
+        let (line, column) = tokens.last().map_or((0, 0), |token| (token.line, token.column));
+        tokens.push(TokenInfo::new(Token::Eof, String::new(), line, column));
+        Self {
+            tokens,
+            current: 0,
+            macros,
+            depth,
+            suggestion: None,
+            next_id,
+            expansion_error: None,
+            no_struct_literal: false,
+        }
+    }
+
+    /// Parses the whole input. After a syntax error, parsing resumes at
+    /// the next declaration, so that every error is reported to `sink`; the
+    /// first one is returned.
+    pub fn parse(&mut self, sink: &mut DiagnosticSink) -> Result<Vec<Stmt>, Diagnostic> {
+        let mut statements = Vec::new();
+        let mut first_error = None;
+        while !self.is_at_end() {
+            match self.declarations() {
+                Ok(declarations) => statements.extend(declarations),
+                Err(message) => {
+                    let error = match self.expansion_error.take() {
+                        Some(error) if error.message == message => error,
+                        _ => self.syntax_error(message),
+                    };
+                    sink.emit(error.clone());
+                    first_error.get_or_insert(error);
+                    self.no_struct_literal = false;
+                    self.synchronize();
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(statements),
+        }
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        let id = NodeId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Parses declarations up to the end of the tokens, stopping at the
+    /// first error, as macro expansions are parsed.
+    fn statements(&mut self) -> Result<Vec<Stmt>, String> {
+        let mut statements = Vec::new();
+        while !self.is_at_end() {
+            statements.extend(self.declarations()?);
+        }
+        Ok(statements)
+    }
+
+    /// The diagnostic for a syntax error found at the current token.
+    fn syntax_error(&mut self, message: String) -> Diagnostic {
+        let token = self.peek();
+        let label = match token.token {
+            Token::Eof => "unexpected end of input".to_string(),
+            _ => format!("unexpected `{}`", token.lexeme),
+        };
+        let error = Diagnostic::error(message)
+            .with_code(codes::SYNTAX)
+            .with_label(token.span(), label);
+        match self.suggestion.take() {
+            Some(fix) => error.with_help(did_you_mean(&fix.replacement)).with_fix(fix),
+            None => error,
+        }
+    }
+
+    /// Skips the rest of a declaration that failed to parse: up to the `;`
+    /// that ends it or the `}` that closes the block it started in, or to
+    /// the next keyword that starts a declaration.
+    fn synchronize(&mut self) {
+        // How deep in braces the error is, relative to the declaration.
+        let mut depth = self.tokens[..self.current]
+            .iter()
+            .fold(0isize, |depth, token| match token.token {
+                Token::Punctuation(Punctuation::OpenBrace) => depth + 1,
+                Token::Punctuation(Punctuation::CloseBrace) => depth - 1,
+                _ => depth,
+            })
+            .max(0);
+        let mut skipped = false;
+        while !self.is_at_end() {
+            if depth == 0 && skipped && self.starts_declaration() {
+                return;
+            }
+            skipped = true;
+            match self.advance().token {
+                Token::Punctuation(Punctuation::Semicolon) if depth == 0 => return,
+                Token::Punctuation(Punctuation::OpenBrace) => depth += 1,
+                Token::Punctuation(Punctuation::CloseBrace) => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn starts_declaration(&self) -> bool {
+        matches!(
+            self.peek().token,
+            Token::Reserved(
+                Reserved::Let
+                    | Reserved::Fn
+                    | Reserved::Const
+                    | Reserved::Static
+                    | Reserved::TypeAlias
+                    | Reserved::TypeDef
+                    | Reserved::Struct
+                    | Reserved::Enum
+                    | Reserved::Macro
+            ) | Token::Punctuation(Punctuation::At | Punctuation::Hashtag)
+        )
+    }
+
+    /// Parses one declaration. A macro definition yields no statements, and
+    /// a macro invoked as a statement yields every statement it expands to.
+    fn declarations(&mut self) -> Result<Vec<Stmt>, String> {
+        if self.match_token(Token::Reserved(Reserved::Macro)) {
+            self.macro_definition()?;
+            return Ok(Vec::new());
+        }
+        if self.at_statement_macro() {
+            let name = self.advance().clone();
+            let arguments = self.macro_arguments()?;
+            let expansion = self.expand(&name, arguments)?;
+            let statements = self.parse_expansion(&name, expansion, |parser| parser.statements())?;
+            self.match_token(Token::Punctuation(Punctuation::Semicolon));
+            return Ok(statements);
+        }
+        Ok(vec![self.declaration()?])
+    }
+
+    fn declaration(&mut self) -> Result<Stmt, String> {
+        if self.match_directive(Reserved::If) {
+            return self.conditional();
+        }
+        let attributes = self.attributes()?;
+        // `fn name(...)` declares a function, while `fn(...)` starts a lambda expression.
+        if self.check(Token::Reserved(Reserved::Fn)) && matches!(self.peek_next().token, Token::Identifier(_)) {
+            self.advance();
+            return self.function(attributes, false);
+        }
+        if self.check(Token::Reserved(Reserved::Const)) && self.peek_next().token == Token::Reserved(Reserved::Fn) {
+            self.advance();
+            self.advance();
+            return self.function(attributes, true);
+        }
+        if self.match_token(Token::Reserved(Reserved::Let)) {
+            return self.let_statement(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::Static)) {
+            return self.static_item(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::Const)) {
+            return self.const_item(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::TypeAlias)) {
+            return self.type_alias(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::TypeDef)) {
+            return self.type_def(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::Struct)) {
+            return self.struct_item(attributes);
+        }
+        if self.match_token(Token::Reserved(Reserved::Enum)) {
+            return self.enum_item(attributes);
+        }
+        if !attributes.is_empty() {
+            return Err("Attributes must be followed by an item or let declaration.".to_string());
+        }
+        self.statement()
+    }
+
+    /// Parses `condition { ... }` after `#if`, with an optional `#else { ... }`
+    /// or `#else #if ...` after it.
+    fn conditional(&mut self) -> Result<Stmt, String> {
+        let span = self.tokens[self.current - 2].span();
+        let condition = self.condition()?;
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after #if condition.")?;
+        let then_branch = self.block()?;
+        let else_branch = if !self.match_directive(Reserved::Else) {
+            Vec::new()
+        } else if self.match_directive(Reserved::If) {
+            vec![self.conditional()?]
+        } else {
+            self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' or '#if' after '#else'.")?;
+            self.block()?
+        };
+        Ok(Stmt::Conditional {
+            condition,
+            then_branch,
+            else_branch,
+            span,
+            id: self.node_id(),
+        })
+    }
+
+    /// Consumes `#keyword` if it comes next.
+    fn match_directive(&mut self, keyword: Reserved) -> bool {
+        if self.check(Token::Punctuation(Punctuation::Hashtag)) && self.peek_next().token == Token::Reserved(keyword) {
+            self.advance();
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    /// Parses any `@name` or `@name(argument, ...)` annotations before a declaration.
+    fn attributes(&mut self) -> Result<Vec<Attribute>, String> {
+        let mut attributes = Vec::new();
+        while self.match_token(Token::Punctuation(Punctuation::At)) {
+            // Attribute names may coincide with keywords, as `@export` does.
+            let name = match &self.peek().token {
+                Token::Reserved(_) => self.advance().lexeme.clone(),
+                _ => self.identifier("Expect attribute name after '@'.")?,
+            };
+            let mut arguments = Vec::new();
+            if self.match_token(Token::Punctuation(Punctuation::OpenParen)) {
+                if !self.check(Token::Punctuation(Punctuation::CloseParen)) {
+                    loop {
+                        let argument = match &self.advance().token {
+                            Token::Identifier(name) => AttributeArgument::Identifier(name.clone()),
+                            Token::String(value) => AttributeArgument::String(value.clone()),
+                            other => return Err(format!("Expected name or string in attribute arguments, found {}", other.describe())),
+                        };
+                        arguments.push(argument);
+                        if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(Token::Punctuation(Punctuation::CloseParen), "Expect ')' after attribute arguments.")?;
+            }
+            attributes.push(Attribute { name, arguments });
+        }
+        Ok(attributes)
+    }
+
+    fn function(&mut self, attributes: Vec<Attribute>, is_const: bool) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect function name.")?;
+        self.consume(Token::Punctuation(Punctuation::OpenParen), "Expect '(' after function name.")?;
+        let params = self.parameters(Token::Punctuation(Punctuation::CloseParen))?;
+        let return_type = self.return_type()?;
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' before function body.")?;
+        let body = self.block()?;
+        Ok(Stmt::Function {
+            attributes,
+            is_const,
+            name,
+            span,
+            params,
+            return_type,
+            body,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `Name<params> = type;` after the `alias` keyword.
+    fn type_alias(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect alias name after 'alias'.")?;
+        let mut params = Vec::new();
+        if self.match_token(Token::Operation(Operation::Less)) {
+            loop {
+                params.push(self.identifier("Expect type parameter name.")?);
+                if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                    break;
+                }
+            }
+            self.consume(Token::Operation(Operation::Greater), "Expect '>' after type parameters.")?;
+        }
+        self.consume(Token::Operation(Operation::Assign), "Expect '=' after alias name.")?;
+        let ty = self.type_expr()?;
+        self.end_statement("Expect ';' after type alias.")?;
+        Ok(Stmt::TypeAlias {
+            attributes,
+            name,
+            span,
+            params,
+            ty,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `Name = type;` after the `def` keyword.
+    fn type_def(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect type name after 'def'.")?;
+        self.consume(Token::Operation(Operation::Assign), "Expect '=' after type name.")?;
+        let ty = self.type_expr()?;
+        self.end_statement("Expect ';' after type definition.")?;
+        Ok(Stmt::TypeDef {
+            attributes,
+            name,
+            span,
+            ty,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `Name { field: type, ... }` after the `struct` keyword.
+    fn struct_item(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect struct name after 'struct'.")?;
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after struct name.")?;
+        // Structs have fields so that `name {}` is never taken for a struct literal.
+        if self.check(Token::Punctuation(Punctuation::CloseBrace)) {
+            return Err("Expect at least one field in a struct.".to_string());
+        }
+        let mut fields = Vec::new();
+        while !self.check(Token::Punctuation(Punctuation::CloseBrace)) {
+            let span = self.peek().span();
+            let name = self.identifier("Expect field name.")?;
+            self.consume(Token::Punctuation(Punctuation::Colon), "Expect ':' and a type after field name.")?;
+            let ty = self.type_expr()?;
+            fields.push(StructField { name, span, ty });
+            if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                break;
+            }
+        }
+        self.consume(Token::Punctuation(Punctuation::CloseBrace), "Expect '}' after struct fields.")?;
+        Ok(Stmt::Struct {
+            attributes,
+            name,
+            span,
+            fields,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `Name { Variant [= value], ... }` after the `enum` keyword.
+    fn enum_item(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect enum name after 'enum'.")?;
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after enum name.")?;
+        let mut variants = Vec::new();
+        while !self.check(Token::Punctuation(Punctuation::CloseBrace)) {
+            let span = self.peek().span();
+            let name = self.identifier("Expect variant name.")?;
+            let value = if self.match_token(Token::Operation(Operation::Assign)) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            variants.push(EnumVariant { name, span, value });
+            if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                break;
+            }
+        }
+        self.consume(Token::Punctuation(Punctuation::CloseBrace), "Expect '}' after enum variants.")?;
+        Ok(Stmt::Enum {
+            attributes,
+            name,
+            span,
+            variants,
+            id: self.node_id(),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Stmt, String> {
+        if self.match_token(Token::Reserved(Reserved::Return)) {
+            return self.return_statement();
+        }
+        if self.match_token(Token::Reserved(Reserved::If)) {
+            return self.if_statement();
+        }
+        if self.match_token(Token::Reserved(Reserved::While)) {
+            let span = self.previous().span();
+            let condition = self.condition()?;
+            self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after while condition.")?;
+            let body = self.block()?;
+            return Ok(Stmt::While {
+                condition,
+                body,
+                span,
+                id: self.node_id(),
+            });
+        }
+        if self.match_token(Token::Reserved(Reserved::For)) {
+            return self.for_statement();
+        }
+        if self.match_token(Token::Reserved(Reserved::Break)) {
+            let span = self.previous().span();
+            self.end_statement("Expect ';' after 'break'.")?;
+            return Ok(Stmt::Break { span, id: self.node_id() });
+        }
+        if self.match_token(Token::Reserved(Reserved::Continue)) {
+            let span = self.previous().span();
+            self.end_statement("Expect ';' after 'continue'.")?;
+            return Ok(Stmt::Continue { span, id: self.node_id() });
+        }
+        if self.match_token(Token::Punctuation(Punctuation::OpenBrace)) {
+            let span = self.previous().span();
+            return Ok(Stmt::Block {
+                statements: self.block()?,
+                span,
+                id: self.node_id(),
+            });
+        }
+        self.expression_statement()
+    }
+
+    /// Parses `condition { ... } [else if ... | else { ... }]` after the `if` keyword.
+    fn if_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let condition = self.condition()?;
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after if condition.")?;
+        let then_branch = self.block()?;
+        let else_branch = if !self.match_token(Token::Reserved(Reserved::Else)) {
+            None
+        } else if self.match_token(Token::Reserved(Reserved::If)) {
+            Some(vec![self.if_statement()?])
+        } else {
+            self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' or 'if' after 'else'.")?;
+            Some(self.block()?)
+        };
+        Ok(Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+            span,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `[initializer]; [condition]; [increment] { ... }` after the `for` keyword.
+    fn for_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let initializer = if self.match_token(Token::Punctuation(Punctuation::Semicolon)) {
+            None
+        } else if self.match_token(Token::Reserved(Reserved::Let)) {
+            Some(Box::new(self.let_statement(Vec::new())?))
+        } else {
+            Some(Box::new(self.expression_statement()?))
+        };
+        let condition = if self.check(Token::Punctuation(Punctuation::Semicolon)) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(Token::Punctuation(Punctuation::Semicolon), "Expect ';' after for condition.")?;
+        let increment = if self.check(Token::Punctuation(Punctuation::OpenBrace)) {
+            None
+        } else {
+            Some(self.condition()?)
+        };
+        self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '{' after for clauses.")?;
+        let body = self.block()?;
+        Ok(Stmt::For {
+            initializer,
+            condition,
+            increment,
+            body,
+            span,
+            id: self.node_id(),
+        })
+    }
+
+    fn let_statement(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let mutable = self.match_token(Token::Reserved(Reserved::Mut));
+        let span = self.peek().span();
+        let name = self.identifier("Expect variable name.")?;
+        let type_annotation = if self.match_token(Token::Punctuation(Punctuation::Colon)) {
+            Some(self.type_expr()?)
+        } else {
+            None
+        };
+        let initializer = if self.match_token(Token::Operation(Operation::Assign)) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.end_statement("Expect ';' after variable declaration.")?;
+        Ok(Stmt::Let {
+            attributes,
+            name,
+            span,
+            mutable,
+            type_annotation,
+            initializer,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `[mut] NAME[: type] = constant;` after the `static` keyword.
+    fn static_item(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let mutable = self.match_token(Token::Reserved(Reserved::Mut));
+        let span = self.peek().span();
+        let name = self.identifier("Expect static name.")?;
+        let type_annotation = if self.match_token(Token::Punctuation(Punctuation::Colon)) {
+            Some(self.type_expr()?)
+        } else {
+            None
+        };
+        self.consume(Token::Operation(Operation::Assign), "Expect '=' after static name.")?;
+        let initializer = self.expression()?;
+        self.end_statement("Expect ';' after static declaration.")?;
+        Ok(Stmt::Static {
+            attributes,
+            name,
+            span,
+            mutable,
+            type_annotation,
+            initializer,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `NAME: type = expression;` after the `const` keyword.
+    fn const_item(&mut self, attributes: Vec<Attribute>) -> Result<Stmt, String> {
+        let span = self.peek().span();
+        let name = self.identifier("Expect constant name.")?;
+        self.consume(Token::Punctuation(Punctuation::Colon), "Expect ':' and a type after constant name.")?;
+        let ty = self.type_expr()?;
+        self.consume(Token::Operation(Operation::Assign), "Expect '=' after constant type.")?;
+        let initializer = self.expression()?;
+        self.end_statement("Expect ';' after constant declaration.")?;
+        Ok(Stmt::Const {
+            attributes,
+            name,
+            span,
+            ty,
+            initializer,
+            id: self.node_id(),
+        })
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, String> {
+        let span = self.previous().span();
+        let value = if self.check(Token::Punctuation(Punctuation::Semicolon)) || self.is_at_end() {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.end_statement("Expect ';' after return value.")?;
+        Ok(Stmt::Return {
+            value,
+            span,
+            id: self.node_id(),
+        })
     }
 
-    pub fn parse(&mut self) -> Result<Expr, String> {
-        self.expression()
+    fn expression_statement(&mut self) -> Result<Stmt, String> {
+        let start = self.current;
+        let expression = self.expression().map_err(|error| self.keyword_typo(start, error))?;
+        self.end_statement("Expect ';' after expression.")
+            .map_err(|error| self.keyword_typo(start, error))?;
+        Ok(Stmt::Expression {
+            expression,
+            id: self.node_id(),
+        })
+    }
+
+    /// Records a suggestion for an error right after a statement that is a
+    /// single identifier spelled like a keyword, as in `retrun x;`, where
+    /// the identifier was meant to start a statement.
+    ///
+    /// Short names are too close to too many keywords for a guess to be
+    /// worth applying with `--fix`, and a name the program declares is
+    /// taken to mean what it says.
+    fn keyword_typo(&mut self, start: usize, error: String) -> String {
+        let token = &self.tokens[start];
+        let Token::Identifier(name) = &token.token else {
+            return error;
+        };
+        if self.current != start + 1 || name.chars().count() < 3 || self.declares(name) {
+            return error;
+        }
+        if let Some(keyword) = closest_match(name, STATEMENT_KEYWORDS.iter().copied()) {
+            self.suggestion = Some(Fix::rename(token.span(), name, keyword));
+        }
+        error
+    }
+
+    /// Whether any declaration in the input introduces `name`: a variable,
+    /// function, static or constant, or a parameter or binding annotated
+    /// with a type.
+    fn declares(&self, name: &str) -> bool {
+        self.tokens.iter().enumerate().any(|(index, token)| {
+            if token.token != Token::Identifier(name.to_string()) {
+                return false;
+            }
+            let previous = index.checked_sub(1).map(|previous| &self.tokens[previous].token);
+            let next = self.tokens.get(index + 1).map(|next| &next.token);
+            matches!(
+                previous,
+                Some(Token::Reserved(Reserved::Let | Reserved::Mut | Reserved::Fn | Reserved::Static | Reserved::Const))
+            ) || next == Some(&Token::Punctuation(Punctuation::Colon))
+        })
+    }
+
+    /// Statements end with ';', which may be left off the last statement of the input.
+    fn end_statement(&mut self, message: &str) -> Result<(), String> {
+        if self.is_at_end() {
+            return Ok(());
+        }
+        self.consume(Token::Punctuation(Punctuation::Semicolon), message)?;
+        Ok(())
+    }
+
+    /// Parses the statements of a block whose '{' has already been consumed.
+    fn block(&mut self) -> Result<Vec<Stmt>, String> {
+        let mut statements = Vec::new();
+        while !self.check(Token::Punctuation(Punctuation::CloseBrace)) && !self.is_at_end() {
+            statements.extend(self.declarations()?);
+        }
+        self.consume(Token::Punctuation(Punctuation::CloseBrace), "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    /// Parses `[mut] name: type, ...` up to and including the closing token.
+    fn parameters(&mut self, close: Token) -> Result<Vec<Param>, String> {
+        let mut params = Vec::new();
+        if !self.check(close.clone()) {
+            loop {
+                let mutable = self.match_token(Token::Reserved(Reserved::Mut));
+                let span = self.peek().span();
+                let name = self.identifier("Expect parameter name.")?;
+                let type_annotation = if self.match_token(Token::Punctuation(Punctuation::Colon)) {
+                    Some(self.type_expr()?)
+                } else {
+                    None
+                };
+                params.push(Param {
+                    name,
+                    span,
+                    mutable,
+                    type_annotation,
+                    id: self.node_id(),
+                });
+                if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                    break;
+                }
+            }
+        }
+        self.consume(close, "Expect end of parameter list.")?;
+        Ok(params)
+    }
+
+    fn return_type(&mut self) -> Result<Option<TypeExpr>, String> {
+        if self.match_token(Token::Punctuation(Punctuation::Arrow)) {
+            Ok(Some(self.type_expr()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn type_expr(&mut self) -> Result<TypeExpr, String> {
+        let token = self.advance().clone();
+        match token.token {
+            Token::Identifier(name) if self.match_token(Token::Operation(Operation::Less)) => {
+                let mut args = Vec::new();
+                loop {
+                    args.push(self.type_expr()?);
+                    if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                        break;
+                    }
+                }
+                self.consume(Token::Operation(Operation::Greater), "Expect '>' after type arguments.")?;
+                Ok(TypeExpr::Generic { name, args })
+            }
+            Token::Identifier(name) => Ok(TypeExpr::Named(name)),
+            Token::Reserved(Reserved::Void) => Ok(TypeExpr::Named("void".to_string())),
+            Token::Punctuation(Punctuation::OpenBracket) => {
+                let element = self.type_expr()?;
+                self.consume(Token::Punctuation(Punctuation::Semicolon), "Expect ';' and a length after array element type.")?;
+                let length = match self.advance().token {
+                    Token::Integer(length) => length,
+                    ref other => return Err(format!("Expected array length, found {}", other.describe())),
+                };
+                self.consume(Token::Punctuation(Punctuation::CloseBracket), "Expect ']' after array length.")?;
+                Ok(TypeExpr::Array {
+                    element: Box::new(element),
+                    length,
+                })
+            }
+            Token::Reserved(Reserved::Fn) => {
+                self.consume(Token::Punctuation(Punctuation::OpenParen), "Expect '(' after 'fn' in function type.")?;
+                let mut params = Vec::new();
+                if !self.check(Token::Punctuation(Punctuation::CloseParen)) {
+                    loop {
+                        params.push(self.type_expr()?);
+                        if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                            break;
+                        }
+                    }
+                }
+                self.consume(Token::Punctuation(Punctuation::CloseParen), "Expect ')' after function type parameters.")?;
+                let return_type = self.return_type()?.unwrap_or_else(|| TypeExpr::Named("void".to_string()));
+                Ok(TypeExpr::Function {
+                    params,
+                    return_type: Box::new(return_type),
+                })
+            }
+            _ => Err(format!("Expected type, found {}", token.token.describe())),
+        }
+    }
+
+    fn identifier(&mut self, message: &str) -> Result<String, String> {
+        match &self.peek().token {
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(message.to_string()),
+        }
+    }
+
+    /// Parses `macro name(pattern) { body }` or `macro name { (pattern) => { body }, ... }`
+    /// after the `macro` keyword.
+    fn macro_definition(&mut self) -> Result<(), String> {
+        let (line, column) = (self.peek().line, self.peek().column);
+        let name = self.identifier("Expect macro name.")?;
+        let mut rules = Vec::new();
+        if self.check(Token::Punctuation(Punctuation::OpenParen)) {
+            let pattern = self.delimited()?;
+            let template = self.macro_body()?;
+            rules.push(MacroRule::new(&pattern, &template).map_err(|error| format!("In macro '{}': {}", name, error))?);
+        } else {
+            self.consume(Token::Punctuation(Punctuation::OpenBrace), "Expect '(' or '{' after macro name.")?;
+            while !self.check(Token::Punctuation(Punctuation::CloseBrace)) && !self.is_at_end() {
+                if !self.check(Token::Punctuation(Punctuation::OpenParen)) {
+                    return Err("Expect '(' before macro rule pattern.".to_string());
+                }
+                let pattern = self.delimited()?;
+                self.consume(Token::Punctuation(Punctuation::FatArrow), "Expect '=>' after macro rule pattern.")?;
+                let template = self.macro_body()?;
+                rules.push(MacroRule::new(&pattern, &template).map_err(|error| format!("In macro '{}': {}", name, error))?);
+                if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                    self.match_token(Token::Punctuation(Punctuation::Semicolon));
+                }
+            }
+            self.consume(Token::Punctuation(Punctuation::CloseBrace), "Expect '}' after macro rules.")?;
+            if rules.is_empty() {
+                return Err(format!("Macro '{}' has no rules", name));
+            }
+        }
+        self.match_token(Token::Punctuation(Punctuation::Semicolon));
+
+        self.macros.definitions.insert(
+            name.clone(),
+            MacroDefinition {
+                name,
+                line,
+                column,
+                rules,
+            },
+        );
+        Ok(())
+    }
+
+    fn macro_body(&mut self) -> Result<Vec<TokenInfo>, String> {
+        if !self.check(Token::Punctuation(Punctuation::OpenBrace)) {
+            return Err("Expect '{' before macro body.".to_string());
+        }
+        self.delimited()
+    }
+
+    /// Consumes a bracketed token tree starting at the current token and
+    /// returns the tokens between the brackets.
+    fn delimited(&mut self) -> Result<Vec<TokenInfo>, String> {
+        let open = self.current;
+        let close = matching_close(&self.tokens, open)?;
+        self.current = close + 1;
+        Ok(self.tokens[open + 1..close].to_vec())
+    }
+
+    /// Whether the input continues with a `name!(...)` invocation that is not
+    /// followed by an operator, and so stands as a statement of its own.
+    fn at_statement_macro(&self) -> bool {
+        let open = self.current + 2;
+        if !matches!(self.peek().token, Token::Identifier(_))
+            || self.peek_next().token != Token::Operation(Operation::Not)
+            || self.tokens.get(open).map(|token| &token.token) != Some(&Token::Punctuation(Punctuation::OpenParen))
+        {
+            return false;
+        }
+        match matching_close(&self.tokens, open) {
+            Ok(close) => self
+                .tokens
+                .get(close + 1)
+                .is_some_and(|token| self.get_precedence(&token.token) == Precedence::None),
+            Err(_) => false,
+        }
+    }
+
+    /// Consumes `!(arguments)` after a macro name and returns the argument tokens.
+    fn macro_arguments(&mut self) -> Result<Vec<TokenInfo>, String> {
+        self.consume(Token::Operation(Operation::Not), "Expect '!' after macro name.")?;
+        if !self.check(Token::Punctuation(Punctuation::OpenParen)) {
+            return Err("Expect '(' after '!' in macro invocation.".to_string());
+        }
+        self.delimited()
+    }
+
+    /// Matches an invocation against the rules of the macro it names and
+    /// returns the hygienic expansion of the first rule that matches.
+    fn expand(&mut self, name: &TokenInfo, arguments: Vec<TokenInfo>) -> Result<Vec<TokenInfo>, String> {
+        let definition = self
+            .macros
+            .definitions
+            .get(&name.lexeme)
+            .cloned()
+            .ok_or_else(|| format!("Undefined macro '{}' at line {}, column {}", name.lexeme, name.line, name.column))?;
+        if self.depth >= RECURSION_LIMIT {
+            let error = format!("Recursion limit of {} reached while expanding macro '{}'", RECURSION_LIMIT, definition.name);
+            return Err(self.in_expansion(name, error));
+        }
+
+        for rule in &definition.rules {
+            let Some(bindings) = match_pattern(&rule.pattern, &arguments, &|tokens, kind| self.fragment_length(tokens, kind))
+            else {
+                continue;
+            };
+            let mut output = Vec::new();
+            transcribe(&rule.template, &bindings, &mut output).map_err(|error| self.in_expansion(name, error))?;
+            let mark = self.macros.expansions;
+            self.macros.expansions += 1;
+            return Ok(apply_hygiene(output, mark));
+        }
+        let error = format!("No rule of macro '{}' matches the invocation", definition.name);
+        Err(self.in_expansion(name, error))
+    }
+
+    /// Parses the tokens of an expansion with `parse`, which must consume all of them.
+    fn parse_expansion<T>(
+        &mut self,
+        name: &TokenInfo,
+        tokens: Vec<TokenInfo>,
+        parse: impl FnOnce(&mut Parser) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut parser = Parser::nested(tokens, std::mem::take(&mut self.macros), self.depth + 1, self.next_id);
+        let result = parse(&mut parser).and_then(|value| {
+            if parser.is_at_end() {
+                Ok(value)
+            } else {
+                Err(format!("Unexpected {} in macro expansion", parser.peek().token.describe()))
+            }
+        });
+        self.macros = parser.macros;
+        self.next_id = parser.next_id;
+        result.map_err(|error| self.in_expansion(name, error))
+    }
+
+    /// Passes on an error found while expanding the invocation `name`. The
+    /// outermost parser also records its diagnostic, labelled at the
+    /// invocation in the source and at the macro's definition; nested
+    /// expansions leave that to it, so the note is added only once.
+    fn in_expansion(&mut self, name: &TokenInfo, error: String) -> String {
+        if self.depth > 0 {
+            return error;
+        }
+        let mut diagnostic = Diagnostic::error(error.clone())
+            .with_code(codes::SYNTAX)
+            .with_label(name.span(), "in this macro invocation");
+        if let Some(definition) = self.macros.definitions.get(&name.lexeme) {
+            let span = Span::new(definition.line, definition.column, definition.name.chars().count());
+            diagnostic = diagnostic.with_secondary_label(span, format!("macro '{}' defined here", definition.name));
+        }
+        self.expansion_error = Some(diagnostic.with_note(format!("in expansion of macro '{}'", name.lexeme)));
+        error
+    }
+
+    /// How many tokens at the start of `tokens` form a fragment of the given
+    /// kind, found by parsing them.
+    fn fragment_length(&self, tokens: &[TokenInfo], kind: FragmentKind) -> Option<usize> {
+        if tokens.is_empty() {
+            return None;
+        }
+        match kind {
+            FragmentKind::Ident => matches!(tokens.first()?.token, Token::Identifier(_)).then_some(1),
+            FragmentKind::Block => {
+                if tokens.first()?.token != Token::Punctuation(Punctuation::OpenBrace) {
+                    return None;
+                }
+                matching_close(tokens, 0).ok().map(|close| close + 1)
+            }
+            FragmentKind::Expr | FragmentKind::Ty => {
+                let mut parser = Parser::nested(tokens.to_vec(), self.macros.clone(), self.depth + 1, self.next_id);
+                let parsed = match kind {
+                    FragmentKind::Expr => parser.expression().map(|_| ()),
+                    _ => parser.type_expr().map(|_| ()),
+                };
+                parsed.ok().map(|_| parser.current)
+            }
+        }
     }
 
     fn expression(&mut self) -> Result<Expr, String> {
         self.parse_precedence(Precedence::Assignment)
     }
 
+    /// Parses an expression that a `{` starting a block follows.
+    fn condition(&mut self) -> Result<Expr, String> {
+        let restricted = std::mem::replace(&mut self.no_struct_literal, true);
+        let condition = self.expression();
+        self.no_struct_literal = restricted;
+        condition
+    }
+
+    /// Parses an expression inside brackets, where struct literals are
+    /// allowed again.
+    fn bracketed(&mut self) -> Result<Expr, String> {
+        let restricted = std::mem::replace(&mut self.no_struct_literal, false);
+        let expression = self.expression();
+        self.no_struct_literal = restricted;
+        expression
+    }
+
     fn parse_precedence(&mut self, precedence: Precedence) -> Result<Expr, String> {
         let mut left = self.prefix_rule()?;
 
@@ -45,37 +1038,319 @@ impl Parser {
     }
 
     fn prefix_rule(&mut self) -> Result<Expr, String> {
-        let token = self.advance();
+        let token = self.advance().clone();
         match &token.token {
             Token::Punctuation(crate::lexer::token::Punctuation::OpenParen) => self.grouping(),
             Token::Operation(crate::lexer::token::Operation::Subtract) | Token::Operation(crate::lexer::token::Operation::Not) => self.unary(),
-            Token::Number(_) | Token::String(_) | Token::Reserved(_) => self.literal(),
-            _ => Err(format!("Expected expression, found {:?}", token.token)),
+            Token::Operation(Operation::Increment | Operation::Decrement) => {
+                let operand = self.parse_precedence(Precedence::Unary)?;
+                self.update(&token.token, operand, true)
+            }
+            Token::Identifier(_)
+                if self.check(Token::Operation(Operation::Not))
+                    && self.peek_next().token == Token::Punctuation(Punctuation::OpenParen) =>
+            {
+                let name = token.clone();
+                let arguments = self.macro_arguments()?;
+                let expansion = self.expand(&name, arguments)?;
+                let expression = self.parse_expansion(&name, expansion, |parser| parser.expression())?;
+                Ok(Expr::Grouping {
+                    expression: Box::new(expression),
+                    from_macro: true,
+                    id: self.node_id(),
+                })
+            }
+            Token::Identifier(name)
+                if self.check(Token::Punctuation(Punctuation::Colon))
+                    && self.peek_next().token == Token::Punctuation(Punctuation::Colon) =>
+            {
+                self.advance();
+                self.advance();
+                let span = self.peek().span();
+                let variant = self.identifier("Expect variant name after '::'.")?;
+                Ok(Expr::EnumVariant {
+                    enum_name: name.clone(),
+                    variant,
+                    span,
+                    id: self.node_id(),
+                })
+            }
+            Token::Identifier(name) if !self.no_struct_literal && self.at_struct_literal() => {
+                self.struct_literal(name.clone(), token.span())
+            }
+            Token::Identifier(name) => Ok(Expr::Variable {
+                name: name.clone(),
+                span: token.span(),
+                id: self.node_id(),
+            }),
+            Token::Punctuation(Punctuation::Pipe) => self.lambda(Token::Punctuation(Punctuation::Pipe), token.span()),
+            Token::Operation(Operation::Or) => self.lambda_body(Vec::new(), token.span()),
+            Token::Reserved(Reserved::Fn) => {
+                self.consume(Token::Punctuation(Punctuation::OpenParen), "Expect '(' after 'fn'.")?;
+                self.lambda(Token::Punctuation(Punctuation::CloseParen), token.span())
+            }
+            Token::Integer(_) | Token::Number(_) | Token::String(_) | Token::Reserved(_) => self.literal(),
+            Token::Punctuation(Punctuation::OpenBracket) => {
+                let mut elements = Vec::new();
+                while !self.check(Token::Punctuation(Punctuation::CloseBracket)) {
+                    elements.push(self.bracketed()?);
+                    if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                        break;
+                    }
+                }
+                self.consume(Token::Punctuation(Punctuation::CloseBracket), "Expect ']' after array elements.")?;
+                Ok(Expr::ArrayLiteral {
+                    elements,
+                    span: token.span(),
+                    id: self.node_id(),
+                })
+            }
+            _ => {
+                // Leave the token for the error to point at.
+                self.current -= 1;
+                Err(format!("Expected expression, found {}", token.token.describe()))
+            }
         }
     }
 
     fn infix_rule(&mut self, left: Expr) -> Result<Expr, String> {
         let token = self.advance().clone();
         let precedence = self.get_precedence(&token.token);
-        let right = self.parse_precedence(precedence)?;
-        Ok(Expr::Binary {
-            left: Box::new(left),
-            operator: token.token,
-            right: Box::new(right),
+        match &token.token {
+            Token::Punctuation(crate::lexer::token::Punctuation::QuestionMark) => self.ternary(left, token.span()),
+            Token::Punctuation(Punctuation::OpenParen) => self.call(left),
+            Token::Punctuation(Punctuation::Dot) => {
+                let span = self.peek().span();
+                let name = self.identifier("Expect field name after '.'.")?;
+                Ok(Expr::Field {
+                    object: Box::new(left),
+                    name,
+                    span,
+                    id: self.node_id(),
+                })
+            }
+            Token::Punctuation(Punctuation::OpenBracket) => {
+                let index = self.bracketed()?;
+                self.consume(Token::Punctuation(Punctuation::CloseBracket), "Expect ']' after index.")?;
+                Ok(Expr::Index {
+                    object: Box::new(left),
+                    index: Box::new(index),
+                    span: token.span(),
+                    id: self.node_id(),
+                })
+            }
+            Token::Reserved(Reserved::As) => Ok(Expr::Cast {
+                expr: Box::new(left),
+                ty: self.type_expr()?,
+                span: token.span(),
+                id: self.node_id(),
+            }),
+            Token::Operation(Operation::Assign) => {
+                let value = self.parse_precedence(Precedence::Assignment)?;
+                match left {
+                    Expr::Variable { name, span, .. } => Ok(Expr::Assign {
+                        name,
+                        value: Box::new(value),
+                        span,
+                        id: self.node_id(),
+                    }),
+                    left => self.set(left, None, value),
+                }
+            }
+            Token::Operation(operation) if compound_operator(operation).is_some() => {
+                let value = self.parse_precedence(Precedence::Assignment)?;
+                let operator = Token::Operation(compound_operator(operation).unwrap());
+                match left {
+                    Expr::Variable { name, span, .. } => Ok(Expr::CompoundAssign {
+                        name,
+                        operator,
+                        value: Box::new(value),
+                        span,
+                        id: self.node_id(),
+                    }),
+                    left => self.set(left, Some(operator), value),
+                }
+            }
+            Token::Operation(Operation::Increment | Operation::Decrement) => self.update(&token.token, left, false),
+            Token::Operation(crate::lexer::token::Operation::And) | Token::Operation(crate::lexer::token::Operation::Or) => {
+                let right = self.parse_precedence(precedence.next())?;
+                Ok(Expr::Logical {
+                    left: Box::new(left),
+                    span: token.span(),
+                    operator: token.token,
+                    right: Box::new(right),
+                    id: self.node_id(),
+                })
+            }
+            _ => {
+                let right = self.parse_precedence(precedence.next())?;
+                Ok(Expr::Binary {
+                    left: Box::new(left),
+                    span: token.span(),
+                    operator: token.token,
+                    right: Box::new(right),
+                    id: self.node_id(),
+                })
+            }
+        }
+    }
+
+    fn ternary(&mut self, condition: Expr, span: Span) -> Result<Expr, String> {
+        // As in C, anything may appear between '?' and ':', while the else
+        // branch binds at ternary precedence so that nesting is right-associative.
+        let then_branch = self.expression()?;
+        self.consume(Token::Punctuation(crate::lexer::token::Punctuation::Colon), "Expect ':' after then branch of conditional expression.")?;
+        let else_branch = self.parse_precedence(Precedence::Ternary)?;
+        Ok(Expr::Ternary {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+            span,
+            id: self.node_id(),
+        })
+    }
+
+    /// Builds an assignment to a field or an element, or with `operator` set,
+    /// a compound assignment.
+    fn set(&mut self, target: Expr, operator: Option<Token>, value: Expr) -> Result<Expr, String> {
+        match target {
+            Expr::Field { object, name, span, .. } => Ok(Expr::SetField {
+                object,
+                name,
+                operator,
+                value: Box::new(value),
+                span,
+                id: self.node_id(),
+            }),
+            Expr::Index { object, index, span, .. } => Ok(Expr::SetIndex {
+                object,
+                index,
+                operator,
+                value: Box::new(value),
+                span,
+                id: self.node_id(),
+            }),
+            _ => Err("Invalid assignment target.".to_string()),
+        }
+    }
+
+    /// Whether the `{` after the identifier just consumed opens a struct
+    /// literal: it is followed by a field name and `:`.
+    fn at_struct_literal(&self) -> bool {
+        if !self.check(Token::Punctuation(Punctuation::OpenBrace)) {
+            return false;
+        }
+        let after = |offset: usize| &self.tokens[(self.current + offset).min(self.tokens.len() - 1)].token;
+        matches!(after(1), Token::Identifier(_)) && *after(2) == Token::Punctuation(Punctuation::Colon)
+    }
+
+    /// Parses `{ field: value, ... }` after the struct name.
+    fn struct_literal(&mut self, name: String, span: Span) -> Result<Expr, String> {
+        self.advance();
+        let mut fields = Vec::new();
+        while !self.check(Token::Punctuation(Punctuation::CloseBrace)) {
+            let span = self.peek().span();
+            let name = self.identifier("Expect field name.")?;
+            self.consume(Token::Punctuation(Punctuation::Colon), "Expect ':' after field name.")?;
+            let value = self.expression()?;
+            fields.push(FieldInitializer { name, span, value });
+            if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                break;
+            }
+        }
+        self.consume(Token::Punctuation(Punctuation::CloseBrace), "Expect '}' after struct fields.")?;
+        Ok(Expr::StructLiteral {
+            name,
+            fields,
+            span,
+            id: self.node_id(),
+        })
+    }
+
+    /// Builds `++operand` or `operand++` (or `--`), whose operand must be a variable.
+    fn update(&mut self, token: &Token, operand: Expr, prefix: bool) -> Result<Expr, String> {
+        let (operator, symbol) = match token {
+            Token::Operation(Operation::Increment) => (Operation::Add, "++"),
+            _ => (Operation::Subtract, "--"),
+        };
+        match operand {
+            Expr::Variable { name, span, .. } => Ok(Expr::Update {
+                name,
+                operator: Token::Operation(operator),
+                prefix,
+                span,
+                id: self.node_id(),
+            }),
+            _ => Err(format!("Operand of '{}' must be a variable.", symbol)),
+        }
+    }
+
+    fn call(&mut self, callee: Expr) -> Result<Expr, String> {
+        let mut arguments = Vec::new();
+        if !self.check(Token::Punctuation(Punctuation::CloseParen)) {
+            loop {
+                arguments.push(self.bracketed()?);
+                if !self.match_token(Token::Punctuation(Punctuation::Comma)) {
+                    break;
+                }
+            }
+        }
+        self.consume(Token::Punctuation(Punctuation::CloseParen), "Expect ')' after arguments.")?;
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            arguments,
+            id: self.node_id(),
+        })
+    }
+
+    /// Parses `|params| body` or `fn(params) => body`, after the opening token.
+    fn lambda(&mut self, close: Token, span: Span) -> Result<Expr, String> {
+        let params = self.parameters(close)?;
+        self.lambda_body(params, span)
+    }
+
+    fn lambda_body(&mut self, params: Vec<Param>, span: Span) -> Result<Expr, String> {
+        let return_type = self.return_type()?;
+        let body = if self.match_token(Token::Punctuation(Punctuation::OpenBrace)) {
+            let restricted = std::mem::replace(&mut self.no_struct_literal, false);
+            let body = self.block();
+            self.no_struct_literal = restricted;
+            body?
+        } else {
+            self.match_token(Token::Punctuation(Punctuation::FatArrow));
+            let value = self.expression()?;
+            vec![Stmt::Return {
+                value: Some(value),
+                span,
+                id: self.node_id(),
+            }]
+        };
+        Ok(Expr::Lambda {
+            params,
+            return_type,
+            body,
+            span,
+            id: self.node_id(),
         })
     }
 
     fn literal(&mut self) -> Result<Expr, String> {
         Ok(Expr::Literal {
             value: self.previous().token.clone(),
+            span: self.previous().span(),
+            id: self.node_id(),
         })
     }
 
     fn grouping(&mut self) -> Result<Expr, String> {
-        let expr = self.expression()?;
+        // Parentheses a macro expansion put around a pasted expression were never written.
+        let from_macro = self.previous().synthesized;
+        let expr = self.bracketed()?;
         self.consume(Token::Punctuation(crate::lexer::token::Punctuation::CloseParen), "Expect ')' after expression.")?;
         Ok(Expr::Grouping {
             expression: Box::new(expr),
+            from_macro,
+            id: self.node_id(),
         })
     }
 
@@ -83,8 +1358,10 @@ impl Parser {
         let operator = self.previous().clone();
         let right = self.parse_precedence(Precedence::Unary)?;
         Ok(Expr::Unary {
+            span: operator.span(),
             operator: operator.token,
             right: Box::new(right),
+            id: self.node_id(),
         })
     }
 
@@ -92,11 +1369,21 @@ impl Parser {
         match token {
             Token::Operation(op) => match op {
                 crate::lexer::token::Operation::Add | crate::lexer::token::Operation::Subtract => Precedence::Term,
-                crate::lexer::token::Operation::Multiply | crate::lexer::token::Operation::Divide => Precedence::Factor,
+                crate::lexer::token::Operation::Multiply
+                | crate::lexer::token::Operation::Divide
+                | crate::lexer::token::Operation::Modulo => Precedence::Factor,
                 crate::lexer::token::Operation::IfEqual | crate::lexer::token::Operation::NotEqual => Precedence::Equality,
                 crate::lexer::token::Operation::Greater | crate::lexer::token::Operation::GreaterEqual | crate::lexer::token::Operation::Less | crate::lexer::token::Operation::LessEqual => Precedence::Comparison,
+                crate::lexer::token::Operation::And => Precedence::And,
+                crate::lexer::token::Operation::Or => Precedence::Or,
+                crate::lexer::token::Operation::Assign => Precedence::Assignment,
+                crate::lexer::token::Operation::Increment | crate::lexer::token::Operation::Decrement => Precedence::Call,
+                operation if compound_operator(operation).is_some() => Precedence::Assignment,
                 _ => Precedence::None,
             },
+            Token::Punctuation(crate::lexer::token::Punctuation::QuestionMark) => Precedence::Ternary,
+            Token::Punctuation(Punctuation::OpenParen | Punctuation::Dot | Punctuation::OpenBracket) => Precedence::Call,
+            Token::Reserved(Reserved::As) => Precedence::Cast,
             _ => Precedence::None,
         }
     }
@@ -113,7 +1400,22 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        std::mem::discriminant(&self.peek().token) == std::mem::discriminant(&token_type)
+        // Tokens carrying source values match by kind, keywords and symbols exactly.
+        match (&self.peek().token, &token_type) {
+            (Token::Identifier(_), Token::Identifier(_))
+            | (Token::Integer(_), Token::Integer(_))
+            | (Token::Number(_), Token::Number(_))
+            | (Token::String(_), Token::String(_)) => true,
+            (token, expected) => token == expected,
+        }
+    }
+
+    fn match_token(&mut self, token_type: Token) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            return true;
+        }
+        false
     }
 
     fn advance(&mut self) -> &TokenInfo {
@@ -132,6 +1434,10 @@ impl Parser {
         &self.tokens[self.current]
     }
 
+    fn peek_next(&self) -> &TokenInfo {
+        &self.tokens[(self.current + 1).min(self.tokens.len() - 1)]
+    }
+
     fn previous(&self) -> &TokenInfo {
         &self.tokens[self.current - 1]
     }
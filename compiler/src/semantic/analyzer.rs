@@ -1,70 +1,2132 @@
-use crate::parser::ast::Expr;
-use crate::semantic::symbol_table::{SymbolTable, Type};
+use crate::diagnostics::{closest_match, codes, did_you_mean, Diagnostic, DiagnosticSink, Fix, Span};
+use crate::lexer::token::{Operation, Reserved, Token};
+use crate::parser::ast::{Attribute, EnumVariant, Expr, NodeId, Param, Stmt, StructField, TypeExpr};
+use crate::semantic::attributes::{AttributeTarget, Attributes};
+use crate::semantic::inference::{source_text, Inference, Mismatch, VariableKind};
+use crate::semantic::lints::{
+    at_level, Lint, LintLevel, LintLevels, DEPRECATED, FLOAT_EQUALITY, MUTABLE_STATICS, REDUNDANT_PARENS, SHADOWING,
+    UNUSED_FUNCTIONS, UNUSED_VARIABLES,
+};
+use crate::semantic::operators::{binary_typing, unary_typing, BinaryOperator, OperatorClass, UnaryOperator};
+use crate::semantic::symbol_table::{FloatType, IntType, ScopeId, Symbol, SymbolKind, SymbolTable, Type};
+use std::collections::{HashMap, HashSet};
 
+const BUILTIN_TYPES: &[&str] = &[
+    "int", "float", "bool", "string", "void", "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "isize", "usize",
+    "f32", "f64",
+];
+
+/// The name of the `bool` constant the condition of an `#if` is analyzed
+/// and evaluated as, which no program can declare.
+pub const CONDITION: &str = "#if";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptureMode {
+    ByValue,
+    ByReference,
+}
+
+/// A variable from an enclosing function used inside a lambda.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub name: String,
+    pub variable: usize,
+    pub ty: Type,
+    pub mode: CaptureMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariableInfo {
+    pub name: String,
+    /// Where the variable is declared.
+    pub span: Span,
+    pub ty: Type,
+    /// Declared with `let mut` or as a `mut` parameter.
+    pub mutable: bool,
+    /// Declared without a value, as in `let x;`, so that even an immutable
+    /// one is assigned once, which flow checking makes sure of.
+    pub deferred: bool,
+    /// Assigned to after its declaration.
+    pub reassigned: bool,
+    /// Read after its declaration; assignments alone do not count.
+    pub used: bool,
+    /// Used inside a lambda nested in the declaring function.
+    pub captured: bool,
+    pub attributes: Attributes,
+    pub scope: ScopeId,
+}
+
+impl VariableInfo {
+    /// Captured variables that are reassigned are shared through a heap cell
+    /// so that the declaring function and every closure see the same value.
+    pub fn is_boxed(&self) -> bool {
+        self.captured && self.reassigned
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StaticInfo {
+    pub name: String,
+    pub ty: Type,
+    pub mutable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConstInfo {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructInfo {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumInfo {
+    pub name: String,
+    /// Each variant with the `const` holding its discriminant, which is
+    /// named `Enum::Variant`.
+    pub variants: Vec<(String, usize)>,
+}
+
+/// The declaration an identifier refers to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolution {
+    pub symbol: SymbolKind,
+    /// The scope the declaration belongs to.
+    pub scope: ScopeId,
+    /// Where the identifier is written.
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct LambdaInfo {
+    pub ty: Type,
+    captures: Vec<usize>,
+    /// Where the lambda starts.
+    pub span: Span,
+}
+
+enum TypeDeclaration {
+    Alias { params: Vec<String>, ty: TypeExpr },
+    Def { ty: TypeExpr },
+    Struct { fields: Vec<(String, TypeExpr)> },
+    Enum { variants: Vec<String> },
+}
+
+/// A check that waits until the types it involves are inferred.
+/// `span` is where errors about the check point.
+enum DeferredCheck {
+    Literal { value: u64, negative: bool, ty: Type, span: Span },
+    Binary {
+        op: BinaryOperator,
+        left: Type,
+        right: Type,
+        /// The level of `float_equality` where the operator is written.
+        float_equality: LintLevel,
+        span: Span,
+    },
+    Unary { op: UnaryOperator, operand: Type, span: Span },
+}
+
+struct FunctionContext {
+    /// `None` until the first `return` of a lambda without a declared return type.
+    return_type: Option<Type>,
+    lambda: Option<usize>,
+    top_level: bool,
+    /// Name of the `const fn` being analyzed, whose body must be evaluable
+    /// at compile time.
+    const_function: Option<String>,
+    /// The function item being analyzed, whose uses inside its own body do
+    /// not count as uses.
+    item: Option<usize>,
+    /// Attributes of the function item, which set lint levels for its body
+    /// and for the lambdas in it.
+    attributes: Attributes,
+}
+
+/// Variables, lambdas and function items are numbered in the order the
+/// analyzer declares them; HIR lowering walks the AST in the same order.
 pub struct SemanticAnalyzer {
     pub symbol_table: SymbolTable,
+    pub variables: Vec<VariableInfo>,
+    pub lambdas: Vec<LambdaInfo>,
+    pub functions: Vec<Type>,
+    pub function_attributes: Vec<Attributes>,
+    /// Whether each function item is a `const fn`.
+    pub const_functions: Vec<bool>,
+    pub consts: Vec<ConstInfo>,
+    pub statics: Vec<StaticInfo>,
+    /// Struct and enum items, in declaration order.
+    pub structs: Vec<StructInfo>,
+    pub enums: Vec<EnumInfo>,
+    /// Lint levels for the whole program, which attributes override.
+    pub lint_levels: LintLevels,
+    /// Lints that fired and errors the analysis went on after, in the order
+    /// they were found, until `analyze_program` emits them.
+    diagnostics: Vec<Diagnostic>,
+    /// Whether each function item is used outside its own body.
+    used_functions: Vec<bool>,
+    /// Functions the conditions of `#if`s call, which count as used.
+    pub called_by_conditions: Vec<String>,
+    type_declarations: HashMap<String, TypeDeclaration>,
+    /// The type of every expression analyzed, which HIR lowering takes as is.
+    expr_types: HashMap<NodeId, Type>,
+    /// Calls that convert to a type rather than call a function.
+    conversions: HashSet<NodeId>,
+    /// The declaration each `Variable` and `Assign` expression refers to.
+    resolutions: HashMap<NodeId, Resolution>,
+    /// Names of variables a scope declares further down, which cannot be
+    /// used before then even if an enclosing scope declares them too.
+    undeclared: HashMap<ScopeId, HashSet<String>>,
+    inference: Inference,
+    deferred: Vec<DeferredCheck>,
+    contexts: Vec<FunctionContext>,
 }
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
         Self {
-            symbol_table: SymbolTable::new(None),
+            symbol_table: SymbolTable::new(),
+            variables: Vec::new(),
+            lambdas: Vec::new(),
+            functions: Vec::new(),
+            function_attributes: Vec::new(),
+            const_functions: Vec::new(),
+            consts: Vec::new(),
+            statics: Vec::new(),
+            structs: Vec::new(),
+            enums: Vec::new(),
+            lint_levels: LintLevels::new(),
+            diagnostics: Vec::new(),
+            used_functions: Vec::new(),
+            called_by_conditions: Vec::new(),
+            type_declarations: HashMap::new(),
+            expr_types: HashMap::new(),
+            conversions: HashSet::new(),
+            resolutions: HashMap::new(),
+            undeclared: HashMap::new(),
+            inference: Inference::new(),
+            deferred: Vec::new(),
+            contexts: Vec::new(),
         }
     }
 
-    pub fn analyze(&mut self, expr: &Expr) -> Result<Type, String> {
-        match expr {
-            Expr::Binary { left, operator, right } => {
-                let left_type = self.analyze(left)?;
-                let right_type = self.analyze(right)?;
+    /// Checks a whole program and returns the type of its result, which is
+    /// the value of a trailing top-level expression statement, if any. Every
+    /// error and lint is reported to `sink`, and the first error is returned.
+    pub fn analyze_program(&mut self, statements: &[Stmt], sink: &mut DiagnosticSink) -> Result<Type, Diagnostic> {
+        let result = self.check_program(statements);
+        if let Err(error) = &result {
+            self.diagnostics.push(error.clone().or_code(codes::SEMANTIC));
+        }
+        let mut first_error = None;
+        for diagnostic in std::mem::take(&mut self.diagnostics) {
+            if diagnostic.is_error() {
+                first_error.get_or_insert_with(|| diagnostic.clone());
+            }
+            sink.emit(diagnostic);
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+
+    fn check_program(&mut self, statements: &[Stmt]) -> Result<Type, Diagnostic> {
+        for statement in statements {
+            check_item_attributes(statement).map_err(|error| error.or_label(statement.span()))?;
+        }
+        for statement in statements {
+            let (name, span, declaration) = match statement {
+                Stmt::TypeAlias { name, span, params, ty, .. } => (
+                    name,
+                    *span,
+                    TypeDeclaration::Alias {
+                        params: params.clone(),
+                        ty: ty.clone(),
+                    },
+                ),
+                Stmt::TypeDef { name, span, ty, .. } => (name, *span, TypeDeclaration::Def { ty: ty.clone() }),
+                Stmt::Struct { name, span, fields, .. } => (name, *span, struct_declaration(fields)?),
+                Stmt::Enum { name, span, variants, .. } => (name, *span, enum_declaration(variants)?),
+                _ => continue,
+            };
+            if BUILTIN_TYPES.contains(&name.as_str()) {
+                return Err(Diagnostic::from(format!("Cannot redefine built-in type '{}'", name)).with_label(span, ""));
+            }
+            if self.type_declarations.insert(name.clone(), declaration).is_some() {
+                return Err(Diagnostic::from(format!("Type '{}' is already defined", name)).with_label(span, ""));
+            }
+        }
+        self.check_type_declarations(statements)?;
+        for statement in statements {
+            match statement {
+                Stmt::Struct { name, .. } => {
+                    let ty = self.resolve_type(&TypeExpr::Named(name.clone()))?;
+                    let fields = struct_fields(&ty).expect("a struct item declares a struct type").to_vec();
+                    self.structs.push(StructInfo { name: name.clone(), fields });
+                }
+                _ => {}
+            }
+        }
+
+        let mut has_main = false;
+        for statement in statements {
+            if let Stmt::Function { attributes, is_const, name, span, params, return_type, .. } = statement {
+                let at_name = |error: Diagnostic| error.or_label(*span);
+                if self.symbol_table.contains_local(name) {
+                    return Err(at_name(format!("Function '{}' is already defined", name).into()));
+                }
+                has_main |= name == "main";
+                let ty = self.function_type(params, return_type).map_err(at_name)?;
+                let attributes = self.function_attributes(name, params, attributes).map_err(at_name)?;
+                self.symbol_table.insert(name.clone(), ty.clone(), SymbolKind::Function { id: self.functions.len() });
+                self.functions.push(ty);
+                self.function_attributes.push(attributes);
+                self.const_functions.push(*is_const);
+                self.used_functions.push(false);
+            }
+        }
+        if let Some(statement) = statements.iter().find(|statement| !statement.is_item()).filter(|_| has_main) {
+            let error = Diagnostic::from("Top-level statements cannot be combined with a 'main' function".to_string());
+            return Err(error.with_label(statement.span(), ""));
+        }
 
-                if left_type != right_type {
+        self.contexts.push(FunctionContext {
+            return_type: None,
+            lambda: None,
+            top_level: true,
+            const_function: None,
+            item: None,
+            attributes: Attributes::default(),
+        });
+        // Constants may refer to each other in any order, so all of them are
+        // declared before any initializer is checked. The discriminants of
+        // enum variants are constants too, in the order they are declared.
+        for statement in statements {
+            match statement {
+                Stmt::Const { name, span, ty, .. } => {
+                    if self.symbol_table.contains_local(name) {
+                        let error = format!("'{}' is already defined in this scope", name);
+                        return Err(Diagnostic::from(error).with_label(*span, ""));
+                    }
+                    let ty = self.resolve_type(ty).map_err(|error| error.or_label(*span))?;
+                    let id = self.consts.len();
+                    self.consts.push(ConstInfo {
+                        name: name.clone(),
+                        ty: ty.clone(),
+                    });
+                    self.symbol_table.insert(name.clone(), ty, SymbolKind::Const { id });
+                }
+                Stmt::Enum { name, variants, .. } => {
+                    let ty = self.resolve_type(&TypeExpr::Named(name.clone()))?;
+                    let mut ids = Vec::new();
+                    for variant in variants {
+                        ids.push((variant.name.clone(), self.consts.len()));
+                        self.consts.push(ConstInfo {
+                            name: format!("{}::{}", name, variant.name),
+                            ty: ty.clone(),
+                        });
+                    }
+                    self.enums.push(EnumInfo { name: name.clone(), variants: ids });
+                }
+                _ => {}
+            }
+        }
+        for statement in statements {
+            if let Stmt::Static { name, span, mutable, type_annotation, initializer, .. } = statement {
+                self.declare_static(name, *mutable, type_annotation, initializer)
+                    .map_err(|error| error.or_label(*span))?;
+            }
+        }
+        let mut next_const = 0;
+        for statement in statements {
+            match statement {
+                Stmt::Const { name, initializer, .. } if name == CONDITION => {
+                    next_const += 1;
+                    self.check_constant(initializer, "the '#if' condition")
+                        .and_then(|_| self.analyze_condition(initializer, "#if"))
+                        .map_err(|error| error.or_label(initializer.span()))?;
+                }
+                Stmt::Const { name, span, initializer, .. } => {
+                    let ty = self.consts[next_const].ty.clone();
+                    next_const += 1;
+                    self.check_const(name, &ty, initializer).map_err(|error| error.or_label(*span))?;
+                }
+                Stmt::Enum { name, variants, .. } => {
+                    next_const += variants.len();
+                    for variant in variants {
+                        self.check_discriminant(name, variant).map_err(|error| error.or_label(variant.span))?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.declare_later(statements);
+        let mut result_type = Type::Void;
+        for statement in statements {
+            let result = match statement {
+                Stmt::Expression { expression, .. } => self.analyze(expression),
+                // Hoisted above.
+                Stmt::Const { .. }
+                | Stmt::Static { .. }
+                | Stmt::TypeAlias { .. }
+                | Stmt::TypeDef { .. }
+                | Stmt::Struct { .. }
+                | Stmt::Enum { .. } => Ok(Type::Void),
+                _ => self.analyze_stmt(statement).map(|_| Type::Void),
+            };
+            match result {
+                Ok(ty) => result_type = ty,
+                Err(error) => {
+                    // Go on with the next statement, so that its errors are
+                    // reported too.
+                    self.diagnostics.push(error.or_code(codes::SEMANTIC));
+                    self.recover();
+                    self.declare_failed(statement);
+                }
+            }
+        }
+        if self.diagnostics.iter().any(Diagnostic::is_error) {
+            // Inference would only add errors that follow from these;
+            // `analyze_program` returns the first of them.
+            return Ok(Type::Void);
+        }
+        self.lint_unused_variables(ScopeId::ROOT);
+        self.contexts.pop();
+        self.finish_inference()?;
+        self.lint_unused_functions(statements);
+        Ok(self.inference.resolve(&result_type))
+    }
+
+    fn check_const(&mut self, name: &str, ty: &Type, initializer: &Expr) -> Result<(), Diagnostic> {
+        self.check_constant(initializer, &format!("constant '{}'", name))?;
+        let initializer_type = self.analyze_expr(initializer, Some(ty))?;
+        self.expect_type(ty, &initializer_type, &source_text(initializer), |ty, found| {
+            format!(
+                "Type mismatch: constant '{}' is declared as {} but initialized with {}",
+                name, ty, found
+            )
+        })
+    }
+
+    /// Checks the value given to an enum variant, which is an `int` constant.
+    fn check_discriminant(&mut self, enum_name: &str, variant: &EnumVariant) -> Result<(), Diagnostic> {
+        let Some(value) = &variant.value else {
+            return Ok(());
+        };
+        let item = format!("variant '{}::{}'", enum_name, variant.name);
+        self.check_constant(value, &item).map_err(|error| error.or_label(value.span()))?;
+        let value_type = self.analyze_expr(value, Some(&Type::int()))?;
+        self.expect_type(&Type::int(), &value_type, &source_text(value), |expected, found| {
+            format!("Type mismatch: the discriminant of {} must be {}, found {}", item, expected, found)
+        })
+    }
+
+    /// Leaves the functions and scopes a top-level statement was in when it
+    /// failed.
+    fn recover(&mut self) {
+        self.contexts.truncate(1);
+        while self.symbol_table.current() != ScopeId::ROOT {
+            self.undeclared.remove(&self.symbol_table.current());
+            self.symbol_table.exit_scope();
+        }
+    }
+
+    /// Declares the variable of a `let` that failed to check, so that uses
+    /// of it report their own errors rather than that it is undeclared. Its
+    /// type is the annotated one if that is valid, and otherwise unknown.
+    fn declare_failed(&mut self, statement: &Stmt) {
+        let Stmt::Let { name, span, mutable, type_annotation, .. } = statement else {
+            return;
+        };
+        if self.symbol_table.contains_local(name) {
+            return;
+        }
+        let ty = match type_annotation.as_ref().map(|annotation| self.resolve_type(annotation)) {
+            Some(Ok(ty)) => ty,
+            _ => self.inference.fresh(VariableKind::General, format!("variable '{}'", name)),
+        };
+        if self.declare_variable(name, *span, ty, *mutable, Attributes::default()).is_ok() {
+            self.variables.last_mut().expect("the variable was just declared").used = true;
+        }
+    }
+
+    /// Defaults open literal types, runs the checks that waited for types to
+    /// be known, and replaces type variables in every recorded type.
+    fn finish_inference(&mut self) -> Result<(), Diagnostic> {
+        self.inference.default_literals();
+        for check in std::mem::take(&mut self.deferred) {
+            match check {
+                DeferredCheck::Literal { value, negative, ty, span } => {
+                    check_literal_range(value, negative, &self.inference.resolve(&ty), span)?
+                }
+                DeferredCheck::Binary { op, left, right, float_equality, span } => {
+                    let (left, right) = (self.inference.resolve(&left), self.inference.resolve(&right));
+                    binary_typing(op, &left, &right).map_err(|error| operator_mismatch(error, span))?;
+                    if let Some(message) = float_comparison(op, &left, &right) {
+                        self.report(&FLOAT_EQUALITY, float_equality, Diagnostic::warning(message).with_label(span, ""));
+                    }
+                }
+                DeferredCheck::Unary { op, operand, span } => {
+                    unary_typing(op, &self.inference.resolve(&operand)).map_err(|error| operator_mismatch(error, span))?;
+                }
+            }
+        }
+
+        for variable in &mut self.variables {
+            if let Some(origin) = self.inference.unresolved(&variable.ty) {
+                let error = Diagnostic::from(format!("Type annotation needed for {}", origin));
+                return Err(error.with_label(variable.span, ""));
+            }
+            variable.ty = self.inference.resolve(&variable.ty);
+        }
+        for lambda in &mut self.lambdas {
+            if let Some(origin) = self.inference.unresolved(&lambda.ty) {
+                let error = Diagnostic::from(format!("Type annotation needed for {}", origin));
+                return Err(error.with_label(lambda.span, ""));
+            }
+            lambda.ty = self.inference.resolve(&lambda.ty);
+        }
+        for item in &mut self.statics {
+            item.ty = self.inference.resolve(&item.ty);
+        }
+        for item in &mut self.consts {
+            item.ty = self.inference.resolve(&item.ty);
+        }
+        for ty in self.expr_types.values_mut() {
+            *ty = self.inference.resolve(ty);
+        }
+        for symbol in self.symbol_table.symbols_mut() {
+            symbol.symbol_type = self.inference.resolve(&symbol.symbol_type);
+        }
+        Ok(())
+    }
+
+    /// Unifies the type an expression has with the type its context
+    /// requires. On a mismatch, `message` describes it from both types as far
+    /// as they are known, and notes say which expressions forced the parts
+    /// that were inferred.
+    fn expect_type(
+        &mut self,
+        expected: &Type,
+        found: &Type,
+        site: &str,
+        message: impl FnOnce(&Type, &Type) -> String,
+    ) -> Result<(), Diagnostic> {
+        self.inference
+            .unify(expected, found, site)
+            .map_err(|Mismatch { expected: settled_expected, found: settled_found }| {
+                let mut error = Diagnostic::error(message(&settled_expected, &settled_found)).with_code(codes::TYPE_MISMATCH);
+                let mut notes = self.inference.explain(expected);
+                notes.extend(self.inference.explain(found));
+                notes.dedup();
+                for note in notes {
+                    error = error.with_note(note);
+                }
+                error
+            })
+    }
+
+    /// Types a binary operation. While an operand's type is still being
+    /// inferred, both operands must have the same type, and the operator is
+    /// checked once it is known.
+    fn binary_type(&mut self, expr: &Expr, op: BinaryOperator, left: &Type, right: &Type) -> Result<Type, Diagnostic> {
+        let (left, right) = (self.inference.shallow(left), self.inference.shallow(right));
+        if !matches!(left, Type::Variable(_)) && !matches!(right, Type::Variable(_)) {
+            let typing = binary_typing(op, &left, &right).map_err(|error| operator_mismatch(error, expr.span()))?;
+            if let Some(message) = float_comparison(op, &left, &right) {
+                self.lint(&FLOAT_EQUALITY, expr.span(), message);
+            }
+            return Ok(typing.result);
+        }
+        let site = source_text(expr);
+        let mismatch = |left: &Type, right: &Type| {
+            format!(
+                "Type mismatch: cannot apply operator '{}' to types {} and {}",
+                op, left, right
+            )
+        };
+        if op.class() == OperatorClass::Logical {
+            let result = self.inference.unify(&Type::Boolean, &left, &site);
+            if result.and_then(|_| self.inference.unify(&Type::Boolean, &right, &site)).is_err() {
+                let error = mismatch(&self.inference.settled(&left), &self.inference.settled(&right));
+                return Err(Diagnostic::error(error).with_code(codes::TYPE_MISMATCH));
+            }
+            return Ok(Type::Boolean);
+        }
+        self.expect_type(&left, &right, &site, mismatch)?;
+        let float_equality = self.lint_level(&FLOAT_EQUALITY, None);
+        self.deferred.push(DeferredCheck::Binary {
+            op,
+            left: left.clone(),
+            right,
+            float_equality,
+            span: expr.span(),
+        });
+        Ok(match op.class() {
+            OperatorClass::Arithmetic => left,
+            _ => Type::Boolean,
+        })
+    }
+
+    /// Rejects type declarations that refer to unknown types or, directly or
+    /// through other declarations, to themselves.
+    fn check_type_declarations(&self, statements: &[Stmt]) -> Result<(), Diagnostic> {
+        let mut checked = HashSet::new();
+        for statement in statements {
+            if let Stmt::TypeAlias { name, span, .. }
+            | Stmt::TypeDef { name, span, .. }
+            | Stmt::Struct { name, span, .. }
+            | Stmt::Enum { name, span, .. } = statement
+            {
+                self.check_type_declaration(name, &mut Vec::new(), &mut checked)
+                    .map_err(|error| error.or_label(*span))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_type_declaration<'b>(
+        &'b self,
+        name: &'b str,
+        path: &mut Vec<&'b str>,
+        checked: &mut HashSet<&'b str>,
+    ) -> Result<(), Diagnostic> {
+        if checked.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|other| *other == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name);
+            return Err(format!("Type '{}' is defined in terms of itself: {}", name, cycle.join(" -> ")).into());
+        }
+        let (params, types) = match &self.type_declarations[name] {
+            TypeDeclaration::Alias { params, ty } => (params.as_slice(), vec![ty]),
+            TypeDeclaration::Def { ty } => (&[][..], vec![ty]),
+            TypeDeclaration::Struct { fields } => (&[][..], fields.iter().map(|(_, ty)| ty).collect()),
+            TypeDeclaration::Enum { .. } => (&[][..], Vec::new()),
+        };
+
+        path.push(name);
+        let mut referenced = Vec::new();
+        for ty in types {
+            type_names(ty, &mut referenced);
+        }
+        for other in referenced {
+            if params.iter().any(|param| param == other) || BUILTIN_TYPES.contains(&other) {
+                continue;
+            }
+            if !self.type_declarations.contains_key(other) {
+                return Err(format!("Unknown type '{}' in the declaration of '{}'", other, name).into());
+            }
+            self.check_type_declaration(other, path, checked)?;
+        }
+        path.pop();
+        checked.insert(name);
+        Ok(())
+    }
+
+    pub fn is_type_name(&self, name: &str) -> bool {
+        BUILTIN_TYPES.contains(&name) || self.type_declarations.contains_key(name)
+    }
+
+    /// A one-line description of a type or top-level binding, for hovers.
+    pub fn describe(&self, name: &str) -> Option<String> {
+        match self.type_declarations.get(name) {
+            Some(TypeDeclaration::Alias { params, .. }) => {
+                // Parameters stand for themselves and print as their names.
+                let bound: HashMap<String, Type> = params
+                    .iter()
+                    .map(|param| (param.clone(), Type::Nominal { name: param.clone(), underlying: Box::new(Type::Void) }))
+                    .collect();
+                let TypeDeclaration::Alias { ty, .. } = &self.type_declarations[name] else {
+                    unreachable!("matched as an alias above");
+                };
+                let target = self.resolve_type_with(ty, &bound).ok()?;
+                if params.is_empty() {
+                    Some(format!("alias {} = {}", name, target))
+                } else {
+                    Some(format!("alias {}<{}> = {}", name, params.join(", "), target))
+                }
+            }
+            Some(TypeDeclaration::Def { ty }) => Some(format!("def {} = {}", name, self.resolve_type(ty).ok()?)),
+            Some(TypeDeclaration::Struct { .. }) => {
+                let ty = self.resolve_type(&TypeExpr::Named(name.to_string())).ok()?;
+                Some(format!("struct {} {}", name, ty.representation()))
+            }
+            Some(TypeDeclaration::Enum { variants }) => Some(format!("enum {} {{{}}}", name, variants.join(", "))),
+            None => self
+                .symbol_table
+                .lookup_in(ScopeId::ROOT, name)
+                .map(|symbol| format!("{}: {}", name, symbol.symbol_type)),
+        }
+    }
+
+    /// Describes the identifier written at `span`: what a use resolves to,
+    /// or the variable a declaration introduces. Other names, such as
+    /// types, are described as `describe` does.
+    pub fn describe_at(&self, name: &str, span: Span) -> Option<String> {
+        let resolution = self.resolutions.values().find(|resolution| resolution.span == span);
+        let ty = match resolution.map(|resolution| &resolution.symbol) {
+            Some(SymbolKind::Variable { id, .. }) => &self.variables[*id].ty,
+            Some(SymbolKind::Function { id }) => &self.functions[*id],
+            Some(SymbolKind::Const { id }) => &self.consts[*id].ty,
+            Some(SymbolKind::Static { id }) => &self.statics[*id].ty,
+            None => match self.variables.iter().find(|variable| variable.span == span) {
+                Some(variable) => &variable.ty,
+                None => return self.describe(name),
+            },
+        };
+        Some(format!("{}: {}", name, ty))
+    }
+
+    fn function_attributes(
+        &self,
+        name: &str,
+        params: &[Param],
+        attributes: &[Attribute],
+    ) -> Result<Attributes, Diagnostic> {
+        let attributes = Attributes::resolve(attributes, AttributeTarget::Function)?;
+        if attributes.test && (name == "main" || !params.is_empty()) {
+            return Err(format!(
+                "Test function '{}' must take no parameters and cannot be the entry point",
+                name
+            ).into());
+        }
+        if let Some(export) = &attributes.export {
+            if export == "main" {
+                return Err(format!("Function '{}' cannot be exported as 'main', which names the entry point", name).into());
+            }
+            if self.function_attributes.iter().any(|other| other.export.as_ref() == Some(export)) {
+                return Err(format!("Export name '{}' is used more than once", export).into());
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// The type analysis inferred for an expression.
+    pub fn expr_type(&self, expr: &Expr) -> Option<&Type> {
+        self.expr_types.get(&expr.id())
+    }
+
+    /// Whether a call expression converts its argument to a type, as in `UserId(5)`.
+    pub fn is_conversion(&self, expr: &Expr) -> bool {
+        self.conversions.contains(&expr.id())
+    }
+
+    /// The declaration a `Variable` or `Assign` expression refers to.
+    pub fn resolution(&self, expr: &Expr) -> Option<&Resolution> {
+        self.resolutions.get(&expr.id())
+    }
+
+    /// Every identifier that was resolved, by the node it is written in.
+    pub fn resolutions(&self) -> impl Iterator<Item = (NodeId, &Resolution)> {
+        self.resolutions.iter().map(|(&id, resolution)| (id, resolution))
+    }
+
+    pub fn captures(&self, lambda: usize) -> Vec<Capture> {
+        self.lambdas[lambda]
+            .captures
+            .iter()
+            .map(|&variable| {
+                let info = &self.variables[variable];
+                Capture {
+                    name: info.name.clone(),
+                    variable,
+                    ty: info.ty.clone(),
+                    mode: if info.reassigned { CaptureMode::ByReference } else { CaptureMode::ByValue },
+                }
+            })
+            .collect()
+    }
+
+    fn analyze_stmt(&mut self, statement: &Stmt) -> Result<(), Diagnostic> {
+        self.check_stmt(statement).map_err(|error| error.or_label(statement.span()))
+    }
+
+    fn check_stmt(&mut self, statement: &Stmt) -> Result<(), Diagnostic> {
+        match statement {
+            Stmt::Block { statements, .. } => self.analyze_block(statements),
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.analyze_condition(condition, "if")?;
+                self.analyze_block(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => self.analyze_block(else_branch),
+                    None => Ok(()),
+                }
+            }
+            Stmt::While { condition, body, .. } => {
+                self.analyze_condition(condition, "while")?;
+                self.analyze_block(body)
+            }
+            Stmt::For { initializer, condition, increment, body, .. } => {
+                self.begin_scope(initializer.as_deref().map(std::slice::from_ref).unwrap_or_default());
+                if let Some(initializer) = initializer {
+                    self.analyze_stmt(initializer)?;
+                }
+                if let Some(condition) = condition {
+                    self.analyze_condition(condition, "for")?;
+                }
+                if let Some(increment) = increment {
+                    self.lint_parens(increment);
+                    self.analyze(increment)?;
+                }
+                self.analyze_block(body)?;
+                self.end_scope();
+                Ok(())
+            }
+            // Checked against the enclosing loops on the HIR control-flow graph.
+            Stmt::Break { .. } | Stmt::Continue { .. } => Ok(()),
+            Stmt::Expression { expression, .. } => {
+                self.lint_parens(expression);
+                self.analyze(expression).map(|_| ())
+            }
+            Stmt::Function { is_const, name, params, body, .. } => {
+                let current = self.contexts.last().expect("analysis runs inside a function context");
+                if !current.top_level {
                     return Err(format!(
-                        "Type mismatch: cannot apply operator {:?} to types {:?} and {:?}",
-                        operator, left_type, right_type
-                    ));
+                        "Function '{}' must be declared at the top level; use a closure instead",
+                        name
+                    ).into());
+                }
+                let (id, param_types, return_type) = match self.symbol_table.lookup(name) {
+                    Some(Symbol {
+                        symbol_type: Type::Function { params, return_type },
+                        kind: SymbolKind::Function { id },
+                        ..
+                    }) => (*id, params.clone(), (**return_type).clone()),
+                    _ => return Err(format!("Undefined function '{}'", name).into()),
+                };
+
+                self.contexts.push(FunctionContext {
+                    return_type: Some(return_type),
+                    lambda: None,
+                    top_level: false,
+                    const_function: is_const.then(|| name.clone()),
+                    item: Some(id),
+                    attributes: self.function_attributes[id].clone(),
+                });
+                self.begin_scope(body);
+                for (param, ty) in params.iter().zip(param_types) {
+                    self.declare_variable(&param.name, param.span, ty, param.mutable, Attributes::default())?;
+                }
+                for statement in body {
+                    self.analyze_stmt(statement)?;
+                }
+                self.end_scope();
+                self.contexts.pop();
+                Ok(())
+            }
+            Stmt::Let { attributes, name, span, mutable, type_annotation, initializer, .. } => {
+                let attributes = Attributes::resolve(attributes, AttributeTarget::Variable)?;
+                let annotated = match type_annotation {
+                    Some(annotation) => Some(self.resolve_type(annotation)?),
+                    None => None,
+                };
+                if let Some(initializer) = initializer {
+                    self.lint_parens(initializer);
+                }
+                let ty = match (annotated, initializer) {
+                    (Some(annotated), Some(initializer)) => {
+                        let initializer_type = self.analyze_expr(initializer, Some(&annotated))?;
+                        self.expect_type(&annotated, &initializer_type, &source_text(initializer), |annotated, found| {
+                            format!(
+                                "Type mismatch: variable '{}' is declared as {} but initialized with {}",
+                                name, annotated, found
+                            )
+                        })?;
+                        annotated
+                    }
+                    (Some(annotated), None) => annotated,
+                    (None, Some(initializer)) => self.analyze(initializer)?,
+                    // Inferred from how the variable is used.
+                    (None, None) => self.inference.fresh(VariableKind::General, format!("variable '{}'", name)),
+                };
+                if self.inference.shallow(&ty) == Type::Void {
+                    return Err(format!("Variable '{}' cannot have type void", name).into());
                 }
+                self.declare_variable(name, *span, ty, *mutable, attributes)?;
+                if initializer.is_none() {
+                    self.variables.last_mut().expect("the variable was just declared").deferred = true;
+                }
+                Ok(())
+            }
+            Stmt::Static { name, .. } => Err(format!("Static '{}' must be declared at the top level", name).into()),
+            Stmt::Const { name, .. } => Err(format!("Constant '{}' must be declared at the top level", name).into()),
+            Stmt::TypeAlias { name, .. } | Stmt::TypeDef { name, .. } | Stmt::Struct { name, .. } | Stmt::Enum { name, .. } => {
+                Err(format!("Type '{}' must be declared at the top level", name).into())
+            }
+            Stmt::Conditional { .. } => Err("'#if' must be written at the top level".to_string().into()),
+            Stmt::Return { value, .. } => {
+                let context = self.contexts.last().expect("analysis runs inside a function context");
+                if context.top_level {
+                    return Err("Cannot return from top-level code".to_string().into());
+                }
+                let expected = context.return_type.clone();
+                let value_type = match value {
+                    Some(value) => {
+                        self.lint_parens(value);
+                        self.analyze_expr(value, expected.as_ref())?
+                    }
+                    None => Type::Void,
+                };
+                let site = match value {
+                    Some(value) => format!("return {}", source_text(value)),
+                    None => "return".to_string(),
+                };
+                match expected {
+                    Some(expected) => self.expect_type(&expected, &value_type, &site, |expected, found| {
+                        format!("Type mismatch: expected return value of type {}, found {}", expected, found)
+                    }),
+                    None => {
+                        self.contexts.last_mut().expect("analysis runs inside a function context").return_type = Some(value_type);
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn analyze_block(&mut self, statements: &[Stmt]) -> Result<(), Diagnostic> {
+        self.begin_scope(statements);
+        for statement in statements {
+            self.analyze_stmt(statement)?;
+        }
+        self.end_scope();
+        Ok(())
+    }
+
+    fn analyze_condition(&mut self, condition: &Expr, keyword: &str) -> Result<(), Diagnostic> {
+        self.lint_parens(condition);
+        let ty = self.analyze_expr(condition, Some(&Type::Boolean))?;
+        self.expect_type(&Type::Boolean, &ty, &source_text(condition), |_, found| {
+            format!("Type mismatch: '{}' condition must be bool, found {}", keyword, found)
+        })
+    }
+
+    pub fn analyze(&mut self, expr: &Expr) -> Result<Type, Diagnostic> {
+        self.analyze_expr(expr, None)
+    }
 
-                Ok(left_type)
+    /// `expected` is the type the context requires, which lets lambda
+    /// parameters and return types be omitted where they are obvious. The
+    /// type is recorded for HIR lowering.
+    /// Errors that do not point anywhere yet point at the expression.
+    fn analyze_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> Result<Type, Diagnostic> {
+        let ty = self.infer_expr(expr, expected).map_err(|error| error.or_label(expr.span()))?;
+        self.expr_types.insert(expr.id(), ty.clone());
+        Ok(ty)
+    }
+
+    fn infer_expr(&mut self, expr: &Expr, expected: Option<&Type>) -> Result<Type, Diagnostic> {
+        match expr {
+            Expr::Assign { name, value, span, .. } => {
+                let (ty, variable) = self.assignment_target(expr, name, *span)?;
+                self.lint_parens(value);
+                let value_type = self.analyze_expr(value, Some(&ty))?;
+                self.expect_assignable(&ty, &value_type, expr, name)?;
+                self.note_assignment(variable)?;
+                Ok(ty)
+            }
+            Expr::CompoundAssign { name, operator, value, span, .. } => {
+                let op = BinaryOperator::from_token(operator)
+                    .ok_or_else(|| format!("Invalid binary operator: {}", operator.describe()))?;
+                let (ty, variable) = self.assignment_target(expr, name, *span)?;
+                self.lint_parens(value);
+                let value_type = self.analyze_expr(value, Some(&ty))?;
+                let result = self.binary_type(expr, op, &ty, &value_type)?;
+                self.expect_assignable(&ty, &result, expr, name)?;
+                self.note_assignment(variable)?;
+                Ok(ty)
+            }
+            Expr::Update { name, operator, span, .. } => {
+                let op = BinaryOperator::from_token(operator)
+                    .ok_or_else(|| format!("Invalid binary operator: {}", operator.describe()))?;
+                let (ty, variable) = self.assignment_target(expr, name, *span)?;
+                let shallow = self.inference.shallow(&ty);
+                if !matches!(shallow, Type::Variable(_)) && !shallow.is_numeric() {
+                    return Err(format!("Cannot apply '{}' to variable '{}' of type {}", source_text(expr), name, ty).into());
+                }
+                // The operation adds or subtracts a literal `1`.
+                let one = self.analyze_integer_literal(expr, 1, false, Some(&ty))?;
+                self.binary_type(expr, op, &ty, &one)?;
+                self.note_assignment(variable)?;
+                Ok(ty)
+            }
+            Expr::Binary { left, operator, right, .. } | Expr::Logical { left, operator, right, .. } => {
+                let op = BinaryOperator::from_token(operator)
+                    .ok_or_else(|| format!("Invalid binary operator: {}", operator.describe()))?;
+                // Only the result of arithmetic has the operands' type.
+                let expected = expected.filter(|_| op.class() == OperatorClass::Arithmetic);
+                let (left_type, right_type) = self.analyze_operands(left, right, expected)?;
+                self.binary_type(expr, op, &left_type, &right_type)
+            }
+            Expr::Call { callee, arguments, .. } => {
+                // Calling a type name converts to it, e.g. `UserId(5)` or `int(id)`.
+                if let Expr::Variable { name, .. } = callee.as_ref() {
+                    if self.symbol_table.lookup(name).is_none() && self.is_type_name(name) {
+                        let target = self.analyze_conversion(name, arguments)?;
+                        self.conversions.insert(expr.id());
+                        return Ok(target);
+                    }
+                }
+                let callee_type = self.analyze(callee)?;
+                if let Some(function) = self.const_function() {
+                    match self.resolution(callee).map(|resolution| &resolution.symbol) {
+                        Some(SymbolKind::Function { id }) if self.const_functions[*id] => {}
+                        Some(SymbolKind::Function { .. }) => {
+                            return Err(format!(
+                                "Cannot call non-const function '{}' from const function '{}'",
+                                source_text(callee),
+                                function
+                            ).into())
+                        }
+                        _ => {
+                            return Err(format!(
+                                "Const function '{}' can only call const functions by name, not `{}`",
+                                function,
+                                source_text(callee)
+                            ).into())
+                        }
+                    }
+                }
+                let callee_type = match self.inference.shallow(&callee_type) {
+                    // A value whose type is still being inferred must be a function.
+                    Type::Variable(_) => {
+                        let site = source_text(expr);
+                        let function = Type::Function {
+                            params: (1..=arguments.len())
+                                .map(|index| {
+                                    self.inference.fresh(VariableKind::General, format!("argument {} of `{}`", index, site))
+                                })
+                                .collect(),
+                            return_type: Box::new(
+                                self.inference.fresh(VariableKind::General, format!("the result of `{}`", site)),
+                            ),
+                        };
+                        self.expect_type(&callee_type, &function, &site, |callee, _| {
+                            format!("Cannot call a value of type {}", callee)
+                        })?;
+                        function
+                    }
+                    ty => ty,
+                };
+                let (params, return_type) = match callee_type.unaliased().clone() {
+                    Type::Function { params, return_type } => (params, return_type),
+                    other => return Err(format!("Cannot call a value of type {}", other).into()),
+                };
+                if params.len() != arguments.len() {
+                    return Err(format!(
+                        "Expected {} arguments but got {}",
+                        params.len(),
+                        arguments.len()
+                    ).into());
+                }
+                for (argument, param) in arguments.iter().zip(&params) {
+                    self.lint_parens(argument);
+                    let argument_type = self.analyze_expr(argument, Some(param))?;
+                    self.expect_type(param, &argument_type, &source_text(argument), |param, found| {
+                        format!("Type mismatch: expected argument of type {}, found {}", param, found)
+                    })?;
+                }
+                Ok(*return_type)
+            }
+            Expr::Cast { expr: operand, ty, .. } => {
+                let target = self.resolve_type(ty)?;
+                let source = self.analyze(operand)?;
+                // What is cast must be known here, so literals take their default types.
+                self.inference.default_literal(&source);
+                let source = self.inference.resolve(&source);
+                if self.inference.unresolved(&source).is_some() {
+                    return Err(format!("Type annotation needed for the operand of `{}`", source_text(expr)).into());
+                }
+                let castable = match (source.representation(), target.representation()) {
+                    (Type::Integer(_) | Type::Float(_) | Type::Boolean, Type::Integer(_)) => true,
+                    (Type::Integer(_) | Type::Float(_), Type::Float(_)) => true,
+                    _ => source == target,
+                };
+                if !castable {
+                    return Err(format!("Cannot cast {} to {}", source, target).into());
+                }
+                Ok(target)
+            }
+            Expr::Grouping { expression, from_macro, .. } => {
+                if !from_macro && is_atomic(expression) {
+                    self.lint(
+                        &REDUNDANT_PARENS,
+                        expression.span(),
+                        format!("Unnecessary parentheses around `{}`", source_text(expression)),
+                    );
+                }
+                self.analyze_expr(expression, expected)
+            }
+            Expr::Lambda { params, return_type, body, span, .. } => {
+                if let Some(function) = self.const_function() {
+                    return Err(format!("Cannot create a closure in const function '{}'", function).into());
+                }
+                self.analyze_lambda(params, return_type, body, *span, expected)
             }
-            Expr::Grouping { expression } => self.analyze(expression),
-            Expr::Literal { value } => match value {
-                crate::lexer::token::Token::Number(_) => Ok(Type::Float),
+            Expr::Literal { value, .. } => match value {
+                Token::Integer(value) => self.analyze_integer_literal(expr, *value, false, expected),
+                Token::Number(_) => {
+                    let ty = match expected.map(|expected| self.inference.shallow(expected)) {
+                        Some(expected) if matches!(expected.unaliased(), Type::Float(_)) => expected,
+                        None | Some(Type::Variable(_)) => {
+                            self.inference.fresh(VariableKind::Float, format!("the literal `{}`", source_text(expr)))
+                        }
+                        Some(_) => Type::float(),
+                    };
+                    Ok(ty)
+                }
                 crate::lexer::token::Token::String(_) => Ok(Type::String),
                 crate::lexer::token::Token::Reserved(r) => match r {
                     crate::lexer::token::Reserved::True | crate::lexer::token::Reserved::False => Ok(Type::Boolean),
                     crate::lexer::token::Reserved::Null => Ok(Type::Null),
-                    _ => Err(format!("Invalid literal type: {:?}", r)),
+                    _ => Err(format!("Invalid literal: {}", value.describe()).into()),
                 },
-                _ => Err(format!("Invalid literal type: {:?}", value)),
+                _ => Err(format!("Invalid literal: {}", value.describe()).into()),
             },
-            Expr::Unary { operator, right } => {
-                let right_type = self.analyze(right)?;
-                match operator {
-                    crate::lexer::token::Token::Operation(op) => match op {
-                        crate::lexer::token::Operation::Subtract => {
-                            if right_type != Type::Float && right_type != Type::Integer {
-                                return Err(format!(
-                                    "Type mismatch: cannot apply operator {:?} to type {:?}",
-                                    operator, right_type
-                                ));
-                            }
-                            Ok(right_type)
+            Expr::Ternary { condition, then_branch, else_branch, .. } => {
+                let condition_type = self.analyze(condition)?;
+                self.expect_type(&Type::Boolean, &condition_type, &source_text(condition), |_, found| {
+                    format!(
+                        "Type mismatch: conditional expression requires a bool condition, found {}",
+                        found
+                    )
+                })?;
+
+                let (then_type, else_type) = self.analyze_operands(then_branch, else_branch, expected)?;
+                self.expect_type(&then_type, &else_type, &source_text(expr), |then_type, else_type| {
+                    format!(
+                        "Type mismatch: conditional branches have incompatible types {} and {}",
+                        then_type, else_type
+                    )
+                })?;
+                Ok(then_type)
+            }
+            Expr::Unary { operator, right, .. } => {
+                let op = UnaryOperator::from_token(operator)
+                    .ok_or_else(|| format!("Invalid unary operator: {}", operator.describe()))?;
+                let right_type = match (op, right.as_ref()) {
+                    (UnaryOperator::Negate, Expr::Literal { value: Token::Integer(value), .. }) => {
+                        self.analyze_integer_literal(right, *value, true, expected)?
+                    }
+                    (UnaryOperator::Negate, _) => self.analyze_expr(right, expected)?,
+                    (UnaryOperator::Not, _) => self.analyze(right)?,
+                };
+                match (op, self.inference.shallow(&right_type)) {
+                    (UnaryOperator::Negate, operand @ Type::Variable(_)) => {
+                        self.deferred.push(DeferredCheck::Unary { op, operand: operand.clone(), span: expr.span() });
+                        Ok(operand)
+                    }
+                    (UnaryOperator::Not, Type::Variable(_)) => {
+                        let site = source_text(expr);
+                        self.expect_type(&Type::Boolean, &right_type, &site, |_, found| {
+                            format!("Type mismatch: cannot apply operator '{}' to type {}", op, found)
+                        })?;
+                        Ok(Type::Boolean)
+                    }
+                    (_, operand) => unary_typing(op, &operand).map_err(|error| operator_mismatch(error, expr.span())),
+                }
+            }
+            Expr::StructLiteral { name, fields, .. } => {
+                let ty = match self.type_declarations.get(name) {
+                    Some(TypeDeclaration::Struct { .. }) => self.resolve_type(&TypeExpr::Named(name.clone()))?,
+                    _ => return Err(format!("Unknown struct '{}'", name).into()),
+                };
+                let declared = struct_fields(&ty).expect("a struct item declares a struct type").to_vec();
+                let mut initialized = HashSet::new();
+                for field in fields {
+                    let Some((_, field_type)) = declared.iter().find(|(declared, _)| *declared == field.name) else {
+                        let uninitialized = declared.iter().map(|(declared, _)| declared.as_str());
+                        let uninitialized = uninitialized.filter(|declared| !initialized.contains(declared));
+                        return Err(unknown_field(name, &field.name, field.span, uninitialized));
+                    };
+                    if !initialized.insert(field.name.as_str()) {
+                        let error = format!("Field '{}' is initialized more than once", field.name);
+                        return Err(Diagnostic::from(error).with_label(field.span, ""));
+                    }
+                    self.lint_parens(&field.value);
+                    let value_type = self.analyze_expr(&field.value, Some(field_type))?;
+                    self.expect_type(field_type, &value_type, &source_text(&field.value), |field_type, found| {
+                        format!(
+                            "Type mismatch: field '{}' of {} has type {} but is initialized with {}",
+                            field.name, name, field_type, found
+                        )
+                    })?;
+                }
+                let missing: Vec<String> = declared
+                    .iter()
+                    .filter(|(field, _)| !initialized.contains(field.as_str()))
+                    .map(|(field, _)| format!("'{}'", field))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(format!("Missing {} in initializer of struct '{}'", missing.join(", "), name).into());
+                }
+                Ok(ty)
+            }
+            Expr::ArrayLiteral { elements, .. } => {
+                let expected = expected.map(|expected| self.inference.shallow(expected));
+                let expected_element = match expected.as_ref().map(Type::unaliased) {
+                    Some(Type::Array { element, .. }) => Some((**element).clone()),
+                    _ => None,
+                };
+                let element = match expected_element {
+                    Some(element) => element,
+                    None => self
+                        .inference
+                        .fresh(VariableKind::General, format!("the elements of `{}`", source_text(expr))),
+                };
+                for value in elements {
+                    self.lint_parens(value);
+                    let value_type = self.analyze_expr(value, Some(&element))?;
+                    self.expect_type(&element, &value_type, &source_text(value), |element, found| {
+                        format!("Type mismatch: array of {} cannot hold an element of type {}", element, found)
+                    })?;
+                }
+                Ok(Type::Array {
+                    element: Box::new(element),
+                    length: elements.len() as u64,
+                })
+            }
+            Expr::EnumVariant { enum_name, variant, .. } => match self.type_declarations.get(enum_name) {
+                Some(TypeDeclaration::Enum { variants }) => {
+                    if !variants.contains(variant) {
+                        return Err(format!("Enum '{}' has no variant '{}'", enum_name, variant).into());
+                    }
+                    self.resolve_type(&TypeExpr::Named(enum_name.clone()))
+                }
+                _ => Err(format!("Unknown enum '{}'", enum_name).into()),
+            },
+            Expr::Field { object, name, span, .. } => self.field_type(object, name, *span),
+            Expr::Index { object, index, .. } => self.element_type(object, index),
+            Expr::SetField { object, name, operator, value, span, .. } => {
+                let field_type = self.field_type(object, name, *span)?;
+                self.check_writable(object, &format!("field '{}'", name))?;
+                let target = format!("field '{}'", name);
+                self.analyze_store(expr, &field_type, operator.as_ref(), value, &target)?;
+                Ok(field_type)
+            }
+            Expr::SetIndex { object, index, operator, value, .. } => {
+                let element_type = self.element_type(object, index)?;
+                self.check_writable(object, "an element")?;
+                self.analyze_store(expr, &element_type, operator.as_ref(), value, "array element")?;
+                Ok(element_type)
+            }
+            Expr::Variable { name, span, .. } => {
+                let symbol = self.resolve(expr, name, *span)?;
+                match symbol.kind {
+                    SymbolKind::Variable { id, depth } => {
+                        self.note_use(id, depth)?;
+                        self.variables[id].used = true;
+                    }
+                    SymbolKind::Function { id } => {
+                        if self.contexts.iter().all(|context| context.item != Some(id)) {
+                            self.used_functions[id] = true;
                         }
-                        crate::lexer::token::Operation::Not => {
-                            if right_type != Type::Boolean {
-                                return Err(format!(
-                                    "Type mismatch: cannot apply operator {:?} to type {:?}",
-                                    operator, right_type
-                                ));
-                            }
-                            Ok(Type::Boolean)
+                        if let Some(message) = self.function_attributes[id].deprecated.clone() {
+                            self.lint(&DEPRECATED, *span, format!("Use of deprecated function '{}': {}", name, message));
                         }
-                        _ => Err(format!("Invalid unary operator: {:?}", operator)),
-                    },
-                    _ => Err(format!("Invalid unary operator: {:?}", operator)),
+                    }
+                    SymbolKind::Static { .. } if self.const_function().is_some() => {
+                        return Err(self.static_in_const_function(name).into())
+                    }
+                    // Statics and constants live for the whole program, so
+                    // uses need no capture.
+                    SymbolKind::Const { .. } => {}
+                    SymbolKind::Static { id } => {
+                        if self.statics[id].mutable {
+                            self.lint(&MUTABLE_STATICS, *span, format!("Use of mutable static '{}'", name));
+                        }
+                    }
+                }
+                Ok(symbol.symbol_type)
+            }
+        }
+    }
+
+    /// Analyzes two operands that must have the same type, letting a literal
+    /// take its type from the other operand: in `2 * x` the literal is
+    /// analyzed after `x`, which has no effect on numbering since literals
+    /// declare nothing.
+    fn analyze_operands(&mut self, left: &Expr, right: &Expr, expected: Option<&Type>) -> Result<(Type, Type), Diagnostic> {
+        if is_numeric_literal(left) && !is_numeric_literal(right) {
+            let right_type = self.analyze_expr(right, expected)?;
+            let left_type = self.analyze_expr(left, Some(&right_type))?;
+            Ok((left_type, right_type))
+        } else {
+            let left_type = self.analyze_expr(left, expected)?;
+            let right_type = self.analyze_expr(right, Some(&left_type))?;
+            Ok((left_type, right_type))
+        }
+    }
+
+    /// The type of field `name` of `object`, whose type must be known to be
+    /// a struct type. `span` is where the field name is written.
+    fn field_type(&mut self, object: &Expr, name: &str, span: Span) -> Result<Type, Diagnostic> {
+        let object_type = self.analyze(object)?;
+        let object_type = self.inference.resolve(&object_type);
+        if self.inference.unresolved(&object_type).is_some() {
+            return Err(format!("Type annotation needed for `{}` to access its field '{}'", source_text(object), name).into());
+        }
+        let Some(fields) = struct_fields(&object_type) else {
+            return Err(format!("Type {} has no field '{}'", object_type, name).into());
+        };
+        match fields.iter().find(|(field, _)| field == name) {
+            Some((_, ty)) => Ok(ty.clone()),
+            None => Err(unknown_field(&object_type.to_string(), name, span, fields.iter().map(|(field, _)| field.as_str()))),
+        }
+    }
+
+    /// The element type of the array `object`, whose type must be known,
+    /// indexed by the integer `index`. A literal index must be in bounds.
+    fn element_type(&mut self, object: &Expr, index: &Expr) -> Result<Type, Diagnostic> {
+        let object_type = self.analyze(object)?;
+        let object_type = self.inference.resolve(&object_type);
+        if let Type::Variable(_) = object_type {
+            return Err(format!("Type annotation needed for `{}` to index it", source_text(object)).into());
+        }
+        let Type::Array { element, length } = object_type.unaliased().clone() else {
+            return Err(format!("Cannot index a value of type {}", object_type).into());
+        };
+        self.lint_parens(index);
+        let index_type = self.analyze_expr(index, Some(&Type::int()))?;
+        match self.inference.shallow(&index_type) {
+            Type::Variable(_) => self.expect_type(&Type::int(), &index_type, &source_text(index), |_, found| {
+                format!("Array index must be an integer, found {}", found)
+            })?,
+            ty if !matches!(ty.representation(), Type::Integer(_)) => {
+                let error = Diagnostic::error(format!("Array index must be an integer, found {}", ty));
+                return Err(error.with_code(codes::TYPE_MISMATCH).with_label(index.span(), ""));
+            }
+            _ => {}
+        }
+        if let Expr::Literal { value: Token::Integer(value), span, .. } = index {
+            if *value >= length {
+                let error = format!("Index {} is out of bounds for an array of length {}", value, length);
+                return Err(Diagnostic::from(error).with_label(*span, ""));
+            }
+        }
+        Ok(*element)
+    }
+
+    /// Checks the value, or for a compound assignment the operation, that
+    /// `expr` stores in a field or element of type `ty`.
+    fn analyze_store(
+        &mut self,
+        expr: &Expr,
+        ty: &Type,
+        operator: Option<&Token>,
+        value: &Expr,
+        target: &str,
+    ) -> Result<(), Diagnostic> {
+        self.lint_parens(value);
+        let value_type = self.analyze_expr(value, Some(ty))?;
+        let result = match operator {
+            Some(operator) => {
+                let op = BinaryOperator::from_token(operator)
+                    .ok_or_else(|| format!("Invalid binary operator: {}", operator.describe()))?;
+                self.binary_type(expr, op, ty, &value_type)?
+            }
+            None => value_type,
+        };
+        self.expect_type(ty, &result, &source_text(expr), |ty, found| {
+            format!("Type mismatch: cannot assign {} to {} of type {}", found, target, ty)
+        })
+    }
+
+    /// Rejects a write to `target`, a field or element of `object`, unless
+    /// `object` is reached through a `mut` variable or a mutable static.
+    /// Values are shared, so a write is seen through every copy of them.
+    fn check_writable(&mut self, object: &Expr, target: &str) -> Result<(), Diagnostic> {
+        match object {
+            Expr::Field { object, .. } | Expr::Index { object, .. } | Expr::Grouping { expression: object, .. } => {
+                self.check_writable(object, target)
+            }
+            Expr::Variable { name, span, .. } => match self.resolution(object).map(|resolution| resolution.symbol.clone()) {
+                Some(SymbolKind::Variable { id, .. }) if !self.variables[id].mutable => {
+                    let message = format!(
+                        "Cannot assign to {} of immutable variable '{}'; declare it with 'mut' to allow assignment",
+                        target, name
+                    );
+                    Err(self.immutable_variable(message, name, *span, id))
+                }
+                Some(SymbolKind::Static { id }) if !self.statics[id].mutable => {
+                    Err(format!("Cannot assign to {} of immutable static '{}'", target, name).into())
+                }
+                Some(SymbolKind::Const { .. }) => Err(format!("Cannot assign to {} of constant '{}'", target, name).into()),
+                _ => Ok(()),
+            },
+            _ => Err(format!("Cannot assign to {} of a temporary value `{}`", target, source_text(object)).into()),
+        }
+    }
+
+    /// The error for an assignment through the immutable variable `id`,
+    /// written as `name` at `span`, with a fix that declares it `mut`.
+    fn immutable_variable(&self, message: String, name: &str, span: Span, id: usize) -> Diagnostic {
+        let declaration = self.variables[id].span;
+        Diagnostic::error(message)
+            .with_code(codes::IMMUTABLE_ASSIGNMENT)
+            .with_label(span, "cannot assign to an immutable variable")
+            .with_secondary_label(declaration, "declared here")
+            .with_fix(Fix {
+                message: format!("declare `{}` with `mut`", name),
+                span: Span::new(declaration.line, declaration.column, 0),
+                replacement: "mut ".to_string(),
+            })
+    }
+
+    /// Resolves the variable or static that `expr` assigns to, with its
+    /// type and, for a variable, its id and function depth.
+    fn assignment_target(&mut self, expr: &Expr, name: &str, span: Span) -> Result<(Type, Option<(usize, usize)>), Diagnostic> {
+        let symbol = self.resolve(expr, name, span)?;
+        let ty = symbol.symbol_type;
+        let variable = match symbol.kind {
+            // A closure could run any number of times, so only the declaring function may
+            // give an immutable variable its value.
+            SymbolKind::Variable { id, depth } if self.variables[id].deferred && depth == self.contexts.len() - 1 => {
+                Some((id, depth))
+            }
+            SymbolKind::Variable { id, .. } if !self.variables[id].mutable => {
+                let message = format!(
+                    "Cannot assign to immutable variable '{}'; declare it with 'mut' to allow assignment",
+                    name
+                );
+                return Err(self.immutable_variable(message, name, span, id));
+            }
+            SymbolKind::Variable { id, depth } => Some((id, depth)),
+            SymbolKind::Static { .. } if self.const_function().is_some() => {
+                return Err(self.static_in_const_function(name).into())
+            }
+            SymbolKind::Static { id } if !self.statics[id].mutable => {
+                return Err(format!("Cannot assign to immutable static '{}'", name).into())
+            }
+            SymbolKind::Static { .. } => {
+                self.lint(&MUTABLE_STATICS, span, format!("Use of mutable static '{}'", name));
+                None
+            }
+            SymbolKind::Function { .. } => return Err(format!("Cannot assign to function '{}'", name).into()),
+            SymbolKind::Const { .. } => return Err(format!("Cannot assign to constant '{}'", name).into()),
+        };
+        Ok((ty, variable))
+    }
+
+    fn expect_assignable(&mut self, ty: &Type, value_type: &Type, expr: &Expr, name: &str) -> Result<(), Diagnostic> {
+        self.expect_type(ty, value_type, &source_text(expr), |ty, found| {
+            format!(
+                "Type mismatch: cannot assign {} to variable '{}' of type {}",
+                found, name, ty
+            )
+        })
+    }
+
+    /// Records an assignment to the variable `assignment_target` found, if any.
+    fn note_assignment(&mut self, variable: Option<(usize, usize)>) -> Result<(), Diagnostic> {
+        if let Some((id, depth)) = variable {
+            self.note_use(id, depth)?;
+            self.variables[id].reassigned = true;
+        }
+        Ok(())
+    }
+
+    /// Gives an integer literal the numeric type its context expects, which
+    /// may still be inferred, and checks that its value fits. `negative` is set for the
+    /// operand of a unary minus, so that e.g. `-128` fits in `i8`.
+    fn analyze_integer_literal(
+        &mut self,
+        expr: &Expr,
+        value: u64,
+        negative: bool,
+        expected: Option<&Type>,
+    ) -> Result<Type, Diagnostic> {
+        let ty = match expected.map(|expected| self.inference.shallow(expected)) {
+            Some(expected) if expected.is_numeric() => expected,
+            None | Some(Type::Variable(_)) => {
+                let sign = if negative { "-" } else { "" };
+                let ty = self
+                    .inference
+                    .fresh(VariableKind::Integer, format!("the literal `{}{}`", sign, value));
+                self.deferred.push(DeferredCheck::Literal {
+                    value,
+                    negative,
+                    ty: ty.clone(),
+                    span: expr.span(),
+                });
+                self.expr_types.insert(expr.id(), ty.clone());
+                return Ok(ty);
+            }
+            Some(_) => Type::int(),
+        };
+        check_literal_range(value, negative, &ty, expr.span())?;
+        self.expr_types.insert(expr.id(), ty.clone());
+        Ok(ty)
+    }
+
+    /// Explicit conversions only change the type, so they are allowed between
+    /// types with the same representation, such as a `def` type and its underlying type.
+    fn analyze_conversion(&mut self, name: &str, arguments: &[Expr]) -> Result<Type, Diagnostic> {
+        let target = self.resolve_type(&TypeExpr::Named(name.to_string()))?;
+        let [argument] = arguments else {
+            return Err(format!("Conversion to {} takes exactly one argument", target).into());
+        };
+        let argument_type = self.analyze_expr(argument, Some(target.representation()))?;
+        if let Type::Variable(_) = self.inference.shallow(&argument_type) {
+            let site = format!("{}({})", name, source_text(argument));
+            let representation = target.representation().clone();
+            self.expect_type(&representation, &argument_type, &site, |_, found| {
+                format!("Cannot convert {} to {}", found, target)
+            })?;
+        } else if argument_type.representation() != target.representation() {
+            return Err(format!("Cannot convert {} to {}", argument_type, target).into());
+        }
+        Ok(target)
+    }
+
+    fn analyze_lambda(
+        &mut self,
+        params: &[Param],
+        return_type: &Option<TypeExpr>,
+        body: &[Stmt],
+        span: Span,
+        expected: Option<&Type>,
+    ) -> Result<Type, Diagnostic> {
+        let expected = expected.map(|expected| self.inference.resolve(expected));
+        let (expected_params, expected_return) = match expected.as_ref().map(Type::unaliased) {
+            Some(Type::Function { params: expected_params, return_type }) if expected_params.len() == params.len() => {
+                (Some(expected_params), Some((**return_type).clone()))
+            }
+            _ => (None, None),
+        };
+
+        let mut param_types = Vec::new();
+        for (index, param) in params.iter().enumerate() {
+            let ty = match (&param.type_annotation, expected_params.and_then(|types| types.get(index))) {
+                (Some(annotation), _) => self.resolve_type(annotation)?,
+                (None, Some(ty)) => ty.clone(),
+                // Inferred from the body or from a call.
+                (None, None) => self
+                    .inference
+                    .fresh(VariableKind::General, format!("lambda parameter '{}'", param.name)),
+            };
+            param_types.push(ty);
+        }
+        let declared_return = match return_type {
+            Some(annotation) => Some(self.resolve_type(annotation)?),
+            None => expected_return,
+        };
+
+        let id = self.lambdas.len();
+        self.lambdas.push(LambdaInfo {
+            ty: Type::Void,
+            captures: Vec::new(),
+            span,
+        });
+        self.contexts.push(FunctionContext {
+            return_type: declared_return,
+            lambda: Some(id),
+            top_level: false,
+            const_function: None,
+            item: None,
+            attributes: Attributes::default(),
+        });
+        self.begin_scope(body);
+        for (param, ty) in params.iter().zip(&param_types) {
+            self.declare_variable(&param.name, param.span, ty.clone(), param.mutable, Attributes::default())?;
+        }
+        for statement in body {
+            self.analyze_stmt(statement)?;
+        }
+        self.end_scope();
+        let context = self.contexts.pop().expect("lambda context was pushed above");
+
+        let ty = Type::Function {
+            params: param_types,
+            return_type: Box::new(context.return_type.unwrap_or(Type::Void)),
+        };
+        self.lambdas[id].ty = ty.clone();
+        Ok(ty)
+    }
+
+    fn function_type(&self, params: &[Param], return_type: &Option<TypeExpr>) -> Result<Type, Diagnostic> {
+        let mut param_types = Vec::new();
+        for param in params {
+            match &param.type_annotation {
+                Some(annotation) => param_types.push(self.resolve_type(annotation)?),
+                None => return Err(format!("Type annotation needed for parameter '{}'", param.name).into()),
+            }
+        }
+        let return_type = match return_type {
+            Some(annotation) => self.resolve_type(annotation)?,
+            None => Type::Void,
+        };
+        Ok(Type::Function {
+            params: param_types,
+            return_type: Box::new(return_type),
+        })
+    }
+
+    fn resolve_type(&self, ty: &TypeExpr) -> Result<Type, Diagnostic> {
+        self.resolve_type_with(ty, &HashMap::new())
+    }
+
+    /// Resolves a type in which the names in `bound` stand for the arguments
+    /// of the generic alias being expanded.
+    fn resolve_type_with(&self, ty: &TypeExpr, bound: &HashMap<String, Type>) -> Result<Type, Diagnostic> {
+        match ty {
+            TypeExpr::Named(name) => self.named_type(name, Vec::new(), bound),
+            TypeExpr::Generic { name, args } => {
+                let mut arg_types = Vec::new();
+                for arg in args {
+                    arg_types.push(self.resolve_type_with(arg, bound)?);
+                }
+                self.named_type(name, arg_types, bound)
+            }
+            TypeExpr::Function { params, return_type } => {
+                let mut param_types = Vec::new();
+                for param in params {
+                    param_types.push(self.resolve_type_with(param, bound)?);
+                }
+                Ok(Type::Function {
+                    params: param_types,
+                    return_type: Box::new(self.resolve_type_with(return_type, bound)?),
+                })
+            }
+            TypeExpr::Array { element, length } => Ok(Type::Array {
+                element: Box::new(self.resolve_type_with(element, bound)?),
+                length: *length,
+            }),
+        }
+    }
+
+    fn named_type(&self, name: &str, args: Vec<Type>, bound: &HashMap<String, Type>) -> Result<Type, Diagnostic> {
+        if let Some(ty) = bound.get(name) {
+            if !args.is_empty() {
+                return Err(format!("Type parameter '{}' does not take type arguments", name).into());
+            }
+            return Ok(ty.clone());
+        }
+        let expected_args = match self.type_declarations.get(name) {
+            Some(TypeDeclaration::Alias { params, .. }) => params.len(),
+            _ => 0,
+        };
+        if args.len() != expected_args {
+            return Err(format!(
+                "Type '{}' expects {} type arguments but got {}",
+                name,
+                expected_args,
+                args.len()
+            ).into());
+        }
+
+        match (name, self.type_declarations.get(name)) {
+            ("int", _) => Ok(Type::int()),
+            ("float", _) => Ok(Type::float()),
+            ("bool", _) => Ok(Type::Boolean),
+            ("string", _) => Ok(Type::String),
+            ("void", _) => Ok(Type::Void),
+            (_, None) if IntType::from_name(name).is_some() || FloatType::from_name(name).is_some() => {
+                Ok(IntType::from_name(name)
+                    .map(Type::Integer)
+                    .or_else(|| FloatType::from_name(name).map(Type::Float))
+                    .expect("checked above"))
+            }
+            (_, Some(TypeDeclaration::Alias { params, ty })) => {
+                let bound = params.iter().cloned().zip(args.iter().cloned()).collect();
+                Ok(Type::Alias {
+                    name: name.to_string(),
+                    args,
+                    target: Box::new(self.resolve_type_with(ty, &bound)?),
+                })
+            }
+            (_, Some(TypeDeclaration::Def { ty })) => Ok(Type::Nominal {
+                name: name.to_string(),
+                underlying: Box::new(self.resolve_type(ty)?),
+            }),
+            (_, Some(TypeDeclaration::Struct { fields })) => {
+                let mut field_types = Vec::new();
+                for (field, ty) in fields {
+                    field_types.push((field.clone(), self.resolve_type(ty)?));
                 }
+                Ok(Type::Nominal {
+                    name: name.to_string(),
+                    underlying: Box::new(Type::Struct { fields: field_types }),
+                })
             }
+            (_, Some(TypeDeclaration::Enum { .. })) => Ok(Type::Nominal {
+                name: name.to_string(),
+                underlying: Box::new(Type::int()),
+            }),
+            (_, None) => Err(format!("Unknown type '{}'", name).into()),
         }
     }
+
+    /// Declares a variable in the current scope, where it shadows any
+    /// declaration of the same name in an enclosing scope.
+    fn declare_variable(
+        &mut self,
+        name: &str,
+        span: Span,
+        ty: Type,
+        mutable: bool,
+        attributes: Attributes,
+    ) -> Result<(), Diagnostic> {
+        if self.symbol_table.contains_local(name) {
+            return Err(format!("'{}' is already defined in this scope", name).into());
+        }
+        if let Some(Symbol { kind: SymbolKind::Variable { id, .. }, .. }) = self.symbol_table.lookup(name) {
+            let shadowed = self.variables[*id].span;
+            let level = self.lint_level(&SHADOWING, Some(&attributes));
+            self.report(
+                &SHADOWING,
+                level,
+                Diagnostic::warning(format!("Variable '{}' shadows a variable of an enclosing scope", name))
+                    .with_label(span, "")
+                    .with_secondary_label(shadowed, "shadowed variable declared here"),
+            );
+        }
+        let scope = self.symbol_table.current();
+        if let Some(names) = self.undeclared.get_mut(&scope) {
+            names.remove(name);
+        }
+        let id = self.variables.len();
+        self.variables.push(VariableInfo {
+            name: name.to_string(),
+            span,
+            ty: ty.clone(),
+            mutable,
+            deferred: false,
+            reassigned: false,
+            used: false,
+            captured: false,
+            attributes,
+            scope,
+        });
+        let depth = self.contexts.len() - 1;
+        self.symbol_table.insert(name.to_string(), ty, SymbolKind::Variable { id, depth });
+        Ok(())
+    }
+
+    /// Rejects an initializer of `item` that cannot be evaluated at compile
+    /// time. Const functions it calls are checked when they are analyzed.
+    fn check_constant(&self, expr: &Expr, item: &str) -> Result<(), Diagnostic> {
+        match expr {
+            Expr::Literal { value, .. } => match value {
+                Token::Integer(_) | Token::Number(_) | Token::String(_) | Token::Reserved(Reserved::True | Reserved::False) => {
+                    Ok(())
+                }
+                _ => Err(format!("Initializer of {} must be a constant", item).into()),
+            },
+            Expr::Grouping { expression, .. } => self.check_constant(expression, item),
+            Expr::Unary { right, .. } => self.check_constant(right, item),
+            Expr::Cast { expr, .. } => self.check_constant(expr, item),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.check_constant(left, item)?;
+                self.check_constant(right, item)
+            }
+            Expr::Ternary { condition, then_branch, else_branch, .. } => {
+                self.check_constant(condition, item)?;
+                self.check_constant(then_branch, item)?;
+                self.check_constant(else_branch, item)
+            }
+            Expr::Variable { name, .. } => match self.symbol_table.lookup(name).map(|symbol| &symbol.kind) {
+                Some(SymbolKind::Static { .. }) => Err(format!("Initializer of {} cannot read static '{}'", item, name).into()),
+                // Unknown names are reported when the initializer is analyzed.
+                Some(SymbolKind::Const { .. }) | None => Ok(()),
+                Some(_) => Err(format!("Initializer of {} must be a constant", item).into()),
+            },
+            Expr::Call { callee, arguments, .. } => {
+                let Expr::Variable { name, .. } = callee.as_ref() else {
+                    return Err(format!("Initializer of {} must be a constant", item).into());
+                };
+                match self.symbol_table.lookup(name).map(|symbol| &symbol.kind) {
+                    Some(SymbolKind::Function { id }) if self.const_functions[*id] => {}
+                    Some(SymbolKind::Function { .. }) => {
+                        return Err(format!("Initializer of {} cannot call non-const function '{}'", item, name).into())
+                    }
+                    // A conversion such as `u8(5)`.
+                    None if self.is_type_name(name) => {}
+                    _ => return Err(format!("Initializer of {} must be a constant", item).into()),
+                }
+                arguments.iter().try_for_each(|argument| self.check_constant(argument, item))
+            }
+            Expr::StructLiteral { fields, .. } => fields.iter().try_for_each(|field| self.check_constant(&field.value, item)),
+            Expr::ArrayLiteral { elements, .. } => elements.iter().try_for_each(|element| self.check_constant(element, item)),
+            Expr::Field { object, .. } => self.check_constant(object, item),
+            Expr::Index { object, index, .. } => {
+                self.check_constant(object, item)?;
+                self.check_constant(index, item)
+            }
+            Expr::EnumVariant { .. } => Ok(()),
+            Expr::Assign { .. }
+            | Expr::CompoundAssign { .. }
+            | Expr::Update { .. }
+            | Expr::Lambda { .. }
+            | Expr::SetField { .. }
+            | Expr::SetIndex { .. } => Err(format!("Initializer of {} must be a constant", item).into()),
+        }
+    }
+
+    /// The `const fn` whose body is being analyzed, if any.
+    fn const_function(&self) -> Option<&str> {
+        self.contexts.last().and_then(|context| context.const_function.as_deref())
+    }
+
+    fn static_in_const_function(&self, name: &str) -> String {
+        format!(
+            "Cannot use static '{}' in const function '{}'",
+            name,
+            self.const_function().unwrap_or_default()
+        )
+    }
+
+    /// Declares a `static` item, whose initializer must be a constant so that
+    /// its value can be placed in the module's data segment.
+    fn declare_static(
+        &mut self,
+        name: &str,
+        mutable: bool,
+        type_annotation: &Option<TypeExpr>,
+        initializer: &Expr,
+    ) -> Result<(), Diagnostic> {
+        if self.symbol_table.contains_local(name) {
+            return Err(format!("'{}' is already defined in this scope", name).into());
+        }
+        self.check_constant(initializer, &format!("static '{}'", name))?;
+        let ty = match type_annotation {
+            Some(annotation) => {
+                let annotated = self.resolve_type(annotation)?;
+                let initializer_type = self.analyze_expr(initializer, Some(&annotated))?;
+                self.expect_type(&annotated, &initializer_type, &source_text(initializer), |annotated, found| {
+                    format!(
+                        "Type mismatch: static '{}' is declared as {} but initialized with {}",
+                        name, annotated, found
+                    )
+                })?;
+                annotated
+            }
+            None => self.analyze(initializer)?,
+        };
+        // Statics are laid out as a single slot each.
+        if matches!(self.inference.resolve(&ty).representation(), Type::Struct { .. } | Type::Array { .. }) {
+            return Err(format!("Static '{}' cannot hold a struct or array", name).into());
+        }
+        let id = self.statics.len();
+        self.statics.push(StaticInfo {
+            name: name.to_string(),
+            ty: ty.clone(),
+            mutable,
+        });
+        self.symbol_table.insert(name.to_string(), ty, SymbolKind::Static { id });
+        Ok(())
+    }
+
+    /// Resolves an identifier written at `span` in the current scope and
+    /// records the declaration it refers to.
+    fn resolve(&mut self, expr: &Expr, name: &str, span: Span) -> Result<Symbol, Diagnostic> {
+        let Some((scope, symbol)) = self.symbol_table.resolve(name) else {
+            let current = self.symbol_table.current();
+            let declared_later = self
+                .symbol_table
+                .ancestors(current)
+                .any(|scope| self.undeclared.get(&scope).is_some_and(|names| names.contains(name)));
+            let error = if declared_later {
+                Diagnostic::error(format!("Cannot use variable '{}' before its declaration", name))
+            } else {
+                Diagnostic::error(format!("Undefined variable '{}'", name))
+            }
+            .with_code(codes::UNDEFINED_NAME)
+            .with_label(span, "");
+            if declared_later {
+                return Err(error);
+            }
+            return Err(match closest_match(name, self.symbol_table.visible_names(current)) {
+                Some(suggestion) => error
+                    .with_help(did_you_mean(suggestion))
+                    .with_fix(Fix::rename(span, name, suggestion)),
+                None => error,
+            });
+        };
+        let symbol = symbol.clone();
+        self.resolutions.insert(
+            expr.id(),
+            Resolution {
+                symbol: symbol.kind.clone(),
+                scope,
+                span,
+            },
+        );
+        Ok(symbol)
+    }
+
+    /// Notes the variables `statements` declare in the current scope, so
+    /// that uses before their declarations can be reported as such.
+    fn declare_later(&mut self, statements: &[Stmt]) {
+        let names = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Stmt::Let { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        self.undeclared.insert(self.symbol_table.current(), names);
+    }
+
+    /// Records a use of a variable declared in the function at `depth`. When
+    /// that is an enclosing function, every lambda in between captures it.
+    fn note_use(&mut self, variable: usize, depth: usize) -> Result<(), Diagnostic> {
+        let current = self.contexts.len() - 1;
+        if depth == current {
+            return Ok(());
+        }
+        for context in &self.contexts[depth + 1..=current] {
+            match context.lambda {
+                Some(lambda) => {
+                    let captures = &mut self.lambdas[lambda].captures;
+                    if !captures.contains(&variable) {
+                        captures.push(variable);
+                    }
+                }
+                None => {
+                    return Err(format!(
+                        "Function items cannot capture local variable '{}'; use a closure instead",
+                        self.variables[variable].name
+                    ).into())
+                }
+            }
+        }
+        self.variables[variable].captured = true;
+        Ok(())
+    }
+
+    /// Opens the scope of a block, function or lambda body.
+    fn begin_scope(&mut self, statements: &[Stmt]) {
+        self.symbol_table.enter_scope();
+        self.declare_later(statements);
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.symbol_table.current();
+        self.lint_unused_variables(scope);
+        self.undeclared.remove(&scope);
+        self.symbol_table.exit_scope();
+    }
+
+    /// The level of a lint inside the current function, where `attributes`
+    /// are those of the declaration the lint is about, if any.
+    fn lint_level(&self, lint: &Lint, attributes: Option<&Attributes>) -> LintLevel {
+        let declared = attributes
+            .into_iter()
+            .chain(self.contexts.iter().rev().map(|context| &context.attributes))
+            .find_map(|attributes| attributes.lint_level(lint));
+        self.lint_levels.level(lint, declared)
+    }
+
+    fn report(&mut self, lint: &Lint, level: LintLevel, diagnostic: impl Into<Diagnostic>) {
+        if let Some(diagnostic) = at_level(lint, level, diagnostic.into()) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn lint(&mut self, lint: &Lint, span: Span, message: String) {
+        let level = self.lint_level(lint, None);
+        self.report(lint, level, Diagnostic::warning(message).with_label(span, ""));
+    }
+
+    /// Lints parentheses around an expression whose position already
+    /// delimits it, such as an initializer or a call argument. Parentheses
+    /// around atomic expressions are linted wherever they appear.
+    fn lint_parens(&mut self, expr: &Expr) {
+        if let Expr::Grouping { expression, from_macro: false, .. } = expr {
+            if !is_atomic(expression) {
+                self.lint(
+                    &REDUNDANT_PARENS,
+                    expression.span(),
+                    format!("Unnecessary parentheses around `{}`", source_text(expression)),
+                );
+            }
+        }
+    }
+
+    /// Lints the variables declared in a scope that is being closed and
+    /// were never read.
+    fn lint_unused_variables(&mut self, scope: ScopeId) {
+        let unused: Vec<usize> = (0..self.variables.len())
+            .filter(|&id| {
+                let variable = &self.variables[id];
+                variable.scope == scope && !variable.used && !variable.name.starts_with('_')
+            })
+            .collect();
+        for id in unused {
+            let variable = &self.variables[id];
+            let level = self.lint_level(&UNUSED_VARIABLES, Some(&variable.attributes));
+            let warning = Diagnostic::warning(format!("Variable '{}' is never used", variable.name))
+                .with_label(variable.span, "")
+                .with_help(format!("if this is intentional, name it `_{}`", variable.name));
+            self.report(&UNUSED_VARIABLES, level, warning);
+        }
+    }
+
+    /// Lints function items that are never used outside their own body,
+    /// except entry points, exports and tests.
+    fn lint_unused_functions(&mut self, statements: &[Stmt]) {
+        let names = statements.iter().filter_map(|statement| match statement {
+            Stmt::Function { name, .. } => Some(name),
+            _ => None,
+        });
+        for (id, name) in names.enumerate() {
+            let attributes = &self.function_attributes[id];
+            if self.used_functions[id]
+                || self.called_by_conditions.contains(name)
+                || name == "main"
+                || name.starts_with('_')
+                || attributes.export.is_some()
+                || attributes.test
+            {
+                continue;
+            }
+            let level = self.lint_level(&UNUSED_FUNCTIONS, Some(attributes));
+            self.report(&UNUSED_FUNCTIONS, level, format!("Function '{}' is never used", name));
+        }
+    }
+}
+
+/// Collects the type names a type expression mentions.
+fn type_names<'b>(ty: &'b TypeExpr, names: &mut Vec<&'b str>) {
+    match ty {
+        TypeExpr::Named(name) => names.push(name),
+        TypeExpr::Generic { name, args } => {
+            names.push(name);
+            for arg in args {
+                type_names(arg, names);
+            }
+        }
+        TypeExpr::Function { params, return_type } => {
+            for param in params {
+                type_names(param, names);
+            }
+            type_names(return_type, names);
+        }
+        TypeExpr::Array { element, .. } => type_names(element, names),
+    }
+}
+
+/// The fields of a struct type, looking through aliases and `def` types.
+pub fn struct_fields(ty: &Type) -> Option<&[(String, Type)]> {
+    match ty.representation() {
+        Type::Struct { fields } => Some(fields),
+        _ => None,
+    }
+}
+
+/// Checks the attributes of an item other than a function. None of them
+/// change how the item is compiled yet.
+fn check_item_attributes(statement: &Stmt) -> Result<(), Diagnostic> {
+    let (attributes, target) = match statement {
+        Stmt::Static { attributes, .. } => (attributes, AttributeTarget::Static),
+        Stmt::Const { attributes, .. } => (attributes, AttributeTarget::Constant),
+        Stmt::TypeAlias { attributes, .. } | Stmt::TypeDef { attributes, .. } => (attributes, AttributeTarget::Type),
+        Stmt::Struct { attributes, .. } => (attributes, AttributeTarget::Struct),
+        Stmt::Enum { attributes, .. } => (attributes, AttributeTarget::Enum),
+        _ => return Ok(()),
+    };
+    if Attributes::resolve(attributes, target)?.packed {
+        return Err("Attribute '@packed' is not supported yet: struct fields always take 8-byte slots".to_string().into());
+    }
+    Ok(())
+}
+
+/// Rejects a struct item that declares a field twice.
+fn struct_declaration(fields: &[StructField]) -> Result<TypeDeclaration, Diagnostic> {
+    let mut declared: Vec<(String, TypeExpr)> = Vec::new();
+    for field in fields {
+        if declared.iter().any(|(name, _)| *name == field.name) {
+            let error = format!("Field '{}' is declared more than once", field.name);
+            return Err(Diagnostic::from(error).with_label(field.span, ""));
+        }
+        declared.push((field.name.clone(), field.ty.clone()));
+    }
+    Ok(TypeDeclaration::Struct { fields: declared })
+}
+
+/// Reports that struct `struct_name` has no field `name`, suggesting the
+/// closest of `fields` if one is near enough.
+fn unknown_field<'f>(struct_name: &str, name: &str, span: Span, fields: impl IntoIterator<Item = &'f str>) -> Diagnostic {
+    let error = Diagnostic::from(format!("Struct '{}' has no field '{}'", struct_name, name)).with_label(span, "");
+    match closest_match(name, fields) {
+        Some(suggestion) => error.with_help(did_you_mean(suggestion)).with_fix(Fix::rename(span, name, suggestion)),
+        None => error,
+    }
+}
+
+fn enum_declaration(variants: &[EnumVariant]) -> Result<TypeDeclaration, Diagnostic> {
+    let mut declared: Vec<String> = Vec::new();
+    for variant in variants {
+        if declared.contains(&variant.name) {
+            let error = format!("Variant '{}' is declared more than once", variant.name);
+            return Err(Diagnostic::from(error).with_label(variant.span, ""));
+        }
+        declared.push(variant.name.clone());
+    }
+    Ok(TypeDeclaration::Enum { variants: declared })
+}
+
+fn check_literal_range(value: u64, negative: bool, ty: &Type, span: Span) -> Result<(), Diagnostic> {
+    if let Type::Integer(int) = ty.unaliased() {
+        let limit = if negative { int.min_magnitude() } else { int.max() };
+        if value > limit {
+            let message = format!(
+                "Literal {}{} is out of range for type {}",
+                if negative { "-" } else { "" },
+                value,
+                ty
+            );
+            return Err(Diagnostic::error(message).with_code(codes::TYPE_MISMATCH).with_label(span, ""));
+        }
+    }
+    Ok(())
+}
+
+/// Operators the operand types do not support are type mismatches, however
+/// the types were found.
+fn operator_mismatch(message: String, span: Span) -> Diagnostic {
+    Diagnostic::error(message).with_code(codes::TYPE_MISMATCH).with_label(span, "")
+}
+
+/// A numeric literal, possibly negated or parenthesized, whose type is
+/// decided by the context it appears in.
+fn is_numeric_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { value, .. } => matches!(value, Token::Integer(_) | Token::Number(_)),
+        Expr::Grouping { expression, .. } => is_numeric_literal(expression),
+        Expr::Unary { operator: Token::Operation(Operation::Subtract), right, .. } => is_numeric_literal(right),
+        _ => false,
+    }
+}
+
+/// An expression that parentheses can never regroup.
+fn is_atomic(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Literal { .. }
+            | Expr::Variable { .. }
+            | Expr::Call { .. }
+            | Expr::Grouping { .. }
+            | Expr::Field { .. }
+            | Expr::Index { .. }
+            | Expr::EnumVariant { .. }
+            | Expr::ArrayLiteral { .. }
+    )
+}
+
+/// The message of the `float_equality` lint for a comparison of floats
+/// with `==` or `!=`.
+fn float_comparison(op: BinaryOperator, left: &Type, right: &Type) -> Option<String> {
+    let is_float = |ty: &Type| matches!(ty.representation(), Type::Float(_));
+    let equality = matches!(op, BinaryOperator::Equal | BinaryOperator::NotEqual);
+    (equality && (is_float(left) || is_float(right)))
+        .then(|| format!("Comparison of floating-point values with '{}'", op))
 }
@@ -0,0 +1,221 @@
+use std::fmt::{Display, Formatter};
+
+use crate::parser::ast::{Attribute, AttributeArgument};
+use crate::semantic::lints::{find_lints, names_lint, Lint, LintLevel};
+
+/// The kinds of declaration an attribute can be written on: every item,
+/// and `let` statements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeTarget {
+    Function,
+    Variable,
+    Static,
+    Constant,
+    /// `alias` and `def` declarations.
+    Type,
+    Struct,
+    Enum,
+}
+
+impl Display for AttributeTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeTarget::Function => write!(f, "functions"),
+            AttributeTarget::Variable => write!(f, "variables"),
+            AttributeTarget::Static => write!(f, "statics"),
+            AttributeTarget::Constant => write!(f, "constants"),
+            AttributeTarget::Type => write!(f, "type declarations"),
+            AttributeTarget::Struct => write!(f, "structs"),
+            AttributeTarget::Enum => write!(f, "enums"),
+        }
+    }
+}
+
+/// The argument list a built-in attribute accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttributeArguments {
+    /// `@name`
+    None,
+    /// `@name("text")`
+    String,
+    /// `@name(a, b, ...)` with at least one identifier.
+    Identifiers,
+}
+
+pub struct BuiltinAttribute {
+    pub name: &'static str,
+    pub arguments: AttributeArguments,
+    pub targets: &'static [AttributeTarget],
+}
+
+/// Every attribute the compiler understands. Anything else is an error.
+pub const BUILTIN_ATTRIBUTES: &[BuiltinAttribute] = &[
+    BuiltinAttribute {
+        name: "inline",
+        arguments: AttributeArguments::None,
+        targets: &[AttributeTarget::Function],
+    },
+    BuiltinAttribute {
+        name: "noinline",
+        arguments: AttributeArguments::None,
+        targets: &[AttributeTarget::Function],
+    },
+    BuiltinAttribute {
+        name: "export",
+        arguments: AttributeArguments::String,
+        targets: &[AttributeTarget::Function],
+    },
+    BuiltinAttribute {
+        name: "deprecated",
+        arguments: AttributeArguments::String,
+        targets: &[AttributeTarget::Function],
+    },
+    BuiltinAttribute {
+        name: "test",
+        arguments: AttributeArguments::None,
+        targets: &[AttributeTarget::Function],
+    },
+    BuiltinAttribute {
+        name: "allow",
+        arguments: AttributeArguments::Identifiers,
+        targets: &[AttributeTarget::Function, AttributeTarget::Variable],
+    },
+    BuiltinAttribute {
+        name: "warn",
+        arguments: AttributeArguments::Identifiers,
+        targets: &[AttributeTarget::Function, AttributeTarget::Variable],
+    },
+    BuiltinAttribute {
+        name: "deny",
+        arguments: AttributeArguments::Identifiers,
+        targets: &[AttributeTarget::Function, AttributeTarget::Variable],
+    },
+    BuiltinAttribute {
+        name: "packed",
+        arguments: AttributeArguments::None,
+        targets: &[AttributeTarget::Struct],
+    },
+];
+
+pub fn builtin_attribute(name: &str) -> Option<&'static BuiltinAttribute> {
+    BUILTIN_ATTRIBUTES.iter().find(|attribute| attribute.name == name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InlineHint {
+    Always,
+    Never,
+}
+
+/// The checked attributes of one declaration, in the form later stages consume.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributes {
+    pub inline: Option<InlineHint>,
+    /// The name the function is exported under, in addition to the entry point.
+    pub export: Option<String>,
+    pub deprecated: Option<String>,
+    pub test: bool,
+    /// Lint names allowed inside the declaration.
+    pub allow: Vec<String>,
+    /// Lint names reported as warnings inside the declaration.
+    pub warn: Vec<String>,
+    /// Lint names reported as errors inside the declaration.
+    pub deny: Vec<String>,
+    /// Whether a struct asks for its fields to be laid out without padding.
+    pub packed: bool,
+}
+
+impl Attributes {
+    /// Checks attributes written on a declaration of the given kind.
+    pub fn resolve(attributes: &[Attribute], target: AttributeTarget) -> Result<Self, String> {
+        let mut resolved = Attributes::default();
+        let mut seen: Vec<&str> = Vec::new();
+
+        for attribute in attributes {
+            let builtin =
+                builtin_attribute(&attribute.name).ok_or_else(|| format!("Unknown attribute '@{}'", attribute.name))?;
+            if !builtin.targets.contains(&target) {
+                let allowed: Vec<String> = builtin.targets.iter().map(|target| target.to_string()).collect();
+                return Err(format!(
+                    "Attribute '@{}' cannot be applied to {}; it applies to {}",
+                    attribute.name,
+                    target,
+                    allowed.join(" and ")
+                ));
+            }
+            if seen.contains(&builtin.name) {
+                return Err(format!("Attribute '@{}' is applied more than once", attribute.name));
+            }
+            seen.push(builtin.name);
+
+            match (builtin.name, arguments(attribute, builtin.arguments)?) {
+                ("inline", _) => resolved.inline = Some(InlineHint::Always),
+                ("noinline", _) => resolved.inline = Some(InlineHint::Never),
+                ("export", mut names) => resolved.export = names.pop(),
+                ("deprecated", mut messages) => resolved.deprecated = messages.pop(),
+                ("test", _) => resolved.test = true,
+                ("allow", lints) => resolved.allow = lints,
+                ("warn", lints) => resolved.warn = lints,
+                ("deny", lints) => resolved.deny = lints,
+                ("packed", _) => resolved.packed = true,
+                (name, _) => unreachable!("built-in attribute '@{}' has no handler", name),
+            }
+        }
+
+        if seen.contains(&"inline") && seen.contains(&"noinline") {
+            return Err("Attributes '@inline' and '@noinline' cannot be combined".to_string());
+        }
+        if resolved.export.as_deref() == Some("") {
+            return Err("Attribute '@export' needs a non-empty name".to_string());
+        }
+        for name in resolved.allow.iter().chain(&resolved.warn).chain(&resolved.deny) {
+            if find_lints(name).is_none() {
+                return Err(format!("Unknown lint '{}'", name));
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// The level these attributes set for a lint, if they name it. When a
+    /// lint is named at several levels the strictest one applies.
+    pub fn lint_level(&self, lint: &Lint) -> Option<LintLevel> {
+        [
+            (&self.deny, LintLevel::Deny),
+            (&self.warn, LintLevel::Warn),
+            (&self.allow, LintLevel::Allow),
+        ]
+        .into_iter()
+        .find(|(names, _)| names.iter().any(|name| names_lint(name, lint)))
+        .map(|(_, level)| level)
+    }
+}
+
+/// Checks the argument list of an attribute against the shape it expects
+/// and returns the argument values.
+fn arguments(attribute: &Attribute, expected: AttributeArguments) -> Result<Vec<String>, String> {
+    let values = match expected {
+        AttributeArguments::None if attribute.arguments.is_empty() => Some(Vec::new()),
+        AttributeArguments::String => match &attribute.arguments[..] {
+            [AttributeArgument::String(value)] => Some(vec![value.clone()]),
+            _ => None,
+        },
+        AttributeArguments::Identifiers if !attribute.arguments.is_empty() => attribute
+            .arguments
+            .iter()
+            .map(|argument| match argument {
+                AttributeArgument::Identifier(name) => Some(name.clone()),
+                AttributeArgument::String(_) => None,
+            })
+            .collect(),
+        _ => None,
+    };
+
+    values.ok_or_else(|| {
+        let usage = match expected {
+            AttributeArguments::None => "takes no arguments",
+            AttributeArguments::String => "takes a single string argument",
+            AttributeArguments::Identifiers => "takes one or more names",
+        };
+        format!("Attribute '@{}' {}", attribute.name, usage)
+    })
+}
@@ -0,0 +1,379 @@
+use crate::lexer::token::{Operation, Reserved, Token};
+use crate::parser::ast::{Expr, TypeExpr};
+use crate::semantic::operators::{BinaryOperator, UnaryOperator};
+use crate::semantic::symbol_table::Type;
+
+/// What a type variable may be bound to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariableKind {
+    /// Any type.
+    General,
+    /// The type of an integer literal: any numeric type, `int` by default.
+    Integer,
+    /// The type of a float literal: any float type, `float` by default.
+    Float,
+}
+
+struct TypeVariable {
+    kind: VariableKind,
+    /// What the variable stands for, e.g. "variable 'x'", for diagnostics.
+    origin: String,
+    binding: Option<Type>,
+    /// Source of the expression whose typing bound the variable.
+    reason: Option<String>,
+}
+
+/// Two types that could not be unified, as far as they are known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub expected: Type,
+    pub found: Type,
+}
+
+/// Type variables and their bindings, which unification fills in as
+/// analysis learns how each value is used.
+///
+/// Variables are monomorphic: a lambda parameter without an annotation gets
+/// one type, decided by the body or by the first call that forces it.
+#[derive(Default)]
+pub struct Inference {
+    variables: Vec<TypeVariable>,
+}
+
+impl Inference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fresh(&mut self, kind: VariableKind, origin: impl Into<String>) -> Type {
+        self.variables.push(TypeVariable {
+            kind,
+            origin: origin.into(),
+            binding: None,
+            reason: None,
+        });
+        Type::Variable(self.variables.len() - 1)
+    }
+
+    /// `ty` with bound variables at its top level replaced, so that its
+    /// outermost constructor is known if anything is.
+    pub fn shallow(&self, ty: &Type) -> Type {
+        let mut ty = ty;
+        while let Type::Variable(id) = ty {
+            match &self.variables[*id].binding {
+                Some(binding) => ty = binding,
+                None => break,
+            }
+        }
+        ty.clone()
+    }
+
+    /// `ty` with every bound variable replaced by its binding.
+    pub fn resolve(&self, ty: &Type) -> Type {
+        match self.shallow(ty) {
+            Type::Function { params, return_type } => Type::Function {
+                params: params.iter().map(|param| self.resolve(param)).collect(),
+                return_type: Box::new(self.resolve(&return_type)),
+            },
+            Type::Reference(inner) => Type::Reference(Box::new(self.resolve(&inner))),
+            Type::Array { element, length } => Type::Array {
+                element: Box::new(self.resolve(&element)),
+                length,
+            },
+            ty => ty,
+        }
+    }
+
+    /// Like `resolve`, but shows literal types that are still open as their
+    /// defaults, which is what they become unless something else forces them.
+    pub fn settled(&self, ty: &Type) -> Type {
+        match self.shallow(ty) {
+            Type::Variable(id) => match self.variables[id].kind {
+                VariableKind::General => Type::Variable(id),
+                VariableKind::Integer => Type::int(),
+                VariableKind::Float => Type::float(),
+            },
+            Type::Function { params, return_type } => Type::Function {
+                params: params.iter().map(|param| self.settled(param)).collect(),
+                return_type: Box::new(self.settled(&return_type)),
+            },
+            Type::Reference(inner) => Type::Reference(Box::new(self.settled(&inner))),
+            Type::Array { element, length } => Type::Array {
+                element: Box::new(self.settled(&element)),
+                length,
+            },
+            ty => ty,
+        }
+    }
+
+    /// Makes `found` and `expected` the same type by binding variables in
+    /// either. `reason` is the source of the expression that requires it.
+    pub fn unify(&mut self, expected: &Type, found: &Type, reason: &str) -> Result<(), Mismatch> {
+        if self.unify_types(expected, found, reason) {
+            Ok(())
+        } else {
+            Err(Mismatch {
+                expected: self.settled(expected),
+                found: self.settled(found),
+            })
+        }
+    }
+
+    fn unify_types(&mut self, expected: &Type, found: &Type, reason: &str) -> bool {
+        let (expected, found) = (self.shallow(expected), self.shallow(found));
+        match (&expected, &found) {
+            (Type::Variable(left), Type::Variable(right)) if left == right => true,
+            (Type::Variable(left), Type::Variable(right)) => {
+                let Some(kind) = merge_kinds(self.variables[*left].kind, self.variables[*right].kind) else {
+                    return false;
+                };
+                // The more general variable is bound, so that the other keeps
+                // its literal default.
+                let (bound, target) = if self.variables[*left].kind == VariableKind::General {
+                    (*left, *right)
+                } else {
+                    (*right, *left)
+                };
+                self.variables[target].kind = kind;
+                self.bind(bound, Type::Variable(target), reason);
+                true
+            }
+            (Type::Variable(id), ty) | (ty, Type::Variable(id)) => {
+                if !self.admits(*id, ty) || self.occurs(*id, ty) {
+                    return false;
+                }
+                self.bind(*id, ty.clone(), reason);
+                true
+            }
+            _ => match (expected.unaliased(), found.unaliased()) {
+                (
+                    Type::Function { params, return_type },
+                    Type::Function {
+                        params: found_params,
+                        return_type: found_return,
+                    },
+                ) if params.len() == found_params.len() => {
+                    params
+                        .iter()
+                        .zip(found_params)
+                        .all(|(param, found_param)| self.unify_types(param, found_param, reason))
+                        && self.unify_types(return_type, found_return, reason)
+                }
+                (Type::Reference(inner), Type::Reference(found_inner)) => self.unify_types(inner, found_inner, reason),
+                (
+                    Type::Array { element, length },
+                    Type::Array {
+                        element: found_element,
+                        length: found_length,
+                    },
+                ) if length == found_length => self.unify_types(element, found_element, reason),
+                _ => expected == found,
+            },
+        }
+    }
+
+    fn bind(&mut self, id: usize, ty: Type, reason: &str) {
+        let variable = &mut self.variables[id];
+        variable.binding = Some(ty);
+        variable.reason = Some(reason.to_string());
+    }
+
+    fn admits(&self, id: usize, ty: &Type) -> bool {
+        match self.variables[id].kind {
+            VariableKind::General => true,
+            VariableKind::Integer => ty.is_numeric(),
+            VariableKind::Float => matches!(ty.unaliased(), Type::Float(_)),
+        }
+    }
+
+    fn occurs(&self, id: usize, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Variable(other) => other == id,
+            Type::Function { params, return_type } => {
+                params.iter().any(|param| self.occurs(id, param)) || self.occurs(id, &return_type)
+            }
+            Type::Reference(inner) | Type::Array { element: inner, .. } => self.occurs(id, &inner),
+            _ => false,
+        }
+    }
+
+    /// Binds a literal type that is still open to its default.
+    pub fn default_literal(&mut self, ty: &Type) {
+        if let Type::Variable(id) = self.shallow(ty) {
+            let default = match self.variables[id].kind {
+                VariableKind::General => return,
+                VariableKind::Integer => Type::int(),
+                VariableKind::Float => Type::float(),
+            };
+            self.bind(id, default, "its default type");
+        }
+    }
+
+    /// Binds every open literal type to its default, once nothing else can
+    /// constrain it.
+    pub fn default_literals(&mut self) {
+        for id in 0..self.variables.len() {
+            self.default_literal(&Type::Variable(id));
+        }
+    }
+
+    /// What the first variable still open in `ty` stands for, if any.
+    pub fn unresolved(&self, ty: &Type) -> Option<&str> {
+        match self.shallow(ty) {
+            Type::Variable(id) => Some(&self.variables[id].origin),
+            Type::Function { params, return_type } => params
+                .iter()
+                .find_map(|param| self.unresolved(param))
+                .or_else(|| self.unresolved(&return_type)),
+            Type::Reference(inner) | Type::Array { element: inner, .. } => self.unresolved(&inner),
+            _ => None,
+        }
+    }
+
+    /// Notes on which expressions forced the inferred parts of `ty`.
+    pub fn explain(&self, ty: &Type) -> Vec<String> {
+        let mut notes = Vec::new();
+        self.collect_notes(ty, &mut notes);
+        notes
+    }
+
+    fn collect_notes(&self, ty: &Type, notes: &mut Vec<String>) {
+        match ty {
+            Type::Variable(id) => {
+                let variable = &self.variables[*id];
+                if let (Some(binding), Some(reason)) = (&variable.binding, &variable.reason) {
+                    notes.push(format!(
+                        "{} is inferred as {} from `{}`",
+                        variable.origin,
+                        self.settled(binding),
+                        reason
+                    ));
+                    self.collect_notes(binding, notes);
+                }
+            }
+            Type::Function { params, return_type } => {
+                for param in params {
+                    self.collect_notes(param, notes);
+                }
+                self.collect_notes(return_type, notes);
+            }
+            Type::Reference(inner) | Type::Array { element: inner, .. } => self.collect_notes(inner, notes),
+            _ => {}
+        }
+    }
+}
+
+fn merge_kinds(left: VariableKind, right: VariableKind) -> Option<VariableKind> {
+    match (left, right) {
+        (VariableKind::General, kind) | (kind, VariableKind::General) => Some(kind),
+        (VariableKind::Integer, VariableKind::Integer) => Some(VariableKind::Integer),
+        // An integer literal may also be used as a float.
+        (VariableKind::Float | VariableKind::Integer, VariableKind::Float | VariableKind::Integer) => {
+            Some(VariableKind::Float)
+        }
+    }
+}
+
+/// An expression as it would be written in source, for diagnostics.
+/// Lambda bodies are elided.
+pub fn source_text(expr: &Expr) -> String {
+    match expr {
+        Expr::Assign { name, value, .. } => format!("{} = {}", name, source_text(value)),
+        Expr::CompoundAssign { name, operator, value, .. } => {
+            let operator = BinaryOperator::from_token(operator).map(|op| op.symbol()).unwrap_or("?");
+            format!("{} {}= {}", name, operator, source_text(value))
+        }
+        Expr::Binary { left, operator, right, .. } | Expr::Logical { left, operator, right, .. } => {
+            let operator = BinaryOperator::from_token(operator)
+                .map(|op| op.symbol().to_string())
+                .unwrap_or_else(|| operator.to_string());
+            format!("{} {} {}", source_text(left), operator, source_text(right))
+        }
+        Expr::Call { callee, arguments, .. } => {
+            let arguments: Vec<String> = arguments.iter().map(source_text).collect();
+            format!("{}({})", source_text(callee), arguments.join(", "))
+        }
+        Expr::Cast { expr, ty, .. } => format!("{} as {}", source_text(expr), type_text(ty)),
+        Expr::ArrayLiteral { elements, .. } => {
+            let elements: Vec<String> = elements.iter().map(source_text).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Expr::StructLiteral { name, fields, .. } => {
+            let fields: Vec<String> =
+                fields.iter().map(|field| format!("{}: {}", field.name, source_text(&field.value))).collect();
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        Expr::EnumVariant { enum_name, variant, .. } => format!("{}::{}", enum_name, variant),
+        Expr::Field { object, name, .. } => format!("{}.{}", source_text(object), name),
+        Expr::Index { object, index, .. } => format!("{}[{}]", source_text(object), source_text(index)),
+        Expr::SetField { object, name, operator, value, .. } => {
+            let target = format!("{}.{}", source_text(object), name);
+            format!("{} {}= {}", target, assignment_operator(operator.as_ref()), source_text(value))
+        }
+        Expr::SetIndex { object, index, operator, value, .. } => {
+            let target = format!("{}[{}]", source_text(object), source_text(index));
+            format!("{} {}= {}", target, assignment_operator(operator.as_ref()), source_text(value))
+        }
+        Expr::Grouping { expression, .. } => format!("({})", source_text(expression)),
+        Expr::Lambda { params, .. } => {
+            let params: Vec<&str> = params.iter().map(|param| param.name.as_str()).collect();
+            format!("|{}| ...", params.join(", "))
+        }
+        Expr::Literal { value, .. } => match value {
+            Token::Integer(value) => value.to_string(),
+            Token::Number(value) if value.fract() == 0.0 && value.is_finite() => format!("{:.1}", value),
+            Token::Number(value) => value.to_string(),
+            Token::String(value) => format!("{:?}", value),
+            Token::Reserved(Reserved::True) => "true".to_string(),
+            Token::Reserved(Reserved::False) => "false".to_string(),
+            Token::Reserved(Reserved::Null) => "null".to_string(),
+            other => other.to_string(),
+        },
+        Expr::Ternary { condition, then_branch, else_branch, .. } => format!(
+            "{} ? {} : {}",
+            source_text(condition),
+            source_text(then_branch),
+            source_text(else_branch)
+        ),
+        Expr::Unary { operator, right, .. } => {
+            let operator = UnaryOperator::from_token(operator)
+                .map(|op| op.symbol().to_string())
+                .unwrap_or_else(|| operator.to_string());
+            format!("{}{}", operator, source_text(right))
+        }
+        Expr::Update { name, operator, prefix, .. } => {
+            let operator = match operator {
+                Token::Operation(Operation::Add) => "++",
+                _ => "--",
+            };
+            match prefix {
+                true => format!("{}{}", operator, name),
+                false => format!("{}{}", name, operator),
+            }
+        }
+        Expr::Variable { name, .. } => name.clone(),
+    }
+}
+
+/// The operator a compound assignment writes before its `=`, if any.
+fn assignment_operator(operator: Option<&Token>) -> &'static str {
+    match operator {
+        Some(operator) => BinaryOperator::from_token(operator).map(|op| op.symbol()).unwrap_or("?"),
+        None => "",
+    }
+}
+
+fn type_text(ty: &TypeExpr) -> String {
+    match ty {
+        TypeExpr::Named(name) => name.clone(),
+        TypeExpr::Generic { name, args } => {
+            let args: Vec<String> = args.iter().map(type_text).collect();
+            format!("{}<{}>", name, args.join(", "))
+        }
+        TypeExpr::Function { params, return_type } => {
+            let params: Vec<String> = params.iter().map(type_text).collect();
+            format!("fn({}) -> {}", params.join(", "), type_text(return_type))
+        }
+        TypeExpr::Array { element, length } => format!("[{}; {}]", type_text(element), length),
+    }
+}
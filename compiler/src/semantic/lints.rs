@@ -0,0 +1,175 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// What happens when a lint fires.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    /// The lint is not reported.
+    Allow,
+    /// The lint is reported as a warning and compilation continues.
+    Warn,
+    /// The lint is reported as an error and compilation fails.
+    Deny,
+}
+
+impl Display for LintLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // `pad` so that the level can be aligned in tables.
+        f.pad(match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        })
+    }
+}
+
+/// A named check for code that is valid but probably not what was meant.
+pub struct Lint {
+    pub name: &'static str,
+    pub default_level: LintLevel,
+    pub description: &'static str,
+}
+
+pub static UNUSED_VARIABLES: Lint = Lint {
+    name: "unused_variables",
+    default_level: LintLevel::Warn,
+    description: "variables and parameters that are never read; names starting with '_' are exempt",
+};
+
+pub static UNUSED_FUNCTIONS: Lint = Lint {
+    name: "unused_functions",
+    default_level: LintLevel::Warn,
+    description: "functions that are never used outside their own body",
+};
+
+pub static UNUSED_IMPORTS: Lint = Lint {
+    name: "unused_imports",
+    default_level: LintLevel::Warn,
+    description: "imports that are never used; never fires until the language has imports",
+};
+
+pub static DEAD_CODE: Lint = Lint {
+    name: "dead_code",
+    default_level: LintLevel::Warn,
+    description: "statements that can never run",
+};
+
+pub static SHADOWING: Lint = Lint {
+    name: "shadowing",
+    default_level: LintLevel::Allow,
+    description: "variables that shadow a variable of an enclosing scope",
+};
+
+pub static FLOAT_EQUALITY: Lint = Lint {
+    name: "float_equality",
+    default_level: LintLevel::Warn,
+    description: "floating-point values compared with '==' or '!='",
+};
+
+pub static REDUNDANT_PARENS: Lint = Lint {
+    name: "redundant_parens",
+    default_level: LintLevel::Warn,
+    description: "parentheses that do not change how an expression is grouped",
+};
+
+pub static DEPRECATED: Lint = Lint {
+    name: "deprecated",
+    default_level: LintLevel::Warn,
+    description: "uses of functions marked '@deprecated'",
+};
+
+pub static MUTABLE_STATICS: Lint = Lint {
+    name: "mutable_statics",
+    default_level: LintLevel::Warn,
+    description: "reads and writes of a 'static mut'",
+};
+
+/// Every lint the compiler knows.
+pub static LINTS: &[&Lint] = &[
+    &UNUSED_VARIABLES,
+    &UNUSED_FUNCTIONS,
+    &UNUSED_IMPORTS,
+    &DEAD_CODE,
+    &SHADOWING,
+    &FLOAT_EQUALITY,
+    &REDUNDANT_PARENS,
+    &DEPRECATED,
+    &MUTABLE_STATICS,
+];
+
+/// Names that stand for several lints at once.
+pub static LINT_GROUPS: &[(&str, &[&str])] =
+    &[("unused", &["unused_variables", "unused_functions", "unused_imports", "dead_code"])];
+
+/// The lints a lint or group name stands for.
+pub fn find_lints(name: &str) -> Option<Vec<&'static Lint>> {
+    if let Some(lint) = LINTS.iter().find(|lint| lint.name == name) {
+        return Some(vec![*lint]);
+    }
+    let (_, members) = LINT_GROUPS.iter().find(|(group, _)| *group == name)?;
+    Some(LINTS.iter().copied().filter(|lint| members.contains(&lint.name)).collect())
+}
+
+/// Whether a lint or group name covers `lint`.
+pub fn names_lint(name: &str, lint: &Lint) -> bool {
+    name == lint.name || LINT_GROUPS.iter().any(|(group, members)| *group == name && members.contains(&lint.name))
+}
+
+/// Lint levels set for a whole compilation, e.g. from the command line.
+/// Attributes in the source override them for the declarations they are
+/// written on.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<&'static str, LintLevel>,
+    /// Report every lint that would warn as an error instead.
+    pub deny_warnings: bool,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the level of a lint, or of every lint in a group.
+    pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        let lints = find_lints(name).ok_or_else(|| format!("Unknown lint '{}'", name))?;
+        for lint in lints {
+            self.levels.insert(lint.name, level);
+        }
+        Ok(())
+    }
+
+    /// The level of a lint where the source declares `declared`, if
+    /// anything, after `deny_warnings` is applied.
+    pub fn level(&self, lint: &Lint, declared: Option<LintLevel>) -> LintLevel {
+        let level = declared
+            .or_else(|| self.levels.get(lint.name).copied())
+            .unwrap_or(lint.default_level);
+        match level {
+            LintLevel::Warn if self.deny_warnings => LintLevel::Deny,
+            level => level,
+        }
+    }
+}
+
+/// The diagnostic of a lint that fires at `level`, with the lint's name as
+/// its code: a warning, an error if the lint is denied, or nothing if it is
+/// allowed.
+pub fn at_level(lint: &Lint, level: LintLevel, diagnostic: Diagnostic) -> Option<Diagnostic> {
+    let diagnostic = diagnostic.with_code(lint.name);
+    match level {
+        LintLevel::Allow => None,
+        LintLevel::Warn => Some(Diagnostic {
+            severity: Severity::Warning,
+            ..diagnostic
+        }),
+        LintLevel::Deny => Some(
+            Diagnostic {
+                severity: Severity::Error,
+                ..diagnostic
+            }
+            .with_note(format!("lint '{}' is denied", lint.name)),
+        ),
+    }
+}
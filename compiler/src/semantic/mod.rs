@@ -1,2 +1,6 @@
 pub mod analyzer;
+pub mod attributes;
+pub mod inference;
+pub mod lints;
+pub mod operators;
 pub mod symbol_table;
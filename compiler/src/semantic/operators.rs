@@ -0,0 +1,218 @@
+use std::fmt::{Display, Formatter};
+
+use crate::lexer::token::{Operation, Token};
+use crate::semantic::symbol_table::{FloatType, Type};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+/// How a binary operator types its operands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperatorClass {
+    /// Numeric operands converted to a common type, which is also the result
+    /// type; `+` additionally concatenates strings.
+    Arithmetic,
+    /// Numeric operands converted to a common type; the result is a `bool`.
+    Comparison,
+    /// Operands of the same comparable type, after numeric conversion; the
+    /// result is a `bool`.
+    Equality,
+    /// `bool` operands and result.
+    Logical,
+}
+
+impl BinaryOperator {
+    pub fn from_token(token: &Token) -> Option<Self> {
+        let Token::Operation(operation) = token else {
+            return None;
+        };
+        Some(match operation {
+            Operation::Add => BinaryOperator::Add,
+            Operation::Subtract => BinaryOperator::Subtract,
+            Operation::Multiply => BinaryOperator::Multiply,
+            Operation::Divide => BinaryOperator::Divide,
+            Operation::Modulo => BinaryOperator::Remainder,
+            Operation::IfEqual => BinaryOperator::Equal,
+            Operation::NotEqual => BinaryOperator::NotEqual,
+            Operation::Less => BinaryOperator::Less,
+            Operation::LessEqual => BinaryOperator::LessEqual,
+            Operation::Greater => BinaryOperator::Greater,
+            Operation::GreaterEqual => BinaryOperator::GreaterEqual,
+            Operation::And => BinaryOperator::And,
+            Operation::Or => BinaryOperator::Or,
+            _ => return None,
+        })
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        }
+    }
+
+    pub fn class(self) -> OperatorClass {
+        match self {
+            BinaryOperator::Add
+            | BinaryOperator::Subtract
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Remainder => OperatorClass::Arithmetic,
+            BinaryOperator::Less | BinaryOperator::LessEqual | BinaryOperator::Greater | BinaryOperator::GreaterEqual => {
+                OperatorClass::Comparison
+            }
+            BinaryOperator::Equal | BinaryOperator::NotEqual => OperatorClass::Equality,
+            BinaryOperator::And | BinaryOperator::Or => OperatorClass::Logical,
+        }
+    }
+}
+
+impl Display for BinaryOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+impl UnaryOperator {
+    pub fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Operation(Operation::Subtract) => Some(UnaryOperator::Negate),
+            Token::Operation(Operation::Not) => Some(UnaryOperator::Not),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        }
+    }
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// The result of typing a binary operation: both operands are converted to
+/// `operands` before the operation, which produces a `result`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatorTyping {
+    pub operands: Type,
+    pub result: Type,
+}
+
+pub fn binary_typing(op: BinaryOperator, left: &Type, right: &Type) -> Result<OperatorTyping, String> {
+    let mismatch = || {
+        format!(
+            "Type mismatch: cannot apply operator '{}' to types {} and {}",
+            op, left, right
+        )
+    };
+    let typing = match op.class() {
+        OperatorClass::Arithmetic if op == BinaryOperator::Add && is_string(left) && is_string(right) => {
+            Some((left.clone(), left.clone()))
+        }
+        OperatorClass::Arithmetic => arithmetic_conversion(left, right).map(|ty| (ty.clone(), ty)),
+        OperatorClass::Comparison => arithmetic_conversion(left, right).map(|ty| (ty, Type::Boolean)),
+        OperatorClass::Equality => arithmetic_conversion(left, right)
+            .or_else(|| (left == right && is_comparable(left)).then(|| left.clone()))
+            .map(|ty| (ty, Type::Boolean)),
+        OperatorClass::Logical => {
+            (*left == Type::Boolean && *right == Type::Boolean).then_some((Type::Boolean, Type::Boolean))
+        }
+    };
+    let (operands, result) = typing.ok_or_else(mismatch)?;
+    Ok(OperatorTyping { operands, result })
+}
+
+pub fn unary_typing(op: UnaryOperator, operand: &Type) -> Result<Type, String> {
+    let allowed = match op {
+        // Unsigned values cannot be negated.
+        UnaryOperator::Negate => match operand.representation() {
+            Type::Integer(int) => int.is_signed(),
+            Type::Float(_) => true,
+            _ => false,
+        },
+        UnaryOperator::Not => *operand == Type::Boolean,
+    };
+    if !allowed {
+        return Err(format!("Type mismatch: cannot apply operator '{}' to type {}", op, operand));
+    }
+    Ok(operand.clone())
+}
+
+/// The usual arithmetic conversions: the type two numeric operands are
+/// converted to, if one of them can represent every value of the other.
+///
+/// Integers widen within their signedness, and unsigned integers also widen
+/// to strictly wider signed ones. `f32` widens to `f64`, and integers
+/// convert to a float type whose significand holds all of their values.
+/// Integers of the same width but different type, such as `i32` and
+/// `isize`, need an explicit cast. `def` types only combine with themselves.
+pub fn arithmetic_conversion(left: &Type, right: &Type) -> Option<Type> {
+    if left == right {
+        return matches!(left.representation(), Type::Integer(_) | Type::Float(_)).then(|| left.clone());
+    }
+    let widens = |from: &Type, to: &Type| match (from.unaliased(), to.unaliased()) {
+        (Type::Integer(from), Type::Integer(to)) => {
+            to.bits() > from.bits() && (from.is_signed() == to.is_signed() || to.is_signed())
+        }
+        (Type::Float(FloatType::F32), Type::Float(FloatType::F64)) => true,
+        (Type::Integer(from), Type::Float(FloatType::F32)) => from.bits() <= 16,
+        (Type::Integer(from), Type::Float(FloatType::F64)) => from.bits() <= 32,
+        _ => false,
+    };
+    if widens(left, right) {
+        Some(right.clone())
+    } else if widens(right, left) {
+        Some(left.clone())
+    } else {
+        None
+    }
+}
+
+fn is_string(ty: &Type) -> bool {
+    *ty.unaliased() == Type::String
+}
+
+/// Types whose values `==` and `!=` can compare.
+fn is_comparable(ty: &Type) -> bool {
+    matches!(
+        ty.representation(),
+        Type::Integer(_) | Type::Float(_) | Type::Boolean | Type::String
+    )
+}
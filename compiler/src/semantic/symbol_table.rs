@@ -1,50 +1,414 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Clone, PartialEq)]
+/// Integer types. `isize` and `usize` have the width of a WebAssembly
+/// address, 32 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntType {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Isize,
+    Usize,
+}
+
+impl IntType {
+    pub const ALL: [IntType; 10] = [
+        IntType::I8,
+        IntType::I16,
+        IntType::I32,
+        IntType::I64,
+        IntType::U8,
+        IntType::U16,
+        IntType::U32,
+        IntType::U64,
+        IntType::Isize,
+        IntType::Usize,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::Isize => "isize",
+            IntType::Usize => "usize",
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            IntType::I8 | IntType::U8 => 8,
+            IntType::I16 | IntType::U16 => 16,
+            IntType::I32 | IntType::U32 | IntType::Isize | IntType::Usize => 32,
+            IntType::I64 | IntType::U64 => 64,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        matches!(self, IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64 | IntType::Isize)
+    }
+
+    /// The smallest value of the type, as a magnitude below zero.
+    pub fn min_magnitude(self) -> u64 {
+        if self.is_signed() {
+            1 << (self.bits() - 1)
+        } else {
+            0
+        }
+    }
+
+    pub fn max(self) -> u64 {
+        match (self.is_signed(), self.bits()) {
+            (false, 64) => u64::MAX,
+            (false, bits) => (1 << bits) - 1,
+            (true, bits) => (1 << (bits - 1)) - 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatType {
+    F32,
+    F64,
+}
+
+impl FloatType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "f32" => Some(FloatType::F32),
+            "f64" => Some(FloatType::F64),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FloatType::F32 => "f32",
+            FloatType::F64 => "f64",
+        }
+    }
+}
+
+/// Types compare structurally, except that aliases are transparent and
+/// `def` types are only equal to themselves.
+#[derive(Debug, Clone)]
 pub enum Type {
-    Integer,
-    Float,
+    Integer(IntType),
+    Float(FloatType),
     String,
     Boolean,
     Null,
     Void,
+    Function {
+        params: Vec<Type>,
+        return_type: Box<Type>,
+    },
+    /// The fields of a struct, in declaration order. Only appears as the
+    /// underlying type of the `Nominal` type a `struct` item declares.
+    Struct {
+        fields: Vec<(String, Type)>,
+    },
+    /// `[element; length]`, a fixed-length array on the heap.
+    Array {
+        element: Box<Type>,
+        length: u64,
+    },
+    /// A heap cell holding a value of the inner type. Introduced by the
+    /// compiler for variables captured by reference; it cannot be written in source.
+    Reference(Box<Type>),
+    /// A use of `alias Name<args> = target`. It is interchangeable with its
+    /// target but keeps its name for diagnostics.
+    Alias {
+        name: String,
+        args: Vec<Type>,
+        target: Box<Type>,
+    },
+    /// A `def Name = underlying` type: represented like its underlying type,
+    /// but distinct from it and only converted explicitly.
+    Nominal {
+        name: String,
+        underlying: Box<Type>,
+    },
+    /// A type still being inferred, numbered by `Inference`. None remain
+    /// once analysis succeeds.
+    Variable(usize),
+}
+
+impl Type {
+    /// `int`, the built-in alias for `i64` and the type of integer literals
+    /// that nothing else gives a type.
+    pub fn int() -> Type {
+        Type::Alias {
+            name: "int".to_string(),
+            args: Vec::new(),
+            target: Box::new(Type::Integer(IntType::I64)),
+        }
+    }
+
+    /// `float`, the built-in alias for `f64` and the default type of
+    /// literals with a fractional part.
+    pub fn float() -> Type {
+        Type::Alias {
+            name: "float".to_string(),
+            args: Vec::new(),
+            target: Box::new(Type::Float(FloatType::F64)),
+        }
+    }
+
+    /// Whether the type is an integer or float type, looking through aliases
+    /// but not `def` types.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self.unaliased(), Type::Integer(_) | Type::Float(_))
+    }
+
+    /// The type with any aliases around it expanded.
+    pub fn unaliased(&self) -> &Type {
+        match self {
+            Type::Alias { target, .. } => target.unaliased(),
+            ty => ty,
+        }
+    }
+
+    /// The type values are stored as, looking through aliases and `def` types.
+    pub fn representation(&self) -> &Type {
+        match self.unaliased() {
+            Type::Nominal { underlying, .. } => underlying.representation(),
+            ty => ty,
+        }
+    }
+
+    /// Whether no part of the type is still being inferred.
+    pub fn is_resolved(&self) -> bool {
+        match self {
+            Type::Function { params, return_type } => params.iter().all(Type::is_resolved) && return_type.is_resolved(),
+            Type::Struct { fields } => fields.iter().all(|(_, ty)| ty.is_resolved()),
+            Type::Array { element, .. } => element.is_resolved(),
+            Type::Reference(inner) => inner.is_resolved(),
+            Type::Alias { args, target, .. } => args.iter().all(Type::is_resolved) && target.is_resolved(),
+            Type::Nominal { underlying, .. } => underlying.is_resolved(),
+            Type::Variable(_) => false,
+            _ => true,
+        }
+    }
+}
+
+impl PartialEq for Type {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unaliased(), other.unaliased()) {
+            (Type::Integer(left), Type::Integer(right)) => left == right,
+            (Type::Float(left), Type::Float(right)) => left == right,
+            (Type::String, Type::String)
+            | (Type::Boolean, Type::Boolean)
+            | (Type::Null, Type::Null)
+            | (Type::Void, Type::Void) => true,
+            (
+                Type::Function { params, return_type },
+                Type::Function {
+                    params: other_params,
+                    return_type: other_return_type,
+                },
+            ) => params == other_params && return_type == other_return_type,
+            (Type::Struct { fields }, Type::Struct { fields: other_fields }) => fields == other_fields,
+            (
+                Type::Array { element, length },
+                Type::Array {
+                    element: other_element,
+                    length: other_length,
+                },
+            ) => element == other_element && length == other_length,
+            (Type::Reference(inner), Type::Reference(other_inner)) => inner == other_inner,
+            (Type::Nominal { name, .. }, Type::Nominal { name: other_name, .. }) => name == other_name,
+            (Type::Variable(left), Type::Variable(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+/// Prints types as they are written in source, keeping alias names.
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Integer(ty) => write!(f, "{}", ty.name()),
+            Type::Float(ty) => write!(f, "{}", ty.name()),
+            Type::String => write!(f, "string"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Null => write!(f, "null"),
+            Type::Void => write!(f, "void"),
+            Type::Function { params, return_type } => {
+                let params: Vec<String> = params.iter().map(|param| param.to_string()).collect();
+                write!(f, "fn({}) -> {}", params.join(", "), return_type)
+            }
+            Type::Struct { fields } => {
+                let fields: Vec<String> = fields.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Type::Array { element, length } => write!(f, "[{}; {}]", element, length),
+            Type::Reference(inner) => write!(f, "ref {}", inner),
+            Type::Alias { name, args, .. } if args.is_empty() => write!(f, "{}", name),
+            Type::Alias { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+            Type::Nominal { name, .. } => write!(f, "{}", name),
+            Type::Variable(_) => write!(f, "_"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    /// A local variable or parameter, numbered in declaration order and
+    /// declared in the function at `depth` of the enclosing-function stack.
+    Variable { id: usize, depth: usize },
+    /// A function item, numbered in declaration order.
+    Function { id: usize },
+    /// A `static` item, numbered in declaration order.
+    Static { id: usize },
+    /// A `const` item, numbered in declaration order.
+    Const { id: usize },
 }
 
 #[derive(Debug, Clone)]
 pub struct Symbol {
     pub name: String,
     pub symbol_type: Type,
+    pub kind: SymbolKind,
+}
+
+/// Index of a scope in a `SymbolTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(usize);
+
+impl ScopeId {
+    /// The scope of top-level declarations.
+    pub const ROOT: ScopeId = ScopeId(0);
 }
 
 #[derive(Debug, Clone)]
-pub struct SymbolTable {
+pub struct Scope {
+    pub parent: Option<ScopeId>,
     symbols: HashMap<String, Symbol>,
-    parent: Option<Box<SymbolTable>>,
 }
 
-impl SymbolTable {
-    pub fn new(parent: Option<Box<SymbolTable>>) -> Self {
+impl Scope {
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.values()
+    }
+}
+
+/// Every scope of a program, kept after it is exited so that later stages
+/// can look names up in it again. Scopes form a tree through their parents.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+    current: ScopeId,
+}
+
+impl Default for SymbolTable {
+    /// A table with only the root scope.
+    fn default() -> Self {
         Self {
-            symbols: HashMap::new(),
-            parent,
+            scopes: vec![Scope {
+                parent: None,
+                symbols: HashMap::new(),
+            }],
+            current: ScopeId::ROOT,
         }
     }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    pub fn insert(&mut self, name: String, symbol_type: Type) {
+    /// Opens a scope nested in the current one and makes it current.
+    pub fn enter_scope(&mut self) -> ScopeId {
+        let id = ScopeId(self.scopes.len());
+        self.scopes.push(Scope {
+            parent: Some(self.current),
+            symbols: HashMap::new(),
+        });
+        self.current = id;
+        id
+    }
+
+    pub fn exit_scope(&mut self) {
+        self.current = self.scopes[self.current.0]
+            .parent
+            .expect("exit_scope called without a matching enter_scope");
+    }
+
+    pub fn current(&self) -> ScopeId {
+        self.current
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    /// `id` followed by each of its enclosing scopes, innermost first.
+    pub fn ancestors(&self, id: ScopeId) -> impl Iterator<Item = ScopeId> + '_ {
+        std::iter::successors(Some(id), |id| self.scopes[id.0].parent)
+    }
+
+    pub fn insert(&mut self, name: String, symbol_type: Type, kind: SymbolKind) {
         let symbol = Symbol {
             name: name.clone(),
             symbol_type,
+            kind,
         };
-        self.symbols.insert(name, symbol);
+        self.scopes[self.current.0].symbols.insert(name, symbol);
     }
 
     pub fn lookup(&self, name: &str) -> Option<&Symbol> {
-        if let Some(symbol) = self.symbols.get(name) {
-            Some(symbol)
-        } else if let Some(parent) = &self.parent {
-            parent.lookup(name)
-        } else {
-            None
+        self.lookup_in(self.current, name)
+    }
+
+    /// Like `lookup`, also returning the scope the symbol is declared in.
+    pub fn resolve(&self, name: &str) -> Option<(ScopeId, &Symbol)> {
+        self.ancestors(self.current)
+            .find_map(|id| self.scopes[id.0].symbols.get(name).map(|symbol| (id, symbol)))
+    }
+
+    /// Looks `name` up as seen from `scope`, which need not be current.
+    pub fn lookup_in(&self, scope: ScopeId, name: &str) -> Option<&Symbol> {
+        self.ancestors(scope).find_map(|id| self.scopes[id.0].symbols.get(name))
+    }
+
+    pub fn symbols_mut(&mut self) -> impl Iterator<Item = &mut Symbol> {
+        self.scopes.iter_mut().flat_map(|scope| scope.symbols.values_mut())
+    }
+
+    /// The names visible from `scope`, innermost scope first and in
+    /// alphabetical order within a scope.
+    pub fn visible_names(&self, scope: ScopeId) -> Vec<&str> {
+        let mut names = Vec::new();
+        for id in self.ancestors(scope) {
+            let mut declared: Vec<&str> = self.scopes[id.0].symbols.keys().map(|name| name.as_str()).collect();
+            declared.sort_unstable();
+            names.extend(declared);
         }
+        names
+    }
+
+    /// Whether `name` is declared directly in the current scope, ignoring parents.
+    pub fn contains_local(&self, name: &str) -> bool {
+        self.scopes[self.current.0].symbols.contains_key(name)
     }
 }
@@ -0,0 +1,183 @@
+use crate::codegen::wat::WatGenerator;
+use crate::diagnostics::{Diagnostic, DiagnosticSink};
+use crate::hir::conditional::resolve_conditionals;
+use crate::hir::hir::HirProgram;
+use crate::hir::lowerer::HirLowerer;
+use crate::lexer::lexer::Lexer;
+use crate::lir::lir::LirModule;
+use crate::lir::lowerer::LirLowerer;
+use crate::mir::lowerer::MirLowerer;
+use crate::mir::mir::MirModule;
+use crate::mir::passes::OptLevel;
+use crate::parser::ast::Stmt;
+use crate::parser::pratt_parser::Parser;
+use crate::semantic::analyzer::SemanticAnalyzer;
+use crate::semantic::lints::LintLevels;
+use crate::{compile, CompileOptions, CompileOutput, Emit};
+
+pub mod tests_attributes;
+pub mod tests_closures;
+pub mod tests_compile;
+pub mod tests_consts;
+pub mod tests_desugar;
+pub mod tests_diagnostics;
+pub mod tests_flow;
+pub mod tests_hir_types;
+pub mod tests_inference;
+pub mod tests_lints;
+pub mod tests_macros;
+pub mod tests_mir;
+pub mod tests_mir_text;
+pub mod tests_mutability;
+pub mod tests_node_ids;
+pub mod tests_numeric;
+pub mod tests_operators;
+pub mod tests_optimizations;
+pub mod tests_parser;
+pub mod tests_scopes;
+pub mod tests_ssa;
+pub mod tests_structs;
+pub mod tests_suggestions;
+pub mod tests_types;
+pub mod tests_verify;
+
+pub fn parse(source: &str) -> Result<Vec<Stmt>, String> {
+    let mut sink = DiagnosticSink::new();
+    let tokens = Lexer::new(source).tokenize(&mut sink)?;
+    Ok(Parser::new(tokens).parse(&mut sink)?)
+}
+
+/// Parses and analyzes a program the way `compile` does, passing every
+/// diagnostic to `sink`, and returns the statements the analyzer checked
+/// along with it.
+pub fn analyze_with(
+    source: &str,
+    lint_levels: LintLevels,
+    sink: &mut DiagnosticSink,
+) -> Result<(Vec<Stmt>, SemanticAnalyzer), String> {
+    let tokens = Lexer::new(source).tokenize(sink)?;
+    let ast = Parser::new(tokens).parse(sink)?;
+    let mut analyzer = SemanticAnalyzer::new();
+    analyzer.lint_levels = lint_levels;
+    let ast = resolve_conditionals(ast, &mut analyzer, sink)?;
+    analyzer.analyze_program(&ast, sink)?;
+    Ok((ast, analyzer))
+}
+
+pub fn analyze(source: &str) -> Result<SemanticAnalyzer, String> {
+    let (_, analyzer) = analyze_with(source, LintLevels::new(), &mut DiagnosticSink::new())?;
+    Ok(analyzer)
+}
+
+/// The messages of the warnings analysis reports.
+pub fn warnings(source: &str) -> Vec<String> {
+    let mut sink = DiagnosticSink::new();
+    analyze_with(source, LintLevels::new(), &mut sink).unwrap();
+    sink.warnings().map(|warning| warning.message.clone()).collect()
+}
+
+/// Lowers a program to HIR, passing every diagnostic to `sink`.
+pub fn lower_with(source: &str, lint_levels: LintLevels, sink: &mut DiagnosticSink) -> Result<HirProgram, String> {
+    let (ast, analyzer) = analyze_with(source, lint_levels, sink)?;
+    Ok(HirLowerer::new().lower_program(&ast, &analyzer, sink)?)
+}
+
+pub fn lower(source: &str) -> Result<HirProgram, String> {
+    lower_with(source, LintLevels::new(), &mut DiagnosticSink::new())
+}
+
+/// Lowers a program to LIR without optimizing it.
+pub fn lower_to_lir(source: &str) -> Result<LirModule, String> {
+    let mut sink = DiagnosticSink::new();
+    let mir = MirLowerer::new().lower(lower_with(source, LintLevels::new(), &mut sink)?, &mut sink)?;
+    Ok(LirLowerer::new().lower(mir, &mut sink)?)
+}
+
+/// The WebAssembly text of a program that is not optimized.
+pub fn wat(source: &str) -> String {
+    WatGenerator::new().generate(&lower_to_lir(source).unwrap(), &mut DiagnosticSink::new()).unwrap()
+}
+
+/// Compiles a program to MIR at `opt_level`, passing every diagnostic and
+/// the remarks of the optimizer to `sink`.
+pub fn mir_at(source: &str, opt_level: OptLevel, sink: &mut DiagnosticSink) -> MirModule {
+    let options = CompileOptions {
+        emit: Emit::Mir,
+        opt_level,
+        ..CompileOptions::new()
+    };
+    match compile(source, &options, sink).unwrap() {
+        CompileOutput::Mir(module) => module,
+        other => panic!("expected MIR, found {:?}", other),
+    }
+}
+
+pub fn mir(source: &str) -> MirModule {
+    mir_at(source, OptLevel::O0, &mut DiagnosticSink::new())
+}
+
+/// Compiles a program to WebAssembly.
+pub fn build(source: &str) -> Result<CompileOutput, Diagnostic> {
+    let options = CompileOptions {
+        emit: Emit::Wasm,
+        ..CompileOptions::new()
+    };
+    compile(source, &options, &mut DiagnosticSink::new())
+}
+
+/// The first error compiling a program reports.
+pub fn compile_error(source: &str) -> Diagnostic {
+    build(source).expect_err("the program should not compile")
+}
+
+/// The message of the first error compiling a program reports, with its
+/// help if it has any.
+pub fn error(source: &str) -> String {
+    compile_error(source).into()
+}
+
+/// Compiles a program to WebAssembly at every optimization level, runs it
+/// and returns the value of `main` converted to `f64`. Fails if the levels
+/// disagree.
+pub fn run(source: &str) -> Result<f64, String> {
+    let unoptimized = run_at(source, OptLevel::O0);
+    for level in [OptLevel::O1, OptLevel::O2] {
+        let optimized = run_at(source, level);
+        if optimized != unoptimized {
+            return Err(format!("-O{} gives {:?} but -O0 gives {:?}", level.name(), optimized, unoptimized));
+        }
+    }
+    unoptimized
+}
+
+pub fn run_at(source: &str, opt_level: OptLevel) -> Result<f64, String> {
+    let options = CompileOptions {
+        emit: Emit::Wasm,
+        opt_level,
+        ..CompileOptions::new()
+    };
+    let binary = match compile(source, &options, &mut DiagnosticSink::new())? {
+        CompileOutput::Wasm(binary) => binary,
+        other => return Err(format!("Expected a WebAssembly binary, found {:?}", other)),
+    };
+
+    let engine = wasmi::Engine::default();
+    let module = wasmi::Module::new(&engine, &binary[..]).map_err(|e| e.to_string())?;
+    let mut store = wasmi::Store::new(&engine, ());
+    let instance = wasmi::Linker::<()>::new(&engine)
+        .instantiate(&mut store, &module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(|e| e.to_string())?;
+    let main = instance
+        .get_func(&store, "main")
+        .ok_or_else(|| "Module has no 'main' export".to_string())?;
+    let mut results: Vec<wasmi::Value> = main.ty(&store).results().iter().map(|ty| wasmi::Value::default(*ty)).collect();
+    main.call(&mut store, &[], &mut results).map_err(|e| e.to_string())?;
+    match results.first() {
+        Some(wasmi::Value::I32(value)) => Ok(*value as f64),
+        Some(wasmi::Value::I64(value)) => Ok(*value as f64),
+        Some(wasmi::Value::F32(value)) => Ok(f32::from(*value) as f64),
+        Some(wasmi::Value::F64(value)) => Ok(f64::from(*value)),
+        other => Err(format!("'main' returned {:?}", other)),
+    }
+}
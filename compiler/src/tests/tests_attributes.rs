@@ -0,0 +1,160 @@
+use crate::codegen::wat::WatGenerator;
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::parser::ast::{AttributeArgument, Stmt};
+use crate::parser::pratt_parser::Parser;
+use crate::semantic::attributes::InlineHint;
+use crate::tests::{analyze, lower_to_lir, warnings};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attributes_parse() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("@export(\"area\") @allow(unused, shadowing) fn f() {}").tokenize(&mut sink).unwrap();
+        let statements = Parser::new(tokens).parse(&mut sink).unwrap();
+
+        match &statements[..] {
+            [Stmt::Function { attributes, .. }] => {
+                assert_eq!(attributes.len(), 2);
+                assert_eq!(attributes[0].name, "export");
+                assert_eq!(attributes[0].arguments, vec![AttributeArgument::String("area".to_string())]);
+                assert_eq!(attributes[1].name, "allow");
+                assert_eq!(
+                    attributes[1].arguments,
+                    vec![
+                        AttributeArgument::Identifier("unused".to_string()),
+                        AttributeArgument::Identifier("shadowing".to_string())
+                    ]
+                );
+            }
+            _ => panic!("Expected function, found {:?}", statements),
+        }
+    }
+
+    #[test]
+    fn test_attributes_need_a_declaration() {
+        let parse_error = |source: &str| {
+            let mut sink = DiagnosticSink::new();
+            let tokens = Lexer::new(source).tokenize(&mut sink).unwrap();
+            Parser::new(tokens).parse(&mut sink).unwrap_err().message
+        };
+        let expected = "Attributes must be followed by an item or let declaration.";
+
+        assert_eq!(parse_error("@inline 1 + 2"), expected);
+        assert_eq!(parse_error("@allow(unused) while false { }"), expected);
+    }
+
+    #[test]
+    fn test_unknown_attribute() {
+        assert!(analyze("@fast fn f() {}").is_err());
+    }
+
+    #[test]
+    fn test_attribute_targets() {
+        assert!(analyze("@allow(unused) let x = 1;").is_ok());
+        assert!(analyze("@inline let x = 1;").is_err());
+        assert_eq!(
+            analyze("@packed fn f() {}").err().unwrap(),
+            "Attribute '@packed' cannot be applied to functions; it applies to structs"
+        );
+        // Every item kind takes attributes, which are checked against it.
+        assert_eq!(
+            analyze("@inline static mut N: int = 0;").err().unwrap(),
+            "Attribute '@inline' cannot be applied to statics; it applies to functions"
+        );
+        assert_eq!(
+            analyze("@test const N: int = 0;").err().unwrap(),
+            "Attribute '@test' cannot be applied to constants; it applies to functions"
+        );
+        assert_eq!(
+            analyze("@allow(unused) alias Id = int;").err().unwrap(),
+            "Attribute '@allow' cannot be applied to type declarations; it applies to functions and variables"
+        );
+        assert_eq!(
+            analyze("@packed enum E { A }").err().unwrap(),
+            "Attribute '@packed' cannot be applied to enums; it applies to structs"
+        );
+        assert_eq!(analyze("@fast def Meters = float;").err().unwrap(), "Unknown attribute '@fast'");
+    }
+
+    #[test]
+    fn test_packed_structs_are_not_supported_yet() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("@packed struct P { x: int, y: i32 }").tokenize(&mut sink).unwrap();
+        match &Parser::new(tokens).parse(&mut sink).unwrap()[..] {
+            [Stmt::Struct { attributes, .. }] => assert_eq!(attributes[0].name, "packed"),
+            statements => panic!("Expected struct, found {:?}", statements),
+        }
+
+        assert_eq!(
+            analyze("@packed struct P { x: int, y: i32 }").err().unwrap(),
+            "Attribute '@packed' is not supported yet: struct fields always take 8-byte slots"
+        );
+        assert_eq!(
+            analyze("@packed(x) struct P { x: int }").err().unwrap(),
+            "Attribute '@packed' takes no arguments"
+        );
+    }
+
+    #[test]
+    fn test_attribute_arguments() {
+        assert!(analyze("@inline(always) fn f() {}").is_err());
+        assert!(analyze("@export fn f() {}").is_err());
+        assert!(analyze("@export(f) fn f() {}").is_err());
+        assert!(analyze("@allow fn f() {}").is_err());
+        assert!(analyze("@allow(\"unused\") fn f() {}").is_err());
+    }
+
+    #[test]
+    fn test_conflicting_attributes() {
+        assert!(analyze("@inline @inline fn f() {}").is_err());
+        assert!(analyze("@inline @noinline fn f() {}").is_err());
+        assert!(analyze("@export(\"g\") fn f() {} @export(\"g\") fn g() {}").is_err());
+        assert!(analyze("@export(\"main\") fn f() {}").is_err());
+        assert!(analyze("@test fn t(x: float) {}").is_err());
+    }
+
+    #[test]
+    fn test_deprecated_function_use_warns() {
+        assert_eq!(
+            warnings("@deprecated(\"use g\") fn f() {} fn g() {} g(); f()"),
+            vec!["Use of deprecated function 'f': use g".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_attributes_reach_lir() {
+        let module = lower_to_lir("@inline fn f() {} @noinline @test fn g() {} @allow(unused) let x = 1; f(); g()")
+            .unwrap();
+
+        assert_eq!(module.functions[0].attributes.inline, Some(InlineHint::Always));
+        assert_eq!(module.functions[1].attributes.inline, Some(InlineHint::Never));
+        assert!(module.functions[1].attributes.test);
+        assert!(module.functions[module.entry].attributes.allow.is_empty());
+    }
+
+    #[test]
+    fn test_export_adds_wasm_export() {
+        let mut sink = DiagnosticSink::new();
+        let module = lower_to_lir("@export(\"twice\") fn double(x: float) -> float { return x * 2; } double(1)")
+            .unwrap();
+        let text = WatGenerator::new().generate(&module, &mut sink).unwrap();
+        let binary = wat::parse_str(&text).unwrap();
+
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, &binary[..]).unwrap();
+        let mut store = wasmi::Store::new(&engine, ());
+        let instance = wasmi::Linker::<()>::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .unwrap();
+        let twice = instance
+            .get_typed_func::<wasmi::core::F64, wasmi::core::F64>(&store, "twice")
+            .unwrap();
+
+        assert_eq!(f64::from(twice.call(&mut store, 21.0.into()).unwrap()), 42.0);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::parser::ast::{Expr, Stmt};
+use crate::parser::pratt_parser::Parser;
+use crate::semantic::analyzer::CaptureMode;
+use crate::tests::{analyze, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lambda_forms_parse() {
+        let mut sink = DiagnosticSink::new();
+        for source in ["|x: float| x * 2", "|x, y| => x", "|| 1", "fn(x: float) -> float { return x; }"] {
+            let tokens = Lexer::new(source).tokenize(&mut sink).unwrap();
+            let statements = Parser::new(tokens).parse(&mut sink).unwrap();
+
+            assert!(
+                matches!(&statements[..], [Stmt::Expression { expression: Expr::Lambda { .. }, .. }]),
+                "{} parsed as {:?}",
+                source,
+                statements
+            );
+        }
+    }
+
+    #[test]
+    fn test_call_chains_parse() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("f(1)(2, 3)").tokenize(&mut sink).unwrap();
+        let statements = Parser::new(tokens).parse(&mut sink).unwrap();
+
+        match &statements[..] {
+            [Stmt::Expression { expression: Expr::Call { callee, arguments, .. }, .. }] => {
+                assert_eq!(arguments.len(), 2);
+                assert!(matches!(**callee, Expr::Call { .. }));
+            }
+            _ => panic!("Expected call, found {:?}", statements),
+        }
+    }
+
+    #[test]
+    fn test_capture_modes() {
+        let analyzer = analyze("let a = 1; let mut b = 2; let f = || { b = a + b; }; f()").unwrap();
+        let captures = analyzer.captures(0);
+
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[0].name, "a");
+        assert_eq!(captures[0].mode, CaptureMode::ByValue);
+        assert_eq!(captures[1].name, "b");
+        assert_eq!(captures[1].mode, CaptureMode::ByReference);
+    }
+
+    #[test]
+    fn test_function_items_cannot_capture() {
+        assert!(analyze("fn main() { let a = 1; fn inner() -> float { return a; } }").is_err());
+        assert!(analyze("let a = 1; fn f() -> float { return a; }").is_err());
+    }
+
+    #[test]
+    fn test_untyped_parameter_needs_context() {
+        assert!(analyze("let f = |x| x").is_err());
+        assert!(analyze("let f: fn(float) -> float = |x| x").is_ok());
+    }
+
+    #[test]
+    fn test_closure_captures_by_value() {
+        assert_eq!(run("let k = 3.0; let f = |x: float| x * k; f(2)"), Ok(6.0));
+    }
+
+    #[test]
+    fn test_closure_captures_by_reference() {
+        assert_eq!(run("let mut n = 0; let inc = || { n = n + 1; }; inc(); inc(); n"), Ok(2.0));
+    }
+
+    #[test]
+    fn test_closures_created_in_a_loop_grow_memory() {
+        let source = "
+            let mut total = 0;
+            for let mut i = 0; i < 20000; i++ {
+                let mut calls = 0;
+                let add = |x: int| { calls += 1; return x + i + calls; };
+                total = add(total) - i;
+            }
+            total
+        ";
+
+        assert_eq!(run(source), Ok(20000.0));
+    }
+
+    #[test]
+    fn test_closure_outlives_its_frame() {
+        let source = "
+            fn counter() -> fn() -> float {
+                let mut count: float = 0;
+                return || { count = count + 1; return count; };
+            }
+            let next = counter();
+            next();
+            next()
+        ";
+
+        assert_eq!(run(source), Ok(2.0));
+    }
+
+    #[test]
+    fn test_lambda_passed_to_function() {
+        let source = "
+            fn apply(f: fn(float) -> float, x: float) -> float { return f(x); }
+            apply(|x| x + 1, 41)
+        ";
+
+        assert_eq!(run(source), Ok(42.0));
+    }
+
+    #[test]
+    fn test_function_item_as_value() {
+        let source = "
+            fn double(x: float) -> float { return x * 2; }
+            let f = double;
+            f(21)
+        ";
+
+        assert_eq!(run(source), Ok(42.0));
+    }
+
+    #[test]
+    fn test_nested_closures() {
+        assert_eq!(run("let a = 1.0; let f = |x: float| |y: float| x + y + a; f(2)(3)"), Ok(6.0));
+    }
+}
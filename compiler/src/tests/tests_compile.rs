@@ -0,0 +1,72 @@
+use crate::diagnostics::{codes, DiagnosticSink};
+use crate::lexer::token::Token;
+use crate::parser::ast::Stmt;
+use crate::{compile, CompileOptions, CompileOutput, Emit};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emit(source: &str, emit: Emit) -> CompileOutput {
+        let options = CompileOptions {
+            emit,
+            ..CompileOptions::new()
+        };
+        compile(source, &options, &mut DiagnosticSink::new()).unwrap()
+    }
+
+    #[test]
+    fn test_each_stage_can_be_emitted() {
+        let source = "fn double(x: int) -> int { return x * 2; } double(21)";
+
+        match emit(source, Emit::Tokens) {
+            CompileOutput::Tokens(tokens) => assert_eq!(tokens.last().unwrap().token, Token::Eof),
+            other => panic!("expected tokens, found {:?}", other),
+        }
+        match emit(source, Emit::Ast) {
+            CompileOutput::Ast(statements) => assert!(matches!(statements[0], Stmt::Function { .. })),
+            other => panic!("expected an AST, found {:?}", other),
+        }
+        match emit(source, Emit::Hir) {
+            CompileOutput::Hir(hir) => assert_eq!(hir.functions[0].name, "double"),
+            other => panic!("expected HIR, found {:?}", other),
+        }
+        match emit(source, Emit::Mir) {
+            CompileOutput::Mir(mir) => assert_eq!(mir.functions[0].name, "double"),
+            other => panic!("expected MIR, found {:?}", other),
+        }
+        match emit(source, Emit::Lir) {
+            CompileOutput::Lir(lir) => assert_eq!(lir.functions[0].name, "double"),
+            other => panic!("expected LIR, found {:?}", other),
+        }
+        match emit(source, Emit::Wat) {
+            CompileOutput::Wat(text) => assert!(text.starts_with("(module")),
+            other => panic!("expected WebAssembly text, found {:?}", other),
+        }
+        match emit(source, Emit::Wasm) {
+            CompileOutput::Wasm(binary) => assert_eq!(&binary[..4], b"\0asm"),
+            other => panic!("expected a WebAssembly binary, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compilation_stops_after_the_emitted_stage() {
+        // Lexing alone accepts what the parser rejects, and parsing alone
+        // accepts what the analyzer rejects.
+        assert!(matches!(emit("let = ;", Emit::Tokens), CompileOutput::Tokens(_)));
+        assert!(matches!(emit("undefined + 1", Emit::Ast), CompileOutput::Ast(_)));
+
+        let mut sink = DiagnosticSink::new();
+        let error = compile("undefined + 1", &CompileOptions::new(), &mut sink).unwrap_err();
+        assert_eq!(error.code.as_deref(), Some(codes::UNDEFINED_NAME));
+        assert_eq!(sink.errors().count(), 1);
+    }
+
+    #[test]
+    fn test_emit_names() {
+        for emit in Emit::ALL {
+            assert_eq!(Emit::from_name(emit.name()), Some(emit));
+        }
+        assert_eq!(Emit::from_name("exe"), None);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::hir::const_eval::ConstValue;
+use crate::hir::hir::HirLiteral;
+use crate::semantic::symbol_table::IntType;
+use crate::tests::{error, lower, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constants_are_evaluated_at_compile_time() {
+        let program = lower("const SIZE: i32 = 4 * 8 + 2; const HALF: i32 = SIZE / 2;").unwrap();
+
+        assert_eq!(program.consts.len(), 2);
+        assert!(matches!(program.consts[0].value, ConstValue::Scalar(HirLiteral::Integer(34, IntType::I32))));
+        assert!(matches!(program.consts[1].value, ConstValue::Scalar(HirLiteral::Integer(17, IntType::I32))));
+        assert_eq!(run("const SIZE: int = 6; SIZE * 7"), Ok(42.0));
+    }
+
+    #[test]
+    fn test_float_remainder_matches_run_time() {
+        let at_compile_time = run("const C: f64 = -731271.5117751976 % 84.74352625998633; C");
+        let at_run_time = run("let a = -731271.5117751976; let b = 84.74352625998633; a % b");
+        assert_eq!(at_compile_time, at_run_time);
+        assert_eq!(at_run_time, Ok(-19.623677775613032));
+    }
+
+    #[test]
+    fn test_constants_may_refer_to_later_ones() {
+        assert_eq!(run("const A: int = B + 1; const B: int = 2; A"), Ok(3.0));
+        assert_eq!(
+            error("const A: int = B; const B: int = A;"),
+            "Cannot evaluate constant 'A': the value of 'A' depends on itself"
+        );
+    }
+
+    #[test]
+    fn test_const_functions() {
+        let source = "
+            const fn factorial(n: int) -> int { return n <= 1 ? 1 : n * factorial(n - 1); }
+            const fn clamp(x: float, low: float, high: float) -> float {
+                let below = x < low;
+                return below ? low : x > high ? high : x;
+            }
+            const FACT: int = factorial(10);
+            const LIMIT: float = clamp(7.5, 0, 5);
+            FACT + LIMIT as int
+        ";
+
+        assert_eq!(run(source), Ok(3628805.0));
+        assert_eq!(run("const fn twice(x: int) -> int { return x * 2; } twice(4)"), Ok(8.0));
+    }
+
+    #[test]
+    fn test_statics_use_constant_evaluation() {
+        assert_eq!(run("const BASE: int = 10; static LIMIT: int = BASE * BASE; LIMIT"), Ok(100.0));
+        assert_eq!(run("static SMALL: u8 = 300 as u8; SMALL"), Ok(44.0));
+        assert_eq!(run("static LOW: i8 = -128; LOW"), Ok(-128.0));
+    }
+
+    #[test]
+    fn test_evaluation_errors() {
+        assert_eq!(
+            error("const BIG: u8 = 200 + 100;"),
+            "Cannot evaluate constant 'BIG': integer overflow: `200 + 100` does not fit in u8"
+        );
+        assert_eq!(
+            error("const fn divide(a: int, b: int) -> int { return a / b; } const Q: int = divide(1, 0);"),
+            "Cannot evaluate constant 'Q': division by zero in `1 / 0`"
+        );
+        assert_eq!(
+            error("static R: i32 = 5 % (2 - 2);"),
+            "Cannot evaluate static 'R': division by zero in `5 % 0`"
+        );
+    }
+
+    #[test]
+    fn test_evaluation_is_bounded() {
+        assert_eq!(
+            error("const fn forever(n: int) -> int { return forever(n + 1); } const X: int = forever(0);"),
+            "Cannot evaluate constant 'X': const function calls nest deeper than 128"
+        );
+        let source = "
+            const fn spin(n: int) -> int { return n == 0 ? 0 : spin(n - 1) + spin(n - 1); }
+            const X: int = spin(40);
+        ";
+        assert_eq!(error(source), "Cannot evaluate constant 'X': evaluation takes more than 1000000 steps");
+    }
+
+    #[test]
+    fn test_non_constant_operations() {
+        assert_eq!(
+            error("fn f() -> int { return 1; } const X: int = f();"),
+            "Initializer of constant 'X' cannot call non-const function 'f'"
+        );
+        assert_eq!(
+            error("static S: int = 1; const X: int = S;"),
+            "Initializer of constant 'X' cannot read static 'S'"
+        );
+        assert_eq!(
+            error("fn f() -> int { return 1; } const fn g() -> int { return f(); }"),
+            "Cannot call non-const function 'f' from const function 'g'"
+        );
+        assert_eq!(
+            error("static S: int = 1; const fn g() -> int { return S; }"),
+            "Cannot use static 'S' in const function 'g'"
+        );
+        assert_eq!(
+            error("const fn g() -> int { let f = || 1; return 1; }"),
+            "Cannot create a closure in const function 'g'"
+        );
+    }
+
+    #[test]
+    fn test_constant_declarations() {
+        assert_eq!(error("const X: int = 1; X = 2;"), "Cannot assign to constant 'X'");
+        assert_eq!(error("fn f() { const X: int = 1; }"), "Constant 'X' must be declared at the top level");
+        assert_eq!(
+            error("const X: bool = 1;"),
+            "Type mismatch: constant 'X' is declared as bool but initialized with int"
+        );
+        assert!(error("const X = 1;").starts_with("Expect ':' and a type after constant name."));
+    }
+
+    #[test]
+    fn test_struct_and_array_constants() {
+        let source = "
+            struct Point { x: int, y: int }
+            const fn squares(n: int) -> [int; 4] {
+                let mut values = [0, 0, 0, 0];
+                for let mut i = 0; i < n; i += 1 { values[i] = i * i; }
+                return values;
+            }
+            const SQUARES: [int; 4] = squares(4);
+            const ORIGIN: Point = Point { x: SQUARES[2], y: SQUARES[3] };
+            let mut p = ORIGIN;
+            p.x = 10;
+            p.x + ORIGIN.x + ORIGIN.y
+        ";
+        assert_eq!(run(source), Ok(23.0));
+        assert_eq!(
+            error("const fn f(i: int) -> int { let a = [1, 2]; return a[i]; } const X: int = f(2);"),
+            "Cannot evaluate constant 'X': index 2 is out of bounds for an array of length 2"
+        );
+        assert_eq!(error("static S: [int; 1] = [1];"), "Static 'S' cannot hold a struct or array");
+    }
+
+    #[test]
+    fn test_enum_discriminants_are_constants() {
+        let source = "
+            const fn base() -> int { return 10; }
+            enum Level { Low = base(), Middle, High = int(Level::Middle) * 2 }
+            int(Level::High) + int(Level::Low)
+        ";
+        assert_eq!(run(source), Ok(32.0));
+        assert_eq!(
+            error("enum E { A = 9223372036854775807, B }"),
+            "Cannot evaluate constant 'E::B': integer overflow: `9223372036854775807 + 1` does not fit in i64"
+        );
+        assert_eq!(
+            error("enum E { A = true }"),
+            "Type mismatch: the discriminant of variant 'E::A' must be int, found bool"
+        );
+    }
+
+    #[test]
+    fn test_conditional_items() {
+        let source = "
+            const fn level() -> int { return 2; }
+            const VERBOSE: bool = level() > 1;
+            #if VERBOSE {
+                fn log(x: int) -> int { return x; }
+                #if level() > 5 { const EXTRA: int = 1; } #else { const EXTRA: int = 2; }
+            } #else #if false {
+                fn log(x: int) -> int { return 0; }
+            } #else {
+                fn log(x: int) -> int { return -x; }
+            }
+            log(40 + EXTRA)
+        ";
+        assert_eq!(run(source), Ok(42.0));
+        assert_eq!(run("#if !true { const A: int = 1; } #else { const A: int = 2; } A"), Ok(2.0));
+        assert_eq!(
+            run("const N: int = 3; #if N { }"),
+            Err("Type mismatch: '#if' condition must be bool, found int".to_string())
+        );
+        assert_eq!(
+            run("#if false { const A: int = 1; } A"),
+            Err("Undefined variable 'A'".to_string())
+        );
+        assert_eq!(error("fn f() { #if true { } }"), "'#if' must be written at the top level");
+    }
+}
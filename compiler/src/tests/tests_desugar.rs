@@ -0,0 +1,125 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::hir::hir::{HirExpr, HirFunction, HirStmt, HirStmtKind};
+use crate::semantic::lints::LintLevels;
+use crate::tests::{error, lower, lower_with, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(source: &str) -> HirFunction {
+        let program = lower(source).unwrap();
+        program.functions[program.entry].clone()
+    }
+
+    fn dead_code(source: &str) -> usize {
+        let mut sink = DiagnosticSink::new();
+        lower_with(source, LintLevels::new(), &mut sink).unwrap();
+        sink.warnings().filter(|warning| warning.code.as_deref() == Some("dead_code")).count()
+    }
+
+    #[test]
+    fn test_compound_assignments() {
+        assert_eq!(run("let mut x = 10; x += 5; x -= 3; x *= 4; x /= 6; x %= 5; x"), Ok(3.0));
+        assert_eq!(run("let mut f = 1.5; f *= 2; f"), Ok(3.0));
+        assert_eq!(run("let mut x: i8 = 1; let y = (x += 2) * 10; y + x"), Ok(33.0));
+        assert_eq!(
+            error("let x = 1; x += 1;"),
+            "Cannot assign to immutable variable 'x'; declare it with 'mut' to allow assignment"
+        );
+        assert_eq!(
+            error("let mut x: int = 1; x += 0.5;"),
+            "Type mismatch: cannot apply operator '+' to types int and float"
+        );
+    }
+
+    #[test]
+    fn test_increments_and_decrements() {
+        assert_eq!(run("let mut i = 5; let a = i++; let b = ++i; a * 100 + b * 10 + i"), Ok(577.0));
+        assert_eq!(run("let mut i = 5; let a = i--; let b = --i; a * 100 + b * 10 + i"), Ok(533.0));
+        assert_eq!(run("let mut f = 0.5; f++; f"), Ok(1.5));
+        assert_eq!(error("let mut s = \"a\"; s++;"), "Cannot apply 's++' to variable 's' of type string");
+        assert_eq!(error("5++;"), "Operand of '++' must be a variable.");
+    }
+
+    #[test]
+    fn test_postfix_increments_need_a_temporary_only_for_their_value() {
+        let statement = entry("let mut i = 0; i++; i");
+        assert_eq!(statement.locals.len(), 1);
+        assert!(matches!(statement.body[1].kind, HirStmtKind::Expr(HirExpr::Assign { .. })));
+
+        let value = entry("let mut i = 0; let j = i++; j");
+        assert_eq!(value.locals.iter().map(|local| local.name.as_str()).collect::<Vec<_>>(), ["i", "i.old", "j"]);
+        assert!(matches!(value.body[1].kind, HirStmtKind::Let { value: Some(HirExpr::Block { .. }), .. }));
+    }
+
+    #[test]
+    fn test_for_loops() {
+        assert_eq!(run("let mut sum = 0; for let mut i = 0; i < 5; i++ { sum += i; } sum"), Ok(10.0));
+        // `continue` still runs the increment.
+        let source = "let mut sum = 0; for let mut i = 0; i < 10; i++ { if i % 2 == 0 { continue; } sum += i; } sum";
+        assert_eq!(run(source), Ok(25.0));
+        assert_eq!(run("let mut n = 0; for ;; { n++; if n == 3 { break; } } n"), Ok(3.0));
+        assert_eq!(run("let mut i = 10; for i = 0; i < 4; { i += 3; } i"), Ok(6.0));
+        assert_eq!(error("for let mut i = 0; i < 3; i++ { } i"), "Undefined variable 'i'");
+        assert_eq!(
+            error("for let mut i = 0; i; i++ { }"),
+            "Type mismatch: 'for' condition must be bool, found int"
+        );
+    }
+
+    #[test]
+    fn test_loops_lower_to_a_single_construct() {
+        let function = entry("let mut i = 0; while i < 3 { i = i + 1; }");
+        let HirStmtKind::Loop { body, continuing } = &function.body[1].kind else {
+            panic!("expected a loop, found {:?}", function.body[1]);
+        };
+        assert!(matches!(&body[0].kind, HirStmtKind::Expr(HirExpr::If { .. })));
+        assert!(continuing.is_empty());
+
+        let function = entry("let mut sum = 0; for let mut i = 0; i < 3; i++ { sum += i; }");
+        assert!(matches!(function.body[1].kind, HirStmtKind::Let { .. }));
+        let HirStmtKind::Loop { continuing, .. } = &function.body[2].kind else {
+            panic!("expected a loop, found {:?}", function.body[2]);
+        };
+        assert!(matches!(continuing.as_slice(), [HirStmt { kind: HirStmtKind::Expr(HirExpr::Assign { .. }), .. }]));
+    }
+
+    #[test]
+    fn test_ternaries_and_if_statements_lower_to_if_expressions() {
+        let function = entry("let x = 1 < 2 ? 3 : 4; if x > 3 { x; }");
+        assert!(matches!(function.body[0].kind, HirStmtKind::Let { value: Some(HirExpr::If { .. }), .. }));
+        assert!(matches!(function.body[1].kind, HirStmtKind::Expr(HirExpr::If { .. })));
+        assert_eq!(run("let x = 1 < 2 ? 3 : 4; x"), Ok(3.0));
+    }
+
+    #[test]
+    fn test_desugared_loops_in_control_flow_checks() {
+        assert_eq!(dead_code("fn f() -> int { for ;; { return 1; } } fn g() -> int { while true { } } f() + g()"), 0);
+        // The increment after a body that always returns is not reported as unreachable.
+        assert_eq!(dead_code("fn f() -> int { for let mut i = 0; i < 3; i++ { return i; } return 0; } f()"), 0);
+        assert_eq!(dead_code("fn f() -> int { for ;; { return 1; } return 0; } f()"), 1);
+        assert_eq!(
+            error("fn f() -> int { for let mut i = 0; i < 3; i++ { } } f()"),
+            "Not all paths in function 'f' return a value"
+        );
+    }
+
+    #[test]
+    fn test_desugared_forms_in_constant_evaluation() {
+        let source = "
+            const fn triangle(n: int) -> int {
+                let mut sum = 0;
+                for let mut i = 1; i <= n; i++ { sum += i; }
+                return sum;
+            }
+            const T: int = triangle(4);
+            T
+        ";
+        assert_eq!(run(source), Ok(10.0));
+        assert_eq!(
+            error("const fn spin() -> int { for ;; { } } const X: int = spin();"),
+            "Cannot evaluate constant 'X': evaluation takes more than 1000000 steps"
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{compile, CompileOptions};
+use crate::diagnostics::{apply_fixes, codes, Diagnostic, DiagnosticSink, Severity, Span};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles a program and returns everything it reported.
+    fn report(source: &str) -> Vec<Diagnostic> {
+        let mut sink = DiagnosticSink::new();
+        let result = compile(source, &CompileOptions::new(), &mut sink);
+        assert_eq!(result.is_err(), sink.has_errors());
+        sink.into_diagnostics()
+    }
+
+    fn codes_of(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|diagnostic| diagnostic.code.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_every_invalid_token_is_reported() {
+        let diagnostics = report("let a = 1 ~ 2;\nlet b = `;");
+
+        assert_eq!(codes_of(&diagnostics), vec![codes::INVALID_TOKEN, codes::INVALID_TOKEN]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(1, 11, 1)));
+        assert_eq!(diagnostics[1].span(), Some(Span::new(2, 9, 1)));
+    }
+
+    #[test]
+    fn test_parser_recovers_at_the_next_declaration() {
+        let diagnostics = report("let x = ;\nfn f() -> int { let y = ; return 1; }\nlet z = 2 +;\nlet ok = 1;");
+
+        assert_eq!(codes_of(&diagnostics), vec![codes::SYNTAX; 3]);
+        let lines: Vec<usize> = diagnostics.iter().filter_map(|diagnostic| diagnostic.span()).map(|span| span.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+        assert_eq!(diagnostics[0].labels[0].message, "unexpected `;`");
+    }
+
+    #[test]
+    fn test_analysis_reports_errors_of_every_statement() {
+        let diagnostics = report("fn f() -> int { return x; }\nfn g() -> bool { return 1; }\nf()");
+
+        assert_eq!(codes_of(&diagnostics), vec![codes::UNDEFINED_NAME, codes::TYPE_MISMATCH]);
+        assert_eq!(diagnostics[1].message, "Type mismatch: expected return value of type bool, found int");
+    }
+
+    #[test]
+    fn test_semantic_errors_point_at_their_expression() {
+        let diagnostics = report("let small: u8 = 300;\nlet b = true + false;");
+
+        assert_eq!(codes_of(&diagnostics), vec![codes::TYPE_MISMATCH; 2]);
+        assert_eq!(diagnostics[0].message, "Literal 300 is out of range for type u8");
+        assert_eq!(diagnostics[0].span(), Some(Span::new(1, 17, 3)));
+        assert_eq!(diagnostics[1].span(), Some(Span::new(2, 14, 1)));
+
+        let diagnostics = report("let f = |x| { return x + x; };\nf(true)");
+        assert_eq!(codes_of(&diagnostics), vec![codes::TYPE_MISMATCH]);
+        assert_eq!(diagnostics[0].message, "Type mismatch: cannot apply operator '+' to types bool and bool");
+        assert_eq!(diagnostics[0].span(), Some(Span::new(1, 24, 1)));
+
+        let diagnostics = report("static mut N: int = 0;\nN");
+        assert_eq!(codes_of(&diagnostics), vec!["mutable_statics"]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(2, 1, 1)));
+    }
+
+    #[test]
+    fn test_failed_let_still_declares_its_variable() {
+        let diagnostics = report("let m = 1;\nlet b: bool = m;\nb");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Type mismatch: variable 'b' is declared as bool but initialized with int");
+        assert_eq!(diagnostics[0].span(), Some(Span::new(2, 5, 1)));
+    }
+
+    #[test]
+    fn test_syntax_errors_quote_the_token_found() {
+        let diagnostics = report("let x = ;");
+        assert_eq!(diagnostics[0].message, "Expected expression, found `;`");
+        let diagnostics = report("let x: 1 = 2;");
+        assert_eq!(diagnostics[0].message, "Expected type, found `1`");
+    }
+
+    #[test]
+    fn test_control_flow_diagnostics_point_at_the_function() {
+        let diagnostics = report("fn f(c: bool) -> int {\n    if c { return 1; }\n}\nf(true)");
+        assert_eq!(codes_of(&diagnostics), vec![codes::CONTROL_FLOW]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(1, 4, 1)));
+    }
+
+    #[test]
+    fn test_unreachable_code_points_at_the_first_unreachable_statement() {
+        let diagnostics = report("let g = || {\n    return 1;\n    2;\n};\ng()");
+        assert_eq!(codes_of(&diagnostics), vec!["dead_code"]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(3, 5, 1)));
+
+        let source = "fn f() -> int {\n    while true {\n        break;\n        f();\n    }\n    return 1;\n    return 2;\n}\nf()";
+        let diagnostics = report(source);
+        assert_eq!(codes_of(&diagnostics), vec!["dead_code"]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(4, 9, 1)));
+    }
+
+    #[test]
+    fn test_flow_errors_point_at_the_offending_code() {
+        let diagnostics = report("let x;\nif true { x = 1; }\nx");
+        assert_eq!(diagnostics[0].message, "Variable 'x' may be used before it is assigned");
+        assert_eq!(diagnostics[0].span(), Some(Span::new(3, 1, 1)));
+
+        let diagnostics = report("fn f() -> int {\n    let x;\n    x = 1;\n    x = 2;\n    return x;\n}\nf()");
+        assert_eq!(codes_of(&diagnostics), vec![codes::CONTROL_FLOW]);
+        assert_eq!(diagnostics[0].span(), Some(Span::new(4, 5, 1)));
+
+        let diagnostics = report("let x;\nlet y = 1 + x * 2;\ny");
+        assert_eq!(diagnostics[0].span(), Some(Span::new(2, 13, 1)));
+    }
+
+    #[test]
+    fn test_warnings_are_reported_with_errors() {
+        let diagnostics = report("fn f() { let unused = 1; }\nf();\nmissing");
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("unused_variables"));
+        assert_eq!(diagnostics[0].span(), Some(Span::new(1, 14, 6)));
+        assert_eq!(diagnostics[1].severity, Severity::Error);
+        assert_eq!(diagnostics[1].message, "Undefined variable 'missing'");
+    }
+
+    #[test]
+    fn test_assignment_to_immutable_variable() {
+        let source = "let total = 0;\ntotal = 1;\ntotal";
+        let diagnostics = report(source);
+        let error = &diagnostics[0];
+
+        assert_eq!(error.code.as_deref(), Some(codes::IMMUTABLE_ASSIGNMENT));
+        assert_eq!(error.span(), Some(Span::new(2, 1, 5)));
+        let secondary: Vec<_> = error.labels.iter().filter(|label| !label.primary).collect();
+        assert_eq!(secondary.len(), 1);
+        assert_eq!(secondary[0].span, Span::new(1, 5, 5));
+
+        let fixed = apply_fixes(source, &error.fixes);
+        assert_eq!(fixed, "let mut total = 0;\ntotal = 1;\ntotal");
+        assert!(!report(&fixed).iter().any(Diagnostic::is_error));
+    }
+
+    #[test]
+    fn test_rendering() {
+        let source = "let counter = 1;\nwhile countr < 3 { }";
+        let diagnostics = report(source);
+
+        assert_eq!(
+            diagnostics[0].render(source),
+            "error[E0003]: Undefined variable 'countr'\n \
+             --> 2:7\n  \
+             |\n\
+             2 | while countr < 3 { }\n  \
+             |       ^^^^^^\n  \
+             = help: did you mean `counter`?\n"
+        );
+        assert_eq!(
+            String::from(diagnostics[0].clone()),
+            "Undefined variable 'countr'\n  help: did you mean `counter`?"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::semantic::lints::LintLevels;
+use crate::tests::{error, lower_with, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lowers a program to HIR and returns the warnings of the control-flow checks.
+    fn check(source: &str) -> Result<Vec<String>, String> {
+        let mut sink = DiagnosticSink::new();
+        lower_with(source, LintLevels::new(), &mut sink)?;
+        let dead_code = sink.warnings().filter(|warning| warning.code.as_deref() == Some("dead_code"));
+        Ok(dead_code.map(|warning| warning.message.clone()).collect())
+    }
+
+    #[test]
+    fn test_if_statements() {
+        assert_eq!(run("let mut x = 0; if 1 < 2 { x = 5; } else { x = 7; } x"), Ok(5.0));
+        let source = "
+            fn sign(n: int) -> int {
+                if n < 0 { return -1; } else if n == 0 { return 0; } else { return 1; }
+            }
+            sign(-4) * 100 + sign(0) * 10 + sign(9)
+        ";
+        assert_eq!(run(source), Ok(-99.0));
+        assert_eq!(
+            error("if 1 { }"),
+            "Type mismatch: 'if' condition must be bool, found int"
+        );
+    }
+
+    #[test]
+    fn test_while_loops() {
+        let source = "
+            let mut total = 0;
+            let mut i = 0;
+            while i < 10 {
+                i = i + 1;
+                if i % 2 == 0 { continue; }
+                if i > 7 { break; }
+                total = total + i;
+            }
+            total
+        ";
+        assert_eq!(run(source), Ok(16.0));
+        let source = "
+            const fn triangle(n: int) -> int {
+                let mut total = 0;
+                let mut i = 1;
+                while true {
+                    if i > n { return total; }
+                    total = total + i;
+                    i = i + 1;
+                }
+            }
+            const T: int = triangle(100);
+            T
+        ";
+        assert_eq!(run(source), Ok(5050.0));
+    }
+
+    #[test]
+    fn test_definite_assignment() {
+        assert_eq!(
+            error("let c = 1 < 2; let mut x; if c { x = 1; } x"),
+            "Variable 'x' may be used before it is assigned"
+        );
+        assert_eq!(run("let c = 1 < 2; let mut x; if c { x = 1; } else { x = 2; } x"), Ok(1.0));
+        assert_eq!(error("let x: int; x + 1"), "Variable 'x' may be used before it is assigned");
+        assert_eq!(
+            error("let c = true; let mut x; c ? (x = 1) : 0; x"),
+            "Variable 'x' may be used before it is assigned"
+        );
+        assert_eq!(
+            error("let c = true; let mut x; c && (x = 1) == 1; x"),
+            "Variable 'x' may be used before it is assigned"
+        );
+        assert_eq!(
+            error("let c = true; let mut x; while c { x = 1; } x"),
+            "Variable 'x' may be used before it is assigned"
+        );
+        assert_eq!(run("let mut x; while true { x = 3; break; } x"), Ok(3.0));
+        assert_eq!(
+            error("let x: int; let f = || x; f()"),
+            "Variable 'x' may be used before it is assigned"
+        );
+    }
+
+    #[test]
+    fn test_immutable_variables_are_assigned_once() {
+        assert_eq!(run("let c = 1 < 2; let v; if c { v = 1; } else { v = 2; } v"), Ok(1.0));
+        assert_eq!(run("let mut total = 0; for let mut i = 0; i < 3; i++ { let v; v = i; total += v; } total"), Ok(3.0));
+        assert_eq!(error("let x; x = 1; x = 2;"), "Cannot assign twice to immutable variable 'x'");
+        assert_eq!(
+            error("let c = 1 < 2; let v; if c { v = 1; } v = 2; v"),
+            "Cannot assign twice to immutable variable 'v'"
+        );
+        assert_eq!(error("let v; while true { v = 1; } v"), "Cannot assign twice to immutable variable 'v'");
+    }
+
+    #[test]
+    fn test_missing_returns() {
+        assert_eq!(
+            error("fn f(c: bool) -> int { if c { return 1; } }"),
+            "Not all paths in function 'f' return a value"
+        );
+        assert!(check("fn f(c: bool) -> int { if c { return 1; } else { return 2; } }").is_ok());
+        assert!(check("fn f() -> int { while true { return 1; } }").is_ok());
+        assert!(check("fn f(c: bool) { if c { return; } }").is_ok());
+    }
+
+    #[test]
+    fn test_break_and_continue_need_a_loop() {
+        assert_eq!(error("break;"), "'break' outside of a loop in function 'main'");
+        assert_eq!(error("fn f() { continue; }"), "'continue' outside of a loop in function 'f'");
+        assert_eq!(
+            error("while true { let f = || { break; }; break; }"),
+            "'break' outside of a loop in function 'main::lambda#0'"
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code_warns() {
+        assert_eq!(
+            check("fn f() -> int { return 1; let y = 2; }"),
+            Ok(vec!["Unreachable code in function 'f'".to_string()])
+        );
+        assert_eq!(
+            check("fn f(c: bool) -> int { if c { return 1; } else { return 2; } return 3; }"),
+            Ok(vec!["Unreachable code in function 'f'".to_string()])
+        );
+        assert_eq!(check("let mut i = 0; while i < 3 { i = i + 1; }"), Ok(Vec::new()));
+    }
+}
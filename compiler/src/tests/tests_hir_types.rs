@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use crate::hir::hir::{DefId, HirExpr, HirFunction, HirLiteral, HirProgram, HirStmt, HirStmtKind};
+use crate::hir::validate::check_types;
+use crate::semantic::attributes::Attributes;
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+use crate::tests::lower;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The expression the entry function of `source` returns.
+    fn result(source: &str) -> HirExpr {
+        let mut hir = lower(source).unwrap();
+        match hir.functions.swap_remove(hir.entry).body.pop().map(|statement| statement.kind) {
+            Some(HirStmtKind::Return(Some(expr))) => expr,
+            other => panic!("Expected a returned value, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_binary_takes_analyzed_operand_type() {
+        let expr = result("let x: i32 = 1; let y: i32 = 2; x + y");
+
+        assert!(matches!(expr, HirExpr::Binary { .. }));
+        assert_eq!(expr.ty(), &Type::Integer(IntType::I32));
+    }
+
+    #[test]
+    fn test_comparison_is_bool() {
+        let expr = result("let x: f32 = 1; x < 2");
+
+        assert!(matches!(expr, HirExpr::Binary { .. }));
+        assert_eq!(expr.ty(), &Type::Boolean);
+    }
+
+    #[test]
+    fn test_unary_takes_analyzed_operand_type() {
+        let expr = result("let x: f32 = 1.5; -x");
+
+        assert!(matches!(expr, HirExpr::Unary { .. }));
+        assert_eq!(expr.ty(), &Type::Float(FloatType::F32));
+    }
+
+    #[test]
+    fn test_inferred_literal_types_reach_hir() {
+        let expr = result("let x: u8 = 1; 2 * x - 1");
+
+        assert_eq!(expr.ty(), &Type::Integer(IntType::U8));
+        let HirExpr::Binary { left, right, .. } = expr else {
+            panic!("Expected a binary expression");
+        };
+        assert_eq!(left.ty(), &Type::Integer(IntType::U8));
+        assert!(matches!(*right, HirExpr::Literal { value: HirLiteral::Integer(1, IntType::U8), .. }));
+    }
+
+    #[test]
+    fn test_alias_names_are_kept() {
+        assert_eq!(result("alias Meters = float; let d: Meters = 3; d * 2").ty().to_string(), "Meters");
+        assert_eq!(result("let b = true; b ? 1.5 : 2").ty().to_string(), "float");
+    }
+
+    #[test]
+    fn test_unresolved_type_is_internal_error() {
+        let function = HirFunction {
+            def_id: DefId::Function(0),
+            name: "main".to_string(),
+            params: 0,
+            locals: Vec::new(),
+            return_type: Type::Void,
+            env: None,
+            body: vec![HirStmt::synthesized(HirStmtKind::Expr(HirExpr::Literal {
+                value: HirLiteral::Null,
+                expr_type: Type::Function {
+                    params: vec![Type::Variable(0)],
+                    return_type: Box::new(Type::Void),
+                },
+                origin: None,
+            }))],
+            attributes: Attributes::default(),
+            is_const: false,
+        };
+        let program = HirProgram {
+            functions: vec![function],
+            consts: Vec::new(),
+            statics: Vec::new(),
+            structs: Vec::new(),
+            enums: Vec::new(),
+            entry: 0,
+            origins: HashMap::new(),
+        };
+
+        let error = check_types(&program).unwrap_err();
+        assert!(error.contains("function 'main'"), "{}", error);
+        assert!(error.contains("unresolved type"), "{}", error);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::semantic::inference::{Inference, VariableKind};
+use crate::semantic::symbol_table::{IntType, Type};
+use crate::tests::{analyze, error, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literals_default_when_unconstrained() {
+        let analyzer = analyze("let x = 3; let y = 1.5; let z = -x;").unwrap();
+
+        assert_eq!(analyzer.variables[0].ty, Type::int());
+        assert_eq!(analyzer.variables[1].ty, Type::float());
+        assert_eq!(analyzer.variables[2].ty, Type::int());
+    }
+
+    #[test]
+    fn test_literal_types_follow_later_uses() {
+        let analyzer = analyze("let x = 200; let y: u8 = x;").unwrap();
+
+        assert_eq!(analyzer.variables[0].ty, Type::Integer(IntType::U8));
+        assert_eq!(run("let x = 200; let y: u8 = x; x + y"), Ok(144.0));
+        assert_eq!(run("let x = 3; x + 0.5"), Ok(3.5));
+        assert_eq!(
+            error("let x = 300; let y: u8 = x;"),
+            "Literal 300 is out of range for type u8"
+        );
+    }
+
+    #[test]
+    fn test_variables_without_initializers() {
+        assert_eq!(run("let mut x; x = 2.5; x * 2"), Ok(5.0));
+        assert_eq!(run("let mut flag; flag = 1 < 2; flag ? 10 : 20"), Ok(10.0));
+        assert_eq!(error("let x;"), "Type annotation needed for variable 'x'");
+    }
+
+    #[test]
+    fn test_lambda_parameters() {
+        assert_eq!(run("let double = |n| n * 2; double(4)"), Ok(8.0));
+        assert_eq!(run("let half = |x| x / 2; half(3.0)"), Ok(1.5));
+        assert_eq!(run("let negate = |b| !b; negate(false)"), Ok(1.0));
+        assert_eq!(run("let apply = |f, x| f(x); apply(|n: int| n + 1, 41)"), Ok(42.0));
+        assert_eq!(error("let f = |x| 1;"), "Type annotation needed for lambda parameter 'x'");
+    }
+
+    #[test]
+    fn test_operators_are_checked_once_inferred() {
+        assert_eq!(
+            error("let f = |a, b| a - b; f(\"x\", \"y\")"),
+            "Type mismatch: cannot apply operator '-' to types string and string"
+        );
+        assert!(analyze("let join = |a, b| a + b; let s: string = join(\"x\", \"y\");").is_ok());
+        assert_eq!(
+            error("let f = |a| a && true; f(1)"),
+            "Type mismatch: expected argument of type bool, found int\n  note: lambda parameter 'a' is inferred as bool from `a && true`"
+        );
+    }
+
+    #[test]
+    fn test_errors_explain_inferred_types() {
+        assert_eq!(
+            error("let mut x; x = 1.5; let y: int = x;"),
+            "Type mismatch: variable 'y' is declared as int but initialized with float\n  note: variable 'x' is inferred as float from `x = 1.5`"
+        );
+        assert_eq!(
+            error("let f = |a| a + 1; f(\"s\")"),
+            "Type mismatch: expected argument of type int, found string\n  note: lambda parameter 'a' is inferred as int from `a + 1`"
+        );
+        assert_eq!(
+            error("let g = |x| x; let y: bool = g(1);"),
+            "Type mismatch: variable 'y' is declared as bool but initialized with int\n  note: lambda parameter 'x' is inferred as int from `1`"
+        );
+    }
+
+    #[test]
+    fn test_unification() {
+        let mut inference = Inference::new();
+        let general = inference.fresh(VariableKind::General, "a");
+        let integer = inference.fresh(VariableKind::Integer, "b");
+        let float = inference.fresh(VariableKind::Float, "c");
+
+        assert!(inference.unify(&general, &integer, "site").is_ok());
+        assert!(inference.unify(&general, &Type::String, "site").is_err());
+        assert!(inference.unify(&integer, &float, "site").is_ok());
+        assert_eq!(inference.settled(&general), Type::float());
+
+        let function = Type::Function {
+            params: vec![general.clone()],
+            return_type: Box::new(Type::Boolean),
+        };
+        let occurs = inference.fresh(VariableKind::General, "d");
+        let recursive = Type::Function {
+            params: vec![occurs.clone()],
+            return_type: Box::new(Type::Void),
+        };
+        assert!(inference.unify(&occurs, &recursive, "site").is_err());
+        assert!(inference.unify(&function, &Type::Boolean, "site").is_err());
+
+        inference.default_literals();
+        assert_eq!(inference.resolve(&function).to_string(), "fn(float) -> bool");
+    }
+}
@@ -0,0 +1,142 @@
+use crate::diagnostics::{Diagnostic, DiagnosticSink};
+use crate::semantic::lints::{LintLevel, LintLevels};
+use crate::tests::lower_with;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Analyzes and lowers a program with the given lint levels and returns
+    /// the messages of the warnings of both stages, or of every error.
+    fn lint_with(source: &str, lint_levels: LintLevels) -> Result<Vec<String>, Vec<String>> {
+        let mut sink = DiagnosticSink::new();
+        let result = lower_with(source, lint_levels, &mut sink);
+        let messages = |diagnostics: &mut dyn Iterator<Item = &Diagnostic>| {
+            diagnostics.map(|diagnostic| diagnostic.message.clone()).collect()
+        };
+        match result {
+            Ok(_) => Ok(messages(&mut sink.warnings())),
+            Err(_) => Err(messages(&mut sink.errors())),
+        }
+    }
+
+    fn lint(source: &str) -> Vec<String> {
+        lint_with(source, LintLevels::new()).unwrap()
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        assert_eq!(
+            lint("fn f(a: int, _b: int) -> int { let c = 1; let _d = 2; return 0; } f(1, 2)"),
+            vec!["Variable 'a' is never used".to_string(), "Variable 'c' is never used".to_string()]
+        );
+        assert_eq!(lint("let mut x = 1; x = 2;"), vec!["Variable 'x' is never used".to_string()]);
+        assert!(lint("let x = 1; let f = || x; f()").is_empty());
+        assert!(lint("@allow(unused_variables) let x = 1;").is_empty());
+        assert!(lint("@allow(unused) fn f() { let x = 1; }").is_empty());
+    }
+
+    #[test]
+    fn test_unused_functions() {
+        assert_eq!(
+            lint("fn f() -> int { return f(); } fn g() {} g()"),
+            vec!["Function 'f' is never used".to_string()]
+        );
+        assert!(lint("fn main() {} @export(\"h\") fn h() {} @test fn t() {} fn _spare() {}").is_empty());
+    }
+
+    #[test]
+    fn test_shadowing_is_allowed_by_default() {
+        let source = "fn f(x: int) -> int { if x > 0 { let x = 2; return x; } return x; } f(1)";
+        assert!(lint(source).is_empty());
+
+        let mut lint_levels = LintLevels::new();
+        lint_levels.set("shadowing", LintLevel::Warn).unwrap();
+        assert_eq!(
+            lint_with(source, lint_levels),
+            Ok(vec!["Variable 'x' shadows a variable of an enclosing scope".to_string()])
+        );
+        assert_eq!(
+            lint("@warn(shadowing) fn f() { let y = 1; let g = |y: int| y; g(y); } f()"),
+            vec!["Variable 'y' shadows a variable of an enclosing scope".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_float_equality() {
+        let warning = vec!["Comparison of floating-point values with '=='".to_string()];
+        assert_eq!(lint("let x = 0.1; x == 0.3"), warning);
+        assert_eq!(lint("1.5 == 1.5"), warning);
+        assert_eq!(
+            lint("fn f(x: f32) -> bool { return x != 0; } f(1)"),
+            vec!["Comparison of floating-point values with '!='".to_string()]
+        );
+        assert!(lint("let x = 0.1; x < 0.3").is_empty());
+        assert!(lint("@allow(float_equality) fn f() -> bool { return 1.5 == 1.5; } f()").is_empty());
+    }
+
+    #[test]
+    fn test_redundant_parentheses() {
+        assert_eq!(lint("let x = (1 + 2); x"), vec!["Unnecessary parentheses around `1 + 2`".to_string()]);
+        assert_eq!(lint("-(5) * 2"), vec!["Unnecessary parentheses around `5`".to_string()]);
+        assert_eq!(
+            lint("fn f(n: int) -> int { return (n); } f((1 + 1))"),
+            vec![
+                "Unnecessary parentheses around `n`".to_string(),
+                "Unnecessary parentheses around `1 + 1`".to_string()
+            ]
+        );
+        assert!(lint("(1 + 2) * 3").is_empty());
+        assert!(lint("macro square($x:expr) { $x * $x } let y = square!(3); y").is_empty());
+        // Nor around expressions a macro pastes where its body delimits them.
+        assert!(lint("macro sq($x:expr) { let t = $x; t * t } sq!(1 + 2)").is_empty());
+    }
+
+    #[test]
+    fn test_denied_lints_fail_compilation() {
+        let mut lint_levels = LintLevels::new();
+        lint_levels.set("unused", LintLevel::Deny).unwrap();
+        assert_eq!(
+            lint_with("let x = 1; let y = 2;", lint_levels.clone()),
+            Err(vec!["Variable 'x' is never used".to_string(), "Variable 'y' is never used".to_string()])
+        );
+        let mut sink = DiagnosticSink::new();
+        assert!(lower_with("let x = 1;", lint_levels, &mut sink).is_err());
+        let error = sink.errors().next().unwrap();
+        assert_eq!(error.code.as_deref(), Some("unused_variables"));
+        assert_eq!(error.notes, vec!["lint 'unused_variables' is denied".to_string()]);
+
+        assert_eq!(
+            lint_with("@deny(float_equality) fn f() -> bool { return 1.5 == 1.5; } f()", LintLevels::new()),
+            Err(vec!["Comparison of floating-point values with '=='".to_string()])
+        );
+        assert_eq!(LintLevels::new().set("unused_imports", LintLevel::Deny), Ok(()));
+        assert_eq!(
+            LintLevels::new().set("unused_import", LintLevel::Deny),
+            Err("Unknown lint 'unused_import'".to_string())
+        );
+        assert_eq!(
+            lint_with("@allow(bogus) let x = 1;", LintLevels::new()),
+            Err(vec!["Unknown lint 'bogus'".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        let mut lint_levels = LintLevels::new();
+        lint_levels.deny_warnings = true;
+        assert_eq!(
+            lint_with("static mut N: int = 0; N", lint_levels.clone()),
+            Err(vec!["Use of mutable static 'N'".to_string()])
+        );
+        assert_eq!(
+            lint_with("fn f() -> int { return 1; let y = 2; } f()", lint_levels.clone()),
+            Err(vec!["Variable 'y' is never used".to_string()])
+        );
+        assert_eq!(
+            lint_with("@allow(unused_variables) fn f() -> int { return 1; let y = 2; } f()", lint_levels.clone()),
+            Err(vec!["Unreachable code in function 'f'".to_string()])
+        );
+        assert!(lint_with("@allow(unused) fn f() -> int { return 1; let y = 2; } f()", lint_levels).is_ok());
+    }
+}
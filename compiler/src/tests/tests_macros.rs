@@ -0,0 +1,194 @@
+use crate::diagnostics::Span;
+use crate::parser::ast::{Expr, Stmt};
+use crate::tests::{compile_error, parse, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression_macro_keeps_argument_grouping() {
+        assert_eq!(run("macro square($x:expr) { $x * $x } square!(1 + 2)"), Ok(9.0));
+    }
+
+    #[test]
+    fn test_definitions_produce_no_statements() {
+        let statements = parse("macro one() { 1 } one!() + one!()").unwrap();
+
+        match &statements[..] {
+            [Stmt::Expression { expression: Expr::Binary { left, .. }, .. }] => {
+                assert!(matches!(**left, Expr::Grouping { .. }));
+            }
+            _ => panic!("Expected a single expanded expression, found {:?}", statements),
+        }
+    }
+
+    #[test]
+    fn test_rules_are_tried_in_order() {
+        let source = "
+            macro pick {
+                (first $a:expr) => { $a },
+                (second $a:expr, $b:expr) => { $b }
+            }
+            pick!(first 1) + pick!(second 2, 3)
+        ";
+
+        assert_eq!(run(source), Ok(4.0));
+    }
+
+    #[test]
+    fn test_repetition_with_separator() {
+        let source = "
+            macro sum {
+                ($($x:expr),+) => { 0 $(+ $x)+ }
+            }
+            sum!(1, 2, 3, 4)
+        ";
+
+        assert_eq!(run(source), Ok(10.0));
+    }
+
+    #[test]
+    fn test_one_or_more_needs_an_iteration() {
+        let source = "macro sum { ($($x:expr),+) => { 0 $(+ $x)+ } } sum!()";
+
+        assert!(parse(source).unwrap_err().starts_with("No rule of macro 'sum' matches"));
+    }
+
+    #[test]
+    fn test_recursive_macro() {
+        let source = "
+            macro add_all {
+                ($x:expr) => { $x },
+                ($x:expr, $($rest:expr),+) => { $x + add_all!($($rest),+) }
+            }
+            add_all!(1, 2, 3)
+        ";
+
+        assert_eq!(run(source), Ok(6.0));
+    }
+
+    #[test]
+    fn test_ident_ty_and_block_fragments() {
+        let source = "
+            macro function($name:ident, $t:ty, $body:block) { fn $name() -> $t $body }
+            function!(answer, float, { return 42; })
+            answer()
+        ";
+
+        assert_eq!(run(source), Ok(42.0));
+    }
+
+    #[test]
+    fn test_statement_macro_declares_caller_named_variable() {
+        let source = "
+            macro declare($name:ident, $value:expr) { let $name = $value; }
+            declare!(x, 5);
+            x + 1
+        ";
+
+        assert_eq!(run(source), Ok(6.0));
+    }
+
+    #[test]
+    fn test_macro_locals_do_not_capture_caller_variables() {
+        let source = "
+            macro double_it($e:expr) { let t = $e; let result = t + t; }
+            let t = 10;
+            double_it!(t + 1);
+            t
+        ";
+
+        assert_eq!(run(source), Ok(10.0));
+    }
+
+    #[test]
+    fn test_mutable_macro_locals_are_hygienic() {
+        let source = "
+            macro bump($e:expr) { let mut t = $e; t = t + 1; }
+            let mut t = 10;
+            bump!(t);
+            t
+        ";
+        assert_eq!(run(source), Ok(10.0));
+        let source = "
+            macro add_one($f:ident) { fn $f(mut t: int) -> int { t += 1; return t; } }
+            add_one!(g);
+            let t = 1;
+            g(t)
+        ";
+        assert_eq!(run(source), Ok(2.0));
+    }
+
+    #[test]
+    fn test_swap_with_hygienic_temporary() {
+        let source = "
+            macro swap($a:ident, $b:ident) { let tmp = $a; $a = $b; $b = tmp; }
+            let mut tmp = 1;
+            let mut other = 2;
+            swap!(tmp, other);
+            tmp * 10 + other
+        ";
+
+        assert_eq!(run(source), Ok(21.0));
+    }
+
+    #[test]
+    fn test_macro_locals_are_renamed_only_where_in_scope() {
+        let source = "
+            macro m() { let y = t + 1; let t = 5; y + t }
+            let t = 10;
+            m!()
+        ";
+        assert_eq!(run(source), Ok(16.0));
+        let source = "
+            macro shadow() { let t = t * 2; t }
+            let t = 4;
+            shadow!()
+        ";
+        assert_eq!(run(source), Ok(8.0));
+        let source = "
+            macro after_lambda() { (|t: int| t + 1)(1) + t }
+            let t = 10;
+            after_lambda!()
+        ";
+        assert_eq!(run(source), Ok(12.0));
+    }
+
+    #[test]
+    fn test_lambda_parameters_are_hygienic() {
+        let source = "
+            macro apply_twice($f:expr, $v:expr) { (|x: float| $f(($f)(x)))($v) }
+            let x = 100.0;
+            apply_twice!(|y: float| y + x, 1)
+        ";
+
+        assert_eq!(run(source), Ok(201.0));
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let error = compile_error("macro forever($x:expr) { forever!($x) }\nforever!(1)");
+
+        assert_eq!(error.message, "Recursion limit of 64 reached while expanding macro 'forever'");
+        assert_eq!(error.notes, vec!["in expansion of macro 'forever'".to_string()]);
+        assert_eq!(error.span(), Some(Span::new(2, 1, 7)));
+    }
+
+    #[test]
+    fn test_errors_name_call_site_and_definition() {
+        let error = compile_error("macro broken() { 1 + }\nbroken!()");
+
+        assert_eq!(error.notes, vec!["in expansion of macro 'broken'".to_string()]);
+        let labels: Vec<_> = error.labels.iter().map(|label| (label.span, label.primary)).collect();
+        assert_eq!(labels, vec![(Span::new(2, 1, 6), true), (Span::new(1, 7, 6), false)]);
+    }
+
+    #[test]
+    fn test_definition_errors() {
+        assert!(parse("macro m($x:expr, $x:expr) { $x }").is_err());
+        assert!(parse("macro m($x:expr) { $y }").is_err());
+        assert!(parse("macro m($x:thing) { $x }").is_err());
+        assert!(parse("undefined!(1)").is_err());
+    }
+}
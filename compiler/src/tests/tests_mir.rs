@@ -0,0 +1,171 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::mir::lowerer::MirLowerer;
+use crate::mir::mir::{BlockId, MirBody, MirFunction, MirInstructionKind, MirOperand, MirTerminator};
+use crate::parser::pratt_parser::Parser;
+use crate::semantic::analyzer::SemanticAnalyzer;
+use crate::semantic::symbol_table::{IntType, Type};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower_module(source: &str) -> Result<Vec<MirFunction>, String> {
+        let hir = crate::tests::lower(source)?;
+        let mut module = MirLowerer::new().lower(hir, &mut DiagnosticSink::new())?;
+        let entry = module.functions.remove(module.entry);
+        module.functions.push(entry);
+        Ok(module.functions)
+    }
+
+    /// The entry function's body.
+    fn lower(source: &str) -> Result<MirBody, String> {
+        Ok(lower_module(source)?.pop().unwrap().body)
+    }
+
+    #[test]
+    fn test_straight_line_expression_is_one_block() {
+        let body = lower("1 + 2 * 3").unwrap();
+
+        assert_eq!(body.blocks.len(), 1);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Return(_)));
+    }
+
+    #[test]
+    fn test_and_skips_right_operand() {
+        let body = lower("!true && !false").unwrap();
+
+        // entry, right operand, join
+        assert_eq!(body.blocks.len(), 3);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Branch(_, BlockId(1), BlockId(2))));
+        assert_eq!(body.blocks[0].instructions.len(), 2);
+        assert_eq!(body.blocks[1].instructions.len(), 2);
+        assert!(matches!(body.blocks[1].terminator, MirTerminator::Goto(BlockId(2))));
+        assert!(matches!(body.blocks[2].terminator, MirTerminator::Return(_)));
+    }
+
+    #[test]
+    fn test_or_branches_to_join_when_true() {
+        let body = lower("true || false").unwrap();
+
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Branch(_, BlockId(2), BlockId(1))));
+    }
+
+    #[test]
+    fn test_ternary_has_then_else_and_join_blocks() {
+        let body = lower("!false ? 10 : 20").unwrap();
+
+        assert_eq!(body.blocks.len(), 4);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Branch(_, BlockId(1), BlockId(2))));
+        assert!(matches!(body.blocks[1].terminator, MirTerminator::Goto(BlockId(3))));
+        assert!(matches!(body.blocks[2].terminator, MirTerminator::Goto(BlockId(3))));
+        assert!(matches!(body.blocks[3].terminator, MirTerminator::Return(_)));
+    }
+
+    #[test]
+    fn test_ternary_requires_boolean_condition() {
+        assert!(lower("1 ? 2 : 3").is_err());
+    }
+
+    #[test]
+    fn test_ternary_branches_need_common_type() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("true ? 1 : \"one\"").tokenize(&mut sink).unwrap();
+        let ast = Parser::new(tokens).parse(&mut sink).unwrap();
+
+        assert!(SemanticAnalyzer::new().analyze_program(&ast, &mut sink).is_err());
+
+        let tokens = Lexer::new("true ? 1 : 2").tokenize(&mut sink).unwrap();
+        let ast = Parser::new(tokens).parse(&mut sink).unwrap();
+
+        assert_eq!(SemanticAnalyzer::new().analyze_program(&ast, &mut sink), Ok(Type::int()));
+    }
+
+    fn function(source: &str, name: &str) -> MirFunction {
+        lower_module(source).unwrap().into_iter().find(|function| function.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_while_loop_has_back_edge() {
+        let body = lower("let mut i = 0; while i < 3 { i += 1; } i").unwrap();
+
+        // entry, loop body, exit, the `else { break }` branch, then the rest of the body
+        assert_eq!(body.blocks.len(), 5);
+        assert_eq!(body.successors(BlockId::ENTRY), [BlockId(1)]);
+        assert_eq!(body.successors(BlockId(1)), [BlockId(4), BlockId(3)]);
+        assert_eq!(body.successors(BlockId(3)), [BlockId(2)]);
+        let predecessors = body.predecessors();
+        assert_eq!(predecessors[1], [BlockId::ENTRY, BlockId(4)]);
+        assert_eq!(predecessors[2], [BlockId(3)]);
+        assert!(matches!(body.block(BlockId(2)).terminator, MirTerminator::Return(_)));
+    }
+
+    #[test]
+    fn test_continue_jumps_to_for_loop_increment() {
+        let body = lower("let mut n = 0; for let mut i = 0; i < 4; i++ { if i == 1 { continue; } n += i; } n").unwrap();
+
+        // The increment has its own block, which the end of the body and `continue` both reach.
+        let predecessors = body.predecessors();
+        let increment = body
+            .block_ids()
+            .find(|&id| id != BlockId::ENTRY && body.successors(id) == [BlockId(1)] && predecessors[id.index()].len() == 2)
+            .unwrap();
+        assert!(!body.block(increment).instructions.is_empty());
+    }
+
+    #[test]
+    fn test_early_return_ends_its_block() {
+        let body = function("fn f(x: int) -> int { if x > 0 { return 1; } return 2; } f(1)", "f").body;
+
+        let returns = body.blocks.iter().filter(|block| matches!(block.terminator, MirTerminator::Return(_))).count();
+        assert_eq!(returns, 2);
+        // The branch without an `else` goes straight to the join block.
+        let MirTerminator::Branch(_, then_block, join_block) = body.block(BlockId::ENTRY).terminator else {
+            panic!("expected a branch");
+        };
+        assert_eq!(body.successors(then_block), []);
+        assert_eq!(body.predecessors()[join_block.index()], [BlockId::ENTRY]);
+    }
+
+    #[test]
+    fn test_branch_to_one_block_has_one_successor() {
+        let body = lower("let b = 1 < 2; if b { } b").unwrap();
+
+        assert!(matches!(body.block(BlockId::ENTRY).terminator, MirTerminator::Branch(_, BlockId(1), BlockId(1))));
+        assert_eq!(body.successors(BlockId::ENTRY), [BlockId(1)]);
+        assert_eq!(body.predecessors()[1], [BlockId::ENTRY]);
+    }
+
+    #[test]
+    fn test_body_lists_typed_arguments_and_locals() {
+        let source = "fn add(a: int, b: i8) -> int { let c = a + b as int; return c < 0 ? 0 : c; } add(1, 2)";
+        let body = function(source, "add").body;
+
+        assert_eq!(body.args, 2);
+        assert_eq!(body.arg_types().cloned().collect::<Vec<_>>(), [Type::int(), Type::Integer(IntType::I8)]);
+        assert_eq!(body.locals[0].name.as_deref(), Some("a"));
+        assert!(body.locals.iter().any(|local| local.name.as_deref() == Some("c") && local.ty == Type::int()));
+        // Comparisons write a bool whatever they compare.
+        let comparison = body
+            .blocks
+            .iter()
+            .flat_map(|block| &block.instructions)
+            .filter(|instruction| matches!(instruction.kind, MirInstructionKind::BinaryOp(..)))
+            .find(|instruction| instruction.result_type() == Type::Boolean)
+            .unwrap();
+        let MirOperand::Register(dest) = comparison.dest else {
+            panic!("comparison writes a register");
+        };
+        assert_eq!(body.locals[dest].ty, Type::Boolean);
+    }
+
+    #[test]
+    fn test_closure_receives_itself_as_first_argument() {
+        let lambda = function("let k = 1; let f = |x: int| x + k; f(2)", "main::lambda#0");
+
+        assert!(lambda.is_closure);
+        assert_eq!(lambda.body.args, 2);
+        assert!(matches!(lambda.body.locals[0].ty, Type::Function { .. }));
+        assert_eq!(lambda.body.locals[1].name.as_deref(), Some("x"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{MirInstructionKind, MirModule, MirOperand};
+use crate::mir::parser::parse_module;
+use crate::mir::passes::{OptLevel, PassManager};
+use crate::mir::verify::verify;
+use crate::semantic::symbol_table::{FloatType, IntType};
+use crate::tests::mir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Prints a module, reads it back and checks that printing that gives
+    /// the same text.
+    fn assert_round_trips(module: &MirModule) {
+        let text = module.to_string();
+        let parsed = parse_module(&text).unwrap_or_else(|error| panic!("{}\n{}", error, text));
+        assert_eq!(parsed.to_string(), text);
+        assert_eq!(parsed.entry, module.entry);
+    }
+
+    #[test]
+    fn test_printed_mir() {
+        let module = mir("fn half(x: float) -> float { if x > 2.0 { return x * 0.5; } return x; } half(3.0)");
+        let expected = "\
+fn @half(%0: f64 \"x\") -> f64 {
+    let %1: f64
+    let %2: bool
+    let %3: f64
+    let %4: f64
+    let %5: f64
+bb0:
+    %1 = copy f64 %0
+    %2 = gt f64 %1, 2.0
+    br %2, bb1, bb2
+bb1:
+    %3 = copy f64 %0
+    %4 = mul f64 %3, 0.5
+    return %4
+bb2:
+    %5 = copy f64 %0
+    return %5
+}
+
+fn @main() -> f64 entry {
+    let %0: f64
+bb0:
+    %0 = call f64 @half(3.0)
+    return %0
+}
+";
+        assert_eq!(module.to_string(), expected);
+    }
+
+    #[test]
+    fn test_lowered_and_optimized_mir_round_trips() {
+        let source = "static mut total: int = 0;
+            fn f(n: int) -> int { let mut t = 0; for let mut i = 0; i < n; i++ { t += i * 3; } return t; }
+            fn pick(c: bool) -> u8 { if c { return 255; } return 0; }
+            let s = \"tab\\t\\\"quote\\\"\\n\";
+            let mut counter = 0;
+            let g = |y: int| { counter += 1; return y + f(2) + counter; };
+            total = g(4);
+            struct Point { x: int, y: f32 }
+            let mut p = Point { x: 1, y: 2.0 };
+            let mut a = [p.x, 2, 3];
+            a[p.x] += 1;
+            p.y = 0.5;
+            let small: f32 = 2.5;
+            let x = small as float / 3.0 + (-1.0 / 0.0);
+            s != \"\" && pick(x > 0.0) > 3";
+        for level in OptLevel::ALL {
+            let mut module = mir(source);
+            PassManager::for_level(level).run(&mut module).unwrap();
+            assert_round_trips(&module);
+        }
+    }
+
+    #[test]
+    fn test_literals_keep_their_type_and_value() {
+        let module = parse_module(
+            "fn @main() -> i64 entry {
+                let %0: i64
+                let %1: i8
+                let %2: u64
+                let %3: f32
+                let %4: f64
+                let %5: string
+            bb0:
+                %0 = copy i64 -9223372036854775808
+                %1 = copy i8 -128i8
+                %2 = copy u64 18446744073709551615u64
+                %3 = copy f32 0.1f32
+                %4 = copy f64 -inf
+                %5 = copy string \"\\u{e9}\\\\\"
+                return %0
+            }",
+        )
+        .unwrap();
+        let copied: Vec<HirLiteral> = module.functions[0].body.blocks[0]
+            .instructions
+            .iter()
+            .map(|instruction| match &instruction.kind {
+                MirInstructionKind::Load(MirOperand::Literal(literal)) => literal.clone(),
+                other => panic!("expected a copy of a literal, found {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            format!("{:?}", copied),
+            format!("{:?}", vec![
+                HirLiteral::Integer(i64::MIN as u64, IntType::I64),
+                HirLiteral::Integer(-128i64 as u64, IntType::I8),
+                HirLiteral::Integer(u64::MAX, IntType::U64),
+                HirLiteral::Float(0.1f32 as f64, FloatType::F32),
+                HirLiteral::Float(f64::NEG_INFINITY, FloatType::F64),
+                HirLiteral::String("é\\".to_string()),
+            ])
+        );
+        assert_round_trips(&module);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        let error = |text: &str| parse_module(text).unwrap_err();
+        assert_eq!(error("fn @main() -> i64 {\nbb0:\n    return 0\n}"), "line 4: no function is marked 'entry'");
+        assert_eq!(
+            error("fn @main() -> i64 entry {\nbb0:\n    %0 = frob i64 1\n    return 0\n}"),
+            "line 3: unknown instruction 'frob'"
+        );
+        assert_eq!(
+            error("fn @main() -> i64 entry {\n    let %1: i64\nbb0:\n    return 0\n}"),
+            "line 2: expected %0 to be declared next, found %1"
+        );
+        assert_eq!(
+            error("fn @main() -> i64 entry {\nbb0:\n    %0 = call i64 @missing()\n    return 0\n}"),
+            "line 3: unknown function '@missing'"
+        );
+        assert_eq!(error("fn @main() -> u8 entry {\nbb0:\n    return 256u8\n}"), "line 3: 256 does not fit in u8");
+        assert_eq!(
+            error("fn @main() -> i64 entry {\nbb1:\n    return 0\n}"),
+            "line 2: expected bb0 to come next, found bb1"
+        );
+    }
+
+    /// Runs every golden test in `tests/golden`: a module in `name.mir`
+    /// whose first line is `// passes: ` and the passes to run, in order,
+    /// and the module they should leave in `name.expected.mir`. Expected
+    /// files must be in the form the printer writes them.
+    #[test]
+    fn test_golden_files() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tests/golden");
+        let mut inputs: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".mir") && !path.to_string_lossy().ends_with(".expected.mir"))
+            .collect();
+        inputs.sort();
+        assert!(!inputs.is_empty(), "no golden tests in {}", directory.display());
+
+        let mut failures = Vec::new();
+        for input in &inputs {
+            let name = input.file_stem().unwrap().to_string_lossy().to_string();
+            let text = std::fs::read_to_string(input).unwrap();
+            let passes = text.lines().next().and_then(|line| line.strip_prefix("// passes:"));
+            let passes: Vec<&str> = passes
+                .unwrap_or_else(|| panic!("{}: the first line should list the passes", name))
+                .split(',')
+                .map(str::trim)
+                .collect();
+            let mut module = parse_module(&text).unwrap_or_else(|error| panic!("{}.mir: {}", name, error));
+            for function in &module.functions {
+                verify(&function.body).unwrap_or_else(|error| panic!("{}.mir: {}: {}", name, function.name, error));
+            }
+            PassManager::from_names(&passes).unwrap().run(&mut module).unwrap();
+            let actual = module.to_string();
+
+            let expected_path = directory.join(format!("{}.expected.mir", name));
+            let expected = std::fs::read_to_string(&expected_path).unwrap_or_default();
+            if actual != expected {
+                failures.push(format!("{}: after {}:\n{}", name, passes.join(", "), actual));
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::parser::pratt_parser::Parser;
+use crate::tests::{error, run, warnings, wat};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_are_immutable_by_default() {
+        assert_eq!(
+            error("let x = 1; x = 2;"),
+            "Cannot assign to immutable variable 'x'; declare it with 'mut' to allow assignment"
+        );
+        assert_eq!(
+            error("fn f(n: int) -> int { n = n + 1; return n; }"),
+            "Cannot assign to immutable variable 'n'; declare it with 'mut' to allow assignment"
+        );
+    }
+
+    #[test]
+    fn test_immutable_variables_declared_without_a_value() {
+        assert_eq!(run("let v; v = 3.5; v"), Ok(3.5));
+        // The closure could run any number of times.
+        assert_eq!(
+            error("let v: int; let f = || { v = 1; }; f(); v"),
+            "Cannot assign to immutable variable 'v'; declare it with 'mut' to allow assignment"
+        );
+    }
+
+    #[test]
+    fn test_mutable_bindings() {
+        assert_eq!(run("let mut x = 1; x = x + 4; x"), Ok(5.0));
+        assert_eq!(run("fn f(mut n: int) -> int { n = n * 3; return n; } f(7)"), Ok(21.0));
+        assert_eq!(run("let f = |mut n: int| { n = n + 1; return n; }; f(1)"), Ok(2.0));
+    }
+
+    #[test]
+    fn test_assignment_needs_a_variable() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("1 = 2;").tokenize(&mut sink).unwrap();
+        assert!(Parser::new(tokens).parse(&mut sink).is_err());
+        assert_eq!(
+            error("fn f() -> int { return 1; } f = 2;"),
+            "Cannot assign to function 'f'"
+        );
+    }
+
+    #[test]
+    fn test_statics() {
+        assert_eq!(run("static LIMIT: int = 40; LIMIT + 2"), Ok(42.0));
+        assert_eq!(run("static SCALE: f32 = -0.5; SCALE * 4.0"), Ok(-2.0));
+        assert_eq!(run("fn get() -> int { return BASE; } static BASE: i64 = 9; get()"), Ok(9.0));
+        assert_eq!(
+            run("static mut COUNT: int = 0; fn bump() { COUNT = COUNT + 1; } bump(); bump(); COUNT"),
+            Ok(2.0)
+        );
+    }
+
+    #[test]
+    fn test_statics_live_in_the_data_segment() {
+        let text = wat("static A: i32 = 1; static B: i64 = 2; A + B");
+
+        assert!(text.contains("(data (i32.const 8) \"\\01\\00\\00\\00\")"), "{}", text);
+        assert!(text.contains("(data (i32.const 16) \"\\02\\00\\00\\00\\00\\00\\00\\00\")"), "{}", text);
+        assert!(text.contains("(global $heap (mut i32) (i32.const 24))"), "{}", text);
+    }
+
+    #[test]
+    fn test_static_errors() {
+        assert_eq!(error("static A: int = 1; A = 2;"), "Cannot assign to immutable static 'A'");
+        assert_eq!(
+            error("static A: int = (|| 1)();"),
+            "Initializer of static 'A' must be a constant"
+        );
+        assert_eq!(
+            error("fn f() { static A: int = 1; }"),
+            "Static 'A' must be declared at the top level"
+        );
+        assert_eq!(
+            error("static A: bool = 1;"),
+            "Type mismatch: static 'A' is declared as bool but initialized with int"
+        );
+    }
+
+    #[test]
+    fn test_mutable_static_access_warns() {
+        assert_eq!(
+            warnings("static mut N: int = 0; N = 1; N"),
+            vec!["Use of mutable static 'N'".to_string(), "Use of mutable static 'N'".to_string()]
+        );
+        assert!(warnings("static N: int = 0; N").is_empty());
+    }
+}
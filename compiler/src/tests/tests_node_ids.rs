@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use crate::diagnostics::{codes, DiagnosticSink, Span};
+use crate::hir::hir::DefId;
+use crate::hir::lowerer::HirLowerer;
+use crate::parser::ast::{Expr, NodeId, Stmt};
+use crate::semantic::analyzer::SemanticAnalyzer;
+use crate::semantic::symbol_table::{IntType, Type};
+use crate::tests::{lower, parse};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement_ids(statements: &[Stmt], ids: &mut Vec<NodeId>) {
+        for statement in statements {
+            ids.push(statement.id());
+            match statement {
+                Stmt::Block { statements, .. } => statement_ids(statements, ids),
+                Stmt::Expression { expression, .. } => expr_ids(expression, ids),
+                Stmt::Function { params, body, .. } => {
+                    ids.extend(params.iter().map(|param| param.id));
+                    statement_ids(body, ids);
+                }
+                Stmt::Const { initializer, .. } | Stmt::Static { initializer, .. } => expr_ids(initializer, ids),
+                Stmt::Let { initializer, .. } | Stmt::Return { value: initializer, .. } => {
+                    initializer.iter().for_each(|value| expr_ids(value, ids))
+                }
+                Stmt::If { condition, then_branch, else_branch, .. } => {
+                    expr_ids(condition, ids);
+                    statement_ids(then_branch, ids);
+                    statement_ids(else_branch.as_deref().unwrap_or_default(), ids);
+                }
+                Stmt::While { condition, body, .. } => {
+                    expr_ids(condition, ids);
+                    statement_ids(body, ids);
+                }
+                Stmt::For { initializer, condition, increment, body, .. } => {
+                    statement_ids(initializer.as_deref().map(std::slice::from_ref).unwrap_or_default(), ids);
+                    condition.iter().chain(increment).for_each(|expr| expr_ids(expr, ids));
+                    statement_ids(body, ids);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn expr_ids(expr: &Expr, ids: &mut Vec<NodeId>) {
+        ids.push(expr.id());
+        match expr {
+            Expr::Assign { value: inner, .. }
+            | Expr::CompoundAssign { value: inner, .. }
+            | Expr::Cast { expr: inner, .. }
+            | Expr::Grouping { expression: inner, .. }
+            | Expr::Unary { right: inner, .. }
+            | Expr::Field { object: inner, .. } => expr_ids(inner, ids),
+            Expr::Binary { left, right, .. }
+            | Expr::Logical { left, right, .. }
+            | Expr::Index { object: left, index: right, .. }
+            | Expr::SetField { object: left, value: right, .. } => {
+                expr_ids(left, ids);
+                expr_ids(right, ids);
+            }
+            Expr::SetIndex { object, index, value, .. } => {
+                expr_ids(object, ids);
+                expr_ids(index, ids);
+                expr_ids(value, ids);
+            }
+            Expr::ArrayLiteral { elements, .. } => elements.iter().for_each(|element| expr_ids(element, ids)),
+            Expr::StructLiteral { fields, .. } => fields.iter().for_each(|field| expr_ids(&field.value, ids)),
+            Expr::Call { callee, arguments, .. } => {
+                expr_ids(callee, ids);
+                arguments.iter().for_each(|argument| expr_ids(argument, ids));
+            }
+            Expr::Lambda { params, body, .. } => {
+                ids.extend(params.iter().map(|param| param.id));
+                statement_ids(body, ids);
+            }
+            Expr::Ternary { condition, then_branch, else_branch, .. } => {
+                expr_ids(condition, ids);
+                expr_ids(then_branch, ids);
+                expr_ids(else_branch, ids);
+            }
+            Expr::EnumVariant { .. } | Expr::Literal { .. } | Expr::Update { .. } | Expr::Variable { .. } => {}
+        }
+    }
+
+    fn all_ids(source: &str) -> Vec<NodeId> {
+        let mut ids = Vec::new();
+        statement_ids(&parse(source).unwrap(), &mut ids);
+        ids
+    }
+
+    #[test]
+    fn test_node_ids_are_unique_through_macro_expansions() {
+        let source = "macro twice($x:expr) { $x + $x }
+            fn f(a: int) -> int { let g = |b: int| b * twice!(a); if a > 0 { return g(a); } return twice!(1); }
+            f(2)";
+        let ids = all_ids(source);
+
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+    }
+
+    #[test]
+    fn test_node_ids_are_stable_across_parses() {
+        let source = "let x = 1; while x < 10 { x + 1; } x";
+
+        assert_eq!(all_ids(source), all_ids(source));
+    }
+
+    #[test]
+    fn test_expression_types_survive_moving_the_ast() {
+        let ast = parse("let x: i8 = 1; x + 2").unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze_program(&ast, &mut DiagnosticSink::new()).unwrap();
+
+        let mut moved = Vec::with_capacity(ast.len() + 1);
+        moved.extend(ast);
+        let Stmt::Expression { expression, .. } = &moved[1] else {
+            panic!("Expected an expression statement");
+        };
+        assert_eq!(analyzer.expr_type(expression), Some(&Type::Integer(IntType::I8)));
+    }
+
+    #[test]
+    fn test_resolutions_record_where_identifiers_are_written() {
+        let ast = parse("let count = 1;\ncount + count").unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze_program(&ast, &mut DiagnosticSink::new()).unwrap();
+
+        let mut spans: Vec<Span> = analyzer.resolutions().map(|(_, resolution)| resolution.span).collect();
+        spans.sort_by_key(|span| span.column);
+        assert_eq!(spans, vec![Span::new(2, 1, 5), Span::new(2, 9, 5)]);
+    }
+
+    #[test]
+    fn test_items_map_back_to_their_declarations() {
+        let program = lower(
+            "fn add(a: int, b: int) -> int { return a + b; }\nconst TEN: int = 10;\nstatic mut TOTAL: int = 0;\nlet f = |x: int| add(x, TEN);\nf(1)",
+        )
+        .unwrap();
+
+        assert_eq!(program.origin(DefId::Function(0)).unwrap().span, Span::new(1, 4, 3));
+        assert_eq!(program.origin(DefId::Const(0)).unwrap().span, Span::new(2, 7, 3));
+        assert_eq!(program.origin(DefId::Static(0)).unwrap().span, Span::new(3, 12, 5));
+        let lambda = program.functions.iter().find(|function| function.name.contains("lambda")).unwrap();
+        assert_eq!(program.origin(lambda.def_id).unwrap().span, Span::new(4, 9, 1));
+        // The implicit entry function has no declaration.
+        assert!(program.origin(DefId::Function(program.entry)).is_none());
+    }
+
+    #[test]
+    fn test_def_ids_match_item_positions() {
+        let source = "const fn one() -> int { return 1; }\nconst A: int = one();\nstatic B: int = A;\nlet g = one;\n\
+            g()";
+        let program = lower(source).unwrap();
+
+        for (index, function) in program.functions.iter().enumerate() {
+            assert_eq!(function.def_id, DefId::Function(index));
+        }
+        assert_eq!(program.consts[0].def_id, DefId::Const(0));
+        assert_eq!(program.statics[0].def_id, DefId::Static(0));
+        // The closure adapter for `one` points at `one` itself.
+        let adapter = program.functions.iter().find(|function| function.name == "one::closure").unwrap();
+        assert_eq!(program.origin(adapter.def_id), program.origin(DefId::Function(0)));
+    }
+
+    #[test]
+    fn test_const_evaluation_error_points_at_item() {
+        let mut sink = DiagnosticSink::new();
+        let ast = parse("const fn zero() -> int { return 0; }\nconst BAD: int = 1 / zero();").unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze_program(&ast, &mut sink).unwrap();
+
+        let error = HirLowerer::new().lower_program(&ast, &analyzer, &mut sink).unwrap_err();
+        assert_eq!(error.code.as_deref(), Some(codes::CONST_EVALUATION));
+        assert_eq!(error.span(), Some(Span::new(2, 7, 3)));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::lexer::token::Token;
+use crate::lir::lir::LirType;
+use crate::tests::{analyze, lower_to_lir, run, wat};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_and_float_literals_lex_apart() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("3 3.5").tokenize(&mut sink).unwrap();
+
+        assert_eq!(tokens[0].token, Token::Integer(3));
+        assert_eq!(tokens[1].token, Token::Number(3.5));
+        assert!(Lexer::new("18446744073709551616").tokenize(&mut sink).is_err());
+    }
+
+    #[test]
+    fn test_literals_take_their_type_from_context() {
+        assert_eq!(run("let x: u8 = 255; x"), Ok(255.0));
+        assert_eq!(run("let x: i8 = -128; x"), Ok(-128.0));
+        assert_eq!(run("let x: f32 = 1.5; x * 2"), Ok(3.0));
+        assert_eq!(run("fn half(x: f64) -> f64 { return x / 2; } half(3)"), Ok(1.5));
+        assert_eq!(run("let x: u16 = 7; 3 * x"), Ok(21.0));
+    }
+
+    #[test]
+    fn test_literal_range_and_kind() {
+        assert_eq!(
+            analyze("let x: u8 = 256;").err().unwrap(),
+            "Literal 256 is out of range for type u8"
+        );
+        assert_eq!(
+            analyze("let x: i8 = -129;").err().unwrap(),
+            "Literal -129 is out of range for type i8"
+        );
+        assert!(analyze("let x: u32 = -1;").is_err());
+        assert!(analyze("let x: i32 = 2.5;").is_err());
+        assert!(analyze("let x: u64 = 18446744073709551615;").is_ok());
+    }
+
+    #[test]
+    fn test_sized_types_mix_only_by_widening() {
+        assert!(analyze("let a: i32 = 1; let b: i64 = 2; a + b").is_ok());
+        assert!(analyze("let a: f32 = 1; let b: float = 2; a * b").is_ok());
+        assert!(analyze("let a: i32 = 1; let b: u32 = 2; a + b").is_err());
+        assert!(analyze("let a: i32 = 1; let b: isize = 2; a + b").is_err());
+        assert!(analyze("let a: i64 = 1; let b: f64 = 2; a + b").is_err());
+        assert!(analyze("let a: u8 = 1; -a").is_err());
+        assert!(analyze("let a: int = 1; let b: i64 = a;").is_ok());
+    }
+
+    #[test]
+    fn test_narrow_arithmetic_wraps() {
+        assert_eq!(run("let x: u8 = 200; x + 100"), Ok(44.0));
+        assert_eq!(run("let x: i8 = 127; x + 1"), Ok(-128.0));
+        assert_eq!(run("let x: i16 = -32768; -x"), Ok(-32768.0));
+        assert_eq!(run("let x: u16 = 0; x - 1"), Ok(65535.0));
+    }
+
+    #[test]
+    fn test_unsigned_division_and_comparison() {
+        assert_eq!(run("let x: u32 = 4000000000; (x / 2) as i64"), Ok(2000000000.0));
+        assert_eq!(run("let a: u32 = 4000000000; let b: u32 = 1; a > b"), Ok(1.0));
+        assert_eq!(run("let a: i32 = -1; let b: i32 = 1; a > b"), Ok(0.0));
+    }
+
+    #[test]
+    fn test_integer_casts_truncate_and_extend() {
+        assert_eq!(run("300 as u8"), Ok(44.0));
+        assert_eq!(run("-1 as u8"), Ok(255.0));
+        assert_eq!(run("(-1 as i8) as u16"), Ok(65535.0));
+        assert_eq!(run("let x: i8 = -1; x as u32 as i64"), Ok(4294967295.0));
+        assert_eq!(run("let x: u8 = 255; x as i8"), Ok(-1.0));
+        assert_eq!(run("let x: i64 = 4294967297; x as u32 as i64"), Ok(1.0));
+        assert_eq!(run("true as i32 + 1"), Ok(2.0));
+    }
+
+    #[test]
+    fn test_float_to_integer_casts_saturate() {
+        assert_eq!(run("300.5 as u8"), Ok(255.0));
+        assert_eq!(run("-5.7 as i8"), Ok(-5.0));
+        assert_eq!(run("-1.5 as u16"), Ok(0.0));
+        assert_eq!(run("3000000000.0 as i32"), Ok(2147483647.0));
+        assert_eq!(run("(0.0 / 0.0) as i32"), Ok(0.0));
+    }
+
+    #[test]
+    fn test_float_casts() {
+        assert_eq!(run("let x: u64 = 3; x as f32 / 2"), Ok(1.5));
+        assert_eq!(run("let x: i8 = -3; x as f64"), Ok(-3.0));
+        assert_eq!(run("let x: f32 = 1.5; (x as f64) * 2"), Ok(3.0));
+        assert_eq!(run("let x: f64 = 0.1; x as f32"), Ok(0.1f32 as f64));
+        // f32 literals hold f32 values, whether the optimizer folds the cast or not.
+        assert_eq!(run("let f: f32 = 0.1; (f as f64) * 10.0"), Ok(0.1f32 as f64 * 10.0));
+        assert_eq!(run("let f: f32 = 16777217.0; f as f64"), Ok(16777216.0));
+        assert_eq!(run("let f: f32 = 16777217; f as f64"), Ok(16777216.0));
+    }
+
+    #[test]
+    fn test_invalid_casts() {
+        assert_eq!(analyze("\"a\" as i32").err().unwrap(), "Cannot cast string to i32");
+        assert!(analyze("1 as bool").is_err());
+        assert!(analyze("true as f64").is_err());
+        assert!(analyze("1 as Missing").is_err());
+    }
+
+    #[test]
+    fn test_types_map_to_wasm_value_types() {
+        let source = "fn f(a: i8, b: u64, c: f32, d: usize, e: bool) -> f32 { return c; } f(1, 2, 3, 4, true)";
+        let module = lower_to_lir(source).unwrap();
+        let function = &module.functions[0];
+
+        assert_eq!(
+            function.registers[..function.params],
+            [LirType::I32, LirType::I64, LirType::F32, LirType::I32, LirType::I32]
+        );
+        assert_eq!(function.result, Some(LirType::F32));
+    }
+
+    #[test]
+    fn test_instruction_selection() {
+        assert!(wat("let x: i8 = 1; x + x").contains("i32.extend8_s"));
+        assert!(wat("let x: u16 = 1; x * x").contains("i32.and"));
+        assert!(wat("let x: u32 = 1; x / x").contains("i32.div_u"));
+        assert!(wat("let x: u64 = 1; x < x").contains("i64.lt_u"));
+        assert!(wat("let x: u64 = 1; x as f32").contains("f32.convert_i64_u"));
+        assert!(wat("let x: f64 = 1; x as i32").contains("i32.trunc_sat_f64_s"));
+        assert!(wat("let x: i32 = 1; x as i64").contains("i64.extend_i32_s"));
+        assert!(wat("let x: f32 = 1; x as f64").contains("f64.promote_f32"));
+    }
+}
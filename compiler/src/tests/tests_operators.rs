@@ -0,0 +1,119 @@
+use crate::semantic::operators::{arithmetic_conversion, binary_typing, BinaryOperator};
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+use crate::tests::{analyze, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comparisons_yield_bool() {
+        assert!(analyze("let b: bool = 1 < 2;").is_ok());
+        assert!(analyze("let b: bool = 1.5 >= 2.0;").is_ok());
+        assert!(analyze("let x: float = 1 < 2;").is_err());
+        assert_eq!(run("let a: u8 = 3; let b: u8 = 4; a <= b"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_arithmetic_requires_numbers() {
+        assert_eq!(
+            analyze("\"a\" - \"b\"").err().unwrap(),
+            "Type mismatch: cannot apply operator '-' to types string and string"
+        );
+        assert!(analyze("true * 2").is_err());
+        assert!(analyze("let s: string = \"a\" + \"b\";").is_ok());
+        assert!(analyze("\"a\" + 1").is_err());
+    }
+
+    #[test]
+    fn test_logical_and_equality_operands() {
+        assert_eq!(
+            analyze("1 && true").err().unwrap(),
+            "Type mismatch: cannot apply operator '&&' to types int and bool"
+        );
+        assert!(analyze("let b: bool = true == false;").is_ok());
+        assert!(analyze("\"a\" == \"a\"").is_ok());
+        assert!(analyze("true == 1").is_err());
+        assert!(analyze("fn f() -> float { return 1.0; } f == f").is_err());
+        assert_eq!(
+            analyze("-true").err().unwrap(),
+            "Type mismatch: cannot apply operator '-' to type bool"
+        );
+    }
+
+    #[test]
+    fn test_remainder() {
+        assert_eq!(run("7 % 3"), Ok(1.0));
+        assert_eq!(run("-7 % 3"), Ok(-1.0));
+        assert_eq!(run("1 + 7 % 4"), Ok(4.0));
+        assert_eq!(run("let x: u32 = 4000000001; x % 2"), Ok(1.0));
+        assert_eq!(run("7.5 % 2.0"), Ok(1.5));
+        assert_eq!(run("let x: f32 = -7.5; x % 2"), Ok(-1.5));
+    }
+
+    #[test]
+    fn test_string_concatenation() {
+        assert_eq!(run("let s = \"ab\" + \"c\"; s == \"abc\""), Ok(1.0));
+        assert_eq!(run("let s = \"ab\" + \"c\"; s != \"abd\""), Ok(1.0));
+        assert_eq!(run("\"\" + \"x\" == \"x\" + \"\""), Ok(1.0));
+        assert_eq!(run("\"ab\" == \"abc\""), Ok(0.0));
+        let source = "
+            fn greet(name: string) -> string { return \"hello, \" + name + \"!\"; }
+            let mut s = \"\";
+            for let mut i = 0; i < 3; i += 1 { s = s + \"ab\"; }
+            greet(s) == \"hello, ababab!\"
+        ";
+        assert_eq!(run(source), Ok(1.0));
+        let source = "
+            static mut LOG: string = \"a\";
+            let append = |s: string| LOG = LOG + s;
+            append(\"b\");
+            append(\"c\");
+            LOG == \"abc\"
+        ";
+        assert_eq!(run(source), Ok(1.0));
+    }
+
+    #[test]
+    fn test_binary_operators_are_left_associative() {
+        assert_eq!(run("10 - 3 - 2"), Ok(5.0));
+        assert_eq!(run("100 / 10 / 5"), Ok(2.0));
+        assert_eq!(run("100 % 7 % 3"), Ok(2.0));
+        assert_eq!(run("let a = 20; let b = 4; a - b - 1 + a / b / 5"), Ok(16.0));
+        assert_eq!(run("1 < 2 == true"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_widening_conversions_execute() {
+        assert_eq!(run("let a: i32 = 1; let b: i64 = 2; a + b"), Ok(3.0));
+        assert_eq!(run("let x: u8 = 200; let y: i16 = 100; x + y"), Ok(300.0));
+        assert_eq!(run("let x: u32 = 4000000000; let y: i64 = 1; x + y"), Ok(4000000001.0));
+        assert_eq!(run("let x: i32 = 3; let y: f64 = 0.5; x * y"), Ok(1.5));
+        assert_eq!(run("let x: i8 = -1; let y: u64 = 1; (x as i64) < 0"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_arithmetic_conversion_table() {
+        let int = |int| Type::Integer(int);
+        let float = |float| Type::Float(float);
+
+        assert_eq!(
+            arithmetic_conversion(&int(IntType::U16), &int(IntType::I32)),
+            Some(int(IntType::I32))
+        );
+        assert_eq!(arithmetic_conversion(&int(IntType::U32), &int(IntType::I32)), None);
+        assert_eq!(arithmetic_conversion(&int(IntType::I8), &int(IntType::U64)), None);
+        assert_eq!(
+            arithmetic_conversion(&int(IntType::I16), &float(FloatType::F32)),
+            Some(float(FloatType::F32))
+        );
+        assert_eq!(arithmetic_conversion(&int(IntType::I32), &float(FloatType::F32)), None);
+        assert_eq!(
+            binary_typing(BinaryOperator::Less, &int(IntType::I8), &int(IntType::I64)),
+            Ok(crate::semantic::operators::OperatorTyping {
+                operands: int(IntType::I64),
+                result: Type::Boolean,
+            })
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::hir::const_eval::decode;
+use crate::hir::hir::HirLiteral;
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBinaryOp, MirBody, MirInstruction, MirInstructionKind, MirLocal, MirModule, MirOperand,
+    MirTerminator, MirUnaryOp,
+};
+use crate::mir::passes::const_fold::{fold, ConstantFolding};
+use crate::mir::passes::copy_propagation::CopyPropagation;
+use crate::mir::passes::dce::DeadCodeElimination;
+use crate::mir::passes::gvn::GlobalValueNumbering;
+use crate::mir::passes::licm::{natural_loops, LoopInvariantCodeMotion};
+use crate::mir::passes::sccp::Sccp;
+use crate::mir::passes::simplify_cfg::{RemoveUnreachableBlocks, SimplifyCfg};
+use crate::mir::passes::{OptLevel, Pass, PassContext, PassManager};
+use crate::semantic::symbol_table::{FloatType, IntType, Type};
+use crate::tests::{mir_at, run, run_at};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I64: Type = Type::Integer(IntType::I64);
+
+    /// A body of `locals` i64 registers, the first `args` of them arguments,
+    /// with the given blocks.
+    fn body(locals: usize, args: usize, blocks: Vec<(Vec<MirInstruction>, MirTerminator)>) -> MirBody {
+        MirBody {
+            locals: vec![MirLocal { ty: I64, name: None }; locals],
+            args,
+            blocks: blocks
+                .into_iter()
+                .map(|(instructions, terminator)| MirBasicBlock {
+                    instructions,
+                    terminator,
+                })
+                .collect(),
+        }
+    }
+
+    fn run_pass(pass: &mut impl Pass, body: &mut MirBody) -> bool {
+        pass.run(body, &mut PassContext { functions: &[], function: 0, remarks: &mut Vec::new() })
+    }
+
+    fn r(register: usize) -> MirOperand {
+        MirOperand::Register(register)
+    }
+
+    fn int(value: i64) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Integer(value as u64, IntType::I64))
+    }
+
+    fn boolean(value: bool) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Boolean(value))
+    }
+
+    fn instruction(dest: usize, kind: MirInstructionKind) -> MirInstruction {
+        MirInstruction {
+            kind,
+            dest: r(dest),
+            ty: I64,
+        }
+    }
+
+    fn binary(dest: usize, op: MirBinaryOp, left: MirOperand, right: MirOperand) -> MirInstruction {
+        instruction(dest, MirInstructionKind::BinaryOp(op, left, right))
+    }
+
+    fn copy(dest: usize, value: MirOperand) -> MirInstruction {
+        instruction(dest, MirInstructionKind::Load(value))
+    }
+
+    fn phi(dest: usize, inputs: Vec<(usize, MirOperand)>) -> MirInstruction {
+        let inputs = inputs.into_iter().map(|(pred, value)| (BlockId(pred), value)).collect();
+        instruction(dest, MirInstructionKind::Phi(inputs))
+    }
+
+    fn ret(value: MirOperand) -> MirTerminator {
+        MirTerminator::Return(value)
+    }
+
+    fn goto(target: usize) -> MirTerminator {
+        MirTerminator::Goto(BlockId(target))
+    }
+
+    fn branch(condition: MirOperand, then_block: usize, else_block: usize) -> MirTerminator {
+        MirTerminator::Branch(condition, BlockId(then_block), BlockId(else_block))
+    }
+
+    /// Folds one instruction on literal operands.
+    fn folded(kind: MirInstructionKind, ty: Type) -> Option<HirLiteral> {
+        let literals: Vec<HirLiteral> = kind
+            .operands()
+            .into_iter()
+            .map(|operand| match operand {
+                MirOperand::Literal(literal) => literal.clone(),
+                MirOperand::Register(_) => panic!("expected literal operands"),
+            })
+            .collect();
+        fold(&kind, &ty, &literals.iter().collect::<Vec<_>>())
+    }
+
+    fn integer(literal: Option<HirLiteral>) -> Option<i128> {
+        match literal? {
+            HirLiteral::Integer(bits, int) => Some(decode(bits, int)),
+            other => panic!("expected an integer, found {:?}", other),
+        }
+    }
+
+    fn float(literal: Option<HirLiteral>) -> Option<f64> {
+        match literal? {
+            HirLiteral::Float(value, _) => Some(value),
+            other => panic!("expected a float, found {:?}", other),
+        }
+    }
+
+    fn typed(value: i64, int: IntType) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Integer(value as u64, int))
+    }
+
+    fn f(value: f64, float: FloatType) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Float(value, float))
+    }
+
+    fn arithmetic(op: MirBinaryOp, left: MirOperand, right: MirOperand) -> MirInstructionKind {
+        MirInstructionKind::BinaryOp(op, left, right)
+    }
+
+    fn optimized(source: &str, opt_level: OptLevel) -> (MirModule, Vec<String>) {
+        let mut sink = DiagnosticSink::new();
+        let module = mir_at(source, opt_level, &mut sink);
+        (module, sink.remarks().iter().map(|remark| remark.to_string()).collect())
+    }
+
+    fn optimized_entry(source: &str, opt_level: OptLevel) -> MirBody {
+        let (mut module, _) = optimized(source, opt_level);
+        module.functions.remove(module.entry).body
+    }
+
+    fn calls(body: &MirBody) -> usize {
+        let instructions = body.blocks.iter().flat_map(|block| &block.instructions);
+        instructions.filter(|instruction| matches!(instruction.kind, MirInstructionKind::Call(..))).count()
+    }
+
+    #[test]
+    fn test_folding_wraps_integers_like_the_generated_code() {
+        let i8 = Type::Integer(IntType::I8);
+        let add = arithmetic(MirBinaryOp::Add, typed(127, IntType::I8), typed(1, IntType::I8));
+        assert_eq!(integer(folded(add, i8.clone())), Some(-128));
+        let multiply = arithmetic(MirBinaryOp::Multiply, int(i64::MAX), int(3));
+        assert_eq!(integer(folded(multiply, I64)), Some(i64::MAX.wrapping_mul(3) as i128));
+        let negate = MirInstructionKind::UnaryOp(MirUnaryOp::Negate, int(i64::MIN));
+        assert_eq!(integer(folded(negate, I64)), Some(i64::MIN as i128));
+        let unsigned = arithmetic(MirBinaryOp::Subtract, typed(0, IntType::U32), typed(1, IntType::U32));
+        assert_eq!(integer(folded(unsigned, Type::Integer(IntType::U32))), Some(u32::MAX as i128));
+        let less = arithmetic(MirBinaryOp::LessThan, typed(-1, IntType::I8), typed(1, IntType::I8));
+        assert!(matches!(folded(less, i8.clone()), Some(HirLiteral::Boolean(true))));
+
+        // Division traps at run time on zero, and on -1 for the smallest 32- and 64-bit values.
+        assert_eq!(integer(folded(arithmetic(MirBinaryOp::Divide, int(7), int(0)), I64)), None);
+        assert_eq!(integer(folded(arithmetic(MirBinaryOp::Remainder, int(7), int(0)), I64)), None);
+        assert_eq!(integer(folded(arithmetic(MirBinaryOp::Divide, int(i64::MIN), int(-1)), I64)), None);
+        let narrow = arithmetic(MirBinaryOp::Divide, typed(-128, IntType::I8), typed(-1, IntType::I8));
+        assert_eq!(integer(folded(narrow, i8)), Some(-128));
+        assert_eq!(integer(folded(arithmetic(MirBinaryOp::Remainder, int(-7), int(2)), I64)), Some(-1));
+    }
+
+    #[test]
+    fn test_folding_follows_float_rules() {
+        let f32 = Type::Float(FloatType::F32);
+        let sum = arithmetic(MirBinaryOp::Add, f(0.1f32 as f64, FloatType::F32), f(0.2f32 as f64, FloatType::F32));
+        assert_eq!(float(folded(sum, f32)), Some((0.1f32 + 0.2f32) as f64));
+        let f64 = Type::Float(FloatType::F64);
+        let remainder = arithmetic(MirBinaryOp::Remainder, f(-5.5, FloatType::F64), f(2.0, FloatType::F64));
+        assert_eq!(float(folded(remainder, f64.clone())), Some(-1.5));
+        let infinity = arithmetic(MirBinaryOp::Divide, f(1.0, FloatType::F64), f(0.0, FloatType::F64));
+        assert_eq!(float(folded(infinity, f64)), Some(f64::INFINITY));
+
+        let nan = f(f64::NAN, FloatType::F64);
+        let equal = arithmetic(MirBinaryOp::Equal, nan.clone(), nan.clone());
+        assert!(matches!(folded(equal, Type::Float(FloatType::F64)), Some(HirLiteral::Boolean(false))));
+        let not_equal = arithmetic(MirBinaryOp::NotEqual, nan.clone(), nan);
+        assert!(matches!(folded(not_equal, Type::Float(FloatType::F64)), Some(HirLiteral::Boolean(true))));
+    }
+
+    #[test]
+    fn test_folding_casts() {
+        let to_u8 = |value: MirOperand, from: Type| {
+            folded(MirInstructionKind::Cast(value, from), Type::Integer(IntType::U8))
+        };
+        assert_eq!(integer(to_u8(int(300), I64)), Some(44));
+        assert_eq!(integer(to_u8(f(-1.5, FloatType::F64), Type::Float(FloatType::F64))), Some(0));
+        assert_eq!(integer(to_u8(f(f64::NAN, FloatType::F64), Type::Float(FloatType::F64))), Some(0));
+        let big = MirInstructionKind::Cast(f(1e10, FloatType::F64), Type::Float(FloatType::F64));
+        assert_eq!(integer(folded(big, Type::Integer(IntType::I32))), Some(i32::MAX as i128));
+        // Rounded once, as the conversion instruction does.
+        let large = (1i64 << 60) + (1 << 36) + 1;
+        let to_f32 = MirInstructionKind::Cast(int(large), I64);
+        assert_eq!(float(folded(to_f32, Type::Float(FloatType::F32))), Some(large as f32 as f64));
+    }
+
+    #[test]
+    fn test_constant_folding_pass_turns_operations_into_copies() {
+        let mut body = body(
+            4,
+            1,
+            vec![(
+                vec![
+                    binary(1, MirBinaryOp::Multiply, int(2), int(3)),
+                    binary(2, MirBinaryOp::Add, r(0), int(1)),
+                    binary(3, MirBinaryOp::Divide, int(1), int(0)),
+                ],
+                ret(r(2)),
+            )],
+        );
+
+        assert!(run_pass(&mut ConstantFolding, &mut body));
+        let instructions = &body.blocks[0].instructions;
+        assert!(matches!(
+            instructions[0].kind,
+            MirInstructionKind::Load(MirOperand::Literal(HirLiteral::Integer(6, _)))
+        ));
+        assert!(matches!(instructions[1].kind, MirInstructionKind::BinaryOp(..)));
+        assert!(matches!(instructions[2].kind, MirInstructionKind::BinaryOp(..)));
+        assert!(!run_pass(&mut ConstantFolding, &mut body));
+    }
+
+    #[test]
+    fn test_copy_propagation_follows_chains_and_trivial_phis() {
+        // bb1 is a loop whose phi only ever holds register 0.
+        let mut body = body(
+            5,
+            1,
+            vec![
+                (vec![copy(1, r(0)), copy(2, r(1))], goto(1)),
+                (
+                    vec![phi(3, vec![(0, r(2)), (1, r(3))]), binary(4, MirBinaryOp::LessThan, r(3), int(9))],
+                    branch(r(4), 1, 2),
+                ),
+                (Vec::new(), ret(r(3))),
+            ],
+        );
+
+        assert!(run_pass(&mut CopyPropagation, &mut body));
+        let compare = &body.blocks[1].instructions[1].kind;
+        assert!(matches!(compare, MirInstructionKind::BinaryOp(_, MirOperand::Register(0), _)));
+        assert!(matches!(body.blocks[2].terminator, MirTerminator::Return(MirOperand::Register(0))));
+        // `null` has no type of its own, so it is not copied into readers.
+        let mut body = body_with_null_copy();
+        assert!(!run_pass(&mut CopyPropagation, &mut body));
+    }
+
+    fn body_with_null_copy() -> MirBody {
+        let instructions = vec![copy(0, MirOperand::Literal(HirLiteral::Null)), binary(1, MirBinaryOp::Add, r(0), int(1))];
+        body(2, 0, vec![(instructions, ret(r(1)))])
+    }
+
+    #[test]
+    fn test_dce_keeps_effects_and_what_they_need() {
+        let mut body = body(
+            8,
+            1,
+            vec![
+                (
+                    vec![
+                        binary(1, MirBinaryOp::Add, r(0), int(1)),
+                        binary(2, MirBinaryOp::Multiply, r(1), int(2)),
+                        instruction(3, MirInstructionKind::Call(0, vec![r(2)])),
+                        // May divide by zero, so it stays though its result is unused.
+                        binary(4, MirBinaryOp::Divide, int(1), r(0)),
+                        binary(5, MirBinaryOp::Divide, r(0), int(4)),
+                    ],
+                    goto(1),
+                ),
+                // A phi cycle that nothing outside it reads.
+                (
+                    vec![phi(6, vec![(0, int(0)), (1, r(7))]), binary(7, MirBinaryOp::Add, r(6), int(1))],
+                    branch(r(0), 1, 2),
+                ),
+                (Vec::new(), ret(int(0))),
+            ],
+        );
+
+        assert!(run_pass(&mut DeadCodeElimination, &mut body));
+        let kept: Vec<usize> = body.blocks[0]
+            .instructions
+            .iter()
+            .map(|instruction| instruction.dest.register().unwrap())
+            .collect();
+        assert_eq!(kept, [1, 2, 3, 4]);
+        assert!(body.blocks[1].instructions.is_empty());
+        assert!(!run_pass(&mut DeadCodeElimination, &mut body));
+    }
+
+    #[test]
+    fn test_sccp_finds_constants_around_loops() {
+        // x = 10; loop { x = (x == 10) ? x : x + 1 } — x never changes.
+        let mut body = body(
+            8,
+            1,
+            vec![
+                (Vec::new(), goto(1)),
+                (
+                    vec![
+                        phi(1, vec![(0, int(10)), (4, r(5))]),
+                        phi(2, vec![(0, int(0)), (4, r(6))]),
+                        binary(7, MirBinaryOp::LessThan, r(2), r(0)),
+                    ],
+                    branch(r(7), 2, 5),
+                ),
+                (vec![binary(3, MirBinaryOp::Equal, r(1), int(10))], branch(r(3), 4, 3)),
+                (vec![binary(4, MirBinaryOp::Add, r(1), int(1))], goto(4)),
+                (
+                    vec![phi(5, vec![(2, r(1)), (3, r(4))]), binary(6, MirBinaryOp::Add, r(2), int(1))],
+                    goto(1),
+                ),
+                (Vec::new(), ret(r(1))),
+            ],
+        );
+
+        assert!(run_pass(&mut Sccp::new(), &mut body));
+        let returned = &body.blocks[5].terminator;
+        assert!(matches!(returned, MirTerminator::Return(MirOperand::Literal(HirLiteral::Integer(10, _)))));
+        // The branch that would change x is never taken, which leaves its block unreachable.
+        assert!(matches!(body.blocks[2].terminator, MirTerminator::Goto(BlockId(4))));
+        assert!(body.predecessors()[3].is_empty());
+        // The loop counter really varies, so its branch stays.
+        assert!(matches!(body.blocks[1].terminator, MirTerminator::Branch(MirOperand::Register(7), _, _)));
+    }
+
+    #[test]
+    fn test_remove_unreachable_blocks_renumbers() {
+        let mut body = body(
+            2,
+            0,
+            vec![
+                (Vec::new(), goto(2)),
+                (Vec::new(), goto(2)),
+                (vec![phi(0, vec![(0, int(1)), (1, int(2))])], goto(3)),
+                (Vec::new(), ret(r(0))),
+            ],
+        );
+
+        assert!(run_pass(&mut RemoveUnreachableBlocks, &mut body));
+        assert_eq!(body.blocks.len(), 3);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Goto(BlockId(1))));
+        assert!(matches!(body.blocks[1].terminator, MirTerminator::Goto(BlockId(2))));
+        let MirInstructionKind::Phi(inputs) = &body.blocks[1].instructions[0].kind else {
+            panic!("expected a phi");
+        };
+        assert!(matches!(inputs[..], [(BlockId(0), _)]));
+        assert!(!run_pass(&mut RemoveUnreachableBlocks, &mut body));
+    }
+
+    #[test]
+    fn test_simplify_cfg_folds_branches_and_merges_blocks() {
+        let mut body = body(
+            3,
+            1,
+            vec![
+                (Vec::new(), branch(boolean(true), 1, 2)),
+                (vec![binary(1, MirBinaryOp::Add, r(0), int(1))], goto(3)),
+                (Vec::new(), goto(3)),
+                (vec![phi(2, vec![(1, r(1)), (2, int(0))])], ret(r(2))),
+            ],
+        );
+
+        assert!(run_pass(&mut SimplifyCfg, &mut body));
+        // Everything now runs in one straight line, the phi became a copy.
+        assert_eq!(body.blocks.len(), 1);
+        let block = &body.blocks[0];
+        assert!(matches!(block.instructions[1].kind, MirInstructionKind::Load(MirOperand::Register(1))));
+        assert!(matches!(block.terminator, MirTerminator::Return(MirOperand::Register(2))));
+    }
+
+    #[test]
+    fn test_simplify_cfg_forwards_empty_blocks() {
+        // bb1 only jumps on. Forwarding bb0's jump to it would give the phi
+        // in bb2 two inputs from bb0, so that one stays.
+        let mut body = body(
+            2,
+            1,
+            vec![
+                (Vec::new(), branch(r(0), 1, 2)),
+                (Vec::new(), goto(2)),
+                (vec![phi(1, vec![(0, int(1)), (1, int(2))])], ret(r(1))),
+            ],
+        );
+        assert!(!run_pass(&mut SimplifyCfg, &mut body));
+
+        // Without the phi, the branch goes to bb2 either way and everything merges into one block.
+        body.blocks[2] = MirBasicBlock {
+            instructions: Vec::new(),
+            terminator: ret(int(0)),
+        };
+        assert!(run_pass(&mut SimplifyCfg, &mut body));
+        assert_eq!(body.blocks.len(), 1);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Return(_)));
+    }
+
+    #[test]
+    fn test_pipelines() {
+        assert!(PassManager::for_level(OptLevel::O0).pass_names().is_empty());
+        assert!(!PassManager::for_level(OptLevel::O1).pass_names().contains(&"sccp"));
+        let o2 = PassManager::for_level(OptLevel::O2).pass_names();
+        assert_eq!((o2[0], o2[o2.len() - 1]), ("sccp", "inline"));
+        let o1 = PassManager::for_level(OptLevel::O1).pass_names();
+        assert!(["inline", "gvn", "licm"].iter().all(|name| !o1.contains(name)));
+        assert_eq!(OptLevel::from_name("2"), Some(OptLevel::O2));
+        assert_eq!(OptLevel::from_name("3"), None);
+
+        assert_eq!(optimized_entry("1 + 2 * 3", OptLevel::O0).blocks[0].instructions.len(), 2);
+        let body = optimized_entry("1 + 2 * 3", OptLevel::O1);
+        assert!(body.blocks[0].instructions.is_empty());
+        let returned = &body.blocks[0].terminator;
+        assert!(matches!(returned, MirTerminator::Return(MirOperand::Literal(HirLiteral::Integer(7, _)))));
+
+        let source = "let mut x = 10; let mut i = 0; while i < 5 { if x == 10 { x = 10; } else { x += 1; } i++; } x";
+        let body = optimized_entry(source, OptLevel::O2);
+        // Only the loop counter is left: entry, loop header, loop body and exit.
+        assert_eq!(body.blocks.len(), 4);
+        let returns_ten = |block: &MirBasicBlock| {
+            matches!(block.terminator, MirTerminator::Return(MirOperand::Literal(HirLiteral::Integer(10, _))))
+        };
+        assert!(body.blocks.iter().any(returns_ten));
+    }
+
+    #[test]
+    fn test_optimized_programs_behave_the_same() {
+        // `run` compares every optimization level, traps included.
+        assert_eq!(run("let x: i8 = 100; let y: i8 = x + 100; y"), Ok(-56.0));
+        assert!(run("let zero = 0; 10 / zero").is_err());
+        assert_eq!(run("let a = 7; let b = a * 2; let c = b - a; c % 4"), Ok(3.0));
+        assert_eq!(run("let x = 2.5; let y = x * 4.0; y - 1.0"), Ok(9.0));
+        let source = "
+            fn pick(flag: bool) -> int { if flag { return 1; } return 2; }
+            let mut total = 0;
+            for let mut i = 0; i < 10; i++ { if i > 100 { total += 1000; } total += pick(i % 2 == 0); }
+            total
+        ";
+        assert_eq!(run(source), Ok(15.0));
+    }
+
+    #[test]
+    fn test_inlining_small_functions() {
+        let source = "fn square(x: int) -> int { return x * x; } square(3) + square(4)";
+        let (module, remarks) = optimized(source, OptLevel::O2);
+        let entry = &module.functions[module.entry];
+        assert_eq!(calls(&entry.body), 0);
+        assert!(matches!(entry.body.blocks[0].terminator, MirTerminator::Return(MirOperand::Literal(HirLiteral::Integer(25, _)))));
+        assert_eq!(remarks, ["remark[inline]: main: inlined 'square' (cost 1)"]);
+        assert_eq!(run(source), Ok(25.0));
+
+        // Several returns meet in a phi after the inlined body.
+        let source = "fn clamp(x: int) -> int { if x > 10 { return 10; } if x < 0 { return 0; } return x; }
+            let mut total = 0; for let mut i = -5; i < 20; i++ { total += clamp(i); } total";
+        assert_eq!(calls(&optimized_entry(source, OptLevel::O2)), 0);
+        assert_eq!(run(source), Ok(145.0));
+    }
+
+    #[test]
+    fn test_inline_hints_and_recursion() {
+        let big = (0..20).map(|i| format!("x = x * {} + 1; ", i + 2)).collect::<String>();
+        let source = format!(
+            "fn big(a: int) -> int {{ let mut x = a; {big}return x; }}
+            @inline fn forced(a: int) -> int {{ let mut x = a; {big}return x; }}
+            @noinline fn tiny(a: int) -> int {{ return a + 1; }}
+            fn fact(n: int) -> int {{ if n <= 1 {{ return 1; }} return n * fact(n - 1); }}
+            let n = 3; big(n) + forced(n) + tiny(n) + fact(n)"
+        );
+        let (module, remarks) = optimized(&source, OptLevel::O2);
+        // `big`, `tiny` and `fact` are still called; `forced` is not.
+        assert_eq!(calls(&module.functions[module.entry].body), 3);
+        let has = |text: &str| remarks.iter().any(|remark| remark == text);
+        assert!(has("remark[inline]: main: inlined 'forced' (cost 40)"), "{:?}", remarks);
+        assert!(has("missed[inline]: main: did not inline 'big': its cost 40 is over the threshold of 12"));
+        assert!(has("missed[inline]: main: did not inline 'tiny': it is marked @noinline"));
+        assert!(has("missed[inline]: main: did not inline 'fact': it is recursive"));
+        assert!(has("missed[inline]: fact: did not inline 'fact': it is recursive"));
+        assert_eq!(run(&source), run_at(&source, OptLevel::O0));
+    }
+
+    #[test]
+    fn test_global_value_numbering() {
+        // %2 = a + b; %3 = b + a; %4 = %2 * %3, then the same again in bb1,
+        // which bb0 dominates, and in bb2 a computation bb1 does not dominate.
+        let mut body = body(
+            9,
+            2,
+            vec![
+                (
+                    vec![
+                        binary(2, MirBinaryOp::Add, r(0), r(1)),
+                        binary(3, MirBinaryOp::Add, r(1), r(0)),
+                        binary(4, MirBinaryOp::Multiply, r(2), r(3)),
+                        binary(8, MirBinaryOp::LessThan, r(0), r(1)),
+                    ],
+                    branch(r(8), 1, 2),
+                ),
+                (vec![binary(5, MirBinaryOp::Multiply, r(3), r(2)), binary(6, MirBinaryOp::Subtract, r(5), r(0))], goto(2)),
+                (vec![binary(7, MirBinaryOp::Subtract, r(4), r(0))], ret(r(7))),
+            ],
+        );
+        assert!(run_pass(&mut GlobalValueNumbering, &mut body));
+        assert_eq!(body.blocks[0].instructions.len(), 3);
+        assert!(matches!(body.blocks[0].instructions[1].kind, MirInstructionKind::BinaryOp(_, MirOperand::Register(2), MirOperand::Register(2))));
+        // %5 repeats %4, so %6 repeats %7's computation, but bb1 does not dominate bb2.
+        assert!(matches!(body.blocks[1].instructions[..], [MirInstruction { dest: MirOperand::Register(6), .. }]));
+        assert!(matches!(body.blocks[1].instructions[0].kind, MirInstructionKind::BinaryOp(_, MirOperand::Register(4), _)));
+        assert_eq!(body.blocks[2].instructions.len(), 1);
+        assert!(!run_pass(&mut GlobalValueNumbering, &mut body));
+
+        // Subtraction does not commute, and different types do not mix.
+        let mut body = self::body(
+            4,
+            2,
+            vec![(
+                vec![binary(2, MirBinaryOp::Subtract, r(0), r(1)), binary(3, MirBinaryOp::Subtract, r(1), r(0))],
+                ret(r(3)),
+            )],
+        );
+        assert!(!run_pass(&mut GlobalValueNumbering, &mut body));
+        let mut repeated = self::body(4, 1, vec![(vec![binary(1, MirBinaryOp::Add, r(0), int(1)), binary(2, MirBinaryOp::Add, r(0), int(1))], ret(r(2)))]);
+        repeated.blocks[0].instructions[1].ty = Type::Integer(IntType::I32);
+        assert!(!run_pass(&mut GlobalValueNumbering, &mut repeated));
+    }
+
+    /// bb0 -> bb1 (header) -> bb2 (body) -> bb1, and bb1 -> bb3 (exit), with
+    /// `instructions` in the body of a loop counting %1 up to the argument %0.
+    fn counting_loop(locals: usize, entry: Vec<MirInstruction>, instructions: Vec<MirInstruction>) -> MirBody {
+        let mut loop_body = vec![binary(3, MirBinaryOp::Add, r(1), int(1))];
+        loop_body.extend(instructions);
+        body(
+            locals,
+            1,
+            vec![
+                (entry, goto(1)),
+                (vec![phi(1, vec![(0, int(0)), (2, r(3))]), binary(2, MirBinaryOp::LessThan, r(1), r(0))], branch(r(2), 2, 3)),
+                (loop_body, goto(1)),
+                (Vec::new(), ret(r(1))),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_loop_invariant_code_motion() {
+        let mut body = counting_loop(
+            7,
+            Vec::new(),
+            vec![
+                binary(4, MirBinaryOp::Multiply, r(0), int(3)),
+                binary(5, MirBinaryOp::Add, r(4), int(1)),
+                binary(6, MirBinaryOp::Add, r(5), r(1)),
+            ],
+        );
+        assert!(run_pass(&mut LoopInvariantCodeMotion, &mut body));
+        let hoisted: Vec<Option<usize>> = body.blocks[0].instructions.iter().map(|instruction| instruction.dest.register()).collect();
+        assert_eq!(hoisted, [Some(4), Some(5)]);
+        assert_eq!(body.blocks[2].instructions.len(), 2);
+        assert!(!run_pass(&mut LoopInvariantCodeMotion, &mut body));
+
+        // A division by a register might trap on a trip the loop never takes.
+        let mut body = counting_loop(5, Vec::new(), vec![binary(4, MirBinaryOp::Divide, int(100), r(0))]);
+        assert!(!run_pass(&mut LoopInvariantCodeMotion, &mut body));
+    }
+
+    #[test]
+    fn test_loop_invariant_code_motion_adds_preheaders() {
+        // The entry branches to the header, so the header needs a block of its own to hoist into.
+        let mut body = counting_loop(6, vec![binary(5, MirBinaryOp::LessThan, r(0), int(0))], vec![binary(4, MirBinaryOp::Multiply, r(0), r(0))]);
+        body.blocks[0].terminator = branch(r(5), 3, 1);
+        body.blocks[3].instructions.push(phi(6, vec![(0, int(-1)), (1, r(1))]));
+        body.locals.push(MirLocal { ty: I64, name: None });
+        body.blocks[3].terminator = ret(r(6));
+        assert!(run_pass(&mut LoopInvariantCodeMotion, &mut body));
+        assert_eq!(body.blocks.len(), 5);
+        assert!(matches!(body.blocks[0].terminator, MirTerminator::Branch(_, BlockId(3), BlockId(4))));
+        assert!(matches!(body.blocks[4].terminator, MirTerminator::Goto(BlockId(1))));
+        assert_eq!(body.blocks[4].instructions[0].dest.register(), Some(4));
+        let MirInstructionKind::Phi(inputs) = &body.blocks[1].instructions[0].kind else {
+            panic!("expected the header to start with a phi");
+        };
+        assert_eq!(inputs.iter().map(|(pred, _)| *pred).collect::<Vec<_>>(), [BlockId(2), BlockId(4)]);
+
+        let source = "fn f(a: int, n: int) -> int {
+                let mut total = 0;
+                for let mut i = 0; i < n; i++ { for let mut j = 0; j < n; j++ { total += a * a + j; } }
+                return total;
+            }
+            f(3, 4)";
+        assert_eq!(run(source), Ok(168.0));
+        let (module, remarks) = optimized(source, OptLevel::O2);
+        assert!(remarks.iter().any(|remark| remark.starts_with("remark[licm]: f: hoisted")), "{:?}", remarks);
+        let f = &module.functions[0].body;
+        // `a * a` leaves both loops.
+        assert!(matches!(f.blocks[0].instructions[..], [MirInstruction { kind: MirInstructionKind::BinaryOp(MirBinaryOp::Multiply, ..), .. }]));
+        let loops = natural_loops(f, &Dominators::new(f));
+        assert_eq!(loops.len(), 2);
+        assert!(loops[1].blocks.iter().all(|&id| !loops[0].contains(id) || loops[1].contains(id)));
+    }
+}
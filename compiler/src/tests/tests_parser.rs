@@ -0,0 +1,68 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::lexer::lexer::Lexer;
+use crate::lexer::token::{Operation, Token};
+use crate::parser::ast::{Expr, Stmt};
+use crate::tests::parse;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The expression a program made of one expression statement consists of.
+    fn expression(source: &str) -> Result<Expr, String> {
+        match parse(source)?.pop() {
+            Some(Stmt::Expression { expression, .. }) => Ok(expression),
+            other => Err(format!("Expected expression statement, found {:?}", other)),
+        }
+    }
+
+    #[test]
+    fn test_logical_operator_tokens() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("&& ||").tokenize(&mut sink).unwrap();
+
+        assert_eq!(tokens[0].token, Token::Operation(Operation::And));
+        assert_eq!(tokens[1].token, Token::Operation(Operation::Or));
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        let expr = expression("true || false && false").unwrap();
+
+        match expr {
+            Expr::Logical { operator, right, .. } => {
+                assert_eq!(operator, Token::Operation(Operation::Or));
+                assert!(matches!(*right, Expr::Logical { operator: Token::Operation(Operation::And), .. }));
+            }
+            _ => panic!("Expected logical expression, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_ternary_binds_looser_than_or() {
+        let expr = expression("true || false ? 1 : 2").unwrap();
+
+        match expr {
+            Expr::Ternary { condition, .. } => assert!(matches!(*condition, Expr::Logical { .. })),
+            _ => panic!("Expected conditional expression, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_nested_ternary_is_right_associative() {
+        let expr = expression("true ? 1 : false ? 2 : 3").unwrap();
+
+        match expr {
+            Expr::Ternary { then_branch, else_branch, .. } => {
+                assert!(matches!(*then_branch, Expr::Literal { .. }));
+                assert!(matches!(*else_branch, Expr::Ternary { .. }));
+            }
+            _ => panic!("Expected conditional expression, found {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn test_ternary_without_colon() {
+        assert!(expression("true ? 1 2").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::diagnostics::DiagnosticSink;
+use crate::parser::ast::Stmt;
+use crate::semantic::lints::LintLevels;
+use crate::semantic::symbol_table::{ScopeId, SymbolKind};
+use crate::tests::{analyze_with, error, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inner_declarations_shadow_outer_ones() {
+        assert_eq!(run("let x = 1; { let mut x = 2.5; x = x * 2.0; } x"), Ok(1.0));
+        assert_eq!(run("let mut x = 1; { x = 3; let x = 2; } x"), Ok(3.0));
+        assert_eq!(run("fn f() -> int { return 1; } { let f = 5; } f()"), Ok(1.0));
+        assert_eq!(run("let x = 1; fn g(x: float) -> float { return x; } g(2.5)"), Ok(2.5));
+        assert_eq!(run("let x = 1; let f = |x: int| x * 10; f(x + 1)"), Ok(20.0));
+    }
+
+    #[test]
+    fn test_duplicate_definitions() {
+        assert_eq!(error("let x = 1; let x = 2;"), "'x' is already defined in this scope");
+        assert_eq!(error("{ let y = 1; let y = 2; }"), "'y' is already defined in this scope");
+        assert_eq!(
+            error("fn f(a: int, a: int) -> int { return a; }"),
+            "'a' is already defined in this scope"
+        );
+        assert_eq!(
+            error("fn f(a: int) -> int { let a = 2; return a; }"),
+            "'a' is already defined in this scope"
+        );
+        assert_eq!(error("fn f() {} let f = 1;"), "'f' is already defined in this scope");
+        assert_eq!(error("fn f() {} fn f() {}"), "Function 'f' is already defined");
+    }
+
+    #[test]
+    fn test_use_before_declaration() {
+        assert_eq!(error("x; let x = 1;"), "Cannot use variable 'x' before its declaration");
+        assert_eq!(error("let x = x + 1;"), "Cannot use variable 'x' before its declaration");
+        assert_eq!(
+            error("{ let f = || y; let y = 1; }"),
+            "Cannot use variable 'y' before its declaration"
+        );
+        assert_eq!(error("{ let y = 1; } y"), "Undefined variable 'y'");
+        assert_eq!(error("z = 1;"), "Undefined variable 'z'");
+    }
+
+    #[test]
+    fn test_resolution_table() {
+        let source = "let x = 1; { let x = 2; x; } x";
+        let (ast, analyzer) = analyze_with(source, LintLevels::new(), &mut DiagnosticSink::new()).unwrap();
+        let [_, Stmt::Block { statements, .. }, Stmt::Expression { expression: outer, .. }] = &ast[..] else {
+            panic!("unexpected program {:?}", ast);
+        };
+        let [_, Stmt::Expression { expression: inner, .. }] = &statements[..] else {
+            panic!("unexpected block {:?}", statements);
+        };
+
+        let outer = analyzer.resolution(outer).unwrap();
+        assert_eq!(outer.symbol, SymbolKind::Variable { id: 0, depth: 0 });
+        assert_eq!(outer.scope, ScopeId::ROOT);
+
+        let inner = analyzer.resolution(inner).unwrap();
+        assert_eq!(inner.symbol, SymbolKind::Variable { id: 1, depth: 0 });
+        assert_eq!(analyzer.symbol_table.scope(inner.scope).parent, Some(ScopeId::ROOT));
+        assert_eq!(analyzer.variables[1].scope, inner.scope);
+    }
+
+    #[test]
+    fn test_scopes_can_be_revisited() {
+        let source = "fn f(a: int) -> int { let b = a; return b; } let c = 1;";
+        let (ast, analyzer) = analyze_with(source, LintLevels::new(), &mut DiagnosticSink::new()).unwrap();
+        let [Stmt::Function { body, .. }, ..] = &ast[..] else {
+            panic!("unexpected program {:?}", ast);
+        };
+        let Stmt::Return { value: Some(value), .. } = &body[1] else {
+            panic!("unexpected body {:?}", body);
+        };
+        let scope = analyzer.resolution(value).unwrap().scope;
+
+        let table = &analyzer.symbol_table;
+        assert!(table.lookup_in(scope, "a").is_some());
+        assert!(table.lookup_in(scope, "f").is_some());
+        assert!(table.lookup_in(ScopeId::ROOT, "b").is_none());
+        assert_eq!(table.ancestors(scope).collect::<Vec<_>>(), vec![scope, ScopeId::ROOT]);
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use crate::hir::hir::HirLiteral;
+use crate::mir::dominators::Dominators;
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBinaryOp, MirBody, MirInstruction, MirInstructionKind, MirLocal, MirOperand,
+    MirTerminator,
+};
+use crate::mir::ssa::{into_ssa, out_of_ssa};
+use crate::semantic::symbol_table::{IntType, Type};
+use crate::tests::{mir, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A body with the given terminators and no instructions.
+    fn graph(terminators: Vec<MirTerminator>) -> MirBody {
+        MirBody {
+            locals: vec![local(); 4],
+            args: 0,
+            blocks: terminators
+                .into_iter()
+                .map(|terminator| MirBasicBlock {
+                    instructions: Vec::new(),
+                    terminator,
+                })
+                .collect(),
+        }
+    }
+
+    fn local() -> MirLocal {
+        MirLocal {
+            ty: Type::Integer(IntType::I32),
+            name: None,
+        }
+    }
+
+    fn register(register: usize) -> MirOperand {
+        MirOperand::Register(register)
+    }
+
+    fn int(value: u64) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Integer(value, IntType::I32))
+    }
+
+    fn copy(dest: usize, value: MirOperand) -> MirInstruction {
+        MirInstruction {
+            kind: MirInstructionKind::Load(value),
+            dest: register(dest),
+            ty: Type::Integer(IntType::I32),
+        }
+    }
+
+    fn phi(dest: usize, inputs: Vec<(BlockId, MirOperand)>) -> MirInstruction {
+        MirInstruction {
+            kind: MirInstructionKind::Phi(inputs),
+            dest: register(dest),
+            ty: Type::Integer(IntType::I32),
+        }
+    }
+
+    /// bb0 branches to bb1 and bb2, which both go to bb3.
+    fn diamond() -> MirBody {
+        graph(vec![
+            MirTerminator::Branch(register(0), BlockId(1), BlockId(2)),
+            MirTerminator::Goto(BlockId(3)),
+            MirTerminator::Goto(BlockId(3)),
+            MirTerminator::Return(register(1)),
+        ])
+    }
+
+    /// bb0 enters a loop whose header bb1 either runs bb2, which jumps back,
+    /// or leaves for bb3. bb4 is unreachable.
+    fn looping() -> MirBody {
+        graph(vec![
+            MirTerminator::Goto(BlockId(1)),
+            MirTerminator::Branch(register(0), BlockId(2), BlockId(3)),
+            MirTerminator::Goto(BlockId(1)),
+            MirTerminator::Return(register(1)),
+            MirTerminator::Goto(BlockId(3)),
+        ])
+    }
+
+    fn entry_body(source: &str) -> MirBody {
+        let mut module = mir(source);
+        module.functions.remove(module.entry).body
+    }
+
+    fn instructions(body: &MirBody) -> impl Iterator<Item = &MirInstruction> {
+        body.blocks.iter().flat_map(|block| &block.instructions)
+    }
+
+    /// Runs the copies of a block in order on the given register values.
+    fn execute(instructions: &[MirInstruction], mut values: HashMap<usize, u64>) -> HashMap<usize, u64> {
+        for instruction in instructions {
+            let MirInstructionKind::Load(value) = &instruction.kind else {
+                panic!("expected a copy, found {:?}", instruction);
+            };
+            let value = match value {
+                MirOperand::Register(register) => values[register],
+                MirOperand::Literal(HirLiteral::Integer(value, _)) => *value,
+                other => panic!("unexpected operand {:?}", other),
+            };
+            values.insert(instruction.dest.register().unwrap(), value);
+        }
+        values
+    }
+
+    #[test]
+    fn test_dominators_of_a_diamond() {
+        let body = diamond();
+        let dominators = Dominators::new(&body);
+
+        assert_eq!(dominators.immediate_dominator(BlockId::ENTRY), None);
+        for id in 1..4 {
+            assert_eq!(dominators.immediate_dominator(BlockId(id)), Some(BlockId::ENTRY));
+        }
+        assert!(dominators.dominates(BlockId::ENTRY, BlockId(3)));
+        assert!(!dominators.dominates(BlockId(1), BlockId(3)));
+        assert_eq!(dominators.children(BlockId::ENTRY).len(), 3);
+        let frontiers = dominators.frontiers(&body);
+        assert_eq!(frontiers[1], [BlockId(3)]);
+        assert_eq!(frontiers[2], [BlockId(3)]);
+        assert!(frontiers[0].is_empty() && frontiers[3].is_empty());
+    }
+
+    #[test]
+    fn test_dominators_of_a_loop() {
+        let body = looping();
+        let dominators = Dominators::new(&body);
+
+        assert_eq!(dominators.reverse_postorder()[..2], [BlockId::ENTRY, BlockId(1)]);
+        assert_eq!(dominators.immediate_dominator(BlockId(2)), Some(BlockId(1)));
+        assert_eq!(dominators.immediate_dominator(BlockId(3)), Some(BlockId(1)));
+        assert!(dominators.dominates(BlockId(1), BlockId(1)));
+        assert!(!dominators.dominates(BlockId(2), BlockId(1)));
+        // The unreachable block takes no part, even though it jumps to bb3.
+        assert!(!dominators.is_reachable(BlockId(4)));
+        assert!(!dominators.dominates(BlockId(4), BlockId(3)));
+        let frontiers = dominators.frontiers(&body);
+        assert_eq!(frontiers[1], [BlockId(1)]);
+        assert_eq!(frontiers[2], [BlockId(1)]);
+        assert!(frontiers[3].is_empty() && frontiers[4].is_empty());
+    }
+
+    #[test]
+    fn test_ssa_writes_each_register_once() {
+        let body = entry_body("let mut x = 1; let mut i = 0; while i < 5 { if i % 2 == 0 { x = x * 3; } i++; } x");
+
+        let mut written = vec![false; body.locals.len()];
+        for instruction in instructions(&body) {
+            let dest = instruction.dest.register().unwrap();
+            assert!(!written[dest], "register {} is written twice", dest);
+            written[dest] = true;
+        }
+        // Versions of a variable keep its name.
+        assert!(body.locals.iter().filter(|local| local.name.as_deref() == Some("x")).count() > 1);
+    }
+
+    #[test]
+    fn test_loop_header_has_phis_for_changed_variables() {
+        let body = entry_body("let mut i = 0; let n = 10; while i < n { i += 1; } i");
+        let predecessors = body.predecessors();
+
+        let header = body.block_ids().find(|&id| !body.block(id).phis().is_empty()).unwrap();
+        let phis = body.block(header).phis();
+        // `n` never changes, so only `i` needs a phi.
+        assert_eq!(phis.len(), 1);
+        assert_eq!(body.locals[phis[0].dest.register().unwrap()].name.as_deref(), Some("i"));
+        let MirInstructionKind::Phi(inputs) = &phis[0].kind else {
+            unreachable!();
+        };
+        let sources: Vec<BlockId> = inputs.iter().map(|(pred, _)| *pred).collect();
+        assert_eq!(sources, predecessors[header.index()]);
+        assert!(matches!(inputs[0].1, MirOperand::Register(_)));
+    }
+
+    #[test]
+    fn test_if_value_meets_in_a_phi() {
+        let body = entry_body("let c = 1 < 2; let x = c ? 10 : 20; x");
+
+        let join = body.block_ids().last().unwrap();
+        let [phi] = body.block(join).phis() else {
+            panic!("expected one phi in {:?}", body.block(join));
+        };
+        let MirInstructionKind::Phi(inputs) = &phi.kind else {
+            unreachable!();
+        };
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(phi.ty, Type::int());
+    }
+
+    #[test]
+    fn test_ssa_empties_unreachable_blocks() {
+        let mut body = looping();
+        body.blocks[4].instructions.push(copy(1, int(7)));
+        body.blocks[2].instructions.push(copy(1, int(3)));
+        into_ssa(&mut body);
+
+        assert!(body.blocks[4].instructions.is_empty());
+        assert!(matches!(body.blocks[4].terminator, MirTerminator::Return(MirOperand::Literal(HirLiteral::Null))));
+        // Register 1 is read at bb3 and written in the loop, so the header gets a phi without an input from bb4.
+        let [phi] = body.block(BlockId(1)).phis() else {
+            panic!("expected one phi in {:?}", body.block(BlockId(1)));
+        };
+        let MirInstructionKind::Phi(inputs) = &phi.kind else {
+            unreachable!();
+        };
+        assert_eq!(inputs.iter().map(|(pred, _)| *pred).collect::<Vec<_>>(), [BlockId::ENTRY, BlockId(2)]);
+        // Nothing writes register 1 before the loop.
+        assert!(matches!(inputs[0].1, MirOperand::Literal(HirLiteral::Null)));
+        assert_eq!(inputs[1].1.register(), body.blocks[2].instructions[0].dest.register());
+    }
+
+    #[test]
+    fn test_out_of_ssa_splits_critical_edges_and_breaks_cycles() {
+        // A loop that swaps registers 1 and 2 on every pass through phis
+        // that read each other.
+        let mut body = graph(vec![
+            MirTerminator::Goto(BlockId(1)),
+            MirTerminator::Branch(register(3), BlockId(1), BlockId(2)),
+            MirTerminator::Return(register(1)),
+        ]);
+        body.blocks[1].instructions = vec![
+            phi(1, vec![(BlockId::ENTRY, int(10)), (BlockId(1), register(2))]),
+            phi(2, vec![(BlockId::ENTRY, int(20)), (BlockId(1), register(1))]),
+            MirInstruction {
+                kind: MirInstructionKind::BinaryOp(MirBinaryOp::LessThan, register(1), int(5)),
+                dest: register(3),
+                ty: Type::Integer(IntType::I32),
+            },
+        ];
+        out_of_ssa(&mut body);
+
+        assert!(instructions(&body).all(|instruction| !instruction.is_phi()));
+        assert_eq!(execute(&body.blocks[0].instructions, HashMap::new()), HashMap::from([(1, 10), (2, 20)]));
+        // The back edge leaves a block with two successors, so its copies go on a new block.
+        assert_eq!(body.blocks.len(), 4);
+        assert!(matches!(body.blocks[1].terminator, MirTerminator::Branch(_, BlockId(3), BlockId(2))));
+        assert!(matches!(body.blocks[3].terminator, MirTerminator::Goto(BlockId(1))));
+        assert_eq!(body.blocks[3].instructions.len(), 3);
+        let swapped = execute(&body.blocks[3].instructions, HashMap::from([(1, 10), (2, 20)]));
+        assert_eq!((swapped[&1], swapped[&2]), (20, 10));
+        assert_eq!(body.locals.len(), 5);
+    }
+
+    #[test]
+    fn test_programs_run_the_same_through_ssa() {
+        let swap = "let mut a = 1; let mut b = 2; let mut i = 0; while i < 3 { let t = a; a = b; b = t; i++; }";
+        assert_eq!(run(&format!("{} a * 10 + b", swap)), Ok(21.0));
+        let nested = "
+            let mut total = 0;
+            for let mut i = 0; i < 4; i++ {
+                for let mut j = 0; j < i; j++ {
+                    if j == 2 { continue; }
+                    total += i * j;
+                }
+            }
+            total
+        ";
+        assert_eq!(run(nested), Ok(5.0));
+        assert_eq!(run("fn f(n: int) -> int { let mut m = n; while m > 10 { m -= 7; } return m; } f(40)"), Ok(5.0));
+    }
+}
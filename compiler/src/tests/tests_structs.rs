@@ -0,0 +1,97 @@
+use crate::tests::{analyze, error, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct_fields() {
+        let source = "struct Point { x: int, y: float }
+            fn norm1(p: Point) -> float { return p.x as float + p.y; }
+            let mut p = Point { x: 3, y: 2.5 };
+            p.x += 4;
+            p.y = p.y * 2.0;
+            norm1(p)";
+        assert_eq!(run(source), Ok(12.0));
+    }
+
+    #[test]
+    fn test_struct_literal_fields_in_any_order() {
+        let source = "struct Pair { first: int, second: int }
+            let mut calls = 0;
+            let next = || { calls += 1; return calls; };
+            let pair = Pair { second: next(), first: next() * 10 };
+            pair.first + pair.second";
+        // Initializers run in the order they are written.
+        assert_eq!(run(source), Ok(21.0));
+    }
+
+    #[test]
+    fn test_nested_structs_and_arrays() {
+        let source = "struct Segment { from: [int; 2], to: [int; 2] }
+            let mut s = Segment { from: [1, 2], to: [4, 6] };
+            s.to[1] -= 2;
+            (s.to[0] - s.from[0]) * (s.to[1] - s.from[1])";
+        assert_eq!(run(source), Ok(6.0));
+    }
+
+    #[test]
+    fn test_arrays_in_loops() {
+        let source = "let mut squares: [int; 5] = [0, 0, 0, 0, 0];
+            for let mut i = 0; i < 5; i++ { squares[i] = i * i; }
+            let mut total = 0;
+            for let mut i = 0; i < 5; i++ { total += squares[i]; }
+            total";
+        assert_eq!(run(source), Ok(30.0));
+    }
+
+    #[test]
+    fn test_index_out_of_bounds_traps() {
+        assert!(run("let a = [1, 2, 3]; let i = 3; a[i]").is_err());
+        assert!(run("let a = [1, 2, 3]; let i = -1; a[i]").is_err());
+    }
+
+    #[test]
+    fn test_enum_discriminants() {
+        let source = "enum Level { Low, Medium = 5, High }
+            fn weight(level: Level) -> int { return level == Level::High ? 100 : 1; }
+            weight(Level::High) + weight(Level::Low)";
+        assert_eq!(run(source), Ok(101.0));
+        assert_eq!(run("enum E { A = -2, B } fn f(e: E) -> E { return e; } f(E::B) == E::B ? 1 : 0"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_struct_errors() {
+        assert_eq!(
+            error("struct P { x: int, y: int } let p = P { x: 1 };"),
+            "Missing 'y' in initializer of struct 'P'"
+        );
+        assert_eq!(
+            error("struct P { x: int } let p = P { x: 1 }; p.total"),
+            "Struct 'P' has no field 'total'"
+        );
+        assert_eq!(
+            error("struct P { x: int } let p = P { x: 1 }; p.x = 2;"),
+            "Cannot assign to field 'x' of immutable variable 'p'; declare it with 'mut' to allow assignment"
+        );
+        assert_eq!(error("struct P { x: int, x: float }"), "Field 'x' is declared more than once");
+        assert_eq!(error("struct Unit {}"), "Expect at least one field in a struct.");
+        assert_eq!(
+            error("struct List { next: List }"),
+            "Type 'List' is defined in terms of itself: List -> List"
+        );
+    }
+
+    #[test]
+    fn test_array_errors() {
+        assert_eq!(error("let a = [1, 2]; a[2]"), "Index 2 is out of bounds for an array of length 2");
+        assert!(analyze("let a = [1, 2]; a[1.5]").is_err());
+        assert!(analyze("let a: [int; 3] = [1, 2];").is_err());
+    }
+
+    #[test]
+    fn test_enum_errors() {
+        assert_eq!(error("enum E { A, B } E::C"), "Enum 'E' has no variant 'C'");
+        assert_eq!(error("enum E { A, A }"), "Variant 'A' is declared more than once");
+    }
+}
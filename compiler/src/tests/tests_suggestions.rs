@@ -0,0 +1,100 @@
+use crate::diagnostics::{apply_fixes, closest_match, edit_distance, DiagnosticSink, Fix, Span};
+use crate::lexer::lexer::Lexer;
+use crate::parser::pratt_parser::Parser;
+use crate::semantic::analyzer::SemanticAnalyzer;
+use crate::tests::{build, compile_error};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("counter", "counter"), 0);
+        assert_eq!(edit_distance("countr", "counter"), 1);
+        assert_eq!(edit_distance("whlie", "while"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        assert_eq!(closest_match("countr", ["total", "counter", "count"]), Some("counter"));
+        assert_eq!(closest_match("retrun", ["let", "return"]), Some("return"));
+        assert_eq!(closest_match("x", ["y", "xs"]), Some("y"));
+        assert_eq!(closest_match("value", ["total", "index"]), None);
+    }
+
+    #[test]
+    fn test_unknown_variables_suggest_visible_names() {
+        let failure = compile_error("let counter = 1; fn f() -> int { return 2; } countr + f()");
+
+        assert_eq!(failure.to_string(), "Undefined variable 'countr'\n  help: did you mean `counter`?");
+        assert_eq!(failure.fixes, vec![Fix::rename(Span::new(1, 46, 6), "countr", "counter")]);
+        assert_eq!(compile_error("fn f(total: int) { totl = 1; }").fixes[0].replacement, "total");
+    }
+
+    #[test]
+    fn test_suggestions_only_use_names_in_scope() {
+        let mut sink = DiagnosticSink::new();
+        let tokens = Lexer::new("{ let inner = 1; } innr").tokenize(&mut sink).unwrap();
+        let ast = Parser::new(tokens).parse(&mut sink).unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+
+        let failure = analyzer.analyze_program(&ast, &mut sink).err().unwrap();
+        assert_eq!(failure.to_string(), "Undefined variable 'innr'");
+        assert!(failure.fixes.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_fields_suggest_declared_ones() {
+        let source = "struct Size { width: int, height: int }\nlet mut s = Size { width: 1, height: 2 };\ns.widht";
+        let failure = compile_error(source);
+        assert_eq!(failure.to_string(), "Struct 'Size' has no field 'widht'\n  help: did you mean `width`?");
+        assert_eq!(failure.fixes, vec![Fix::rename(Span::new(3, 3, 5), "widht", "width")]);
+        assert_eq!(apply_fixes(source, &failure.fixes), source.replace("widht", "width"));
+
+        assert_eq!(compile_error("struct P { x: int } let mut p = P { x: 1 }; p.X = 2;").fixes[0].replacement, "x");
+        // A literal is pointed at the fields it has not initialized yet.
+        let failure = compile_error("struct R { low: int, high: int } R { low: 1, hihg: 2 }");
+        assert_eq!(failure.fixes[0].replacement, "high");
+        assert!(compile_error("struct R { low: int, high: int } R { low: 1, lwo: 2 }").fixes.is_empty());
+        assert!(compile_error("struct P { x: int } P { x: 1 }.total").fixes.is_empty());
+    }
+
+    #[test]
+    fn test_keyword_typos() {
+        let failure = compile_error("fn f() -> int {\n    retrun 1;\n}");
+        assert_eq!(failure.to_string(), "Expect ';' after expression.\n  help: did you mean `return`?");
+        assert_eq!(failure.fixes, vec![Fix::rename(Span::new(2, 5, 6), "retrun", "return")]);
+
+        assert_eq!(compile_error("let mut i = 0; whlie i < 3 { i = i + 1; }").fixes[0].replacement, "while");
+        assert_eq!(compile_error("let c = true; if c { } esle { }").fixes[0].replacement, "else");
+        // An identifier spelled like a keyword is only suspect where it breaks the parse.
+        assert!(build("let retrun = 1; retrun").is_ok());
+        // Nor is a short name, or one the program declares.
+        assert!(compile_error("let n = 1; n 2").fixes.is_empty());
+        assert!(compile_error("fn f(i: int) -> int { i 1; return i; }").fixes.is_empty());
+        assert!(compile_error("fi 1").fixes.is_empty());
+        assert!(compile_error("let lte = 1; lte 2").fixes.is_empty());
+        assert_eq!(compile_error("lte x = 1;").fixes[0].replacement, "let");
+    }
+
+    #[test]
+    fn test_applying_fixes() {
+        let source = "let counter = 1;\nwhlie countr < 3 { }";
+        let fixed = apply_fixes(source, &compile_error(source).fixes);
+        assert_eq!(fixed, "let counter = 1;\nwhile countr < 3 { }");
+        let fixed = apply_fixes(&fixed, &compile_error(&fixed).fixes);
+        assert_eq!(fixed, "let counter = 1;\nwhile counter < 3 { }");
+        assert!(build(&fixed).is_ok());
+
+        let overlapping = [
+            Fix::rename(Span::new(1, 1, 3), "abc", "x"),
+            Fix::rename(Span::new(1, 2, 1), "b", "y"),
+        ];
+        assert_eq!(apply_fixes("abc é", &overlapping), "x é");
+        assert_eq!(apply_fixes("é a", &[Fix::rename(Span::new(1, 3, 1), "a", "b")]), "é b");
+        assert_eq!(apply_fixes("a", &[Fix::rename(Span::new(3, 1, 1), "a", "b")]), "a");
+    }
+}
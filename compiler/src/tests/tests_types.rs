@@ -0,0 +1,89 @@
+use crate::diagnostics::Span;
+use crate::tests::{analyze, run};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias_is_transparent() {
+        assert_eq!(run("alias Meters = float; let d: Meters = 3; let f: float = d; f + d"), Ok(6.0));
+    }
+
+    #[test]
+    fn test_def_needs_explicit_conversion() {
+        assert!(analyze("def UserId = float; let id: UserId = 5;").is_err());
+        assert!(analyze("def UserId = float; let id = UserId(5); let n: float = id;").is_err());
+        assert_eq!(run("def UserId = float; let id = UserId(5); float(id) + 1"), Ok(6.0));
+    }
+
+    #[test]
+    fn test_conversion_needs_same_representation() {
+        assert!(analyze("def Flag = bool; Flag(1)").is_err());
+        assert!(analyze("def Flag = bool; Flag(true, false)").is_err());
+    }
+
+    #[test]
+    fn test_alias_cycles_are_rejected() {
+        let error = analyze("alias A = B; alias B = fn(A) -> float;").err().unwrap();
+
+        assert_eq!(error, "Type 'A' is defined in terms of itself: A -> B -> A");
+        assert!(analyze("def Loop = Loop;").is_err());
+    }
+
+    #[test]
+    fn test_generic_alias() {
+        let source = "
+            alias Unary<T> = fn(T) -> T;
+            fn apply(f: Unary<float>, x: float) -> float { return f(x); }
+            apply(|x| x * 3, 2)
+        ";
+
+        assert_eq!(run(source), Ok(6.0));
+        assert!(analyze("alias Unary<T> = fn(T) -> T; let f: Unary = |x: float| x;").is_err());
+        assert!(analyze("alias Unary<T> = fn(T) -> T; let f: Unary<float, float> = |x: float| x;").is_err());
+    }
+
+    #[test]
+    fn test_declaration_errors() {
+        assert!(analyze("alias Id = Missing;").is_err());
+        assert!(analyze("alias int = float;").is_err());
+        assert!(analyze("alias A = float; def A = float;").is_err());
+        assert!(analyze("fn f() { alias A = float; }").is_err());
+    }
+
+    #[test]
+    fn test_diagnostics_print_alias_names() {
+        let error = analyze("alias Meters = float; let d: Meters = true;").err().unwrap();
+
+        assert_eq!(error, "Type mismatch: variable 'd' is declared as Meters but initialized with bool");
+    }
+
+    #[test]
+    fn test_describe() {
+        let analyzer = analyze(
+            "alias Meters = float; alias Unary<T> = fn(T) -> T; def UserId = float; let d: Meters = 1; let f: Unary<Meters> = |x| x;",
+        )
+        .unwrap();
+
+        assert_eq!(analyzer.describe("Meters").as_deref(), Some("alias Meters = float"));
+        assert_eq!(analyzer.describe("Unary").as_deref(), Some("alias Unary<T> = fn(T) -> T"));
+        assert_eq!(analyzer.describe("UserId").as_deref(), Some("def UserId = float"));
+        assert_eq!(analyzer.describe("d").as_deref(), Some("d: Meters"));
+        assert_eq!(analyzer.describe("f").as_deref(), Some("f: Unary<Meters>"));
+        assert_eq!(analyzer.describe("missing"), None);
+    }
+
+    #[test]
+    fn test_describe_at_resolves_shadowed_names() {
+        let analyzer = analyze("let x = 1; fn f(x: bool) -> bool { return x; } let y = x; alias A = float;").unwrap();
+        let at = |column, length| Span::new(1, column, length);
+
+        assert_eq!(analyzer.describe_at("x", at(5, 1)).as_deref(), Some("x: int"));
+        assert_eq!(analyzer.describe_at("x", at(17, 1)).as_deref(), Some("x: bool"));
+        assert_eq!(analyzer.describe_at("x", at(43, 1)).as_deref(), Some("x: bool"));
+        assert_eq!(analyzer.describe_at("x", at(56, 1)).as_deref(), Some("x: int"));
+        assert_eq!(analyzer.describe_at("f", at(15, 1)).as_deref(), Some("f: fn(bool) -> bool"));
+        assert_eq!(analyzer.describe_at("A", at(65, 1)).as_deref(), Some("alias A = float"));
+    }
+}
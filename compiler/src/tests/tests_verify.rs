@@ -0,0 +1,211 @@
+use crate::hir::hir::HirLiteral;
+use crate::mir::mir::{
+    BlockId, MirBasicBlock, MirBinaryOp, MirBody, MirInstruction, MirInstructionKind, MirLocal, MirOperand,
+    MirTerminator,
+};
+use crate::mir::passes::{OptLevel, Pass, PassContext, PassManager};
+use crate::mir::verify::verify;
+use crate::semantic::symbol_table::{IntType, Type};
+use crate::tests::mir;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const I64: Type = Type::Integer(IntType::I64);
+
+    /// A body whose first `args` registers are i64 arguments, followed by
+    /// registers of the given types.
+    fn body(args: usize, types: &[Type], blocks: Vec<(Vec<MirInstruction>, MirTerminator)>) -> MirBody {
+        let locals = std::iter::repeat_n(I64, args).chain(types.iter().cloned());
+        MirBody {
+            locals: locals.map(|ty| MirLocal { ty, name: None }).collect(),
+            args,
+            blocks: blocks
+                .into_iter()
+                .map(|(instructions, terminator)| MirBasicBlock {
+                    instructions,
+                    terminator,
+                })
+                .collect(),
+        }
+    }
+
+    fn r(register: usize) -> MirOperand {
+        MirOperand::Register(register)
+    }
+
+    fn int(value: u64) -> MirOperand {
+        MirOperand::Literal(HirLiteral::Integer(value, IntType::I64))
+    }
+
+    fn add(dest: usize, left: MirOperand, right: MirOperand) -> MirInstruction {
+        MirInstruction {
+            kind: MirInstructionKind::BinaryOp(MirBinaryOp::Add, left, right),
+            dest: r(dest),
+            ty: I64,
+        }
+    }
+
+    fn less(dest: usize, left: MirOperand, right: MirOperand) -> MirInstruction {
+        MirInstruction {
+            kind: MirInstructionKind::BinaryOp(MirBinaryOp::LessThan, left, right),
+            dest: r(dest),
+            ty: I64,
+        }
+    }
+
+    fn phi(dest: usize, inputs: Vec<(usize, MirOperand)>) -> MirInstruction {
+        MirInstruction {
+            kind: MirInstructionKind::Phi(inputs.into_iter().map(|(pred, value)| (BlockId(pred), value)).collect()),
+            dest: r(dest),
+            ty: I64,
+        }
+    }
+
+    fn branch(condition: MirOperand, then_block: usize, else_block: usize) -> MirTerminator {
+        MirTerminator::Branch(condition, BlockId(then_block), BlockId(else_block))
+    }
+
+    /// `if %0 < 10 { %2 = %0 + 1 } else { %3 = %0 + 2 }`, meeting in a phi
+    /// `%4` that bb3 returns.
+    fn diamond() -> MirBody {
+        body(
+            1,
+            &[Type::Boolean, I64, I64, I64],
+            vec![
+                (vec![less(1, r(0), int(10))], branch(r(1), 1, 2)),
+                (vec![add(2, r(0), int(1))], MirTerminator::Goto(BlockId(3))),
+                (vec![add(3, r(0), int(2))], MirTerminator::Goto(BlockId(3))),
+                (vec![phi(4, vec![(1, r(2)), (2, r(3))])], MirTerminator::Return(r(4))),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_well_formed_bodies_verify() {
+        assert_eq!(verify(&diamond()), Ok(()));
+        let source = "fn f(n: int) -> int { let mut total = 0; for let mut i = 0; i < n; i++ { total += i; } return total; }
+            let g = |x: int| x as float * 2.0 > 1.0; g(f(4))";
+        for level in OptLevel::ALL {
+            let mut module = mir(source);
+            PassManager::for_level(level).run(&mut module).unwrap();
+            for function in &module.functions {
+                assert_eq!(verify(&function.body), Ok(()), "{} at -O{}", function.name, level.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_reads_must_be_dominated_by_their_definition() {
+        let mut body = diamond();
+        body.blocks[3].terminator = MirTerminator::Return(r(2));
+        assert_eq!(
+            verify(&body),
+            Err("bb3: terminator reads %2 before it is written, by instruction 0 of bb1".to_string())
+        );
+
+        let mut body = diamond();
+        body.blocks[1].instructions.insert(0, add(5, r(2), int(1)));
+        body.locals.push(MirLocal { ty: I64, name: None });
+        assert_eq!(
+            verify(&body),
+            Err("bb1: instruction 0 reads %2 before it is written, by instruction 1 of bb1".to_string())
+        );
+
+        let mut body = diamond();
+        body.blocks[2].instructions[0].dest = r(2);
+        assert_eq!(verify(&body), Err("bb2: instruction 0 writes %2, which is already written".to_string()));
+
+        let mut body = diamond();
+        body.blocks[1].instructions[0].dest = r(0);
+        assert_eq!(verify(&body), Err("bb1: instruction 0 writes the argument %0".to_string()));
+
+        // A phi input only needs its value at the end of the block it comes from.
+        let mut body = diamond();
+        body.blocks[3].instructions[0] = phi(4, vec![(1, r(2)), (2, r(2))]);
+        assert_eq!(
+            verify(&body),
+            Err("bb3: instruction 0: input from bb2 reads %2 before it is written, by instruction 0 of bb1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_phis_and_terminators() {
+        let mut body = diamond();
+        body.blocks[3].instructions[0] = phi(4, vec![(1, r(2))]);
+        assert_eq!(
+            verify(&body),
+            Err("bb3: instruction 0 has inputs from bb1 but the block's predecessors are bb1, bb2".to_string())
+        );
+
+        let mut body = diamond();
+        body.blocks[3].instructions.insert(0, add(5, r(0), int(3)));
+        body.locals.push(MirLocal { ty: I64, name: None });
+        assert_eq!(verify(&body), Err("bb3: instruction 1 is a phi after other instructions".to_string()));
+
+        let mut body = diamond();
+        body.blocks[1].terminator = MirTerminator::Goto(BlockId(7));
+        assert_eq!(verify(&body), Err("bb1: jumps to bb7, which does not exist".to_string()));
+
+        let mut body = diamond();
+        body.blocks[0].terminator = branch(r(0), 1, 2);
+        assert_eq!(verify(&body), Err("bb0: terminator: the condition has type i64 but bool was expected".to_string()));
+
+        let mut body = diamond();
+        body.blocks[3].terminator = MirTerminator::Goto(BlockId::ENTRY);
+        assert_eq!(verify(&body), Err("bb0 is the entry block but is jumped to".to_string()));
+    }
+
+    #[test]
+    fn test_operand_types_match_the_instruction() {
+        let mut body = diamond();
+        body.blocks[1].instructions[0] = add(2, r(1), int(1));
+        assert_eq!(
+            verify(&body),
+            Err("bb1: instruction 0: the left operand has type bool but i64 was expected".to_string())
+        );
+
+        let mut body = diamond();
+        body.locals[2].ty = Type::Integer(IntType::I32);
+        assert_eq!(
+            verify(&body),
+            Err("bb1: instruction 0: the destination has type i32 but i64 was expected".to_string())
+        );
+
+        // Null takes the type of wherever it goes.
+        let mut body = diamond();
+        body.blocks[1].instructions[0] = add(2, MirOperand::Literal(HirLiteral::Null), int(1));
+        assert_eq!(verify(&body), Ok(()));
+    }
+
+    /// Drops every instruction of the entry block, leaving its readers
+    /// without a definition.
+    struct Breaks;
+
+    impl Pass for Breaks {
+        fn name(&self) -> &'static str {
+            "breaks"
+        }
+
+        fn run(&mut self, body: &mut MirBody, _: &mut PassContext) -> bool {
+            let changed = !body.blocks[0].instructions.is_empty();
+            body.blocks[0].instructions.clear();
+            changed
+        }
+    }
+
+    #[test]
+    fn test_pass_manager_names_the_pass_that_broke_a_body() {
+        let mut module = mir("let x = 2; let y = x * 3; y");
+        let mut passes = PassManager::new();
+        passes.add(Breaks);
+        let error = passes.run(&mut module).unwrap_err();
+        assert!(error.starts_with("MIR of 'main' is invalid after breaks: bb0: terminator reads"), "{}", error);
+
+        let mut module = mir("let x = 2; x");
+        module.functions[module.entry].body.blocks[0].terminator = MirTerminator::Goto(BlockId(3));
+        let error = PassManager::for_level(OptLevel::O0).run(&mut module).unwrap_err();
+        assert_eq!(error, "MIR of 'main' is invalid after MIR lowering: bb0: jumps to bb3, which does not exist");
+    }
+}
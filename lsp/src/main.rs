@@ -1,6 +1,15 @@
+use std::collections::HashMap;
 use std::error::Error;
+use d_compiler::diagnostics::{Diagnostic, DiagnosticSink, Severity, Span};
+use d_compiler::hir::conditional::resolve_conditionals;
+use d_compiler::hir::hir::DefId;
+use d_compiler::hir::lowerer::HirLowerer;
 use d_compiler::lexer::lexer::Lexer;
 use d_compiler::lexer::token::{Token, TokenInfo};
+use d_compiler::parser::pratt_parser::Parser;
+use d_compiler::semantic::analyzer::SemanticAnalyzer;
+use d_compiler::semantic::symbol_table::SymbolKind;
+use d_compiler::CompileOptions;
 
 use lsp_server::{Connection, Message, Request, RequestId, Response};
 use lsp_types::{
@@ -18,6 +27,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         text_document_sync: Some(TextDocumentSyncCapability::Kind(
             TextDocumentSyncKind::FULL,
         )),
+        code_action_provider: Some(lsp_types::CodeActionProviderCapability::Simple(true)),
+        definition_provider: Some(lsp_types::OneOf::Left(true)),
         completion_provider: Some(lsp_types::CompletionOptions {
             resolve_provider: Some(false),
             trigger_characters: Some(vec![".".to_string()]),
@@ -44,7 +55,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                         let content = std::fs::read_to_string(file_path).unwrap();
                         let position = params.text_document_position_params.position;
                         let mut lexer = Lexer::new(&content);
-                        let tokens = lexer.tokenize().unwrap_or_default();
+                        let tokens = lexer.tokenize(&mut DiagnosticSink::new()).unwrap_or_default();
 
                         let token = tokens.iter().find(|token_info| {
                             let token_line = token_info.line as u32 - 1;
@@ -58,7 +69,10 @@ fn main() -> Result<(), Box<dyn Error>> {
 
                         let hover_content = token.map_or_else(
                             || "No token found".to_string(),
-                            |token_info| format_token_info(token_info),
+                            |token_info| {
+                                describe_identifier(&content, token_info)
+                                    .unwrap_or_else(|| format_token_info(token_info))
+                            },
                         );
 
                         let result = Some(lsp_types::Hover {
@@ -78,6 +92,30 @@ fn main() -> Result<(), Box<dyn Error>> {
                             }))?;
                         }
                     }
+                    "textDocument/codeAction" => {
+                        let (id, params) = cast::<lsp_types::request::CodeActionRequest>(req)?;
+                        let uri = params.text_document.uri;
+                        let content = std::fs::read_to_string(uri.to_file_path().unwrap()).unwrap_or_default();
+                        let actions = get_code_actions(&uri, &content, params.range);
+                        let result = serde_json::to_value(&actions).unwrap();
+                        let resp = Response { id, result: Some(result), error: None };
+                        connection.sender.send(Message::Response(resp))?;
+                    }
+                    "textDocument/definition" => {
+                        let (id, params) = cast::<lsp_types::request::GotoDefinition>(req)?;
+                        let uri = params.text_document_position_params.text_document.uri;
+                        let content = std::fs::read_to_string(uri.to_file_path().unwrap()).unwrap_or_default();
+                        let position = params.text_document_position_params.position;
+                        let result = find_definition(&content, position).map(|span| {
+                            lsp_types::GotoDefinitionResponse::Scalar(lsp_types::Location {
+                                uri: uri.clone(),
+                                range: to_range(span),
+                            })
+                        });
+                        let result = serde_json::to_value(&result).unwrap();
+                        let resp = Response { id, result: Some(result), error: None };
+                        connection.sender.send(Message::Response(resp))?;
+                    }
                     "textDocument/completion" => {
                         let (id, _params) = cast::<lsp_types::request::Completion>(req)?;
                         let completions = get_completions();
@@ -130,12 +168,13 @@ where
 
 fn format_token_info(token_info: &TokenInfo) -> String {
     let token_type = match &token_info.token {
-        Token::Reserved(r) => format!("Reserved keyword: `{:?}`", r),
+        Token::Reserved(r) => format!("Reserved keyword: `{}`", r.keyword()),
         Token::Identifier(id) => format!("Identifier: `{}`", id),
+        Token::Integer(n) => format!("Integer literal: `{}`", n),
         Token::String(s) => format!("String literal: `\"{}\"`", s),
         Token::Number(n) => format!("Number literal: `{}`", n),
-        Token::Operation(op) => format!("Operator: `{:?}`", op),
-        Token::Punctuation(p) => format!("Punctuation: `{:?}`", p),
+        Token::Operation(op) => format!("Operator: `{}`", op.symbol()),
+        Token::Punctuation(p) => format!("Punctuation: `{}`", p.symbol()),
         Token::Whitespace => "Whitespace".to_string(),
         Token::Newline => "Newline".to_string(),
         Token::Invalid(i) => format!("Invalid token: `{}`", i),
@@ -144,29 +183,127 @@ fn format_token_info(token_info: &TokenInfo) -> String {
     format!("```\n{}\n```", token_type)
 }
 
+/// Describes what the identifier under the cursor names, following it to
+/// the declaration it resolves to, and prints types the way they are written.
+fn describe_identifier(content: &str, token_info: &TokenInfo) -> Option<String> {
+    let Token::Identifier(name) = &token_info.token else {
+        return None;
+    };
+    let mut sink = DiagnosticSink::new();
+    let tokens = Lexer::new(content).tokenize(&mut sink).ok()?;
+    let ast = Parser::new(tokens).parse(&mut sink).ok()?;
+    let mut analyzer = SemanticAnalyzer::new();
+    let ast = resolve_conditionals(ast, &mut analyzer, &mut sink).ok()?;
+    // Declarations checked before an error are still worth describing.
+    let _ = analyzer.analyze_program(&ast, &mut sink);
+    analyzer
+        .describe_at(name, token_info.span())
+        .map(|description| format!("```\n{}\n```", description))
+}
+
+/// Where the declaration is written that the identifier at `position`
+/// refers to. Variables are found through the analysis and items through
+/// the HIR they were lowered to.
+fn find_definition(content: &str, position: lsp_types::Position) -> Option<Span> {
+    let mut sink = DiagnosticSink::new();
+    let tokens = Lexer::new(content).tokenize(&mut sink).ok()?;
+    let ast = Parser::new(tokens).parse(&mut sink).ok()?;
+    let mut analyzer = SemanticAnalyzer::new();
+    let ast = resolve_conditionals(ast, &mut analyzer, &mut sink).ok()?;
+    analyzer.analyze_program(&ast, &mut sink).ok()?;
+    let (_, resolution) = analyzer.resolutions().find(|(_, resolution)| {
+        let range = to_range(resolution.span);
+        range.start.line == position.line
+            && range.start.character <= position.character
+            && position.character <= range.end.character
+    })?;
+    let def = match resolution.symbol {
+        SymbolKind::Variable { id, .. } => return Some(analyzer.variables[id].span),
+        SymbolKind::Function { id } => DefId::Function(id),
+        SymbolKind::Const { id } => DefId::Const(id),
+        SymbolKind::Static { id } => DefId::Static(id),
+    };
+    let program = HirLowerer::new().lower_program(&ast, &analyzer, &mut sink).ok()?;
+    program.origin(def).map(|origin| origin.span)
+}
+
+/// Every error and warning the compiler reports for a document.
+fn compile_diagnostics(content: &str) -> Vec<Diagnostic> {
+    let mut sink = DiagnosticSink::new();
+    let _ = d_compiler::compile(content, &CompileOptions::new(), &mut sink);
+    sink.into_diagnostics()
+}
+
 fn get_diagnostics(content: &str) -> Vec<lsp_types::Diagnostic> {
-    match d_compiler::compile(content) {
-        Ok(_) => Vec::new(),
-        Err(err) => {
-            let range = lsp_types::Range {
-                start: lsp_types::Position {
-                    line: 0,
-                    character: 0,
-                },
-                end: lsp_types::Position {
-                    line: 0,
-                    character: 0,
-                },
+    compile_diagnostics(content).iter().map(to_lsp_diagnostic).collect()
+}
+
+fn to_lsp_diagnostic(diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    lsp_types::Diagnostic {
+        range: to_range(diagnostic.span().unwrap_or_default()),
+        severity: Some(match diagnostic.severity {
+            Severity::Error => lsp_types::DiagnosticSeverity::ERROR,
+            Severity::Warning => lsp_types::DiagnosticSeverity::WARNING,
+        }),
+        code: diagnostic.code.clone().map(lsp_types::NumberOrString::String),
+        message: diagnostic.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Quick fixes for the diagnostics that `range` touches, either where they
+/// are reported or where their fix applies. Only the first fix is marked
+/// preferred, so that an editor applies at most one automatically.
+fn get_code_actions(
+    uri: &lsp_types::Url,
+    content: &str,
+    range: lsp_types::Range,
+) -> Vec<lsp_types::CodeActionOrCommand> {
+    let mut actions = Vec::new();
+    for diagnostic in compile_diagnostics(content) {
+        let reported = to_lsp_diagnostic(&diagnostic);
+        for fix in &diagnostic.fixes {
+            if !overlaps(reported.range, range) && !overlaps(to_range(fix.span), range) {
+                continue;
+            }
+            let edit = lsp_types::TextEdit {
+                range: to_range(fix.span),
+                new_text: fix.replacement.clone(),
             };
-            let diagnostic = lsp_types::Diagnostic {
-                range,
-                severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-                message: err,
+            actions.push(lsp_types::CodeActionOrCommand::CodeAction(lsp_types::CodeAction {
+                title: fix.message.clone(),
+                kind: Some(lsp_types::CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![reported.clone()]),
+                edit: Some(lsp_types::WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(actions.is_empty()),
                 ..Default::default()
-            };
-            vec![diagnostic]
+            }));
         }
     }
+    actions
+}
+
+/// Whether two ranges share a position. A range that ends where the other
+/// starts touches it, as a cursor placed right after a word does.
+fn overlaps(a: lsp_types::Range, b: lsp_types::Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
+
+/// Converts a compiler span, whose lines and columns count from 1, to an
+/// LSP range, which counts from 0. The default span maps to the start.
+fn to_range(span: Span) -> lsp_types::Range {
+    let line = span.line.saturating_sub(1) as u32;
+    let character = span.column.saturating_sub(1) as u32;
+    lsp_types::Range {
+        start: lsp_types::Position { line, character },
+        end: lsp_types::Position {
+            line,
+            character: character + span.length as u32,
+        },
+    }
 }
 
 fn get_completions() -> Vec<lsp_types::CompletionItem> {